@@ -0,0 +1,463 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Maintenance of the relay database for the server's admin commands.
+//! Message tokens are handed out once as random secrets, the relay keeps
+//! only their SHA-256 hash. Anonymous tokens are stored the same way once
+//! redeemed, and cost proof-of-work that grows with the issuance rate of
+//! the last hour. Relay keys are rotated by adding a new key and retiring all but
+//! the previous one.
+
+use crate::config::config;
+use crate::entities::{
+    federation_outbox, mailbox_challenge, message_token, pow_challenge, received_deposit,
+    relay_blob, relay_key, relay_message,
+};
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::OsRng;
+use crypto_box::SecretKey;
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Token that can still be spent
+pub const TOKEN_ACTIVE: &str = "active";
+/// Token withdrawn by an admin
+pub const TOKEN_REVOKED: &str = "revoked";
+/// Token past its expiry, marked by a sweep
+pub const TOKEN_EXPIRED: &str = "expired";
+
+/// What a sweep removed
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SweepReport {
+    pub messages: u64,
+    pub blobs: u64,
+    pub tokens: u64,
+    /// Mailbox and proof-of-work challenges that are no longer needed
+    pub challenges: u64,
+    /// Received federated deposits past their replay window
+    pub deposits: u64,
+}
+
+/// A newly minted message token. `token` is only ever shown here.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MintedToken {
+    pub id: Uuid,
+    pub token: String,
+    pub max_messages: i32,
+    pub expires_at: DateTimeWithTimeZone,
+}
+
+/// The relay key in use after a rotation
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RotatedKey {
+    pub id: i32,
+    /// Hex encoded X25519 public key
+    pub public_key: String,
+    pub created_at: DateTimeWithTimeZone,
+    /// Number of older keys removed
+    pub retired: u64,
+}
+
+/// Size of the relay's queues
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QueueStats {
+    pub messages: u64,
+    /// Messages past their expiry that the next sweep removes
+    pub expired_messages: u64,
+    /// Mailboxes with at least one waiting message
+    pub mailboxes: u64,
+    pub oldest_message: Option<DateTimeWithTimeZone>,
+    pub blobs: u64,
+    pub blob_bytes: u64,
+    pub active_tokens: u64,
+    /// Messages spent from active tokens
+    pub token_messages_used: u64,
+    /// Messages active tokens allow in total
+    pub token_messages_allowed: u64,
+    pub relay_keys: u64,
+    /// Messages waiting to be forwarded to other relays
+    pub federation_queue: u64,
+    /// Anonymous token batches issued in the last hour
+    pub recent_token_issuances: u64,
+    /// Proof-of-work bits the next batch of anonymous tokens costs
+    pub token_pow_difficulty: u32,
+}
+
+/// Hex SHA-256 hash a token is stored under
+pub fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Parse a token ID as printed by `mint_token`
+pub fn parse_token_id(id: &str) -> Result<Uuid, DbErr> {
+    Uuid::parse_str(id).map_err(|e| DbErr::Custom(format!("Invalid token ID: {e}")))
+}
+
+/// Delete expired messages, blobs and challenges, and mark expired tokens
+pub async fn sweep_expired(db: &DatabaseConnection) -> Result<SweepReport, DbErr> {
+    let messages = relay_message::Entity::delete_many()
+        .filter(Expr::col(relay_message::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    let blobs = relay_blob::Entity::delete_many()
+        .filter(Expr::col(relay_blob::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    let tokens = message_token::Entity::update_many()
+        .col_expr(message_token::Column::Status, Expr::value(TOKEN_EXPIRED))
+        .filter(message_token::Column::Status.eq(TOKEN_ACTIVE))
+        .filter(Expr::col(message_token::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    let mut challenges = mailbox_challenge::Entity::delete_many()
+        .filter(Expr::col(mailbox_challenge::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    // Solved challenges stay until they no longer count toward the rate
+    challenges += pow_challenge::Entity::delete_many()
+        .filter(Expr::cust(
+            "(solved_at IS NULL AND expires_at <= CURRENT_TIMESTAMP) \
+             OR solved_at <= CURRENT_TIMESTAMP - INTERVAL '1 hour'",
+        ))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    let deposits = received_deposit::Entity::delete_many()
+        .filter(Expr::col(received_deposit::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    Ok(SweepReport {
+        messages,
+        blobs,
+        tokens,
+        challenges,
+        deposits,
+    })
+}
+
+/// Create a token allowing `max_messages` deposits for `valid_days`,
+/// within the quotas of the server configuration
+pub async fn mint_token(
+    db: &DatabaseConnection,
+    max_messages: i32,
+    valid_days: u32,
+) -> Result<MintedToken, DbErr> {
+    let quotas = &config().tokens;
+    if !(1..=quotas.max_messages_limit).contains(&max_messages) {
+        return Err(DbErr::Custom(format!(
+            "A token must allow between 1 and {} messages",
+            quotas.max_messages_limit
+        )));
+    }
+    if !(1..=quotas.max_valid_days).contains(&valid_days) {
+        return Err(DbErr::Custom(format!(
+            "A token must be valid for between 1 and {} days",
+            quotas.max_valid_days
+        )));
+    }
+
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+    let token = hex::encode(secret);
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+
+    let now = chrono::Utc::now().fixed_offset();
+    let expires_at = now + chrono::Duration::days(valid_days.into());
+    let model = message_token::ActiveModel {
+        id: Set(Uuid::from_bytes(id)),
+        token_hash: Set(token_hash(&token)),
+        expires_at: Set(expires_at),
+        max_messages: Set(max_messages),
+        messages_used: Set(0),
+        status: Set(TOKEN_ACTIVE.to_string()),
+        created_at: Set(now),
+        last_used_at: Set(None),
+    };
+    let model = message_token::Entity::insert(model)
+        .exec_with_returning(db)
+        .await?;
+
+    Ok(MintedToken {
+        id: model.id,
+        token,
+        max_messages: model.max_messages,
+        expires_at: model.expires_at,
+    })
+}
+
+/// Store a redeemed anonymous token under the hash of its secret, good for
+/// one message so deposits cannot be linked by the token that paid for
+/// them. Returns `None` if the token was redeemed before.
+pub async fn register_redeemed_token(
+    db: &DatabaseConnection,
+    token: &str,
+) -> Result<Option<MintedToken>, DbErr> {
+    let quotas = &config().tokens;
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+
+    let now = chrono::Utc::now().fixed_offset();
+    let expires_at = now + chrono::Duration::days(quotas.default_valid_days.into());
+    let model = message_token::ActiveModel {
+        id: Set(Uuid::from_bytes(id)),
+        token_hash: Set(token_hash(token)),
+        expires_at: Set(expires_at),
+        max_messages: Set(1),
+        messages_used: Set(0),
+        status: Set(TOKEN_ACTIVE.to_string()),
+        created_at: Set(now),
+        last_used_at: Set(None),
+    };
+    // The unique token hash turns a second redemption into a no-op
+    let inserted = message_token::Entity::insert(model)
+        .on_conflict(
+            OnConflict::column(message_token::Column::TokenHash)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    if inserted == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(MintedToken {
+        id: Uuid::from_bytes(id),
+        token: token.to_string(),
+        max_messages: 1,
+        expires_at,
+    }))
+}
+
+/// Spend one message of the token with secret `token`.
+/// The check and the increment are one statement, so concurrent deposits
+/// cannot spend more messages than the token allows. Returns false if the
+/// token is unknown, used up, revoked or expired.
+pub async fn spend_token(db: &impl ConnectionTrait, token: &str) -> Result<bool, DbErr> {
+    let result = message_token::Entity::update_many()
+        .col_expr(
+            message_token::Column::MessagesUsed,
+            Expr::col(message_token::Column::MessagesUsed).add(1),
+        )
+        .col_expr(
+            message_token::Column::LastUsedAt,
+            Expr::cust("CURRENT_TIMESTAMP"),
+        )
+        .filter(message_token::Column::TokenHash.eq(token_hash(token)))
+        .filter(message_token::Column::Status.eq(TOKEN_ACTIVE))
+        .filter(
+            Expr::col(message_token::Column::MessagesUsed)
+                .lt(Expr::col(message_token::Column::MaxMessages)),
+        )
+        .filter(Expr::col(message_token::Column::ExpiresAt).gt(Expr::current_timestamp()))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Anonymous token batches issued in the last hour
+pub async fn recent_token_issuances(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    pow_challenge::Entity::find()
+        .filter(Expr::cust(
+            "solved_at > CURRENT_TIMESTAMP - INTERVAL '1 hour'",
+        ))
+        .count(db)
+        .await
+}
+
+/// Proof-of-work bits the next batch of anonymous tokens costs after
+/// `issued` batches in the last hour
+pub fn token_pow_difficulty(issued: u64) -> u32 {
+    let quotas = &config().tokens;
+    shared::pow::adaptive_difficulty(
+        quotas.pow_base_difficulty,
+        quotas.pow_max_difficulty,
+        quotas.pow_target_per_hour,
+        issued,
+    )
+}
+
+/// Withdraw an active token. Returns false if there is no such active token.
+pub async fn revoke_token(db: &DatabaseConnection, id: Uuid) -> Result<bool, DbErr> {
+    let result = message_token::Entity::update_many()
+        .col_expr(message_token::Column::Status, Expr::value(TOKEN_REVOKED))
+        .filter(message_token::Column::Id.eq(id))
+        .filter(message_token::Column::Status.eq(TOKEN_ACTIVE))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Relay keys in use, newest first.
+/// Empty until `ensure_relay_key` created the first one.
+pub async fn current_relay_keys(db: &DatabaseConnection) -> Result<Vec<relay_key::Model>, DbErr> {
+    relay_key::Entity::find()
+        .order_by_desc(relay_key::Column::Id)
+        .all(db)
+        .await
+}
+
+/// Create the first relay key unless there is one.
+/// Replicas starting together insert the same ID, so only one key is kept.
+/// Returns whether a key was created.
+pub async fn ensure_relay_key(db: &DatabaseConnection) -> Result<bool, DbErr> {
+    if relay_key::Entity::find().one(db).await?.is_some() {
+        return Ok(false);
+    }
+
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let key = relay_key::ActiveModel {
+        id: Set(1),
+        public_key: Set(secret_key.public_key().as_bytes().to_vec()),
+        private_key: Set(secret_key.to_bytes().to_vec()),
+        created_at: Set(chrono::Utc::now().fixed_offset()),
+    };
+    let created = relay_key::Entity::insert(key)
+        .on_conflict(
+            OnConflict::column(relay_key::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(created > 0)
+}
+
+/// Generate a new relay key, keeping the previous one for messages in flight
+pub async fn rotate_relay_key(db: &DatabaseConnection) -> Result<RotatedKey, DbErr> {
+    // The new key and the retirement of old ones take effect together
+    let txn = db.begin().await?;
+    let previous = relay_key::Entity::find()
+        .order_by_desc(relay_key::Column::Id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?;
+
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let public_key = secret_key.public_key();
+    let key = relay_key::ActiveModel {
+        id: Set(previous.as_ref().map_or(1, |key| key.id + 1)),
+        public_key: Set(public_key.as_bytes().to_vec()),
+        private_key: Set(secret_key.to_bytes().to_vec()),
+        created_at: Set(chrono::Utc::now().fixed_offset()),
+    };
+    let key = relay_key::Entity::insert(key)
+        .exec_with_returning(&txn)
+        .await?;
+
+    let retired = match previous {
+        Some(previous) => {
+            relay_key::Entity::delete_many()
+                .filter(relay_key::Column::Id.lt(previous.id))
+                .exec(&txn)
+                .await?
+                .rows_affected
+        }
+        None => 0,
+    };
+    txn.commit().await?;
+
+    Ok(RotatedKey {
+        id: key.id,
+        public_key: hex::encode(key.public_key),
+        created_at: key.created_at,
+        retired,
+    })
+}
+
+/// Count what the relay is holding
+pub async fn queue_stats(db: &DatabaseConnection) -> Result<QueueStats, DbErr> {
+    let messages = relay_message::Entity::find().count(db).await?;
+    let expired_messages = relay_message::Entity::find()
+        .filter(Expr::col(relay_message::Column::ExpiresAt).lte(Expr::current_timestamp()))
+        .count(db)
+        .await?;
+    let mailboxes = relay_message::Entity::find()
+        .select_only()
+        .expr(Expr::cust("COUNT(DISTINCT recipient_hash)"))
+        .into_tuple::<i64>()
+        .one(db)
+        .await?
+        .unwrap_or(0);
+    let oldest_message = relay_message::Entity::find()
+        .select_only()
+        .column(relay_message::Column::CreatedAt)
+        .order_by_asc(relay_message::Column::CreatedAt)
+        .into_tuple::<DateTimeWithTimeZone>()
+        .one(db)
+        .await?;
+
+    let blobs = relay_blob::Entity::find().count(db).await?;
+    let blob_bytes = relay_blob::Entity::find()
+        .select_only()
+        .expr(Expr::cust("COALESCE(SUM(LENGTH(data)), 0)::BIGINT"))
+        .into_tuple::<i64>()
+        .one(db)
+        .await?
+        .unwrap_or(0);
+
+    let active_tokens = message_token::Entity::find()
+        .filter(message_token::Column::Status.eq(TOKEN_ACTIVE))
+        .filter(Expr::col(message_token::Column::ExpiresAt).gt(Expr::current_timestamp()));
+    let (token_messages_used, token_messages_allowed) = active_tokens
+        .clone()
+        .select_only()
+        .expr(Expr::cust("COALESCE(SUM(messages_used), 0)::BIGINT"))
+        .expr(Expr::cust("COALESCE(SUM(max_messages), 0)::BIGINT"))
+        .into_tuple::<(i64, i64)>()
+        .one(db)
+        .await?
+        .unwrap_or((0, 0));
+    let active_tokens = active_tokens.count(db).await?;
+    let relay_keys = relay_key::Entity::find().count(db).await?;
+    let federation_queue = federation_outbox::Entity::find().count(db).await?;
+    let recent_token_issuances = recent_token_issuances(db).await?;
+
+    Ok(QueueStats {
+        messages,
+        expired_messages,
+        mailboxes: mailboxes.max(0) as u64,
+        oldest_message,
+        blobs,
+        blob_bytes: blob_bytes.max(0) as u64,
+        active_tokens,
+        token_messages_used: token_messages_used.max(0) as u64,
+        token_messages_allowed: token_messages_allowed.max(0) as u64,
+        relay_keys,
+        federation_queue,
+        recent_token_issuances,
+        token_pow_difficulty: token_pow_difficulty(recent_token_issuances),
+    })
+}
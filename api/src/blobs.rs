@@ -0,0 +1,107 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Relay storage for encrypted attachment chunks.
+//! Blobs are addressed by the SHA-256 digest of their content, so the relay
+//! never needs to know what they contain and clients can verify what they get.
+
+use dioxus::prelude::*;
+
+#[server]
+pub async fn upload_blob(blob_id: String, data: Vec<u8>) -> Result<(), ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::relay_blob;
+        use sea_orm::sea_query::{Expr, OnConflict};
+        use sea_orm::{
+            ActiveValue::NotSet, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter,
+        };
+        use sha2::{Digest, Sha256};
+
+        let relay = &crate::config::config().relay;
+        if data.len() > relay.max_blob_bytes {
+            return Err(ServerFnError::new("Blob exceeds the maximum size"));
+        }
+        if hex::encode(Sha256::digest(&data)) != blob_id {
+            return Err(ServerFnError::new("Blob ID does not match its content"));
+        }
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let blob = relay_blob::ActiveModel {
+            id: Set(blob_id.clone()),
+            data: Set(data),
+            created_at: NotSet,
+            expires_at: NotSet,
+        };
+
+        // Identical content has an identical ID, so re-uploads are no-ops
+        relay_blob::Entity::insert(blob)
+            .on_conflict(
+                OnConflict::column(relay_blob::Column::Id)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to store blob: {}", e)))?;
+
+        // Re-uploading a blob keeps it for another retention period
+        let ttl_seconds = relay.blob_ttl_secs;
+        relay_blob::Entity::update_many()
+            .col_expr(
+                relay_blob::Column::ExpiresAt,
+                Expr::cust(format!(
+                    "CURRENT_TIMESTAMP + INTERVAL '{ttl_seconds} seconds'"
+                )),
+            )
+            .filter(relay_blob::Column::Id.eq(blob_id))
+            .exec(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to store blob: {}", e)))?;
+    }
+    Ok(())
+}
+
+#[server]
+pub async fn download_blob(blob_id: String) -> Result<Option<Vec<u8>>, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::relay_blob;
+        use sea_orm::sea_query::Expr;
+        use sea_orm::{EntityTrait, QueryFilter};
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let blob = relay_blob::Entity::find_by_id(blob_id)
+            .filter(Expr::col(relay_blob::Column::ExpiresAt).gt(Expr::current_timestamp()))
+            .one(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to load blob: {}", e)))?;
+
+        Ok(blob.map(|blob| blob.data))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Ok(None)
+    }
+}
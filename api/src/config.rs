@@ -0,0 +1,593 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Server configuration.
+//! Settings are read from a TOML file and can be overridden per key with
+//! `MEESEEKS_<SECTION>_<KEY>` environment variables, e.g.
+//! `MEESEEKS_DATABASE_MAX_CONNECTIONS=20`. `DATABASE_URL` is honored as an
+//! alias of `MEESEEKS_DATABASE_URL`. Missing keys keep their defaults.
+//! Lists such as `federation.peers` can only be set in the file.
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Config file read when no path is given and it exists
+pub const DEFAULT_CONFIG_FILE: &str = "meeseeks.toml";
+/// Environment variable naming the config file
+pub const CONFIG_FILE_ENV: &str = "MEESEEKS_CONFIG";
+/// Prefix of per key environment overrides
+const ENV_PREFIX: &str = "MEESEEKS_";
+
+/// Smallest blob that can hold an encrypted chunk: nonce, one byte and tag
+const MIN_BLOB_BYTES: usize = 12 + 1 + 16;
+/// Most proof-of-work bits a client can be asked for in reasonable time
+const MAX_POW_DIFFICULTY: u32 = 40;
+
+static CONFIG: OnceLock<ServerConfig> = OnceLock::new();
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub server: ListenConfig,
+    pub database: DatabaseConfig,
+    pub relay: RelayConfig,
+    pub tokens: TokenConfig,
+    pub federation: FederationConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    /// Address and port the server listens on
+    pub bind_address: String,
+    /// Bearer token `/metrics` requires, metrics are not served while empty
+    pub metrics_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// PostgreSQL connection URL
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub connect_timeout_secs: u64,
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub max_lifetime_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelayConfig {
+    /// How long a message waits in a mailbox at most
+    pub message_ttl_secs: u64,
+    /// How long receipts and typing indicators wait
+    pub ephemeral_ttl_secs: u64,
+    /// How long an attachment blob is kept
+    pub blob_ttl_secs: u64,
+    pub max_message_bytes: usize,
+    pub max_blob_bytes: usize,
+    /// How long a mailbox challenge can be answered
+    pub challenge_ttl_secs: u64,
+    /// How often expired messages, blobs and tokens are swept
+    pub sweep_interval_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    /// Messages a minted token allows unless told otherwise
+    pub default_max_messages: i32,
+    /// Days a minted token is valid unless told otherwise
+    pub default_valid_days: u32,
+    /// Most messages a single token may allow
+    pub max_messages_limit: i32,
+    /// Longest a token may be valid
+    pub max_valid_days: u32,
+    /// Whether clients may obtain anonymous tokens without an admin
+    pub anonymous_issuance: bool,
+    /// Anonymous tokens one proof of work pays for, each good for one message
+    pub anonymous_batch_size: u32,
+    /// Proof-of-work bits a batch of anonymous tokens costs at the target rate
+    pub pow_base_difficulty: u32,
+    /// Proof-of-work bits a batch of anonymous tokens costs at most
+    pub pow_max_difficulty: u32,
+    /// Anonymous token batches per hour above which the difficulty rises
+    pub pow_target_per_hour: u64,
+    /// How long a proof-of-work challenge can be answered
+    pub pow_challenge_ttl_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FederationConfig {
+    /// Whether messages are exchanged with other relays
+    pub enabled: bool,
+    /// URL other relays and clients reach this relay at
+    pub public_url: String,
+    /// Which relays are accepted, see `PeerPolicy`
+    pub policy: PeerPolicy,
+    /// How often queued messages are forwarded
+    pub forward_interval_secs: u64,
+    /// Attempts after which a queued message is dropped
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on every further one
+    pub retry_base_secs: u64,
+    /// Longest delay between retries
+    pub retry_max_secs: u64,
+    /// Deposits accepted per minute from each relay that is not a
+    /// configured peer, under the open policy
+    pub open_deposits_per_minute: u32,
+    /// Known relays, the only ones accepted under the allowlist policy
+    pub peers: Vec<PeerConfig>,
+}
+
+/// Which relays this relay federates with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerPolicy {
+    /// Only the configured peers, with their pinned keys
+    #[default]
+    Allowlist,
+    /// Any relay, keys of relays that are not configured are fetched from them
+    Open,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PeerConfig {
+    /// Public URL of the relay
+    pub url: String,
+    /// Hex encoded relay public key, as printed by `web keys rotate`
+    pub public_key: String,
+}
+
+impl Default for ListenConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:8080".to_string(),
+            metrics_token: String::new(),
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 50,
+            min_connections: 5,
+            connect_timeout_secs: 8,
+            acquire_timeout_secs: 8,
+            idle_timeout_secs: 8,
+            max_lifetime_secs: 30 * 60,
+        }
+    }
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            message_ttl_secs: 7 * 24 * 60 * 60,
+            ephemeral_ttl_secs: 60,
+            blob_ttl_secs: 30 * 24 * 60 * 60,
+            max_message_bytes: 64 * 1024,
+            // One encrypted 256 KiB chunk plus nonce and tag
+            max_blob_bytes: 12 + 256 * 1024 + 16,
+            challenge_ttl_secs: 60,
+            sweep_interval_secs: 5 * 60,
+        }
+    }
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            default_max_messages: 100,
+            default_valid_days: 30,
+            max_messages_limit: 10_000,
+            max_valid_days: 365,
+            anonymous_issuance: true,
+            anonymous_batch_size: 100,
+            pow_base_difficulty: 20,
+            pow_max_difficulty: 28,
+            pow_target_per_hour: 100,
+            pow_challenge_ttl_secs: 5 * 60,
+        }
+    }
+}
+
+impl Default for FederationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            public_url: String::new(),
+            policy: PeerPolicy::Allowlist,
+            forward_interval_secs: 10,
+            max_attempts: 12,
+            retry_base_secs: 30,
+            retry_max_secs: 60 * 60,
+            open_deposits_per_minute: 60,
+            peers: Vec::new(),
+        }
+    }
+}
+
+/// Why the configuration could not be loaded
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The config file could not be read
+    Read { path: PathBuf, message: String },
+    /// The config file or an override is not valid TOML for the settings
+    Parse { source: String, message: String },
+    /// Settings that were read but are not usable
+    Invalid(Vec<String>),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(f, "Cannot read config file {}: {message}", path.display())
+            }
+            ConfigError::Parse { source, message } => {
+                write!(f, "Invalid configuration in {source}: {message}")
+            }
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {problem}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    /// Load the configuration from `path`, or `MEESEEKS_CONFIG`, or
+    /// `meeseeks.toml` if it exists, then apply environment overrides and
+    /// validate the result
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let env_path = std::env::var_os(CONFIG_FILE_ENV).map(PathBuf::from);
+        let path = path
+            .map(Path::to_path_buf)
+            .or(env_path)
+            .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()));
+
+        let mut table = match &path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+                    path: path.clone(),
+                    message: e.to_string(),
+                })?;
+                text.parse::<toml::Table>()
+                    .map_err(|e| ConfigError::Parse {
+                        source: path.display().to_string(),
+                        message: e.to_string(),
+                    })?
+            }
+            None => toml::Table::new(),
+        };
+        apply_env_overrides(&mut table, |name| dotenvy::var(name).ok())?;
+
+        let source = path.map_or_else(
+            || "environment".to_string(),
+            |path| path.display().to_string(),
+        );
+        let config: Self = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse {
+                source,
+                message: e.to_string(),
+            })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that the settings can be used, listing every problem found
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        check(
+            self.server.bind_address.parse::<SocketAddr>().is_ok(),
+            "server.bind_address must be an address and port, e.g. 0.0.0.0:8080",
+        );
+
+        let database = &self.database;
+        check(
+            !database.url.is_empty(),
+            "database.url is not set, set it in the config file or DATABASE_URL",
+        );
+        check(
+            database.url.is_empty()
+                || database.url.starts_with("postgres://")
+                || database.url.starts_with("postgresql://"),
+            "database.url must be a postgres:// URL",
+        );
+        check(
+            database.max_connections >= 1,
+            "database.max_connections must be at least 1",
+        );
+        check(
+            database.min_connections <= database.max_connections,
+            "database.min_connections must not exceed database.max_connections",
+        );
+        check(
+            database.connect_timeout_secs >= 1
+                && database.acquire_timeout_secs >= 1
+                && database.idle_timeout_secs >= 1
+                && database.max_lifetime_secs >= 1,
+            "database timeouts and max_lifetime_secs must be at least 1 second",
+        );
+
+        let relay = &self.relay;
+        check(
+            relay.message_ttl_secs >= 1 && relay.blob_ttl_secs >= 1,
+            "relay.message_ttl_secs and relay.blob_ttl_secs must be at least 1 second",
+        );
+        check(
+            (1..=relay.message_ttl_secs).contains(&relay.ephemeral_ttl_secs),
+            "relay.ephemeral_ttl_secs must be between 1 and relay.message_ttl_secs",
+        );
+        check(
+            relay.max_message_bytes >= 1,
+            "relay.max_message_bytes must be at least 1",
+        );
+        check(
+            relay.max_blob_bytes >= MIN_BLOB_BYTES,
+            "relay.max_blob_bytes is too small for an encrypted chunk",
+        );
+        check(
+            (1..=3600).contains(&relay.challenge_ttl_secs),
+            "relay.challenge_ttl_secs must be between 1 and 3600 seconds",
+        );
+        check(
+            relay.sweep_interval_secs >= 1,
+            "relay.sweep_interval_secs must be at least 1 second",
+        );
+
+        let tokens = &self.tokens;
+        check(
+            tokens.max_messages_limit >= 1,
+            "tokens.max_messages_limit must be at least 1",
+        );
+        check(
+            (1..=tokens.max_messages_limit).contains(&tokens.default_max_messages),
+            "tokens.default_max_messages must be between 1 and tokens.max_messages_limit",
+        );
+        check(
+            (1..=3650).contains(&tokens.max_valid_days),
+            "tokens.max_valid_days must be between 1 and 3650",
+        );
+        check(
+            (1..=tokens.max_valid_days).contains(&tokens.default_valid_days),
+            "tokens.default_valid_days must be between 1 and tokens.max_valid_days",
+        );
+        check(
+            (1..=1000).contains(&tokens.anonymous_batch_size),
+            "tokens.anonymous_batch_size must be between 1 and 1000",
+        );
+        check(
+            tokens.pow_max_difficulty <= MAX_POW_DIFFICULTY,
+            "tokens.pow_max_difficulty must not exceed 40 bits",
+        );
+        check(
+            tokens.pow_base_difficulty <= tokens.pow_max_difficulty,
+            "tokens.pow_base_difficulty must not exceed tokens.pow_max_difficulty",
+        );
+        check(
+            tokens.pow_target_per_hour >= 1,
+            "tokens.pow_target_per_hour must be at least 1",
+        );
+        check(
+            (1..=3600).contains(&tokens.pow_challenge_ttl_secs),
+            "tokens.pow_challenge_ttl_secs must be between 1 and 3600 seconds",
+        );
+
+        let federation = &self.federation;
+        let is_url = |url: &str| url.starts_with("http://") || url.starts_with("https://");
+        check(
+            !federation.enabled || is_url(&federation.public_url),
+            "federation.public_url must be an http:// or https:// URL when federation is enabled",
+        );
+        check(
+            federation.forward_interval_secs >= 1 && federation.retry_base_secs >= 1,
+            "federation.forward_interval_secs and federation.retry_base_secs must be at least 1 second",
+        );
+        check(
+            federation.retry_max_secs >= federation.retry_base_secs,
+            "federation.retry_max_secs must not be less than federation.retry_base_secs",
+        );
+        check(
+            federation.max_attempts >= 1,
+            "federation.max_attempts must be at least 1",
+        );
+        check(
+            federation.open_deposits_per_minute >= 1,
+            "federation.open_deposits_per_minute must be at least 1",
+        );
+        for peer in &federation.peers {
+            check(
+                is_url(&peer.url),
+                &format!(
+                    "federation peer {:?} must have an http:// or https:// URL",
+                    peer.url
+                ),
+            );
+            let key = hex::decode(&peer.public_key).ok();
+            check(
+                key.is_some_and(|key| key.len() == 32)
+                    || (peer.public_key.is_empty() && federation.policy == PeerPolicy::Open),
+                &format!(
+                    "federation peer {:?} needs a 32 byte hex public_key",
+                    peer.url
+                ),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    /// The validated listen address
+    pub fn bind_address(&self) -> Option<SocketAddr> {
+        self.server.bind_address.parse().ok()
+    }
+}
+
+impl DatabaseConfig {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    pub fn max_lifetime(&self) -> Duration {
+        Duration::from_secs(self.max_lifetime_secs)
+    }
+}
+
+impl RelayConfig {
+    pub fn sweep_interval(&self) -> Duration {
+        Duration::from_secs(self.sweep_interval_secs)
+    }
+}
+
+impl FederationConfig {
+    pub fn forward_interval(&self) -> Duration {
+        Duration::from_secs(self.forward_interval_secs)
+    }
+
+    /// Delay before retrying a message that failed `attempts` times
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let delay = self
+            .retry_base_secs
+            .saturating_mul(1u64 << attempts.saturating_sub(1).min(32));
+        Duration::from_secs(delay.min(self.retry_max_secs))
+    }
+}
+
+/// Override keys of `table` from `MEESEEKS_<SECTION>_<KEY>` variables.
+/// Every known key is looked up, values are converted to the type of the
+/// key's default.
+pub(crate) fn apply_env_overrides(
+    table: &mut toml::Table,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError> {
+    let defaults =
+        toml::Value::try_from(ServerConfig::default()).map_err(|e| ConfigError::Parse {
+            source: "defaults".to_string(),
+            message: e.to_string(),
+        })?;
+    let Some(defaults) = defaults.as_table() else {
+        return Ok(());
+    };
+
+    for (section, keys) in defaults {
+        let Some(keys) = keys.as_table() else {
+            continue;
+        };
+        for (key, default) in keys {
+            if default.is_array() {
+                continue;
+            }
+            let name = format!("{ENV_PREFIX}{section}_{key}").to_uppercase();
+            let value = match var(&name) {
+                Some(value) => value,
+                // The conventional database variable is honored too
+                None if section == "database" && key == "url" => match var("DATABASE_URL") {
+                    Some(value) => value,
+                    None => continue,
+                },
+                None => continue,
+            };
+            let value = match default {
+                toml::Value::Integer(_) => value
+                    .trim()
+                    .parse::<i64>()
+                    .map(toml::Value::Integer)
+                    .map_err(|e| ConfigError::Parse {
+                        source: name.clone(),
+                        message: format!("expected a number: {e}"),
+                    })?,
+                toml::Value::Boolean(_) => value
+                    .trim()
+                    .parse::<bool>()
+                    .map(toml::Value::Boolean)
+                    .map_err(|e| ConfigError::Parse {
+                    source: name.clone(),
+                    message: format!("expected true or false: {e}"),
+                })?,
+                _ => toml::Value::String(value),
+            };
+            table
+                .entry(section.clone())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| ConfigError::Parse {
+                    source: name.clone(),
+                    message: format!("[{section}] must be a table"),
+                })?
+                .insert(key.clone(), value);
+        }
+    }
+    Ok(())
+}
+
+/// Make `config` the configuration of this process.
+/// Returns false if one was set before, which then stays in effect.
+pub fn init_config(config: ServerConfig) -> bool {
+    CONFIG.set(config).is_ok()
+}
+
+/// The configuration of this process, loaded on first use without
+/// `init_config`. A missing or invalid one is an error, never the defaults.
+pub fn try_config() -> Result<&'static ServerConfig, ConfigError> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = ServerConfig::load(None)?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The configuration of this process.
+/// Binaries load and report it at startup with `init_config`; using an
+/// invalid configuration that was never installed panics with its problems.
+pub fn config() -> &'static ServerConfig {
+    try_config().unwrap_or_else(|e| panic!("{e}"))
+}
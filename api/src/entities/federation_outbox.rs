@@ -0,0 +1,47 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "federation_outbox")]
+pub struct Model {
+    /// Mailbox entry ID the message gets on every relay
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: Uuid,
+    /// Public URL of the recipient's home relay
+    pub destination: String,
+    pub recipient_hash: String,
+    pub sender_public_key: Vec<u8>,
+    pub encrypted_content: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ephemeral: bool,
+    /// Failed forwarding attempts so far
+    pub attempts: i32,
+    pub next_attempt_at: DateTimeWithTimeZone,
+    pub last_error: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub expires_at: DateTimeWithTimeZone,
+    /// Hash of the secret the sender retracts the message with
+    pub retract_hash: Option<Vec<u8>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -20,3 +20,13 @@ pub mod message_token;
 pub mod relay_message;
 
 pub mod relay_key;
+
+pub mod relay_blob;
+
+pub mod mailbox_challenge;
+
+pub mod federation_outbox;
+
+pub mod pow_challenge;
+
+pub mod received_deposit;
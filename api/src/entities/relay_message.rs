@@ -29,6 +29,8 @@ pub struct Model {
     pub nonce: Vec<u8>,
     pub created_at: DateTimeWithTimeZone,
     pub expires_at: DateTimeWithTimeZone,
+    /// Hash of the secret the sender retracts the message with
+    pub retract_hash: Option<Vec<u8>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,693 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Federation between independent relays.
+//! A message for a recipient whose home relay is another server is queued
+//! in the outbox and forwarded server-to-server. Relays authenticate each
+//! other with their relay keys: every forwarded deposit carries a proof
+//! boxed from the origin's relay key to the destination's, see
+//! `shared::federation`. The `[federation]` policy decides which relays are
+//! accepted, and failed forwards are retried with exponential backoff until
+//! the message expires or runs out of attempts.
+//! Relays that are not configured peers are only contacted over https at
+//! public addresses, so a URL from a client or an incoming deposit cannot
+//! point the relay at internal services. Received deposits are remembered
+//! for their freshness window, so a replayed one is not stored again.
+//! Deposits from such relays are limited per origin before anything is
+//! fetched, and the keys fetched from them are cached for `PEER_KEY_TTL`.
+
+use crate::admin::current_relay_keys;
+use crate::config::{config, PeerPolicy};
+use crate::entities::{federation_outbox, received_deposit};
+use crate::messages::{
+    check_envelope, envelope_ttl, relay_message_id, store_message, RelayEnvelope,
+};
+use crate::metrics::METRICS;
+use dioxus::logger::tracing::warn;
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter,
+};
+use serde::{Deserialize, Serialize};
+use shared::federation::{
+    check_freshness, normalize_relay_url, seal_request, verify_request, RelayProof,
+    MAX_CLOCK_SKEW_SECS,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Path a relay publishes its relay keys at
+pub const KEYS_PATH: &str = "/federation/keys";
+/// Path a relay accepts forwarded deposits at
+pub const DEPOSIT_PATH: &str = "/federation/deposit";
+
+/// Longest a request to another relay may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Queued messages forwarded per run
+const FORWARD_BATCH: u64 = 20;
+/// How long a claimed message is left to the replica forwarding it.
+/// Every forward makes at most two requests, so the claim outlasts a run
+/// in which all of them time out.
+const CLAIM_SECS: u64 = FORWARD_BATCH * 2 * REQUEST_TIMEOUT.as_secs() + 60;
+/// How long keys fetched from a relay that is not pinned are reused
+const PEER_KEY_TTL: Duration = Duration::from_secs(10 * 60);
+/// How long a failed key fetch is remembered before trying again
+const PEER_KEY_RETRY: Duration = Duration::from_secs(60);
+/// Origins tracked by the deposit limit before old windows are dropped
+const MAX_TRACKED_ORIGINS: usize = 10_000;
+
+/// Public relay keys of a relay, newest first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayKeys {
+    pub url: String,
+    pub public_keys: Vec<String>,
+}
+
+/// A message forwarded to the recipient's home relay
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FederatedDeposit {
+    /// Public URL of the forwarding relay
+    pub origin: String,
+    /// Public URL of the receiving relay
+    pub destination: String,
+    pub recipient_hash: String,
+    pub envelope: RelayEnvelope,
+    /// Unix time the deposit was sent at
+    pub sent_at: u64,
+}
+
+/// A forwarded deposit with the proof of the relay that sent it.
+/// The proof covers the exact JSON of the deposit, so it travels as text.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedDeposit {
+    pub deposit: String,
+    pub proof: RelayProof,
+}
+
+/// Outcome of a forwarding run
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ForwardReport {
+    pub forwarded: u64,
+    /// Attempts that failed and are retried later
+    pub failed: u64,
+    /// Messages given up on, expired or out of attempts
+    pub dropped: u64,
+}
+
+/// Why a forwarded deposit was not accepted
+#[derive(Debug, Clone, PartialEq)]
+pub enum DepositError {
+    /// This relay does not federate
+    Disabled,
+    /// The deposit or its envelope cannot be read
+    Malformed(String),
+    /// The origin is not accepted by the peer policy
+    UnknownPeer(String),
+    /// The proof, destination or timestamp do not check out
+    Rejected(String),
+    /// The deposit could not be stored
+    Unavailable(String),
+    /// The origin sent more deposits than its limit allows
+    Throttled(String),
+}
+
+impl std::fmt::Display for DepositError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DepositError::Disabled => write!(f, "Federation is disabled on this relay"),
+            DepositError::Malformed(message) => write!(f, "Malformed deposit: {message}"),
+            DepositError::UnknownPeer(message) => write!(f, "Unknown peer: {message}"),
+            DepositError::Rejected(message) => write!(f, "Deposit rejected: {message}"),
+            DepositError::Unavailable(message) => write!(f, "Deposit not stored: {message}"),
+            DepositError::Throttled(message) => write!(f, "Too many deposits: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for DepositError {}
+
+/// Whether `relay` is the public URL of this relay
+pub fn is_local(relay: &str) -> bool {
+    normalize_relay_url(relay) == normalize_relay_url(&config().federation.public_url)
+}
+
+/// Whether messages for `relay` are forwarded rather than kept here.
+/// Without federation every message stays on this relay, as it always did.
+pub fn is_remote(relay: &str) -> bool {
+    config().federation.enabled && !is_local(relay)
+}
+
+/// Requests to other relays never follow redirects, which could lead past
+/// the address checks
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| client_builder().build().unwrap_or_default())
+}
+
+fn is_configured_peer(relay: &str) -> bool {
+    let url = normalize_relay_url(relay);
+    config()
+        .federation
+        .peers
+        .iter()
+        .any(|peer| normalize_relay_url(&peer.url) == url)
+}
+
+/// Whether `ip` is a public unicast address rather than a private,
+/// loopback, link-local or otherwise reserved one
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Shared address space of carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b == 18 || b == 19))
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(ip));
+            }
+            let [first, second, ..] = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local and link-local
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                // Documentation
+                || (first == 0x2001 && second == 0x0db8))
+        }
+    }
+}
+
+/// Client for requests to `relay`, refused unless the peer policy accepts it.
+/// Configured peers are reached as configured. Other relays, accepted under
+/// the open policy, need an https URL whose host resolves to public
+/// addresses only, and the client is pinned to the address checked so a
+/// second lookup cannot lead elsewhere.
+async fn peer_client(relay: &str) -> Result<reqwest::Client, String> {
+    if is_configured_peer(relay) {
+        return Ok(http_client().clone());
+    }
+    if config().federation.policy == PeerPolicy::Allowlist {
+        return Err(format!("{relay} is not a configured peer"));
+    }
+
+    let url = reqwest::Url::parse(relay.trim()).map_err(|e| format!("{relay} is no URL: {e}"))?;
+    if url.scheme() != "https" {
+        return Err(format!("{relay} is not an https URL"));
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    let Some(host) = url.host_str() else {
+        return Err(format!("{relay} has no host"));
+    };
+    // IPv6 literals keep their brackets in the host
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let (domain, addresses): (Option<&str>, Vec<SocketAddr>) = match literal.parse::<IpAddr>() {
+        Ok(ip) => (None, vec![SocketAddr::new(ip, port)]),
+        Err(_) => {
+            let addresses = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| format!("{relay} cannot be resolved: {e}"))?
+                .collect();
+            (Some(host), addresses)
+        }
+    };
+    let Some(address) = addresses.first().copied() else {
+        return Err(format!("{relay} cannot be resolved"));
+    };
+    if !addresses
+        .iter()
+        .all(|address| is_public_address(address.ip()))
+    {
+        return Err(format!("{relay} does not resolve to a public address"));
+    }
+
+    match domain {
+        Some(domain) => client_builder()
+            .resolve(domain, address)
+            .build()
+            .map_err(|e| e.to_string()),
+        None => Ok(http_client().clone()),
+    }
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+fn endpoint(relay: &str, path: &str) -> String {
+    format!("{}{path}", relay.trim().trim_end_matches('/'))
+}
+
+async fn own_secrets(db: &DatabaseConnection) -> Result<Vec<[u8; 32]>, DbErr> {
+    Ok(current_relay_keys(db)
+        .await?
+        .into_iter()
+        .filter_map(|key| <[u8; 32]>::try_from(key.private_key.as_slice()).ok())
+        .collect())
+}
+
+/// The relay keys this relay publishes at `KEYS_PATH`
+pub async fn relay_keys(db: &DatabaseConnection) -> Result<RelayKeys, DbErr> {
+    Ok(RelayKeys {
+        url: config().federation.public_url.clone(),
+        public_keys: current_relay_keys(db)
+            .await?
+            .into_iter()
+            .map(|key| hex::encode(key.public_key))
+            .collect(),
+    })
+}
+
+/// Deposits per origin in the current minute, for relays that are not
+/// configured peers
+#[derive(Debug, Default)]
+pub struct DepositLimiter {
+    minute: u64,
+    counts: HashMap<String, u32>,
+}
+
+impl DepositLimiter {
+    /// Count a deposit from `origin` at unix time `now`, false once the
+    /// origin has made `limit` deposits this minute
+    pub fn allow(&mut self, origin: &str, now: u64, limit: u32) -> bool {
+        let minute = now / 60;
+        if minute != self.minute {
+            self.minute = minute;
+            self.counts.clear();
+        }
+        // A flood of distinct origins only costs memory until the minute ends
+        if self.counts.len() >= MAX_TRACKED_ORIGINS && !self.counts.contains_key(origin) {
+            return false;
+        }
+        let count = self.counts.entry(origin.to_string()).or_default();
+        if *count >= limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+fn deposit_limiter() -> &'static Mutex<DepositLimiter> {
+    static LIMITER: OnceLock<Mutex<DepositLimiter>> = OnceLock::new();
+    LIMITER.get_or_init(Mutex::default)
+}
+
+/// Keys fetched from relays that are not pinned, with when they were
+/// fetched. Failed fetches are kept too, so they are not retried at once.
+type FetchedKeys = HashMap<String, (Instant, Result<Vec<[u8; 32]>, String>)>;
+
+fn fetched_keys() -> &'static Mutex<FetchedKeys> {
+    static KEYS: OnceLock<Mutex<FetchedKeys>> = OnceLock::new();
+    KEYS.get_or_init(Mutex::default)
+}
+
+/// Keys `relay` may use, newest first, according to the peer policy.
+/// Configured peers use their pinned key, under the open policy the keys
+/// of other relays are fetched from them with `client`, see `peer_client`,
+/// and reused for `PEER_KEY_TTL`.
+async fn peer_keys(relay: &str, client: &reqwest::Client) -> Result<Vec<[u8; 32]>, String> {
+    let federation = &config().federation;
+    let url = normalize_relay_url(relay);
+    let peer = federation
+        .peers
+        .iter()
+        .find(|peer| normalize_relay_url(&peer.url) == url);
+
+    let pinned = peer.filter(|peer| !peer.public_key.is_empty());
+    if let Some(key) = pinned.and_then(|peer| hex::decode(&peer.public_key).ok()) {
+        let key = <[u8; 32]>::try_from(key.as_slice())
+            .map_err(|_| format!("{relay} has an invalid pinned key"))?;
+        return Ok(vec![key]);
+    }
+    if peer.is_none() && federation.policy == PeerPolicy::Allowlist {
+        return Err(format!("{relay} is not a configured peer"));
+    }
+
+    let cached = fetched_keys()
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(&url)
+        .filter(|(fetched_at, keys)| {
+            let ttl = if keys.is_ok() {
+                PEER_KEY_TTL
+            } else {
+                PEER_KEY_RETRY
+            };
+            fetched_at.elapsed() < ttl
+        })
+        .map(|(_, keys)| keys.clone());
+    if let Some(keys) = cached {
+        return keys;
+    }
+    let keys = fetch_keys(relay, &url, client).await;
+    let mut fetched = fetched_keys().lock().map_err(|e| e.to_string())?;
+    fetched.retain(|_, (fetched_at, _)| fetched_at.elapsed() < PEER_KEY_TTL);
+    fetched.insert(url, (Instant::now(), keys.clone()));
+    keys
+}
+
+/// Fetch the relay keys `relay` publishes, `url` being its normalized URL
+async fn fetch_keys(
+    relay: &str,
+    url: &str,
+    client: &reqwest::Client,
+) -> Result<Vec<[u8; 32]>, String> {
+    let keys: RelayKeys = client
+        .get(endpoint(relay, KEYS_PATH))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Fetching the keys of {relay} failed: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("{relay} sent invalid keys: {e}"))?;
+    if normalize_relay_url(&keys.url) != url {
+        return Err(format!("{relay} claims to be {}", keys.url));
+    }
+    let keys: Vec<[u8; 32]> = keys
+        .public_keys
+        .iter()
+        .filter_map(|key| hex::decode(key).ok())
+        .filter_map(|key| <[u8; 32]>::try_from(key.as_slice()).ok())
+        .collect();
+    if keys.is_empty() {
+        return Err(format!("{relay} published no relay keys"));
+    }
+    Ok(keys)
+}
+
+/// Queue a checked envelope for the relay at `destination`.
+/// Queuing the same message again keeps a single copy.
+pub async fn enqueue(
+    db: &impl ConnectionTrait,
+    id: Uuid,
+    destination: &str,
+    recipient_hash: String,
+    envelope: RelayEnvelope,
+) -> Result<(), DbErr> {
+    if !config().federation.enabled {
+        return Err(DbErr::Custom(
+            "Federation is disabled on this relay".to_string(),
+        ));
+    }
+    // The destination comes from the client, it is checked before queuing
+    peer_client(destination).await.map_err(DbErr::Custom)?;
+
+    let now = chrono::Utc::now().fixed_offset();
+    let ttl = envelope_ttl(&envelope);
+    let entry = federation_outbox::ActiveModel {
+        id: Set(id),
+        destination: Set(normalize_relay_url(destination)),
+        recipient_hash: Set(recipient_hash),
+        sender_public_key: Set(envelope.sender_public_key),
+        encrypted_content: Set(envelope.ciphertext),
+        nonce: Set(envelope.nonce),
+        ephemeral: Set(envelope.ephemeral),
+        retract_hash: Set(envelope.retract_hash),
+        attempts: Set(0),
+        next_attempt_at: Set(now),
+        last_error: Set(None),
+        created_at: Set(now),
+        expires_at: Set(now + chrono::Duration::seconds(ttl as i64)),
+    };
+    federation_outbox::Entity::insert(entry)
+        .on_conflict(
+            OnConflict::column(federation_outbox::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
+
+/// Forward the queued messages that are due.
+/// Messages are claimed before forwarding, so replicas running this at the
+/// same time do not send a message twice.
+pub async fn forward_due(db: &DatabaseConnection) -> Result<ForwardReport, DbErr> {
+    let federation = &config().federation;
+    let mut report = ForwardReport::default();
+
+    let due = federation_outbox::Entity::update_many()
+        .col_expr(
+            federation_outbox::Column::NextAttemptAt,
+            Expr::cust(format!(
+                "CURRENT_TIMESTAMP + INTERVAL '{CLAIM_SECS} seconds'"
+            )),
+        )
+        .filter(Expr::cust(format!(
+            "id IN (SELECT id FROM federation_outbox \
+             WHERE next_attempt_at <= CURRENT_TIMESTAMP \
+             ORDER BY next_attempt_at LIMIT {FORWARD_BATCH} FOR UPDATE SKIP LOCKED)"
+        )))
+        .exec_with_returning(db)
+        .await?;
+    if due.is_empty() {
+        return Ok(report);
+    }
+    let Some(origin_secret) = own_secrets(db).await?.into_iter().next() else {
+        return Err(DbErr::Custom("No relay key to sign with".to_string()));
+    };
+
+    for entry in due {
+        let now = chrono::Utc::now().fixed_offset();
+        if entry.expires_at <= now {
+            delete_entry(db, entry.id).await?;
+            report.dropped += 1;
+            continue;
+        }
+        let id = entry.id;
+        let attempts = entry.attempts.max(0) as u32 + 1;
+        let destination = entry.destination.clone();
+        match forward(&origin_secret, entry, now).await {
+            Ok(()) => {
+                delete_entry(db, id).await?;
+                report.forwarded += 1;
+            }
+            Err(e) if attempts >= federation.max_attempts => {
+                warn!("Dropping message for {destination} after {attempts} attempts: {e}");
+                delete_entry(db, id).await?;
+                report.dropped += 1;
+            }
+            Err(e) => {
+                let delay = federation.retry_delay(attempts).as_secs() as i64;
+                let retry_at = now + chrono::Duration::seconds(delay);
+                federation_outbox::Entity::update_many()
+                    .col_expr(
+                        federation_outbox::Column::Attempts,
+                        Expr::value(attempts as i32),
+                    )
+                    .col_expr(
+                        federation_outbox::Column::NextAttemptAt,
+                        Expr::value(retry_at),
+                    )
+                    .col_expr(federation_outbox::Column::LastError, Expr::value(e))
+                    .filter(federation_outbox::Column::Id.eq(id))
+                    .exec(db)
+                    .await?;
+                report.failed += 1;
+            }
+        }
+    }
+
+    METRICS.record_forward(&report);
+    Ok(report)
+}
+
+async fn delete_entry(db: &DatabaseConnection, id: Uuid) -> Result<(), DbErr> {
+    federation_outbox::Entity::delete_by_id(id).exec(db).await?;
+    Ok(())
+}
+
+/// Send one queued message to its destination
+async fn forward(
+    origin_secret: &[u8; 32],
+    entry: federation_outbox::Model,
+    now: DateTimeWithTimeZone,
+) -> Result<(), String> {
+    let client = peer_client(&entry.destination).await?;
+    let destination_key = peer_keys(&entry.destination, &client)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("{} has no relay key", entry.destination))?;
+
+    // The destination keeps the message only as long as it has left here
+    let remaining = (entry.expires_at - now).num_seconds().max(1) as u64;
+    let deposit = FederatedDeposit {
+        origin: config().federation.public_url.clone(),
+        destination: entry.destination.clone(),
+        recipient_hash: entry.recipient_hash,
+        envelope: RelayEnvelope {
+            id: None,
+            sender_public_key: entry.sender_public_key,
+            ciphertext: entry.encrypted_content,
+            nonce: entry.nonce,
+            ephemeral: entry.ephemeral,
+            expires_in: Some(remaining),
+            retract_hash: entry.retract_hash,
+        },
+        sent_at: unix_now(),
+    };
+    let deposit = serde_json::to_string(&deposit).map_err(|e| e.to_string())?;
+    let proof = seal_request(origin_secret, &destination_key, deposit.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let response = client
+        .post(endpoint(&entry.destination, DEPOSIT_PATH))
+        .json(&SignedDeposit { deposit, proof })
+        .send()
+        .await
+        .map_err(|e| format!("Forwarding to {} failed: {e}", entry.destination))?;
+    if response.status().is_success() {
+        return Ok(());
+    }
+    let status = response.status();
+    let reason = response.text().await.unwrap_or_default();
+    Err(format!(
+        "{} refused the message ({status}): {reason}",
+        entry.destination
+    ))
+}
+
+/// Accept a deposit forwarded by another relay
+pub async fn receive_deposit(
+    db: &DatabaseConnection,
+    signed: SignedDeposit,
+) -> Result<(), DepositError> {
+    let result = check_and_store(db, signed).await;
+    METRICS.record_federated_deposit(result.is_ok());
+    result
+}
+
+async fn check_and_store(
+    db: &DatabaseConnection,
+    signed: SignedDeposit,
+) -> Result<(), DepositError> {
+    if !config().federation.enabled {
+        return Err(DepositError::Disabled);
+    }
+    let deposit: FederatedDeposit = serde_json::from_str(&signed.deposit)
+        .map_err(|e| DepositError::Malformed(e.to_string()))?;
+    if !is_local(&deposit.destination) {
+        return Err(DepositError::Rejected(format!(
+            "addressed to {}",
+            deposit.destination
+        )));
+    }
+    check_freshness(deposit.sent_at, unix_now())
+        .map_err(|e| DepositError::Rejected(e.to_string()))?;
+
+    let federation = &config().federation;
+    if federation.policy == PeerPolicy::Open && !is_configured_peer(&deposit.origin) {
+        let allowed = deposit_limiter()
+            .lock()
+            .map_err(|e| DepositError::Unavailable(e.to_string()))?
+            .allow(
+                &normalize_relay_url(&deposit.origin),
+                unix_now(),
+                federation.open_deposits_per_minute,
+            );
+        if !allowed {
+            return Err(DepositError::Throttled(deposit.origin));
+        }
+    }
+
+    // Nothing is fetched for an origin the policy does not accept
+    let client = peer_client(&deposit.origin)
+        .await
+        .map_err(DepositError::UnknownPeer)?;
+    let origin_keys = peer_keys(&deposit.origin, &client)
+        .await
+        .map_err(DepositError::UnknownPeer)?;
+    let own_secrets = own_secrets(db)
+        .await
+        .map_err(|e| DepositError::Unavailable(e.to_string()))?;
+    verify_request(
+        &own_secrets,
+        &origin_keys,
+        signed.deposit.as_bytes(),
+        &signed.proof,
+    )
+    .map_err(|e| DepositError::Rejected(e.to_string()))?;
+
+    check_envelope(&deposit.recipient_hash, &deposit.envelope)
+        .map_err(|e| DepositError::Malformed(e.to_string()))?;
+
+    // Deposits are known by their message ID, the same on both relays. One
+    // received before is accepted again without storing it, so a replay
+    // cannot bring back a message the recipient already acknowledged.
+    let id = relay_message_id(&deposit.recipient_hash, &deposit.envelope.nonce)
+        .map_err(|e| DepositError::Malformed(e.to_string()))?;
+    let received = received_deposit::Entity::find_by_id(id)
+        .one(db)
+        .await
+        .map_err(|e| DepositError::Unavailable(e.to_string()))?;
+    if received.is_some() {
+        return Ok(());
+    }
+
+    store_message(db, deposit.recipient_hash, deposit.envelope)
+        .await
+        .map_err(|e| DepositError::Unavailable(e.to_string()))?;
+    remember_deposit(db, id)
+        .await
+        .map_err(|e| DepositError::Unavailable(e.to_string()))
+}
+
+/// Remember a stored deposit for as long as a replay of it could pass the
+/// freshness check
+async fn remember_deposit(db: &DatabaseConnection, id: Uuid) -> Result<(), DbErr> {
+    let now = chrono::Utc::now().fixed_offset();
+    let entry = received_deposit::ActiveModel {
+        id: Set(id),
+        received_at: Set(now),
+        expires_at: Set(now + chrono::Duration::seconds(2 * MAX_CLOCK_SKEW_SECS as i64)),
+    };
+    received_deposit::Entity::insert(entry)
+        .on_conflict(
+            OnConflict::column(received_deposit::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
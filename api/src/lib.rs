@@ -15,12 +15,46 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+#[cfg(not(target_arch = "wasm32"))]
+pub mod admin;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod entities;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod federation;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod live;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod migration;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod persistence;
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test_config;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test_federation;
+
+mod blobs;
+pub use blobs::{download_blob, upload_blob};
+
+mod tokens;
+#[cfg(not(target_arch = "wasm32"))]
+pub use tokens::redeem_token_apart;
+pub use tokens::{
+    issue_tokens, redeem_token, request_token_challenge, token_issuer_key, AnonymousToken,
+    BlindSignature, PowChallenge, RedeemedToken, TokenIssuerKey, REDEEM_TOKEN_PATH,
+};
+
+mod messages;
+pub use messages::{
+    acknowledge_messages, deposit_message, fetch_messages, live_messages,
+    request_mailbox_challenge, retract_message, LiveEvent, LiveRequest, MailboxChallenge,
+    MailboxProof, RelayEnvelope, TOKEN_REFUSED,
+};
+
 use dioxus::prelude::*;
 
 #[server]
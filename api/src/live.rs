@@ -0,0 +1,88 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Fan-out of relay deposits to live delivery connections.
+//! A database trigger announces every deposit on the `relay_messages`
+//! channel with the recipient's mailbox hash. Each server process listens
+//! once and passes the hashes on to its WebSocket subscribers, so a message
+//! reaches its recipient whichever replica it was deposited on.
+
+use crate::persistence::postgres::establish_connection;
+use dioxus::logger::tracing::warn;
+use sea_orm::sqlx::postgres::PgListener;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Postgres channel the deposit trigger notifies
+pub const CHANNEL: &str = "relay_messages";
+
+/// Wait before listening again after the database connection failed
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Hashes buffered per subscriber before it has to check all its mailboxes
+const BUFFER: usize = 1024;
+
+/// A mailbox that received a deposit, or `None` when deposits may have
+/// been missed and every mailbox should be checked
+pub type Deposited = Option<String>;
+
+static DEPOSITS: OnceLock<broadcast::Sender<Deposited>> = OnceLock::new();
+
+/// Receive the mailbox hashes of new deposits.
+/// The first call starts listening for the whole process.
+pub fn subscribe() -> broadcast::Receiver<Deposited> {
+    DEPOSITS
+        .get_or_init(|| {
+            let (sender, _) = broadcast::channel(BUFFER);
+            tokio::spawn(listen(sender.clone()));
+            sender
+        })
+        .subscribe()
+}
+
+async fn listen(sender: broadcast::Sender<Deposited>) {
+    loop {
+        if let Err(e) = listen_until_error(&sender).await {
+            warn!("Listening for relay deposits failed: {e}");
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+async fn listen_until_error(sender: &broadcast::Sender<Deposited>) -> Result<(), String> {
+    let db = establish_connection().await.map_err(|e| e.to_string())?;
+    let mut listener = PgListener::connect_with(db.get_postgres_connection_pool())
+        .await
+        .map_err(|e| e.to_string())?;
+    listener.listen(CHANNEL).await.map_err(|e| e.to_string())?;
+
+    // Deposits made while we were not listening are only found by a check
+    let _ = sender.send(None);
+    loop {
+        // Without subscribers there is nobody to tell, so send errors are ignored
+        match listener.try_recv().await.map_err(|e| e.to_string())? {
+            Some(notification) => {
+                let _ = sender.send(Some(notification.payload().to_string()));
+            }
+            // The connection dropped and is restored by the next call
+            None => {
+                let _ = sender.send(None);
+            }
+        }
+    }
+}
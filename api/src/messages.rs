@@ -0,0 +1,667 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Relay mailboxes for encrypted messages.
+//! Messages are addressed by the SHA-256 hash of the recipient's room public
+//! key and stay in the mailbox until the recipient acknowledges them by ID,
+//! so a message is not lost when the client fails before applying it.
+//! Every deposit spends one message of a message token, see `crate::tokens`.
+//! Fetching and acknowledging each require answering a single-use challenge
+//! with the room's secret key, so knowing a mailbox address is not enough
+//! to read or drain it. A sender can retract a message that is still
+//! waiting with a secret whose hash was deposited along with it.
+//! Messages for recipients on other relays are forwarded to them, see
+//! `crate::federation`.
+//! Instead of polling, clients can keep a live connection open and have
+//! messages pushed as soon as they are deposited.
+//! Ephemeral envelopes (receipts, typing indicators) expire after a short TTL,
+//! and disappearing messages expire when their sender's timer runs out.
+
+use dioxus::fullstack::{WebSocketOptions, Websocket};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Error a deposit fails with when its message token is not accepted.
+/// Clients drop such a token and obtain a new one.
+pub const TOKEN_REFUSED: &str = "Message token refused";
+
+/// Mailbox entry ID of a message.
+/// Nonces are random per message, so a retried deposit maps to the same ID.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn relay_message_id(
+    recipient_hash: &str,
+    nonce: &[u8],
+) -> Result<sea_orm::prelude::Uuid, ServerFnError> {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::new()
+        .chain_update(recipient_hash.as_bytes())
+        .chain_update(nonce)
+        .finalize();
+    sea_orm::prelude::Uuid::from_slice(&digest[..16])
+        .map_err(|e| ServerFnError::new(format!("Invalid message ID: {}", e)))
+}
+
+/// An encrypted message as it is stored in a relay mailbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RelayEnvelope {
+    /// Mailbox entry ID, set on fetched envelopes to acknowledge them with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub sender_public_key: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub nonce: Vec<u8>,
+    #[serde(default)]
+    pub ephemeral: bool,
+    /// Seconds until a disappearing message expires
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    /// Hash of the secret the sender can retract the message with, see
+    /// `retract_message`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retract_hash: Option<Vec<u8>>,
+}
+
+/// A mailbox ownership challenge, answered with a `MailboxProof`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MailboxChallenge {
+    pub id: String,
+    pub recipient_hash: String,
+    pub server_public_key: Vec<u8>,
+    pub challenge: Vec<u8>,
+}
+
+/// Proof that the client holds the room key of a mailbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MailboxProof {
+    pub challenge_id: String,
+    pub public_key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<shared::mailbox::MailboxChallenge> for MailboxChallenge {
+    fn from(challenge: shared::mailbox::MailboxChallenge) -> Self {
+        Self {
+            id: challenge.id,
+            recipient_hash: challenge.recipient_hash,
+            server_public_key: challenge.server_public_key,
+            challenge: challenge.challenge,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<MailboxChallenge> for shared::mailbox::MailboxChallenge {
+    fn from(challenge: MailboxChallenge) -> Self {
+        Self {
+            id: challenge.id,
+            recipient_hash: challenge.recipient_hash,
+            server_public_key: challenge.server_public_key,
+            challenge: challenge.challenge,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<shared::mailbox::MailboxProof> for MailboxProof {
+    fn from(proof: shared::mailbox::MailboxProof) -> Self {
+        Self {
+            challenge_id: proof.challenge_id,
+            public_key: proof.public_key,
+            nonce: proof.nonce,
+            ciphertext: proof.ciphertext,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<MailboxProof> for shared::mailbox::MailboxProof {
+    fn from(proof: MailboxProof) -> Self {
+        Self {
+            challenge_id: proof.challenge_id,
+            public_key: proof.public_key,
+            nonce: proof.nonce,
+            ciphertext: proof.ciphertext,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_recipient_hash(recipient_hash: &str) -> bool {
+    recipient_hash.len() == 64 && hex::decode(recipient_hash).is_ok()
+}
+
+/// Check that an envelope can be stored in the mailbox of `recipient_hash`
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn check_envelope(
+    recipient_hash: &str,
+    envelope: &RelayEnvelope,
+) -> Result<(), ServerFnError> {
+    if !is_recipient_hash(recipient_hash) {
+        return Err(ServerFnError::new("Invalid recipient hash"));
+    }
+    if envelope.sender_public_key.len() != 32
+        || envelope.nonce.len() != 24
+        || envelope
+            .retract_hash
+            .as_ref()
+            .is_some_and(|hash| hash.len() != 32)
+    {
+        return Err(ServerFnError::new("Invalid message envelope"));
+    }
+    if envelope.ciphertext.len() > crate::config::config().relay.max_message_bytes {
+        return Err(ServerFnError::new("Message exceeds the maximum size"));
+    }
+    Ok(())
+}
+
+/// Seconds an envelope is kept.
+/// Envelopes never outlive the configured TTL, only expire sooner.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn envelope_ttl(envelope: &RelayEnvelope) -> u64 {
+    let relay = &crate::config::config().relay;
+    if envelope.ephemeral {
+        relay.ephemeral_ttl_secs
+    } else {
+        envelope
+            .expires_in
+            .map_or(relay.message_ttl_secs, |seconds| {
+                seconds.clamp(1, relay.message_ttl_secs)
+            })
+    }
+}
+
+/// Put a checked envelope into the mailbox of `recipient_hash`.
+/// Depositing the same envelope again keeps a single copy.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn store_message(
+    db: &impl sea_orm::ConnectionTrait,
+    recipient_hash: String,
+    envelope: RelayEnvelope,
+) -> Result<(), ServerFnError> {
+    use crate::entities::relay_message;
+    use sea_orm::sea_query::OnConflict;
+    use sea_orm::{ActiveValue::NotSet, ActiveValue::Set, EntityTrait};
+
+    let id = relay_message_id(&recipient_hash, &envelope.nonce)?;
+    // Set with the row so a retried deposit cannot extend the TTL
+    let expires_at = chrono::Utc::now().fixed_offset()
+        + chrono::Duration::seconds(envelope_ttl(&envelope) as i64);
+    let message = relay_message::ActiveModel {
+        id: Set(id),
+        recipient_hash: Set(recipient_hash),
+        sender_public_key: Set(envelope.sender_public_key),
+        encrypted_content: Set(envelope.ciphertext),
+        nonce: Set(envelope.nonce),
+        created_at: NotSet,
+        expires_at: Set(expires_at),
+        retract_hash: Set(envelope.retract_hash),
+    };
+
+    relay_message::Entity::insert(message)
+        .on_conflict(
+            OnConflict::column(relay_message::Column::Id)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to store message: {}", e)))?;
+
+    crate::metrics::METRICS.record_deposit();
+    Ok(())
+}
+
+/// Deposit a message for `recipient_hash`, spending one message of `token`.
+/// `relay` names the recipient's home relay; when it is another relay the
+/// message is queued and forwarded there instead of stored here.
+/// The token is only spent if the message is kept.
+#[server]
+pub async fn deposit_message(
+    recipient_hash: String,
+    envelope: RelayEnvelope,
+    relay: Option<String>,
+    token: String,
+) -> Result<(), ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        check_envelope(&recipient_hash, &envelope)?;
+
+        use sea_orm::TransactionTrait;
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        // Spending and storing commit together, so a failed deposit keeps
+        // its message on the token
+        let txn = db
+            .begin()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to begin deposit: {}", e)))?;
+        let spent = crate::admin::spend_token(&txn, &token)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to spend token: {}", e)))?;
+        if !spent {
+            crate::metrics::METRICS.record_refused_deposit();
+            return Err(ServerFnError::new(TOKEN_REFUSED));
+        }
+
+        match relay.filter(|relay| crate::federation::is_remote(relay)) {
+            Some(relay) => {
+                let id = relay_message_id(&recipient_hash, &envelope.nonce)?;
+                crate::federation::enqueue(&txn, id, &relay, recipient_hash, envelope)
+                    .await
+                    .map_err(|e| ServerFnError::new(format!("Failed to queue message: {}", e)))?;
+            }
+            None => store_message(&txn, recipient_hash, envelope).await?,
+        }
+        txn.commit()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to commit deposit: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Issue a single-use challenge that must be answered to fetch a mailbox
+#[server]
+pub async fn request_mailbox_challenge(
+    recipient_hash: String,
+) -> Result<MailboxChallenge, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::mailbox_challenge;
+        use crypto_box::aead::{rand_core::RngCore, OsRng};
+        use sea_orm::prelude::Uuid;
+        use sea_orm::{ActiveValue::Set, EntityTrait};
+
+        if !is_recipient_hash(&recipient_hash) {
+            return Err(ServerFnError::new("Invalid recipient hash"));
+        }
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let id = Uuid::from_bytes(id);
+        let (challenge, server_secret) =
+            shared::mailbox::issue_challenge(id.to_string(), recipient_hash.clone());
+
+        let now = chrono::Utc::now().fixed_offset();
+        let ttl = crate::config::config().relay.challenge_ttl_secs;
+        let model = mailbox_challenge::ActiveModel {
+            id: Set(id),
+            recipient_hash: Set(recipient_hash),
+            challenge: Set(challenge.challenge.clone()),
+            server_secret_key: Set(server_secret.to_vec()),
+            created_at: Set(now),
+            expires_at: Set(now + chrono::Duration::seconds(ttl as i64)),
+        };
+        mailbox_challenge::Entity::insert(model)
+            .exec_without_returning(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to store challenge: {}", e)))?;
+
+        Ok(challenge.into())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(ServerFnError::new(
+            "Mailbox challenges are issued by the server",
+        ))
+    }
+}
+
+/// Mailbox challenges issued by this relay, kept in the database
+#[cfg(feature = "server")]
+struct StoredChallenges<'a>(&'a sea_orm::DatabaseConnection);
+
+#[cfg(feature = "server")]
+impl shared::mailbox::ChallengeStore for StoredChallenges<'_> {
+    type Error = sea_orm::DbErr;
+
+    async fn take(
+        &self,
+        id: &str,
+        recipient_hash: &str,
+    ) -> Result<Option<(shared::mailbox::MailboxChallenge, [u8; 32])>, sea_orm::DbErr> {
+        use crate::entities::mailbox_challenge;
+        use sea_orm::prelude::Uuid;
+        use sea_orm::sea_query::Expr;
+        use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+        let Ok(id) = Uuid::parse_str(id) else {
+            return Ok(None);
+        };
+        let Some(issued) = mailbox_challenge::Entity::delete_many()
+            .filter(mailbox_challenge::Column::Id.eq(id))
+            .filter(mailbox_challenge::Column::RecipientHash.eq(recipient_hash))
+            .filter(Expr::col(mailbox_challenge::Column::ExpiresAt).gt(Expr::current_timestamp()))
+            .exec_with_returning(self.0)
+            .await?
+            .pop()
+        else {
+            return Ok(None);
+        };
+
+        let Ok(server_secret) = <[u8; 32]>::try_from(issued.server_secret_key.as_slice()) else {
+            return Ok(None);
+        };
+        let challenge = shared::mailbox::MailboxChallenge {
+            id: issued.id.to_string(),
+            recipient_hash: issued.recipient_hash,
+            server_public_key: crypto_box::SecretKey::from_bytes(server_secret)
+                .public_key()
+                .as_bytes()
+                .to_vec(),
+            challenge: issued.challenge,
+        };
+        Ok(Some((challenge, server_secret)))
+    }
+}
+
+/// Check a proof, spending the challenge it answers.
+/// A challenge is spent by any attempt, so a proof cannot be tried twice.
+#[cfg(feature = "server")]
+async fn check_mailbox_proof(
+    db: &sea_orm::DatabaseConnection,
+    recipient_hash: &str,
+    proof: MailboxProof,
+) -> Result<(), ServerFnError> {
+    use shared::mailbox::{redeem_proof, RedeemError};
+
+    match redeem_proof(&StoredChallenges(db), recipient_hash, &proof.into()).await {
+        Ok(()) => Ok(()),
+        Err(RedeemError::Store(e)) => Err(ServerFnError::new(format!(
+            "Failed to load challenge: {}",
+            e
+        ))),
+        Err(RedeemError::Rejected(_)) => {
+            crate::metrics::METRICS.record_rejected_proof();
+            Err(ServerFnError::new("Mailbox ownership proof rejected"))
+        }
+    }
+}
+
+/// The waiting messages of a mailbox with their IDs, oldest first.
+/// Messages stay until they are acknowledged or expire.
+#[cfg(feature = "server")]
+async fn read_mailbox(
+    db: &sea_orm::DatabaseConnection,
+    recipient_hash: &str,
+) -> Result<Vec<RelayEnvelope>, ServerFnError> {
+    use crate::entities::relay_message;
+    use sea_orm::sea_query::Expr;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+    // Expired envelopes are skipped here and left to the sweeper
+    let messages = relay_message::Entity::find()
+        .filter(relay_message::Column::RecipientHash.eq(recipient_hash))
+        .filter(Expr::col(relay_message::Column::ExpiresAt).gt(Expr::current_timestamp()))
+        .order_by_asc(relay_message::Column::CreatedAt)
+        .all(db)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to load messages: {}", e)))?;
+
+    Ok(messages
+        .into_iter()
+        .map(|message| RelayEnvelope {
+            id: Some(message.id.to_string()),
+            sender_public_key: message.sender_public_key,
+            ciphertext: message.encrypted_content,
+            nonce: message.nonce,
+            ephemeral: false,
+            expires_in: None,
+            retract_hash: None,
+        })
+        .collect())
+}
+
+/// Fetch the waiting messages of a mailbox without removing them, see
+/// `acknowledge_messages`
+#[server]
+pub async fn fetch_messages(
+    recipient_hash: String,
+    proof: MailboxProof,
+) -> Result<Vec<RelayEnvelope>, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        check_mailbox_proof(&db, &recipient_hash, proof).await?;
+
+        let messages = read_mailbox(&db, &recipient_hash).await?;
+        crate::metrics::METRICS.record_fetch(messages.len());
+        Ok(messages)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Ok(Vec::new())
+    }
+}
+
+/// Remove fetched or pushed messages from a mailbox once the client applied
+/// them. Needs a fresh ownership proof; IDs that are gone are ignored.
+#[server]
+pub async fn acknowledge_messages(
+    recipient_hash: String,
+    proof: MailboxProof,
+    ids: Vec<String>,
+) -> Result<(), ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::relay_message;
+        use sea_orm::prelude::Uuid;
+        use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+        let ids = ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ServerFnError::new("Invalid message ID"))?;
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        check_mailbox_proof(&db, &recipient_hash, proof).await?;
+
+        relay_message::Entity::delete_many()
+            .filter(relay_message::Column::RecipientHash.eq(recipient_hash))
+            .filter(relay_message::Column::Id.is_in(ids))
+            .exec(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to acknowledge messages: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Requests a client sends over a live delivery connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LiveRequest {
+    /// Push the messages of a mailbox, answering a challenge from
+    /// `request_mailbox_challenge`
+    Subscribe {
+        recipient_hash: String,
+        proof: MailboxProof,
+    },
+}
+
+/// Events the relay sends over a live delivery connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LiveEvent {
+    /// The proof was accepted, messages for the mailbox are pushed from now on
+    Subscribed { recipient_hash: String },
+    /// The proof was refused, the mailbox is not pushed
+    Rejected {
+        recipient_hash: String,
+        reason: String,
+    },
+    /// Messages of a subscribed mailbox not pushed on this connection yet,
+    /// removed once acknowledged with `acknowledge_messages`
+    Messages {
+        recipient_hash: String,
+        envelopes: Vec<RelayEnvelope>,
+    },
+}
+
+/// Live delivery of relay messages.
+/// Mailboxes are subscribed with an ownership proof each, waiting messages
+/// are pushed right away and new deposits as they arrive. Every message is
+/// pushed once per connection and stays in the mailbox until acknowledged.
+#[get("/api/relay/live")]
+pub async fn live_messages(
+    options: WebSocketOptions,
+) -> Result<Websocket<LiveRequest, LiveEvent>, ServerFnError> {
+    Ok(options.on_upgrade(move |mut socket| async move {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            use crate::live::subscribe;
+            use crate::metrics::METRICS;
+            use std::collections::{HashMap, HashSet};
+            use tokio::sync::broadcast::error::RecvError;
+
+            let Ok(db) = crate::persistence::postgres::establish_connection().await else {
+                return;
+            };
+            let mut deposits = subscribe();
+            // Subscribed mailboxes and the IDs pushed from each
+            let mut mailboxes: HashMap<String, HashSet<String>> = HashMap::new();
+            let _connection = METRICS.live_connection();
+
+            loop {
+                // Mailboxes to push, after a subscription or a deposit
+                let pending: Vec<String> = tokio::select! {
+                    request = socket.recv() => {
+                        let Ok(LiveRequest::Subscribe { recipient_hash, proof }) = request else {
+                            break;
+                        };
+                        let event = match check_mailbox_proof(&db, &recipient_hash, proof).await {
+                            Ok(()) => {
+                                mailboxes.entry(recipient_hash.clone()).or_default();
+                                LiveEvent::Subscribed { recipient_hash: recipient_hash.clone() }
+                            }
+                            Err(e) => LiveEvent::Rejected {
+                                recipient_hash: recipient_hash.clone(),
+                                reason: e.to_string(),
+                            },
+                        };
+                        let subscribed = matches!(event, LiveEvent::Subscribed { .. });
+                        if socket.send(event).await.is_err() {
+                            break;
+                        }
+                        if subscribed {
+                            vec![recipient_hash]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    deposit = deposits.recv() => match deposit {
+                        Ok(Some(recipient_hash)) if mailboxes.contains_key(&recipient_hash) => {
+                            vec![recipient_hash]
+                        }
+                        Ok(Some(_)) => Vec::new(),
+                        // Deposits may have been missed, check every mailbox
+                        Ok(None) | Err(RecvError::Lagged(_)) => mailboxes.keys().cloned().collect(),
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+
+                for recipient_hash in pending {
+                    let Ok(waiting) = read_mailbox(&db, &recipient_hash).await else {
+                        continue;
+                    };
+                    let pushed = mailboxes.entry(recipient_hash.clone()).or_default();
+                    // Acknowledged messages are gone and need not be remembered
+                    let ids: HashSet<String> =
+                        waiting.iter().filter_map(|e| e.id.clone()).collect();
+                    pushed.retain(|id| ids.contains(id));
+                    let envelopes: Vec<RelayEnvelope> = waiting
+                        .into_iter()
+                        .filter(|envelope| {
+                            envelope.id.as_ref().is_some_and(|id| !pushed.contains(id))
+                        })
+                        .collect();
+                    if envelopes.is_empty() {
+                        continue;
+                    }
+                    pushed.extend(envelopes.iter().filter_map(|e| e.id.clone()));
+                    METRICS.record_push(envelopes.len());
+                    let event = LiveEvent::Messages {
+                        recipient_hash,
+                        envelopes,
+                    };
+                    if socket.send(event).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }))
+}
+
+/// Remove a message that has not been fetched yet. Only the sender knows
+/// `retract_secret`, whose hash was deposited with the message; messages
+/// deposited without one cannot be retracted.
+#[server]
+pub async fn retract_message(
+    recipient_hash: String,
+    nonce: Vec<u8>,
+    retract_secret: Vec<u8>,
+) -> Result<(), ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::{federation_outbox, relay_message};
+        use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+        let id = relay_message_id(&recipient_hash, &nonce)?;
+        let retract_hash = shared::mailbox::retract_hash(&retract_secret);
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        // Already fetched or expired messages are simply gone
+        relay_message::Entity::delete_many()
+            .filter(relay_message::Column::Id.eq(id))
+            .filter(relay_message::Column::RecipientHash.eq(recipient_hash.clone()))
+            .filter(relay_message::Column::Nonce.eq(nonce.clone()))
+            .filter(relay_message::Column::RetractHash.eq(retract_hash.clone()))
+            .exec(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to retract message: {}", e)))?;
+
+        // Messages still waiting to be forwarded are withdrawn as well
+        federation_outbox::Entity::delete_many()
+            .filter(federation_outbox::Column::Id.eq(id))
+            .filter(federation_outbox::Column::RecipientHash.eq(recipient_hash))
+            .filter(federation_outbox::Column::Nonce.eq(nonce))
+            .filter(federation_outbox::Column::RetractHash.eq(retract_hash))
+            .exec(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to retract message: {}", e)))?;
+    }
+    Ok(())
+}
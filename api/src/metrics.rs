@@ -0,0 +1,405 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Relay counters for the server's Prometheus `/metrics` endpoint.
+//! Counters live in memory and restart at zero with the process, queue
+//! depth and token gauges are read from the database by the sweeper, so a
+//! scrape never queries the database.
+
+use crate::admin::{QueueStats, SweepReport};
+use crate::federation::ForwardReport;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Counters of the running relay
+pub struct RelayMetrics {
+    deposits: AtomicU64,
+    fetches: AtomicU64,
+    fetched_messages: AtomicU64,
+    rejected_proofs: AtomicU64,
+    pushed_messages: AtomicU64,
+    live_connections: AtomicU64,
+    forwarded_messages: AtomicU64,
+    failed_forwards: AtomicU64,
+    dropped_forwards: AtomicU64,
+    federated_deposits: AtomicU64,
+    rejected_federated_deposits: AtomicU64,
+    issued_tokens: AtomicU64,
+    redeemed_tokens: AtomicU64,
+    rejected_tokens: AtomicU64,
+    rejected_pow: AtomicU64,
+    refused_deposits: AtomicU64,
+    sweeps: AtomicU64,
+    sweep_failures: AtomicU64,
+    swept_messages: AtomicU64,
+    swept_blobs: AtomicU64,
+    expired_tokens: AtomicU64,
+    /// Gauges of the last sweep, `None` until one succeeds or after a failure
+    stats: Mutex<Option<QueueStats>>,
+}
+
+/// An open live delivery connection, counted while it is alive
+pub struct LiveConnection<'a>(&'a AtomicU64);
+
+impl Drop for LiveConnection<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Counters shared by the server functions and the metrics endpoint
+pub static METRICS: RelayMetrics = RelayMetrics::new();
+
+impl RelayMetrics {
+    pub const fn new() -> Self {
+        Self {
+            deposits: AtomicU64::new(0),
+            fetches: AtomicU64::new(0),
+            fetched_messages: AtomicU64::new(0),
+            rejected_proofs: AtomicU64::new(0),
+            pushed_messages: AtomicU64::new(0),
+            live_connections: AtomicU64::new(0),
+            forwarded_messages: AtomicU64::new(0),
+            failed_forwards: AtomicU64::new(0),
+            dropped_forwards: AtomicU64::new(0),
+            federated_deposits: AtomicU64::new(0),
+            rejected_federated_deposits: AtomicU64::new(0),
+            issued_tokens: AtomicU64::new(0),
+            redeemed_tokens: AtomicU64::new(0),
+            rejected_tokens: AtomicU64::new(0),
+            rejected_pow: AtomicU64::new(0),
+            refused_deposits: AtomicU64::new(0),
+            sweeps: AtomicU64::new(0),
+            sweep_failures: AtomicU64::new(0),
+            swept_messages: AtomicU64::new(0),
+            swept_blobs: AtomicU64::new(0),
+            expired_tokens: AtomicU64::new(0),
+            stats: Mutex::new(None),
+        }
+    }
+
+    /// Note a message stored in a mailbox
+    pub fn record_deposit(&self) {
+        self.deposits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a mailbox fetch and how many messages it returned
+    pub fn record_fetch(&self, messages: usize) {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.fetched_messages
+            .fetch_add(messages as u64, Ordering::Relaxed);
+    }
+
+    /// Note a mailbox fetch refused for a missing or invalid ownership proof
+    pub fn record_rejected_proof(&self) {
+        self.rejected_proofs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note messages pushed over a live delivery connection
+    pub fn record_push(&self, messages: usize) {
+        self.pushed_messages
+            .fetch_add(messages as u64, Ordering::Relaxed);
+    }
+
+    /// Count a live delivery connection until the returned guard is dropped
+    pub fn live_connection(&self) -> LiveConnection<'_> {
+        self.live_connections.fetch_add(1, Ordering::Relaxed);
+        LiveConnection(&self.live_connections)
+    }
+
+    /// Note the outcome of a forwarding run
+    pub fn record_forward(&self, report: &ForwardReport) {
+        self.forwarded_messages
+            .fetch_add(report.forwarded, Ordering::Relaxed);
+        self.failed_forwards
+            .fetch_add(report.failed, Ordering::Relaxed);
+        self.dropped_forwards
+            .fetch_add(report.dropped, Ordering::Relaxed);
+    }
+
+    /// Note a deposit forwarded by another relay, accepted or not
+    pub fn record_federated_deposit(&self, accepted: bool) {
+        if accepted {
+            self.federated_deposits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.rejected_federated_deposits
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Note a batch of `count` blinded tokens signed for a client
+    pub fn record_issued_tokens(&self, count: u64) {
+        self.issued_tokens.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Note an anonymous token redeemed
+    pub fn record_redeemed_token(&self) {
+        self.redeemed_tokens.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note an anonymous token refused as invalid or already redeemed
+    pub fn record_rejected_token(&self) {
+        self.rejected_tokens.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a token request refused for a missing or wrong proof-of-work
+    pub fn record_rejected_pow(&self) {
+        self.rejected_pow.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a deposit refused for a missing, used up or revoked token
+    pub fn record_refused_deposit(&self) {
+        self.refused_deposits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Note a sweeper run, `None` if it failed
+    pub fn record_sweep(&self, report: Option<&SweepReport>) {
+        self.sweeps.fetch_add(1, Ordering::Relaxed);
+        match report {
+            Some(report) => {
+                self.swept_messages
+                    .fetch_add(report.messages, Ordering::Relaxed);
+                self.swept_blobs.fetch_add(report.blobs, Ordering::Relaxed);
+                self.expired_tokens
+                    .fetch_add(report.tokens, Ordering::Relaxed);
+            }
+            None => {
+                self.sweep_failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Keep the gauges read from the database, `None` if reading them failed
+    pub fn record_stats(&self, stats: Option<QueueStats>) {
+        if let Ok(mut current) = self.stats.lock() {
+            *current = stats;
+        }
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    /// Gauges read from the database are left out until the sweeper read them.
+    pub fn render(&self) -> String {
+        let stats = self.stats.lock().ok().and_then(|stats| stats.clone());
+        let stats = stats.as_ref();
+        let mut out = String::new();
+        let counters = [
+            (
+                "relay_deposits_total",
+                "Messages deposited into relay mailboxes",
+                &self.deposits,
+            ),
+            ("relay_fetches_total", "Mailbox fetches", &self.fetches),
+            (
+                "relay_fetched_messages_total",
+                "Messages delivered by mailbox fetches",
+                &self.fetched_messages,
+            ),
+            (
+                "relay_rejected_proofs_total",
+                "Mailbox fetches refused for an invalid ownership proof",
+                &self.rejected_proofs,
+            ),
+            (
+                "relay_pushed_messages_total",
+                "Messages pushed over live delivery connections",
+                &self.pushed_messages,
+            ),
+            (
+                "relay_forwarded_messages_total",
+                "Messages forwarded to other relays",
+                &self.forwarded_messages,
+            ),
+            (
+                "relay_failed_forwards_total",
+                "Forwarding attempts that failed and are retried",
+                &self.failed_forwards,
+            ),
+            (
+                "relay_dropped_forwards_total",
+                "Queued messages given up on, expired or out of attempts",
+                &self.dropped_forwards,
+            ),
+            (
+                "relay_federated_deposits_total",
+                "Messages accepted from other relays",
+                &self.federated_deposits,
+            ),
+            (
+                "relay_rejected_federated_deposits_total",
+                "Messages from other relays refused",
+                &self.rejected_federated_deposits,
+            ),
+            (
+                "relay_issued_tokens_total",
+                "Blinded anonymous tokens signed",
+                &self.issued_tokens,
+            ),
+            (
+                "relay_redeemed_tokens_total",
+                "Anonymous tokens redeemed",
+                &self.redeemed_tokens,
+            ),
+            (
+                "relay_rejected_tokens_total",
+                "Anonymous tokens refused as invalid or already redeemed",
+                &self.rejected_tokens,
+            ),
+            (
+                "relay_rejected_pow_total",
+                "Token requests refused for an invalid proof-of-work",
+                &self.rejected_pow,
+            ),
+            (
+                "relay_refused_deposits_total",
+                "Deposits refused for an invalid or used up message token",
+                &self.refused_deposits,
+            ),
+            ("relay_sweeps_total", "Sweeper runs", &self.sweeps),
+            (
+                "relay_sweep_failures_total",
+                "Sweeper runs that failed",
+                &self.sweep_failures,
+            ),
+            (
+                "relay_swept_messages_total",
+                "Expired messages deleted by the sweeper",
+                &self.swept_messages,
+            ),
+            (
+                "relay_swept_blobs_total",
+                "Expired blobs deleted by the sweeper",
+                &self.swept_blobs,
+            ),
+            (
+                "relay_expired_tokens_total",
+                "Message tokens expired by the sweeper",
+                &self.expired_tokens,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            write_metric(
+                &mut out,
+                name,
+                "counter",
+                help,
+                counter.load(Ordering::Relaxed),
+            );
+        }
+
+        write_metric(
+            &mut out,
+            "relay_database_up",
+            "gauge",
+            "Whether the database answered the last sweep",
+            u64::from(stats.is_some()),
+        );
+        write_metric(
+            &mut out,
+            "relay_live_connections",
+            "gauge",
+            "Open live delivery connections",
+            self.live_connections.load(Ordering::Relaxed),
+        );
+        if let Some(stats) = stats {
+            let gauges = [
+                (
+                    "relay_queued_messages",
+                    "Messages waiting in mailboxes",
+                    stats.messages,
+                ),
+                (
+                    "relay_expired_messages",
+                    "Expired messages waiting for the sweeper",
+                    stats.expired_messages,
+                ),
+                (
+                    "relay_mailboxes",
+                    "Mailboxes with waiting messages",
+                    stats.mailboxes,
+                ),
+                ("relay_blobs", "Stored attachment blobs", stats.blobs),
+                (
+                    "relay_blob_bytes",
+                    "Size of stored attachment blobs",
+                    stats.blob_bytes,
+                ),
+                (
+                    "relay_active_tokens",
+                    "Message tokens that can still be spent",
+                    stats.active_tokens,
+                ),
+                (
+                    "relay_token_messages_used",
+                    "Messages spent from active tokens",
+                    stats.token_messages_used,
+                ),
+                (
+                    "relay_token_messages_allowed",
+                    "Messages allowed by active tokens",
+                    stats.token_messages_allowed,
+                ),
+                (
+                    "relay_federation_queue",
+                    "Messages waiting to be forwarded to other relays",
+                    stats.federation_queue,
+                ),
+                (
+                    "relay_recent_token_issuances",
+                    "Anonymous token batches issued in the last hour",
+                    stats.recent_token_issuances,
+                ),
+                (
+                    "relay_token_pow_difficulty",
+                    "Proof-of-work bits the next anonymous token costs",
+                    stats.token_pow_difficulty.into(),
+                ),
+            ];
+            for (name, help, value) in gauges {
+                write_metric(&mut out, name, "gauge", help, value);
+            }
+        }
+        out
+    }
+}
+
+impl Default for RelayMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `token` is the configured `server.metrics_token`.
+/// Metrics are not served at all while no token is configured.
+pub fn is_authorized(token: Option<&str>) -> bool {
+    let expected = &crate::config::config().server.metrics_token;
+    match token {
+        // Digests are compared so the time taken does not reveal the token
+        Some(token) if !expected.is_empty() => {
+            Sha256::digest(token.as_bytes()) == Sha256::digest(expected.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    let _ = writeln!(out, "{name} {value}");
+}
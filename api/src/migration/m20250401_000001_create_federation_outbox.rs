@@ -0,0 +1,129 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create federation_outbox table for messages waiting to be forwarded
+        manager
+            .create_table(
+                Table::create()
+                    .table(FederationOutbox::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FederationOutbox::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::Destination)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::RecipientHash)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::SenderPublicKey)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::EncryptedContent)
+                            .binary()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(FederationOutbox::Nonce).binary().not_null())
+                    .col(
+                        ColumnDef::new(FederationOutbox::Ephemeral)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::NextAttemptAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(FederationOutbox::LastError).text().null())
+                    .col(
+                        ColumnDef::new(FederationOutbox::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(FederationOutbox::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_federation_outbox_next_attempt_at")
+                    .table(FederationOutbox::Table)
+                    .col(FederationOutbox::NextAttemptAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FederationOutbox::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FederationOutbox {
+    Table,
+    Id,
+    Destination,
+    RecipientHash,
+    SenderPublicKey,
+    EncryptedContent,
+    Nonce,
+    Ephemeral,
+    Attempts,
+    NextAttemptAt,
+    LastError,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -18,11 +18,29 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20250124_000001_create_initial_tables;
+mod m20250301_000001_create_relay_blobs;
+mod m20250310_000001_relay_message_defaults;
+mod m20250320_000001_create_mailbox_challenges;
+mod m20250325_000001_notify_relay_messages;
+mod m20250401_000001_create_federation_outbox;
+mod m20250410_000001_create_pow_challenges;
+mod m20250420_000001_create_received_deposits;
+mod m20250430_000001_add_retract_hashes;
 
 pub struct Migrator;
 
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20250124_000001_create_initial_tables::Migration)]
+        vec![
+            Box::new(m20250124_000001_create_initial_tables::Migration),
+            Box::new(m20250301_000001_create_relay_blobs::Migration),
+            Box::new(m20250310_000001_relay_message_defaults::Migration),
+            Box::new(m20250320_000001_create_mailbox_challenges::Migration),
+            Box::new(m20250325_000001_notify_relay_messages::Migration),
+            Box::new(m20250401_000001_create_federation_outbox::Migration),
+            Box::new(m20250410_000001_create_pow_challenges::Migration),
+            Box::new(m20250420_000001_create_received_deposits::Migration),
+            Box::new(m20250430_000001_add_retract_hashes::Migration),
+        ]
     }
 }
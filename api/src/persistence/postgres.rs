@@ -15,30 +15,39 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::config::try_config;
 use sea_orm::*;
 use sea_orm_migration::MigrationStatus;
-use std::time::Duration;
+use tokio::sync::OnceCell;
 
+/// Connection pool shared by the whole process
+static POOL: OnceCell<DatabaseConnection> = OnceCell::const_new();
+
+/// A handle to the process-wide connection pool, created on first use with
+/// the pool settings of the server configuration
 pub async fn establish_connection() -> Result<DatabaseConnection, DbErr> {
-    let database_url = get_database_url();
+    POOL.get_or_try_init(connect).await.cloned()
+}
 
-    let mut opt = ConnectOptions::new(database_url);
-    opt.max_connections(50)
-        .min_connections(5)
-        .connect_timeout(Duration::from_secs(8))
-        .acquire_timeout(Duration::from_secs(8))
-        .idle_timeout(Duration::from_secs(8))
-        .max_lifetime(Duration::from_secs(1800));
+async fn connect() -> Result<DatabaseConnection, DbErr> {
+    let settings = &try_config()
+        .map_err(|e| DbErr::Custom(e.to_string()))?
+        .database;
+    if settings.url.is_empty() {
+        return Err(DbErr::Custom(
+            "No database URL is configured, set database.url or DATABASE_URL".to_string(),
+        ));
+    }
 
-    Database::connect(opt).await
-}
+    let mut opt = ConnectOptions::new(settings.url.clone());
+    opt.max_connections(settings.max_connections)
+        .min_connections(settings.min_connections)
+        .connect_timeout(settings.connect_timeout())
+        .acquire_timeout(settings.acquire_timeout())
+        .idle_timeout(settings.idle_timeout())
+        .max_lifetime(settings.max_lifetime());
 
-fn get_database_url() -> String {
-    dotenvy::var("DATABASE_URL").unwrap_or_else(|_| {
-        panic!(
-            "DATABASE_URL environment variable must be set and should not use insecure defaults."
-        );
-    })
+    Database::connect(opt).await
 }
 
 pub async fn ping_database(db: &DatabaseConnection) -> Result<(), DbErr> {
@@ -56,11 +65,22 @@ pub async fn run_migrations(db: &DatabaseConnection) -> Result<(), DbErr> {
     Migrator::up(db, None).await
 }
 
+/// Roll back the last `steps` applied migrations
+pub async fn rollback_migrations(db: &DatabaseConnection, steps: u32) -> Result<(), DbErr> {
+    use crate::migration::{Migrator, MigratorTrait};
+
+    Migrator::down(db, Some(steps)).await
+}
+
+/// Name and status of every known migration, oldest first
 pub async fn check_migration_status(
     db: &DatabaseConnection,
-) -> Result<Vec<MigrationStatus>, DbErr> {
+) -> Result<Vec<(String, MigrationStatus)>, DbErr> {
     use crate::migration::{Migrator, MigratorTrait};
 
-    let migrations = Migrator::get_pending_migrations(db).await?;
-    Ok(migrations.into_iter().map(|m| m.status()).collect())
+    let migrations = Migrator::get_migration_with_status(db).await?;
+    Ok(migrations
+        .into_iter()
+        .map(|m| (m.name().to_string(), m.status()))
+        .collect())
 }
@@ -0,0 +1,191 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+    use serial_test::serial;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    fn valid() -> ServerConfig {
+        let mut config = ServerConfig::default();
+        config.database.url = "postgres://relay@localhost/relay".to_string();
+        config
+    }
+
+    fn overrides(vars: &[(&str, &str)]) -> Result<toml::Table, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let mut table = toml::Table::new();
+        apply_env_overrides(&mut table, |name| vars.get(name).cloned())?;
+        Ok(table)
+    }
+
+    #[test]
+    fn test_defaults_with_database_url_are_valid() {
+        assert_eq!(valid().validate(), Ok(()));
+        assert!(valid().bind_address().is_some());
+    }
+
+    #[test]
+    fn test_validation_lists_every_problem() {
+        let mut config = valid();
+        config.server.bind_address = "not an address".to_string();
+        config.database.url = "mysql://localhost".to_string();
+        config.database.min_connections = config.database.max_connections + 1;
+        config.tokens.pow_max_difficulty = 64;
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("an invalid config must be rejected");
+        };
+        assert_eq!(problems.len(), 4);
+        assert!(problems
+            .iter()
+            .any(|p| p.starts_with("server.bind_address")));
+        assert!(problems.iter().any(|p| p.contains("postgres://")));
+        assert!(problems
+            .iter()
+            .any(|p| p.starts_with("database.min_connections")));
+        assert!(problems
+            .iter()
+            .any(|p| p.starts_with("tokens.pow_max_difficulty")));
+    }
+
+    #[test]
+    fn test_database_url_is_required() {
+        let Err(ConfigError::Invalid(problems)) = ServerConfig::default().validate() else {
+            panic!("a config without database URL must be rejected");
+        };
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("database.url is not set"));
+    }
+
+    #[test]
+    fn test_federation_peers_need_url_and_key() {
+        let mut config = valid();
+        config.federation.enabled = true;
+        config.federation.public_url = "https://relay.example".to_string();
+        config.federation.peers.push(PeerConfig {
+            url: "relay.other".to_string(),
+            public_key: "00".to_string(),
+        });
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("an invalid peer must be rejected");
+        };
+        assert_eq!(problems.len(), 2);
+
+        config.federation.peers[0] = PeerConfig {
+            url: "https://relay.other".to_string(),
+            public_key: hex::encode([7u8; 32]),
+        };
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_env_overrides_convert_to_the_default_type() {
+        let table = overrides(&[
+            ("MEESEEKS_DATABASE_MAX_CONNECTIONS", " 20 "),
+            ("MEESEEKS_TOKENS_ANONYMOUS_ISSUANCE", "false"),
+            ("MEESEEKS_SERVER_BIND_ADDRESS", "127.0.0.1:9000"),
+        ])
+        .unwrap();
+        let config: ServerConfig = toml::Value::Table(table).try_into().unwrap();
+
+        assert_eq!(config.database.max_connections, 20);
+        assert!(!config.tokens.anonymous_issuance);
+        assert_eq!(config.server.bind_address, "127.0.0.1:9000");
+        assert_eq!(config.relay, RelayConfig::default());
+    }
+
+    #[test]
+    fn test_database_url_alias() {
+        let table = overrides(&[("DATABASE_URL", "postgres://alias")]).unwrap();
+        assert_eq!(table["database"]["url"].as_str(), Some("postgres://alias"));
+
+        // The prefixed variable wins over the alias
+        let table = overrides(&[
+            ("DATABASE_URL", "postgres://alias"),
+            ("MEESEEKS_DATABASE_URL", "postgres://prefixed"),
+        ])
+        .unwrap();
+        assert_eq!(
+            table["database"]["url"].as_str(),
+            Some("postgres://prefixed")
+        );
+    }
+
+    #[test]
+    fn test_env_overrides_reject_bad_values() {
+        let result = overrides(&[("MEESEEKS_RELAY_MESSAGE_TTL_SECS", "a week")]);
+        assert!(matches!(
+            result,
+            Err(ConfigError::Parse { source, .. }) if source == "MEESEEKS_RELAY_MESSAGE_TTL_SECS"
+        ));
+
+        let result = overrides(&[("MEESEEKS_FEDERATION_ENABLED", "yes")]);
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn test_env_overrides_skip_lists() {
+        let table = overrides(&[("MEESEEKS_FEDERATION_PEERS", "https://relay.other")]).unwrap();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    #[serial(config_env)]
+    fn test_load_applies_env_over_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            "[database]\nurl = \"postgres://file\"\nmax_connections = 10\n\n[relay]\nsweep_interval_secs = 30"
+        )
+        .unwrap();
+
+        std::env::set_var("MEESEEKS_DATABASE_MAX_CONNECTIONS", "12");
+        let loaded = ServerConfig::load(Some(file.path()));
+        std::env::remove_var("MEESEEKS_DATABASE_MAX_CONNECTIONS");
+
+        let config = loaded.unwrap();
+        assert_eq!(config.database.max_connections, 12);
+        assert_eq!(config.relay.sweep_interval_secs, 30);
+    }
+
+    #[test]
+    #[serial(config_env)]
+    fn test_load_reports_invalid_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            "[database]\nurl = \"postgres://file\"\nmax_conections = 10"
+        )
+        .unwrap();
+        assert!(matches!(
+            ServerConfig::load(Some(file.path())),
+            Err(ConfigError::Parse { .. })
+        ));
+
+        let missing = file.path().with_extension("missing");
+        assert!(matches!(
+            ServerConfig::load(Some(&missing)),
+            Err(ConfigError::Read { .. })
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::federation::{is_public_address, DepositLimiter};
+    use std::net::IpAddr;
+
+    fn public(address: &str) -> bool {
+        is_public_address(address.parse::<IpAddr>().unwrap())
+    }
+
+    #[test]
+    fn test_public_addresses() {
+        assert!(public("93.184.216.34"));
+        assert!(public("1.1.1.1"));
+        assert!(public("2606:4700:4700::1111"));
+        assert!(public("::ffff:93.184.216.34"));
+    }
+
+    #[test]
+    fn test_internal_addresses() {
+        for address in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.5.4",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "198.18.0.1",
+            "192.0.0.8",
+            "192.0.2.1",
+            "224.0.0.1",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "ff02::1",
+            "2001:db8::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!public(address), "{address} counted as public");
+        }
+    }
+
+    #[test]
+    fn test_deposit_limit_per_origin() {
+        let mut limiter = DepositLimiter::default();
+        let now = 1_700_000_000 / 60 * 60;
+        assert!(limiter.allow("https://a.example", now, 2));
+        assert!(limiter.allow("https://a.example", now + 1, 2));
+        assert!(!limiter.allow("https://a.example", now + 2, 2));
+        // Other origins have their own count
+        assert!(limiter.allow("https://b.example", now + 2, 2));
+        // The count starts over the next minute
+        assert!(limiter.allow("https://a.example", now + 60, 2));
+    }
+}
@@ -0,0 +1,433 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Anonymous message tokens.
+//! Besides tokens minted by an admin, clients can obtain tokens without the
+//! relay learning which token went to whom: the client has a batch of
+//! blinded tokens signed, unblinds them and redeems each later, see
+//! `shared::blind_token`. A redeemed token pays for one message and is
+//! stored like a minted one under the hash of its secret, and the unique
+//! hash makes every further redemption fail.
+//! Every issued batch costs a single-use proof-of-work challenge, whose
+//! difficulty rises with the issuance rate, see `shared::pow`.
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Path `redeem_token` is served at
+pub const REDEEM_TOKEN_PATH: &str = "/api/tokens/redeem";
+
+/// Public key tokens are currently issued with
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenIssuerKey {
+    /// Relay key the issuer key is derived from
+    pub key_id: i32,
+    pub public_key: Vec<u8>,
+    /// Most blinded tokens one proof of work gets signed
+    pub batch_size: u32,
+}
+
+/// A proof-of-work challenge to answer when asking for a token
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PowChallenge {
+    pub id: String,
+    pub nonce: Vec<u8>,
+    pub difficulty: u32,
+    /// Unix time after which the challenge is no longer accepted
+    pub expires_at: u64,
+}
+
+/// A blinded token signed by the relay, with proof of the key it used
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlindSignature {
+    pub evaluated: Vec<u8>,
+    pub challenge: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+/// An unblinded token to redeem
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnonymousToken {
+    pub key_id: i32,
+    pub nonce: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// A redeemed token, usable like a minted one with its secret
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RedeemedToken {
+    pub id: String,
+    pub max_messages: i32,
+    /// Unix time the token expires at
+    pub expires_at: i64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<shared::pow::PowChallenge> for PowChallenge {
+    fn from(challenge: shared::pow::PowChallenge) -> Self {
+        Self {
+            id: challenge.id,
+            nonce: challenge.nonce,
+            difficulty: challenge.difficulty,
+            expires_at: challenge.expires_at,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<PowChallenge> for shared::pow::PowChallenge {
+    fn from(challenge: PowChallenge) -> Self {
+        Self {
+            id: challenge.id,
+            nonce: challenge.nonce,
+            difficulty: challenge.difficulty,
+            expires_at: challenge.expires_at,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<shared::blind_token::BlindSignature> for BlindSignature {
+    fn from(signature: shared::blind_token::BlindSignature) -> Self {
+        Self {
+            evaluated: signature.evaluated,
+            challenge: signature.challenge,
+            response: signature.response,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<BlindSignature> for shared::blind_token::BlindSignature {
+    fn from(signature: BlindSignature) -> Self {
+        Self {
+            evaluated: signature.evaluated,
+            challenge: signature.challenge,
+            response: signature.response,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<shared::blind_token::AnonymousToken> for AnonymousToken {
+    fn from(token: shared::blind_token::AnonymousToken) -> Self {
+        Self {
+            key_id: token.key_id,
+            nonce: token.nonce,
+            signature: token.signature,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<AnonymousToken> for shared::blind_token::AnonymousToken {
+    fn from(token: AnonymousToken) -> Self {
+        Self {
+            key_id: token.key_id,
+            nonce: token.nonce,
+            signature: token.signature,
+        }
+    }
+}
+
+/// Issuer key of the relay key with `key_id`, or of the newest one
+#[cfg(feature = "server")]
+async fn issuer_key(
+    db: &sea_orm::DatabaseConnection,
+    key_id: Option<i32>,
+) -> Result<(i32, shared::blind_token::IssuerKey), ServerFnError> {
+    let keys = crate::admin::current_relay_keys(db)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to load relay keys: {}", e)))?;
+    let key = match key_id {
+        Some(key_id) => keys.into_iter().find(|key| key.id == key_id),
+        None => keys.into_iter().next(),
+    }
+    .ok_or_else(|| ServerFnError::new("Unknown token issuer key"))?;
+
+    let secret = <[u8; 32]>::try_from(key.private_key.as_slice())
+        .map_err(|_| ServerFnError::new("Invalid relay key"))?;
+    Ok((
+        key.id,
+        shared::blind_token::IssuerKey::from_relay_key(&secret),
+    ))
+}
+
+#[cfg(feature = "server")]
+fn require_issuance() -> Result<(), ServerFnError> {
+    if crate::config::config().tokens.anonymous_issuance {
+        Ok(())
+    } else {
+        Err(ServerFnError::new(
+            "Anonymous tokens are not issued by this relay",
+        ))
+    }
+}
+
+#[server]
+pub async fn token_issuer_key() -> Result<TokenIssuerKey, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        require_issuance()?;
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let (key_id, issuer) = issuer_key(&db, None).await?;
+        Ok(TokenIssuerKey {
+            key_id,
+            public_key: issuer.public_key(),
+            batch_size: crate::config::config().tokens.anonymous_batch_size,
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(ServerFnError::new("Tokens are issued by the server"))
+    }
+}
+
+/// Issue a single-use proof-of-work challenge for `issue_tokens`, as hard
+/// as the recent issuance rate demands
+#[server]
+pub async fn request_token_challenge() -> Result<PowChallenge, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::entities::pow_challenge;
+        use crypto_box::aead::{rand_core::RngCore, OsRng};
+        use sea_orm::prelude::Uuid;
+        use sea_orm::{ActiveValue::Set, EntityTrait};
+
+        require_issuance()?;
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let issued = crate::admin::recent_token_issuances(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to load issuance rate: {}", e)))?;
+        let difficulty = crate::admin::token_pow_difficulty(issued);
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+
+        let now = chrono::Utc::now().fixed_offset();
+        let ttl = crate::config::config().tokens.pow_challenge_ttl_secs;
+        let expires_at = now + chrono::Duration::seconds(ttl as i64);
+        let model = pow_challenge::ActiveModel {
+            id: Set(Uuid::from_bytes(id)),
+            nonce: Set(nonce.to_vec()),
+            difficulty: Set(difficulty as i32),
+            created_at: Set(now),
+            expires_at: Set(expires_at),
+            solved_at: Set(None),
+        };
+        pow_challenge::Entity::insert(model)
+            .exec_without_returning(&db)
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to store challenge: {}", e)))?;
+
+        Ok(PowChallenge {
+            id: Uuid::from_bytes(id).to_string(),
+            nonce: nonce.to_vec(),
+            difficulty,
+            expires_at: expires_at.timestamp().max(0) as u64,
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(ServerFnError::new(
+            "Token challenges are issued by the server",
+        ))
+    }
+}
+
+/// Mark a proof-of-work challenge solved if `solution` answers it for
+/// `payload`. A challenge is spent by any attempt, so it cannot be tried
+/// twice, and only solved ones count toward the issuance rate.
+#[cfg(feature = "server")]
+async fn spend_pow_challenge(
+    db: &sea_orm::DatabaseConnection,
+    challenge_id: &str,
+    payload: &[u8],
+    solution: u64,
+) -> Result<(), ServerFnError> {
+    use crate::entities::pow_challenge;
+    use sea_orm::prelude::Uuid;
+    use sea_orm::sea_query::Expr;
+    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+    let rejected = || {
+        crate::metrics::METRICS.record_rejected_pow();
+        ServerFnError::new("Proof of work rejected")
+    };
+
+    let id = Uuid::parse_str(challenge_id).map_err(|_| rejected())?;
+    let claimed = pow_challenge::Entity::update_many()
+        .col_expr(
+            pow_challenge::Column::SolvedAt,
+            Expr::cust("CURRENT_TIMESTAMP"),
+        )
+        .filter(pow_challenge::Column::Id.eq(id))
+        .filter(pow_challenge::Column::SolvedAt.is_null())
+        .filter(Expr::col(pow_challenge::Column::ExpiresAt).gt(Expr::current_timestamp()))
+        .exec_with_returning(db)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to load challenge: {}", e)))?
+        .pop()
+        .ok_or_else(rejected)?;
+
+    let challenge = shared::pow::PowChallenge {
+        id: claimed.id.to_string(),
+        nonce: claimed.nonce,
+        difficulty: claimed.difficulty.max(0) as u32,
+        expires_at: claimed.expires_at.timestamp().max(0) as u64,
+    };
+    if shared::pow::verify_solution(&challenge, payload, solution) {
+        return Ok(());
+    }
+
+    // A wrong answer is not an issued token
+    pow_challenge::Entity::delete_by_id(id)
+        .exec(db)
+        .await
+        .map_err(|e| ServerFnError::new(format!("Failed to spend challenge: {}", e)))?;
+    Err(rejected())
+}
+
+/// Sign a batch of blinded tokens with the current issuer key, paid for
+/// with a solution to a challenge from `request_token_challenge` over
+/// `shared::blind_token::batch_payload`.
+/// Only the current key signs, so every client gets the same one.
+#[server]
+pub async fn issue_tokens(
+    key_id: i32,
+    blinded: Vec<Vec<u8>>,
+    challenge_id: String,
+    solution: u64,
+) -> Result<Vec<BlindSignature>, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        require_issuance()?;
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let (current_id, issuer) = issuer_key(&db, None).await?;
+        if key_id != current_id {
+            return Err(ServerFnError::new(
+                "Token issuer key has rotated, fetch the current one",
+            ));
+        }
+        let batch_size = crate::config::config().tokens.anonymous_batch_size as usize;
+        if blinded.is_empty() || blinded.len() > batch_size {
+            return Err(ServerFnError::new(format!(
+                "A batch holds between 1 and {batch_size} tokens"
+            )));
+        }
+        // An invalid solution must not cost a signing operation
+        let payload = shared::blind_token::batch_payload(&blinded);
+        spend_pow_challenge(&db, &challenge_id, &payload, solution).await?;
+        let signatures = blinded
+            .iter()
+            .map(|blinded| issuer.sign_blinded(blinded).map(BlindSignature::from))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ServerFnError::new(e.to_string()))?;
+
+        crate::metrics::METRICS.record_issued_tokens(signatures.len() as u64);
+        Ok(signatures)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(ServerFnError::new("Tokens are issued by the server"))
+    }
+}
+
+/// Redeem an anonymous token, signed by the current or previous issuer key.
+/// Served at `REDEEM_TOKEN_PATH`, so clients can reach it with
+/// `redeem_token_apart`.
+#[post("/api/tokens/redeem")]
+pub async fn redeem_token(token: AnonymousToken) -> Result<RedeemedToken, ServerFnError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use crate::metrics::METRICS;
+
+        let db = crate::persistence::postgres::establish_connection()
+            .await
+            .map_err(|e| ServerFnError::new(format!("Database connection failed: {}", e)))?;
+
+        let token: shared::blind_token::AnonymousToken = token.into();
+        let (_, issuer) = issuer_key(&db, Some(token.key_id)).await?;
+        if let Err(e) = issuer.verify(&token) {
+            METRICS.record_rejected_token();
+            return Err(ServerFnError::new(e.to_string()));
+        }
+
+        let redeemed = crate::admin::register_redeemed_token(&db, &token.secret())
+            .await
+            .map_err(|e| ServerFnError::new(format!("Failed to redeem token: {}", e)))?;
+        let Some(redeemed) = redeemed else {
+            METRICS.record_rejected_token();
+            return Err(ServerFnError::new("Token was already redeemed"));
+        };
+
+        METRICS.record_redeemed_token();
+        Ok(RedeemedToken {
+            id: redeemed.id.to_string(),
+            max_messages: redeemed.max_messages,
+            expires_at: redeemed.expires_at.timestamp(),
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        Err(ServerFnError::new("Tokens are redeemed by the server"))
+    }
+}
+
+/// Redeem `token` at the relay set with `dioxus::fullstack::set_server_url`
+/// over a connection of its own. Server functions share one pooled client,
+/// and a redemption on the connection that issued the token would link the
+/// two.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn redeem_token_apart(token: AnonymousToken) -> Result<RedeemedToken, String> {
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(0)
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let url = format!(
+        "{}{REDEEM_TOKEN_PATH}",
+        dioxus::fullstack::get_server_url().trim_end_matches('/')
+    );
+    let response = client
+        .post(url)
+        .json(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .map_err(|e| format!("Redeeming the token failed: {e}"))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let reason = response.text().await.unwrap_or_default();
+        return Err(format!("Token not redeemed ({status}): {reason}"));
+    }
+    response.json().await.map_err(|e| e.to_string())
+}
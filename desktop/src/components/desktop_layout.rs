@@ -18,11 +18,11 @@
 use super::{DesktopNavigation, SharedHeader};
 use crate::Route;
 use dioxus::prelude::*;
-use ui::{I18nContext, UserProfileMini};
+use ui::{current_i18n, I18nContext, UserProfileMini};
 
 #[derive(Props, Clone, PartialEq)]
 pub struct DesktopLayoutProps {
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(default = "User".to_string())]
     pub username: String,
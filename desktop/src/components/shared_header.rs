@@ -16,7 +16,7 @@
  */
 
 use dioxus::prelude::*;
-use ui::{I18nContext, Icon, IconName};
+use ui::{current_i18n, I18nContext, Icon, IconName};
 
 const SHARED_HEADER_CSS: Asset = asset!("/assets/shared_header.css");
 // TODO: needs the real app logo
@@ -25,7 +25,7 @@ const SHARED_HEADER_CSS: Asset = asset!("/assets/shared_header.css");
 pub struct SharedHeaderProps {
     #[props(default = "Cavebat".to_string())]
     pub brand_name: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
 }
 
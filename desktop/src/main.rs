@@ -16,8 +16,10 @@
  */
 
 use dioxus::prelude::*;
+use notifications::DesktopNotifications;
 use views::{ContactsManager, DesktopUserProfileEdit, Messages, RoomDashboard};
 mod components;
+mod notifications;
 mod views;
 pub use components::*;
 
@@ -27,8 +29,8 @@ enum Route {
     #[layout(AppLayout)]
         #[route("/")]
         RoomDashboard {},
-        #[route("/room/:room_id/messages")]
-        Messages { room_id: String },
+        #[route("/room/:room_id/messages?:focus")]
+        Messages { room_id: String, focus: String },
         #[route("/profile/edit")]
         DesktopUserProfileEdit {},
         #[route("/contacts")]
@@ -51,13 +53,56 @@ fn main() {
 #[component]
 fn App() -> Element {
     // Build cool things ✌️
+    let profile = ui::use_profile_provider();
+    // Every view translates with the active profile's language
+    let locale = ui::use_locale_provider(profile);
+    // Custom themes are read from a stylesheet next to the app database
+    use_hook(|| match ui::load_theme_file(ui::CUSTOM_THEMES_FILE) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("Failed to load custom themes: {e}");
+        }
+        _ => {}
+    });
+    // The active profile's theme, "auto" follows the system preference
+    ui::use_theme_provider(move || {
+        profile
+            .active_profile()
+            .map(|profile| profile.theme)
+            .unwrap_or_default()
+    });
+    // Disappearing messages expire even when their room is not open
+    use_hook(shared::retention::start_purge_task);
+    // Presence follows user input and is announced to every room
+    let presence = ui::use_presence_provider(ui::RelayMessages);
+    // Our avatar goes out with profile updates, contact avatars are fetched
+    ui::use_avatar_provider(profile, ui::RelayBlobs, ui::RelayMessages);
+    // Rooms keep receiving while closed and notify about new messages,
+    // pushed over a live connection or polled while there is none
+    ui::use_live_provider(
+        ui::RelaySubscription::connect,
+        ui::RelayMessages,
+        move || DesktopNotifications {
+            i18n: locale.i18n(),
+        },
+    );
+
+    // Right-to-left locales mirror the whole layout
+    let direction = ui::get_text_direction(&locale.locale.read());
 
     rsx! {
         // Global app resources - only variables and shared components
         document::Stylesheet { href: VARIABLES_CSS }
         document::Stylesheet { href: SHARED_CSS }
+        ui::ThemeStyle {}
 
-        Router::<Route> {}
+        div {
+            class: "app-activity",
+            lang: "{locale.locale}",
+            dir: "{direction}",
+            onmousemove: move |_| presence.record_activity(),
+            onkeydown: move |_| presence.record_activity(),
+            Router::<Route> {}
+        }
     }
 }
 
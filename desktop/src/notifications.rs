@@ -0,0 +1,116 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use shared::notifications::{Notification, NotificationSink};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::{Command, Stdio};
+use ui::I18nContext;
+
+/// Desktop notifications through the notification tool of the platform
+pub struct DesktopNotifications {
+    pub i18n: I18nContext,
+}
+
+impl DesktopNotifications {
+    /// Heading and text of a notification
+    fn texts(&self, notification: &Notification) -> (String, String) {
+        let heading = if notification.mentions_user {
+            format!(
+                "{} {} · {}",
+                notification.sender_name,
+                self.i18n.translate("notifications.mentioned"),
+                notification.room_name
+            )
+        } else {
+            format!("{} · {}", notification.sender_name, notification.room_name)
+        };
+        let text = notification
+            .preview
+            .clone()
+            .unwrap_or_else(|| self.i18n.translate("notifications.new_message"));
+        (heading, text)
+    }
+}
+
+/// Run a helper program without waiting for it to finish
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn launch(command: &mut Command) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the helper once it exits
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Escape the markup notification servers interpret in a body
+#[cfg(target_os = "linux")]
+fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Quote a string for AppleScript
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl NotificationSink for DesktopNotifications {
+    fn show(&self, notification: &Notification) -> Result<(), Box<dyn std::error::Error>> {
+        let (heading, text) = self.texts(notification);
+
+        #[cfg(target_os = "linux")]
+        return launch(
+            Command::new("notify-send")
+                .arg("--app-name")
+                .arg(self.i18n.translate("app.name"))
+                // Sender text starting with `-` must not be taken for an option
+                .arg("--")
+                .arg(heading)
+                .arg(escape_markup(&text)),
+        );
+
+        #[cfg(target_os = "macos")]
+        return launch(Command::new("osascript").arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript_string(&text),
+            applescript_string(&heading)
+        )));
+
+        // Other platforms have no notification tool to rely on
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            let _ = (heading, text);
+            Ok(())
+        }
+    }
+
+    fn play_sound(&self) -> Result<(), Box<dyn std::error::Error>> {
+        #[cfg(target_os = "linux")]
+        return launch(Command::new("canberra-gtk-play").args(["-i", "message-new-instant"]));
+
+        #[cfg(target_os = "macos")]
+        return launch(Command::new("afplay").arg("/System/Library/Sounds/Glass.aiff"));
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        Ok(())
+    }
+}
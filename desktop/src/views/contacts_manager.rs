@@ -17,7 +17,13 @@
 
 use crate::DesktopLayout;
 use dioxus::prelude::*;
-use ui::{I18nContext, Icon, IconName};
+use shared::crypto::message::{Contact as StoredContact, PresenceState};
+use shared::local::get_all_contacts;
+use shared::presence::contact_presence;
+use ui::{
+    current_i18n, unix_now, AvatarContext, ContactAvatar, I18nContext, Icon, IconName,
+    ProfileContext, TimeFormatter,
+};
 
 const CONTACTS_CSS: Asset = asset!("/assets/contacts_manager.css");
 
@@ -27,7 +33,7 @@ pub struct ContactsManagerProps {
     username: String,
     #[props(default = "Online".to_string())]
     user_status: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     i18n: I18nContext,
 }
 
@@ -37,8 +43,8 @@ pub struct Contact {
     pub name: String,
     pub username: String,
     pub status: ContactStatus,
-    pub avatar_initial: String,
-    pub last_seen: Option<String>,
+    /// Unix time the contact was last online
+    pub last_seen: Option<u64>,
 }
 
 #[derive(Clone, PartialEq)]
@@ -49,6 +55,17 @@ pub enum ContactStatus {
     DoNotDisturb,
 }
 
+impl From<PresenceState> for ContactStatus {
+    fn from(presence: PresenceState) -> Self {
+        match presence {
+            PresenceState::Online => ContactStatus::Online,
+            PresenceState::Away => ContactStatus::Away,
+            PresenceState::DoNotDisturb => ContactStatus::DoNotDisturb,
+            PresenceState::Offline => ContactStatus::Offline,
+        }
+    }
+}
+
 impl ContactStatus {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -63,7 +80,22 @@ impl ContactStatus {
 #[component]
 pub fn ContactsManager(props: ContactsManagerProps) -> Element {
     #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
-    let contacts = use_signal(|| get_mock_contacts());
+    let mut contacts = use_signal(|| get_mock_contacts());
+    let profile_context = use_context::<ProfileContext>();
+    let avatars = try_use_context::<AvatarContext>();
+
+    // Load the profile's contacts, reloading when new avatars arrived
+    use_effect(move || {
+        let _active_profile = (profile_context.active_profile_id)();
+        let _ = avatars.map(|avatars| (avatars.revision)());
+        spawn(async move {
+            match load_contacts().await {
+                Ok(loaded) if !loaded.is_empty() => contacts.set(loaded),
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to load contacts: {e}"),
+            }
+        });
+    });
     #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
     let mut search_query = use_signal(|| String::new());
     let mut selected_contact = use_signal(|| Option::<String>::None);
@@ -292,9 +324,10 @@ fn ContactListItem(props: ContactListItemProps) -> Element {
                 class: "cm-contact-avatar-section",
                 div {
                     class: "cm-contact-avatar-container",
-                    div {
-                        class: "cm-contact-avatar",
-                        "{props.contact.avatar_initial}"
+                    ContactAvatar {
+                        contact_id: Some(props.contact.id.clone()),
+                        name: props.contact.name.clone(),
+                        class: "cm-contact-avatar".to_string(),
                     }
                     div {
                         class: "cm-status-indicator status-{props.contact.status.as_str()}"
@@ -324,8 +357,12 @@ fn ContactListItem(props: ContactListItemProps) -> Element {
                         match props.contact.status {
                             ContactStatus::Online => props.i18n.translate("status.online"),
                             ContactStatus::Away => props.i18n.translate("status.away"),
-                            ContactStatus::Offline => if let Some(last_seen) = &props.contact.last_seen {
-                                format!("{} {}", props.i18n.translate("contacts.last_seen"), last_seen)
+                            ContactStatus::Offline => if let Some(last_seen) = props.contact.last_seen {
+                                format!(
+                                    "{} {}",
+                                    props.i18n.translate("contacts.last_seen"),
+                                    TimeFormatter::new(&props.i18n).last_seen(last_seen, unix_now())
+                                )
                             } else {
                                 props.i18n.translate("status.offline")
                             },
@@ -376,9 +413,10 @@ fn ContactDetailsPanel(props: ContactDetailsPanelProps) -> Element {
                 class: "cm-contact-details-header",
                 div {
                     class: "cm-contact-details-avatar-container",
-                    div {
-                        class: "cm-contact-details-avatar",
-                        "{props.contact.avatar_initial}"
+                    ContactAvatar {
+                        contact_id: Some(props.contact.id.clone()),
+                        name: props.contact.name.clone(),
+                        class: "cm-contact-details-avatar".to_string(),
                     }
                     div {
                         class: "cm-status-indicator-large status-{props.contact.status.as_str()}"
@@ -557,15 +595,39 @@ fn InfoItem(props: InfoItemProps) -> Element {
     }
 }
 
+/// Contacts of the active profile with their current presence
+async fn load_contacts() -> Result<Vec<Contact>, String> {
+    let now = unix_now();
+    let contact_jsons = get_all_contacts().await.map_err(|e| e.to_string())?;
+    Ok(contact_jsons
+        .iter()
+        .filter_map(|json| serde_json::from_str::<StoredContact>(json).ok())
+        .filter_map(|contact| {
+            let id = contact.id.clone()?;
+            let name = contact
+                .nickname
+                .clone()
+                .unwrap_or_else(|| contact.name.clone());
+            Some(Contact {
+                id,
+                username: contact.name.clone(),
+                status: contact_presence(&contact, now).into(),
+                last_seen: contact.last_seen,
+                name,
+            })
+        })
+        .collect())
+}
+
 // Mock data for development
 fn get_mock_contacts() -> Vec<Contact> {
+    let now = unix_now();
     vec![
         Contact {
             id: "1".to_string(),
             name: "Alice Johnson".to_string(),
             username: "alice_j".to_string(),
             status: ContactStatus::Online,
-            avatar_initial: "A".to_string(),
             last_seen: None,
         },
         Contact {
@@ -573,23 +635,20 @@ fn get_mock_contacts() -> Vec<Contact> {
             name: "Bob Smith".to_string(),
             username: "bob_smith".to_string(),
             status: ContactStatus::Away,
-            avatar_initial: "B".to_string(),
-            last_seen: Some("2 hours ago".to_string()),
+            last_seen: Some(now - 2 * 3600),
         },
         Contact {
             id: "3".to_string(),
             name: "Carol Davis".to_string(),
             username: "carol_d".to_string(),
             status: ContactStatus::Offline,
-            avatar_initial: "C".to_string(),
-            last_seen: Some("Yesterday".to_string()),
+            last_seen: Some(now - 26 * 3600),
         },
         Contact {
             id: "4".to_string(),
             name: "David Wilson".to_string(),
             username: "d_wilson".to_string(),
             status: ContactStatus::DoNotDisturb,
-            avatar_initial: "D".to_string(),
             last_seen: None,
         },
         Contact {
@@ -597,7 +656,6 @@ fn get_mock_contacts() -> Vec<Contact> {
             name: "Eve Brown".to_string(),
             username: "eve_b".to_string(),
             status: ContactStatus::Online,
-            avatar_initial: "E".to_string(),
             last_seen: None,
         },
     ]
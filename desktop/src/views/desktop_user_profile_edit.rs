@@ -15,10 +15,10 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::DesktopLayout;
+use crate::{DesktopLayout, Route};
 use dioxus::prelude::*;
 use shared::user_data::UserData;
-use ui::{I18nContext, UserProfileEdit};
+use ui::{current_i18n, AvatarContext, I18nContext, ProfileContext, UserProfileEdit};
 
 #[derive(Props, Clone, PartialEq)]
 pub struct DesktopUserProfileEditProps {
@@ -26,23 +26,40 @@ pub struct DesktopUserProfileEditProps {
     username: String,
     #[props(default = "Online".to_string())]
     user_status: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     i18n: I18nContext,
 }
 
 #[component]
 pub fn DesktopUserProfileEdit(props: DesktopUserProfileEditProps) -> Element {
-    // Create initial user data
-    let initial_data = UserData::default_for_user(&props.username);
+    let profile_context = use_context::<ProfileContext>();
+    let avatars = use_context::<AvatarContext>();
+    let mut is_saving = use_signal(|| false);
+    let nav = navigator();
+
+    // Edit the active profile, or start a new one if none exists yet
+    let initial_data = profile_context
+        .active_profile()
+        .unwrap_or_else(|| UserData::default_for_user(&props.username));
+    let form_key = initial_data.id.clone().unwrap_or_default();
 
     let handle_save = move |user_data: UserData| {
-        // TODO: Implement save logic
-        println!("Saving user data: {user_data:?}");
+        is_saving.set(true);
+        spawn(async move {
+            match profile_context.save(user_data).await {
+                Ok(()) => {
+                    // Contacts learn about name, status and avatar changes
+                    avatars.share();
+                    nav.push(Route::RoomDashboard {});
+                }
+                Err(e) => eprintln!("Failed to save profile: {e}"),
+            }
+            is_saving.set(false);
+        });
     };
 
     let handle_cancel = move |_| {
-        // TODO: Navigate back or show confirmation
-        println!("User cancelled profile edit");
+        nav.go_back();
     };
 
     rsx! {
@@ -57,11 +74,12 @@ pub fn DesktopUserProfileEdit(props: DesktopUserProfileEditProps) -> Element {
                 class: "content-wrapper",
 
                 UserProfileEdit {
+                    key: "{form_key}",
                     initial_data: initial_data,
                     i18n: props.i18n.clone(),
                     on_save: handle_save,
                     on_cancel: handle_cancel,
-                    is_saving: false
+                    is_saving: is_saving()
                 }
             }
         }
@@ -17,30 +17,271 @@
 
 use crate::Route;
 use dioxus::prelude::*;
-use shared::local::get_room;
-use ui::{I18nContext, RoomData, UserProfileMini};
+use shared::attachments::save_download;
+use shared::crypto::attachment::Attachment;
+use shared::history::{DeliveryState, MessageView, ReactionSummary};
+use shared::local::{
+    add_recent_emoji, delete_message, edit_message, fetch_attachment, get_recent_emojis, get_room,
+    get_room_history, get_room_notification_mode, get_room_retention, get_typing_members,
+    mark_room_read, receive_room_messages, search_messages, send_attachment, send_message,
+    send_reaction, send_typing, set_room_notification_mode, set_room_retention,
+};
+use shared::notifications::NotificationMode;
+use shared::search::SearchHit;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use ui::{
+    current_i18n, download_dir, unix_now, AttachmentCard, ContactAvatar, EmojiPicker, I18nArg,
+    I18nContext, LiveContext, MessageReactions, MessageSearch, NotificationModeSelect,
+    ReactionToggle, RelayBlobs, RelayMessages, RetentionSelect, RoomData, TimeFormatter,
+    UserProfileMini,
+};
 
 const MESSAGES_CSS: Asset = asset!("/assets/messages.css");
 
+/// How often an open room checks the relay for new messages
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Minimum time between typing indicators while the user keeps typing
+const TYPING_REFRESH: Duration = Duration::from_secs(4);
+
 #[derive(Props, Clone, PartialEq)]
 pub struct MessagesProps {
     room_id: String,
-    #[props(default = I18nContext::new("en"))]
+    /// Message to scroll to once the history is loaded
+    #[props(default)]
+    focus: String,
+    #[props(default = current_i18n())]
     i18n: I18nContext,
 }
 
 #[component]
 pub fn Messages(props: MessagesProps) -> Element {
+    // Keyed by room so that jumping to another room starts a fresh view
+    // instead of keeping the previous room's state and polling
+    rsx! {
+        for room_id in std::iter::once(props.room_id.clone()) {
+            RoomMessages {
+                key: "{room_id}",
+                room_id: room_id.clone(),
+                focus: props.focus.clone(),
+                i18n: props.i18n.clone()
+            }
+        }
+    }
+}
+
+#[component]
+fn RoomMessages(props: MessagesProps) -> Element {
     let mut room_data = use_signal(|| Option::<RoomData>::None);
     let mut loading = use_signal(|| true);
     #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
     let mut message_input = use_signal(|| String::new());
+    let mut history = use_signal(Vec::<MessageView>::new);
+    let mut recent_emojis = use_signal(Vec::<String>::new);
+    let mut emoji_picker_open = use_signal(|| false);
+    let mut typing = use_signal(Vec::<String>::new);
+    let mut typing_sent_at = use_signal(|| Option::<Instant>::None);
+    let mut retention = use_signal(|| Option::<u64>::None);
+    let mut notification_mode = use_signal(NotificationMode::default);
+    let mut uploading = use_signal(|| false);
+    let mut attachment_error = use_signal(|| Option::<String>::None);
+    let mut search_open = use_signal(|| false);
+    let mut search_query = use_signal(String::new);
+    let mut search_all_rooms = use_signal(|| false);
+    let mut search_hits = use_signal(Vec::<SearchHit>::new);
+    let mut focused = use_signal(|| Some(props.focus.clone()).filter(|id| !id.is_empty()));
+    let upload_failed = props.i18n.translate("attachments.upload_failed");
+
+    let room_id = props.room_id.clone();
+    let send_text = use_callback(move |_: ()| {
+        let text = message_input().trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        message_input.set(String::new());
+        // The message itself ends our typing indicator on the other side
+        typing_sent_at.set(None);
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_message(&RelayMessages, room_id.clone(), text, Vec::new()).await {
+                eprintln!("Failed to send message: {e}");
+            }
+            history.set(load_history(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let toggle_reaction = use_callback(move |(target_id, toggle): (String, ReactionToggle)| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_reaction(
+                &RelayMessages,
+                room_id.clone(),
+                target_id,
+                toggle.emoji,
+                toggle.remove,
+            )
+            .await
+            {
+                eprintln!("Failed to send reaction: {e}");
+            }
+            recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+            history.set(load_history(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let save_edit = use_callback(move |(message_id, text): (String, String)| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = edit_message(&RelayMessages, room_id.clone(), message_id, text).await {
+                eprintln!("Failed to edit message: {e}");
+            }
+            history.set(load_history(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let delete_for_everyone = use_callback(move |message_id: String| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = delete_message(&RelayMessages, room_id.clone(), message_id).await {
+                eprintln!("Failed to delete message: {e}");
+            }
+            history.set(load_history(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let update_typing = use_callback(move |text: String| {
+        let active = !text.trim().is_empty();
+        let refresh = match typing_sent_at() {
+            Some(sent_at) => !active || sent_at.elapsed() >= TYPING_REFRESH,
+            None => active,
+        };
+        if !refresh {
+            return;
+        }
+        typing_sent_at.set(active.then(Instant::now));
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_typing(&RelayMessages, room_id, active).await {
+                eprintln!("Failed to send typing indicator: {e}");
+            }
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let change_retention = use_callback(move |seconds: Option<u64>| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = set_room_retention(&RelayMessages, room_id.clone(), seconds).await {
+                eprintln!("Failed to set disappearing messages: {e}");
+            }
+            retention.set(get_room_retention(room_id).await.unwrap_or_default());
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let run_search = use_callback(move |(query, all_rooms): (String, bool)| {
+        search_query.set(query.clone());
+        search_all_rooms.set(all_rooms);
+        if query.trim().is_empty() {
+            search_hits.set(Vec::new());
+            return;
+        }
+        let scope = (!all_rooms).then(|| room_id.clone());
+        spawn(async move {
+            let hits = match search_messages(scope, query.clone()).await {
+                Ok(hits) => hits
+                    .iter()
+                    .filter_map(|json| SearchHit::from_json(json).ok())
+                    .collect(),
+                Err(e) => {
+                    eprintln!("Failed to search messages: {e}");
+                    Vec::new()
+                }
+            };
+            // Drop results of a query that was typed over meanwhile
+            if search_query() == query {
+                search_hits.set(hits);
+            }
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let open_hit = use_callback(move |hit: SearchHit| {
+        if hit.room_id == room_id {
+            focused.set(Some(hit.message_id));
+        } else {
+            navigator().push(Route::Messages {
+                room_id: hit.room_id,
+                focus: hit.message_id,
+            });
+        }
+    });
+
+    let room_id = props.room_id.clone();
+    let change_notification_mode = use_callback(move |mode: NotificationMode| {
+        let room_id = room_id.clone();
+        notification_mode.set(mode);
+        spawn(async move {
+            if let Err(e) = set_room_notification_mode(room_id, mode).await {
+                eprintln!("Failed to change notifications: {e}");
+            }
+        });
+    });
+
+    // Reload right away when live delivery applied pushed messages
+    let live = try_use_context::<LiveContext>();
+    let live_room_id = props.room_id.clone();
+    use_effect(move || {
+        let Some(live) = live else {
+            return;
+        };
+        if (live.revision)() == 0 {
+            return;
+        }
+        let room_id = live_room_id.clone();
+        spawn(async move {
+            history.set(reload_history(room_id).await);
+        });
+    });
+
+    // Poll the relay while the room is open, unless messages are pushed
+    let poll_room_id = props.room_id.clone();
+    use_future(move || {
+        let room_id = poll_room_id.clone();
+        async move {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if live.is_none_or(|live| live.polling()) {
+                    history.set(load_history(room_id.clone()).await);
+                } else {
+                    history.set(reload_history(room_id.clone()).await);
+                }
+                retention.set(
+                    get_room_retention(room_id.clone())
+                        .await
+                        .unwrap_or_default(),
+                );
+                typing.set(
+                    get_typing_members(room_id.clone())
+                        .await
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    });
+
+    let upload_room_id = props.room_id.clone();
 
     // Load room data on component initialization
     use_effect(move || {
         let room_id = props.room_id.clone();
         spawn(async move {
-            match get_room(room_id).await {
+            match get_room(room_id.clone()).await {
                 Ok(Some(room_json)) => {
                     if let Ok(room) = serde_json::from_str::<RoomData>(&room_json) {
                         room_data.set(Some(room));
@@ -54,6 +295,18 @@ pub fn Messages(props: MessagesProps) -> Element {
                     loading.set(false);
                 }
             }
+            recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+            notification_mode.set(
+                get_room_notification_mode(room_id.clone())
+                    .await
+                    .unwrap_or_default(),
+            );
+            retention.set(
+                get_room_retention(room_id.clone())
+                    .await
+                    .unwrap_or_default(),
+            );
+            history.set(load_history(room_id).await);
         });
     });
 
@@ -74,6 +327,19 @@ pub fn Messages(props: MessagesProps) -> Element {
             }
         };
     };
+    let members = [("count", I18nArg::from(room.member_count.unwrap_or(0)))];
+    let room_status = props.i18n.translate_with("messages.room_status", &members);
+    let member_count = props.i18n.translate_with("messages.member_count", &members);
+    let typing_text = props.i18n.translate_with(
+        "messages.typing",
+        &[
+            ("count", typing().len().into()),
+            ("names", typing().join(", ").into()),
+        ],
+    );
+    let times = TimeFormatter::new(&props.i18n);
+    let sent_times: Vec<u64> = history().iter().map(|view| view.message.sent_at).collect();
+    let day_separators = times.day_separators(&sent_times, unix_now());
 
     rsx! {
         document::Stylesheet { href: MESSAGES_CSS }
@@ -149,15 +415,7 @@ pub fn Messages(props: MessagesProps) -> Element {
                         }
                         p {
                             class: "room-status",
-                            if let Some(count) = room.member_count {
-                                if count > 0 {
-                                    "{count} members • Active now"
-                                } else {
-                                    "Active now"
-                                }
-                            } else {
-                                "Active now"
-                            }
+                            "{room_status}"
                         }
                     }
                 }
@@ -166,8 +424,9 @@ pub fn Messages(props: MessagesProps) -> Element {
                     class: "header-actions",
 
                     button {
-                        class: "header-button",
+                        class: if search_open() { "header-button active" } else { "header-button" },
                         "title": "{props.i18n.translate(\"messages.search\")}",
+                        onclick: move |_| search_open.toggle(),
                         // Search icon
                         "🔍"
                     }
@@ -188,6 +447,19 @@ pub fn Messages(props: MessagesProps) -> Element {
                 }
             }
 
+            if search_open() {
+                MessageSearch {
+                    query: search_query(),
+                    all_rooms: search_all_rooms(),
+                    hits: search_hits(),
+                    i18n: props.i18n.clone(),
+                    on_query: move |query: String| run_search.call((query, search_all_rooms())),
+                    on_scope: move |all_rooms: bool| run_search.call((search_query(), all_rooms)),
+                    on_select: move |hit: SearchHit| open_hit.call(hit),
+                    on_close: move |_| search_open.set(false)
+                }
+            }
+
             // Messages area
             main {
                 class: "messages-main",
@@ -195,37 +467,59 @@ pub fn Messages(props: MessagesProps) -> Element {
                 div {
                     class: "messages-list",
 
-                    // Sample messages - these will be dynamic later
-                    MessageComponent {
-                        sender_name: "Alice".to_string(),
-                        message: "Hey everyone! How's the project going?".to_string(),
-                        timestamp: "10:30 AM".to_string(),
-                        is_own: false,
-                        i18n: props.i18n.clone()
-                    }
-
-                    MessageComponent {
-                        sender_name: "You".to_string(),
-                        message: "Great! Just finished the authentication module. The encryption is working perfectly.".to_string(),
-                        timestamp: "10:32 AM".to_string(),
-                        is_own: true,
-                        i18n: props.i18n.clone()
-                    }
-
-                    MessageComponent {
-                        sender_name: "Bob".to_string(),
-                        message: "Awesome work! I've been working on the UI components. Should have the messaging interface ready soon.".to_string(),
-                        timestamp: "10:35 AM".to_string(),
-                        is_own: false,
-                        i18n: props.i18n.clone()
+                    if history().is_empty() {
+                        p {
+                            class: "messages-empty",
+                            "{props.i18n.translate(\"messages.empty_state\")}"
+                        }
                     }
 
-                    MessageComponent {
-                        sender_name: "You".to_string(),
-                        message: "Looking forward to seeing it! The backend API is almost complete too.".to_string(),
-                        timestamp: "10:37 AM".to_string(),
-                        is_own: true,
-                        i18n: props.i18n.clone()
+                    for (view, day_separator) in history().into_iter().zip(day_separators) {
+                        if view.message.is_deleted() {
+                            MessageComponent {
+                                key: "{view.message.message_id}",
+                                message_id: view.message.message_id.clone(),
+                                focused: focused().as_ref() == Some(&view.message.message_id),
+                                sender_name: if view.message.is_own { "You".to_string() } else { view.sender_name.clone() },
+                                sender_contact_id: view.sender_contact_id.clone(),
+                                message: String::new(),
+                                day_separator: day_separator,
+                                timestamp: times.time(view.message.sent_at),
+                                is_own: view.message.is_own,
+                                deleted: true,
+                                i18n: props.i18n.clone()
+                            }
+                        } else {
+                            MessageComponent {
+                                key: "{view.message.message_id}",
+                                message_id: view.message.message_id.clone(),
+                                focused: focused().as_ref() == Some(&view.message.message_id),
+                                sender_name: if view.message.is_own { "You".to_string() } else { view.sender_name.clone() },
+                                sender_contact_id: view.sender_contact_id.clone(),
+                                message: view.message.text.clone(),
+                                day_separator: day_separator,
+                                timestamp: times.time(view.message.sent_at),
+                                is_own: view.message.is_own,
+                                attachments: view.message.attachments.clone(),
+                                reactions: view.reactions.clone(),
+                                recent_emojis: recent_emojis(),
+                                edited: view.message.is_edited(),
+                                delivery: view.delivery,
+                                on_react: {
+                                    let message_id = view.message.message_id.clone();
+                                    move |toggle: ReactionToggle| toggle_reaction.call((message_id.clone(), toggle))
+                                },
+                                on_edit: {
+                                    let message_id = view.message.message_id.clone();
+                                    move |text: String| save_edit.call((message_id.clone(), text))
+                                },
+                                on_delete: {
+                                    let message_id = view.message.message_id.clone();
+                                    move |_| delete_for_everyone.call(message_id.clone())
+                                },
+                                i18n: props.i18n.clone()
+                            }
+                        }
                     }
                 }
             }
@@ -234,13 +528,62 @@ pub fn Messages(props: MessagesProps) -> Element {
                 footer {
                     class: "messages-input-area",
 
+                    if !typing().is_empty() {
+                        p {
+                            class: "typing-indicator",
+                            "{typing_text}"
+                        }
+                    }
+
+                    if let Some(error) = attachment_error() {
+                        p { class: "attachment-error", "{error}" }
+                    }
+
                     div {
                         class: "input-container",
 
-                        button {
-                            class: "attachment-button",
+                        label {
+                            class: if uploading() { "attachment-button uploading" } else { "attachment-button" },
                             "title": "{props.i18n.translate(\"messages.attach\")}",
                             "📎"
+                            input {
+                                r#type: "file",
+                                class: "attachment-input",
+                                disabled: uploading(),
+                                onchange: move |evt| {
+                                    let upload_failed = upload_failed.clone();
+                                    let room_id = upload_room_id.clone();
+                                    spawn(async move {
+                                        uploading.set(true);
+                                        attachment_error.set(None);
+                                        for file in evt.files() {
+                                            let mime_type = file
+                                                .content_type()
+                                                .unwrap_or_else(|| "application/octet-stream".to_string());
+                                            let result = match file.read_bytes().await {
+                                                Ok(bytes) => {
+                                                    send_attachment(&RelayBlobs, file.name(), mime_type, bytes.to_vec())
+                                                        .await
+                                                        .map_err(|e| e.to_string())
+                                                }
+                                                Err(e) => Err(e.to_string()),
+                                            };
+                                            let result = match result {
+                                                Ok(json) => send_message(&RelayMessages, room_id.clone(), String::new(), vec![json])
+                                                    .await
+                                                    .map_err(|e| e.to_string()),
+                                                Err(e) => Err(e),
+                                            };
+                                            if let Err(e) = result {
+                                                eprintln!("Failed to upload attachment: {e}");
+                                                attachment_error.set(Some(upload_failed.clone()));
+                                            }
+                                        }
+                                        history.set(load_history(room_id).await);
+                                        uploading.set(false);
+                                    });
+                                }
+                            }
                         }
 
                         input {
@@ -248,30 +591,53 @@ pub fn Messages(props: MessagesProps) -> Element {
                             class: "message-input",
                             placeholder: "{props.i18n.translate(\"messages.placeholder\")}",
                             value: "{message_input()}",
-                            oninput: move |evt| message_input.set(evt.value()),
+                            oninput: move |evt| {
+                                message_input.set(evt.value());
+                                update_typing.call(evt.value());
+                            },
                             onkeypress: move |evt| {
-                                if evt.key() == Key::Enter && !message_input().trim().is_empty() {
-                                    // TODO: Send message logic here
-                                    message_input.set(String::new());
+                                if evt.key() == Key::Enter {
+                                    send_text.call(());
                                 }
                             }
                         }
 
-                        button {
-                            class: "emoji-button",
-                            "title": "{props.i18n.translate(\"messages.emoji\")}",
-                            "😊"
+                        div {
+                            class: "emoji-picker-anchor",
+
+                            button {
+                                class: "emoji-button",
+                                "title": "{props.i18n.translate(\"messages.emoji\")}",
+                                onclick: move |_| emoji_picker_open.toggle(),
+                                "😊"
+                            }
+
+                            if emoji_picker_open() {
+                                div {
+                                    class: "emoji-picker-popover",
+                                    EmojiPicker {
+                                        recent: recent_emojis(),
+                                        i18n: props.i18n.clone(),
+                                        on_select: move |emoji: String| {
+                                            message_input.write().push_str(&emoji);
+                                            emoji_picker_open.set(false);
+                                            spawn(async move {
+                                                if let Err(e) = add_recent_emoji(emoji).await {
+                                                    eprintln!("Failed to save recent emoji: {e}");
+                                                }
+                                                recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+                                            });
+                                        },
+                                        on_close: move |_| emoji_picker_open.set(false),
+                                    }
+                                }
+                            }
                         }
 
                         button {
                             class: "send-button",
                             disabled: message_input().trim().is_empty(),
-                            onclick: move |_| {
-                                if !message_input().trim().is_empty() {
-                                    // TODO: Send message logic here
-                                    message_input.set(String::new());
-                                }
-                            },
+                            onclick: move |_| send_text.call(()),
                             "Send"
                         }
                     }
@@ -313,13 +679,21 @@ pub fn Messages(props: MessagesProps) -> Element {
                         }
                         p {
                             class: "room-member-count",
-                            if let Some(count) = room.member_count {
-                                "{count} members"
-                            } else {
-                                "No members"
-                            }
+                            "{member_count}"
                         }
                     }
+
+                    RetentionSelect {
+                        seconds: retention(),
+                        i18n: props.i18n.clone(),
+                        on_change: move |seconds| change_retention.call(seconds)
+                    }
+
+                    NotificationModeSelect {
+                        mode: notification_mode(),
+                        i18n: props.i18n.clone(),
+                        on_change: move |mode| change_notification_mode.call(mode)
+                    }
                 }
 
                 // Members list
@@ -358,24 +732,85 @@ pub fn Messages(props: MessagesProps) -> Element {
 
 #[derive(Props, Clone, PartialEq)]
 struct MessageProps {
+    message_id: String,
+    /// Scrolled into view and highlighted, e.g. when opened from search
+    #[props(default)]
+    focused: bool,
     sender_name: String,
+    /// Contact whose avatar is shown next to the message
+    #[props(default)]
+    sender_contact_id: Option<String>,
     message: String,
+    /// Label of the day this message starts, e.g. "Today"
+    #[props(default)]
+    day_separator: Option<String>,
     timestamp: String,
     is_own: bool,
+    #[props(default)]
+    attachments: Vec<Attachment>,
+    #[props(default)]
+    reactions: Vec<ReactionSummary>,
+    #[props(default)]
+    recent_emojis: Vec<String>,
+    #[props(default)]
+    edited: bool,
+    #[props(default)]
+    deleted: bool,
+    /// Delivery progress, set for our own messages only
+    #[props(default)]
+    delivery: Option<DeliveryState>,
+    #[props(optional)]
+    on_react: Option<EventHandler<ReactionToggle>>,
+    /// Offered on our own messages only
+    #[props(optional)]
+    on_edit: Option<EventHandler<String>>,
+    #[props(optional)]
+    on_delete: Option<EventHandler<()>>,
     i18n: I18nContext,
 }
 
 #[component]
 fn MessageComponent(props: MessageProps) -> Element {
+    let mut draft = use_signal(|| Option::<String>::None);
+    let mut element = use_signal(|| Option::<Rc<MountedData>>::None);
+    use_effect(use_reactive((&props.focused,), move |(focused,)| {
+        if let (true, Some(element)) = (focused, element()) {
+            spawn(async move {
+                let _ = element.scroll_to(ScrollBehavior::Smooth).await;
+            });
+        }
+    }));
+    let can_change = props.is_own && !props.deleted;
+    let original = props.message.clone();
+    let delivery = props.delivery.map(|state| match state {
+        DeliveryState::Sent => ("message-delivery", "messages.delivery_sent", "✓"),
+        DeliveryState::Delivered => ("message-delivery", "messages.delivery_delivered", "✓✓"),
+        DeliveryState::Read => ("message-delivery read", "messages.delivery_read", "✓✓"),
+    });
+
     rsx! {
+        if let Some(day) = &props.day_separator {
+            div {
+                class: "day-separator",
+                role: "separator",
+                span { "{day}" }
+            }
+        }
         div {
-            class: if props.is_own { "message own-message" } else { "message other-message" },
+            id: "message-{props.message_id}",
+            class: match (props.is_own, props.focused) {
+                (true, true) => "message own-message focused",
+                (true, false) => "message own-message",
+                (false, true) => "message other-message focused",
+                (false, false) => "message other-message",
+            },
+            onmounted: move |evt| element.set(Some(evt.data())),
 
             if !props.is_own {
-                div {
-                    class: "message-avatar",
-                    // Avatar placeholder
-                    "{props.sender_name.chars().next().unwrap_or('?')}"
+                ContactAvatar {
+                    contact_id: props.sender_contact_id.clone(),
+                    name: props.sender_name.clone(),
+                    class: "message-avatar".to_string(),
                 }
             }
 
@@ -385,23 +820,185 @@ fn MessageComponent(props: MessageProps) -> Element {
                 if !props.is_own {
                     div {
                         class: "message-sender",
+                        dir: "auto",
                         "{props.sender_name}"
                     }
                 }
 
                 div {
                     class: "message-bubble",
-                    p {
-                        class: "message-text",
-                        "{props.message}"
+                    if props.deleted {
+                        p {
+                            class: "message-text message-tombstone",
+                            "{props.i18n.translate(\"messages.deleted\")}"
+                        }
+                    } else if let Some(text) = draft() {
+                        div {
+                            class: "message-edit",
+                            input {
+                                r#type: "text",
+                                class: "message-edit-input",
+                                dir: "auto",
+                                value: "{text}",
+                                oninput: move |evt| draft.set(Some(evt.value())),
+                            }
+                            button {
+                                class: "message-edit-save",
+                                disabled: text.trim().is_empty() && props.attachments.is_empty(),
+                                onclick: move |_| {
+                                    if let (Some(text), Some(on_edit)) = (draft(), props.on_edit) {
+                                        if text != original {
+                                            on_edit.call(text);
+                                        }
+                                    }
+                                    draft.set(None);
+                                },
+                                "{props.i18n.translate(\"messages.save\")}"
+                            }
+                            button {
+                                class: "message-edit-cancel",
+                                onclick: move |_| draft.set(None),
+                                "{props.i18n.translate(\"messages.cancel\")}"
+                            }
+                        }
+                    } else {
+                        if !props.message.is_empty() {
+                            p {
+                                class: "message-text",
+                                dir: "auto",
+                                "{props.message}"
+                            }
+                        }
+                        for attachment in props.attachments.iter() {
+                            MessageAttachment {
+                                key: "{attachment.digest_hex()}",
+                                attachment: attachment.clone(),
+                                i18n: props.i18n.clone()
+                            }
+                        }
+                    }
+                }
+
+                if let Some(on_react) = props.on_react {
+                    MessageReactions {
+                        reactions: props.reactions.clone(),
+                        recent: props.recent_emojis.clone(),
+                        i18n: props.i18n.clone(),
+                        on_toggle: on_react
                     }
                 }
 
                 div {
                     class: "message-timestamp",
                     "{props.timestamp}"
+                    if props.edited {
+                        span { class: "message-edited", " · {props.i18n.translate(\"messages.edited\")}" }
+                    }
+                    if let Some((class, title, marks)) = delivery {
+                        span {
+                            class: class,
+                            "title": "{props.i18n.translate(title)}",
+                            " {marks}"
+                        }
+                    }
+                }
+
+                if can_change && draft().is_none() {
+                    div {
+                        class: "message-actions",
+                        if props.on_edit.is_some() {
+                            button {
+                                class: "message-action",
+                                onclick: {
+                                    let text = props.message.clone();
+                                    move |_| draft.set(Some(text.clone()))
+                                },
+                                "{props.i18n.translate(\"messages.edit\")}"
+                            }
+                        }
+                        if let Some(on_delete) = props.on_delete {
+                            button {
+                                class: "message-action danger",
+                                onclick: move |_| on_delete.call(()),
+                                "{props.i18n.translate(\"messages.delete\")}"
+                            }
+                        }
+                    }
                 }
             }
         }
     }
 }
+
+/// Fetch new messages from the relay, mark them read since the room is open
+/// and load the room's history
+async fn load_history(room_id: String) -> Vec<MessageView> {
+    if let Err(e) = receive_room_messages(&RelayMessages, room_id.clone()).await {
+        eprintln!("Failed to receive messages: {e}");
+    }
+    reload_history(room_id).await
+}
+
+/// Mark the room's messages read since it is open and load its history
+async fn reload_history(room_id: String) -> Vec<MessageView> {
+    if let Err(e) = mark_room_read(&RelayMessages, room_id.clone()).await {
+        eprintln!("Failed to mark messages read: {e}");
+    }
+    match get_room_history(room_id).await {
+        Ok(history) => history
+            .iter()
+            .filter_map(|json| MessageView::from_json(json).ok())
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to load history: {e}");
+            Vec::new()
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct MessageAttachmentProps {
+    attachment: Attachment,
+    i18n: I18nContext,
+}
+
+/// File card that downloads, decrypts and saves the attachment when opened
+#[component]
+fn MessageAttachment(props: MessageAttachmentProps) -> Element {
+    let mut progress = use_signal(|| Option::<f32>::None);
+    let mut saved_to = use_signal(|| Option::<String>::None);
+    let download_failed = props.i18n.translate("attachments.download_failed");
+
+    rsx! {
+        AttachmentCard {
+            attachment: props.attachment.clone(),
+            progress: progress(),
+            i18n: props.i18n.clone(),
+            on_open: move |attachment: Attachment| {
+                let download_failed = download_failed.clone();
+                spawn(async move {
+                    progress.set(Some(0.0));
+                    let result = match attachment.to_json() {
+                        Ok(json) => fetch_attachment(&RelayBlobs, json)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    match result {
+                        Ok(data) => {
+                            match save_download(&download_dir("Downloads"), &attachment.file_name, &data) {
+                                Ok(path) => saved_to.set(Some(path.display().to_string())),
+                                Err(e) => saved_to.set(Some(format!("{download_failed}: {e}"))),
+                            }
+                        }
+                        Err(e) => saved_to.set(Some(format!("{download_failed}: {e}"))),
+                    }
+                    progress.set(None);
+                });
+            }
+        }
+        if let Some(saved_to) = saved_to() {
+            p { class: "attachment-saved", "{saved_to}" }
+        }
+    }
+}
@@ -19,19 +19,19 @@ use crate::{DesktopLayout, Route};
 use api::get_server_data;
 use dioxus::prelude::*;
 use shared::local::{create_room, get_all_rooms};
-use ui::{get_language_name, get_text_direction, I18nContext, Icon, IconName, RoomData};
+use ui::{
+    current_i18n, Avatar, AvatarContext, I18nContext, Icon, IconName, ProfileContext, RoomData,
+};
 
 const PARTY_DASH_CSS: Asset = asset!("/assets/room_dash.css");
 
-const IMG_IMG: &str = "https://images.unsplash.com/photo-1472099645785-5658abf4ff4e?w=40&h=40&fit=crop&crop=face&auto=format"; // Placeholder avatar
-
 #[derive(Props, Clone, PartialEq)]
 pub struct RoomDashboardProps {
     #[props(default = "Thom T.".to_string())]
     username: String,
     #[props(default = "View Profile".to_string())]
     user_subtitle: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     i18n: I18nContext,
 }
 
@@ -41,13 +41,16 @@ pub fn RoomDashboard(props: RoomDashboardProps) -> Element {
     let mut rooms = use_signal(|| Vec::<RoomData>::new());
     let mut loading_rooms = use_signal(|| true);
     let mut server_data = use_signal(|| None::<String>);
-    let locale = props.i18n.get_current_locale();
-    // Keep for debugging until language switcher is implemented
-    println!("Language: {}", get_language_name(locale));
-    println!("Text direction: {}", get_text_direction(locale));
-
-    // Load rooms on component initialization
+    let profile_context = use_context::<ProfileContext>();
+    let avatars = use_context::<AvatarContext>();
+    let display_name = profile_context
+        .active_profile()
+        .map(|profile| profile.display_name)
+        .unwrap_or_else(|| props.username.clone());
+
+    // Load rooms on component initialization and whenever the active profile changes
     use_effect(move || {
+        let _active_profile = (profile_context.active_profile_id)();
         spawn(async move {
             match get_all_rooms().await {
                 Ok(room_jsons) => {
@@ -151,14 +154,10 @@ pub fn RoomDashboard(props: RoomDashboardProps) -> Element {
                         }
 
                         // User avatar
-                        div {
-                            class: "header-avatar",
-
-                            img {
-                                src: IMG_IMG,
-                                alt: "User Avatar",
-                                class: "avatar-image"
-                            }
+                        Avatar {
+                            image: (avatars.own)(),
+                            name: display_name,
+                            class: "header-avatar".to_string(),
                         }
                     }
                 }
@@ -361,7 +360,7 @@ fn RoomCardComponent(props: PartyCardProps) -> Element {
                 // Enter button
                 Link {
                     class: "enter-button",
-                    to: Route::Messages { room_id: props.room_id },
+                    to: Route::Messages { room_id: props.room_id, focus: String::new() },
                     onclick: move |_| {
                         // web_sys::console::log_1(&"Enter button clicked!".into());
                     },
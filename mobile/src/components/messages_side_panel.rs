@@ -1,5 +1,5 @@
 use dioxus::prelude::*;
-use ui::{I18nContext, Icon, IconName};
+use ui::{I18nContext, Icon, IconName, RetentionSelect};
 
 #[derive(Clone, PartialEq)]
 pub struct Member {
@@ -21,12 +21,22 @@ pub struct MessagesSidePanelProps {
     pub room_name: String,
     pub members: Vec<Member>,
     pub shared_files: Vec<SharedFile>,
+    /// Disappearing messages timer of the room, in seconds
+    #[props(default)]
+    pub retention: Option<u64>,
+    #[props(optional)]
+    pub on_retention_change: Option<EventHandler<Option<u64>>>,
     pub i18n: I18nContext,
     pub on_close: EventHandler<()>,
 }
 
 #[component]
 pub fn MessagesSidePanel(props: MessagesSidePanelProps) -> Element {
+    let member_count = props.i18n.translate_with(
+        "messages.member_count",
+        &[("count", props.members.len().into())],
+    );
+
     rsx! {
         aside {
             class: "mm-side-panel",
@@ -68,7 +78,15 @@ pub fn MessagesSidePanel(props: MessagesSidePanelProps) -> Element {
                     }
                     p {
                         class: "mm-side-room-members",
-                        "{props.members.len()} members"
+                        "{member_count}"
+                    }
+                    if let Some(on_retention_change) = props.on_retention_change {
+                        RetentionSelect {
+                            seconds: props.retention,
+                            i18n: props.i18n.clone(),
+                            on_change: on_retention_change,
+                            class: "mm-retention-select".to_string()
+                        }
                     }
                 }
 
@@ -1,5 +1,5 @@
 use dioxus::prelude::*;
-use ui::{I18nContext, Icon, IconName};
+use ui::{current_i18n, I18nContext, Icon, IconName};
 
 const MOBILE_HEADER_CSS: Asset = asset!("/assets/mobile_header.css");
 
@@ -7,7 +7,7 @@ const MOBILE_HEADER_CSS: Asset = asset!("/assets/mobile_header.css");
 pub struct MobileHeaderProps {
     #[props(default = "Cavebat".to_string())]
     pub brand_name: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(default = false)]
     pub has_notifications: bool,
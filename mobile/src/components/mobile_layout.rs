@@ -1,12 +1,12 @@
 use super::{MobileHeader, MobileNavigation};
 use dioxus::prelude::*;
-use ui::I18nContext;
+use ui::{current_i18n, I18nContext};
 
 const MOBILE_LAYOUT_CSS: Asset = asset!("/assets/mobile_layout.css");
 
 #[derive(Props, Clone, PartialEq)]
 pub struct MobileLayoutProps {
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(default = "Cavebat".to_string())]
     pub brand_name: String,
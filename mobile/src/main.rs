@@ -1,6 +1,5 @@
 use dioxus::prelude::*;
-
-use ui::I18nContext;
+use shared::notifications::SilentNotifications;
 use views::{MobileMessages, MobileRoomDashboard};
 
 mod components;
@@ -39,11 +38,35 @@ fn main() {
 #[component]
 fn App() -> Element {
     // Build cool things ✌️
+    let profile = ui::use_profile_provider();
+    // Every view translates with the active profile's language
+    let locale = ui::use_locale_provider(profile);
+    // The active profile's theme, "auto" follows the system preference
+    ui::use_theme_provider(move || {
+        profile
+            .active_profile()
+            .map(|profile| profile.theme)
+            .unwrap_or_default()
+    });
+    // Disappearing messages expire even when their room is not open
+    use_hook(shared::retention::start_purge_task);
+    // Presence follows user input and is announced to every room
+    let presence = ui::use_presence_provider(ui::RelayMessages);
+    // Our avatar goes out with profile updates, contact avatars are fetched
+    ui::use_avatar_provider(profile, ui::RelayBlobs, ui::RelayMessages);
+    // Messages are pushed over a live connection, or polled while there is none
+    ui::use_live_provider(ui::RelaySubscription::connect, ui::RelayMessages, || {
+        SilentNotifications
+    });
+
+    // Right-to-left locales mirror the whole layout
+    let direction = ui::get_text_direction(&locale.locale.read());
 
     rsx! {
         // Global app resources
         document::Stylesheet { href: VARIABLES_CSS }
         document::Stylesheet { href: MAIN_CSS }
+        ui::ThemeStyle {}
 
         // Platform-specific styles
         if cfg!(target_os = "ios") {
@@ -60,7 +83,14 @@ fn App() -> Element {
             content: "width=device-width, initial-scale=1.0, viewport-fit=cover"
         }
 
-        Router::<Route> {}
+        div {
+            class: "app-activity",
+            lang: "{locale.locale}",
+            dir: "{direction}",
+            ontouchstart: move |_| presence.record_activity(),
+            onkeydown: move |_| presence.record_activity(),
+            Router::<Route> {}
+        }
     }
 }
 
@@ -70,7 +100,6 @@ fn Home() -> Element {
         MobileRoomDashboard {
             username: "Mobile User".to_string(),
             user_subtitle: "Welcome to Mobile App".to_string(),
-            i18n: I18nContext::new("en")
         }
     }
 }
@@ -81,7 +110,6 @@ fn Messages(room_id: String) -> Element {
         MobileMessages {
             room_id: room_id,
             room_name: "First Mac Room".to_string(),
-            i18n: I18nContext::new("en"),
             show_side_panel: false
         }
     }
@@ -1,17 +1,37 @@
 use crate::components::messages_side_panel::{Member, SharedFile};
 use crate::components::{MessagesSidePanel, MobileLayout};
 use dioxus::prelude::*;
-use ui::{I18nContext, Icon, IconName};
+use shared::attachments::save_download;
+use shared::crypto::attachment::Attachment;
+use shared::history::{DeliveryState, MessageView, ReactionSummary};
+use shared::local::{
+    add_recent_emoji, delete_message, edit_message, fetch_attachment, get_recent_emojis,
+    get_room_history, get_room_retention, get_typing_members, mark_room_read,
+    receive_room_messages, send_attachment, send_message, send_reaction, send_typing,
+    set_room_retention,
+};
+use std::time::{Duration, Instant};
+use ui::{
+    current_i18n, download_dir, unix_now, AttachmentCard, ContactAvatar, EmojiPicker, I18nContext,
+    Icon, IconName, LiveContext, MessageReactions, ReactionToggle, RelayBlobs, RelayMessages,
+    TimeFormatter,
+};
 
 const MOBILE_MESSAGES_CSS: Asset = asset!("/assets/mobile_messages.css");
 
+/// How often an open room checks the relay for new messages
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Minimum time between typing indicators while the user keeps typing
+const TYPING_REFRESH: Duration = Duration::from_secs(4);
+
 #[derive(Props, Clone, PartialEq)]
 pub struct MobileMessagesProps {
     #[props(default = "1".to_string())]
     pub room_id: String,
     #[props(default = "First Mac Room".to_string())]
     pub room_name: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(default = false)]
     pub show_side_panel: bool,
@@ -22,9 +42,44 @@ pub struct Message {
     pub id: String,
     pub sender_id: String,
     pub sender_name: String,
+    /// Contact whose avatar is shown next to received messages
+    pub sender_contact_id: Option<String>,
     pub content: String,
-    pub timestamp: String,
+    pub sent_at: u64,
     pub is_sent: bool,
+    pub attachments: Vec<Attachment>,
+    pub reactions: Vec<ReactionSummary>,
+    pub edited: bool,
+    pub deleted: bool,
+    pub delivery: Option<DeliveryState>,
+}
+
+impl From<MessageView> for Message {
+    fn from(view: MessageView) -> Self {
+        let message = view.message;
+        Self {
+            id: message.message_id,
+            sender_id: message
+                .sender_public
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+            sender_name: if message.is_own {
+                "You".to_string()
+            } else {
+                view.sender_name
+            },
+            sender_contact_id: view.sender_contact_id,
+            content: message.text,
+            sent_at: message.sent_at,
+            is_sent: message.is_own,
+            edited: message.is_edited(),
+            deleted: message.is_deleted(),
+            attachments: message.attachments,
+            reactions: view.reactions,
+            delivery: view.delivery,
+        }
+    }
 }
 
 #[component]
@@ -33,43 +88,164 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
     let mut message_input = use_signal(|| String::new());
     let mut active_tab = use_signal(|| "chat".to_string());
     let mut show_side_panel = use_signal(|| props.show_side_panel);
+    let mut uploading = use_signal(|| false);
+    let upload_failed = props.i18n.translate("attachments.upload_failed");
 
-    // Sample messages data
-    let mut messages = use_signal(|| {
-        vec![
-        Message {
-            id: "1".to_string(),
-            sender_id: "user1".to_string(),
-            sender_name: "A".to_string(),
-            content: "Hey everyone! How's the project going?".to_string(),
-            timestamp: "10:30 AM".to_string(),
-            is_sent: false,
-        },
-        Message {
-            id: "2".to_string(),
-            sender_id: "current_user".to_string(),
-            sender_name: "You".to_string(),
-            content: "Great! Just finished the authentication module. The encryption is working perfectly.".to_string(),
-            timestamp: "10:32 AM".to_string(),
-            is_sent: true,
-        },
-        Message {
-            id: "3".to_string(),
-            sender_id: "user2".to_string(),
-            sender_name: "B".to_string(),
-            content: "Awesome work! I've been working on the UI components. Should have the messaging interface ready soon.".to_string(),
-            timestamp: "10:35 AM".to_string(),
-            is_sent: false,
-        },
-        Message {
-            id: "4".to_string(),
-            sender_id: "current_user".to_string(),
-            sender_name: "You".to_string(),
-            content: "Looking forward to seeing it! The backend API is almost complete too.".to_string(),
-            timestamp: "10:37 AM".to_string(),
-            is_sent: true,
-        },
-    ]
+    let mut messages = use_signal(Vec::<Message>::new);
+    let mut recent_emojis = use_signal(Vec::<String>::new);
+    let mut emoji_picker_open = use_signal(|| false);
+    let mut attachment_error = use_signal(|| Option::<String>::None);
+    let mut typing = use_signal(Vec::<String>::new);
+    let mut typing_sent_at = use_signal(|| Option::<Instant>::None);
+    let mut retention = use_signal(|| Option::<u64>::None);
+
+    let room_id = props.room_id.clone();
+    let send_text = use_callback(move |_: ()| {
+        let text = message_input().trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        message_input.set(String::new());
+        // The message itself ends our typing indicator on the other side
+        typing_sent_at.set(None);
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_message(&RelayMessages, room_id.clone(), text, Vec::new()).await {
+                eprintln!("Failed to send message: {e}");
+            }
+            messages.set(load_messages(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let toggle_reaction = use_callback(move |(target_id, toggle): (String, ReactionToggle)| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_reaction(
+                &RelayMessages,
+                room_id.clone(),
+                target_id,
+                toggle.emoji,
+                toggle.remove,
+            )
+            .await
+            {
+                eprintln!("Failed to send reaction: {e}");
+            }
+            recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+            messages.set(load_messages(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let save_edit = use_callback(move |(message_id, text): (String, String)| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = edit_message(&RelayMessages, room_id.clone(), message_id, text).await {
+                eprintln!("Failed to edit message: {e}");
+            }
+            messages.set(load_messages(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let delete_for_everyone = use_callback(move |message_id: String| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = delete_message(&RelayMessages, room_id.clone(), message_id).await {
+                eprintln!("Failed to delete message: {e}");
+            }
+            messages.set(load_messages(room_id).await);
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let update_typing = use_callback(move |text: String| {
+        let active = !text.trim().is_empty();
+        let refresh = match typing_sent_at() {
+            Some(sent_at) => !active || sent_at.elapsed() >= TYPING_REFRESH,
+            None => active,
+        };
+        if !refresh {
+            return;
+        }
+        typing_sent_at.set(active.then(Instant::now));
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = send_typing(&RelayMessages, room_id, active).await {
+                eprintln!("Failed to send typing indicator: {e}");
+            }
+        });
+    });
+
+    let room_id = props.room_id.clone();
+    let change_retention = use_callback(move |seconds: Option<u64>| {
+        let room_id = room_id.clone();
+        spawn(async move {
+            if let Err(e) = set_room_retention(&RelayMessages, room_id.clone(), seconds).await {
+                eprintln!("Failed to set disappearing messages: {e}");
+            }
+            retention.set(get_room_retention(room_id).await.unwrap_or_default());
+        });
+    });
+
+    // Reload right away when live delivery applied pushed messages
+    let live = try_use_context::<LiveContext>();
+    let live_room_id = props.room_id.clone();
+    use_effect(move || {
+        let Some(live) = live else {
+            return;
+        };
+        if (live.revision)() == 0 {
+            return;
+        }
+        let room_id = live_room_id.clone();
+        spawn(async move {
+            messages.set(reload_messages(room_id).await);
+        });
+    });
+
+    // Poll the relay while the room is open, unless messages are pushed
+    let poll_room_id = props.room_id.clone();
+    use_future(move || {
+        let room_id = poll_room_id.clone();
+        async move {
+            loop {
+                tokio::time::sleep(POLL_INTERVAL).await;
+                if live.is_none_or(|live| live.polling()) {
+                    messages.set(load_messages(room_id.clone()).await);
+                } else {
+                    messages.set(reload_messages(room_id.clone()).await);
+                }
+                retention.set(
+                    get_room_retention(room_id.clone())
+                        .await
+                        .unwrap_or_default(),
+                );
+                typing.set(
+                    get_typing_members(room_id.clone())
+                        .await
+                        .unwrap_or_default(),
+                );
+            }
+        }
+    });
+
+    let upload_room_id = props.room_id.clone();
+
+    // Load the room's history on component initialization
+    let room_id = props.room_id.clone();
+    use_effect(move || {
+        let room_id = room_id.clone();
+        spawn(async move {
+            recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+            retention.set(
+                get_room_retention(room_id.clone())
+                    .await
+                    .unwrap_or_default(),
+            );
+            messages.set(load_messages(room_id).await);
+        });
     });
 
     // Sample members data
@@ -123,6 +299,16 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
         ]
     });
 
+    let typing_text = props.i18n.translate_with(
+        "messages.typing",
+        &[
+            ("count", typing().len().into()),
+            ("names", typing().join(", ").into()),
+        ],
+    );
+    let sent_times: Vec<u64> = messages().iter().map(|message| message.sent_at).collect();
+    let day_separators = TimeFormatter::new(&props.i18n).day_separators(&sent_times, unix_now());
+
     rsx! {
         document::Stylesheet { href: MOBILE_MESSAGES_CSS }
 
@@ -163,7 +349,7 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                                 class: "mm-room-details",
                                 p {
                                     class: "mm-room-status",
-                                    "Active now"
+                                    "{props.i18n.translate(\"messages.active_now\")}"
                                 }
                             }
                         }
@@ -223,17 +409,43 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                         // Messages area
                         main {
                             class: "mm-messages-container",
-                            for message in messages() {
+                            if messages().is_empty() {
+                                p {
+                                    class: "mm-messages-empty",
+                                    "{props.i18n.translate(\"messages.empty_state\")}"
+                                }
+                            }
+                            for (message, day_separator) in messages().into_iter().zip(day_separators) {
                                 if message.is_sent {
                                     MessageSent {
                                         key: "{message.id}",
                                         message: message.clone(),
+                                        day_separator: day_separator,
+                                        recent_emojis: recent_emojis(),
+                                        on_react: {
+                                            let message_id = message.id.clone();
+                                            move |toggle: ReactionToggle| toggle_reaction.call((message_id.clone(), toggle))
+                                        },
+                                        on_edit: {
+                                            let message_id = message.id.clone();
+                                            move |text: String| save_edit.call((message_id.clone(), text))
+                                        },
+                                        on_delete: {
+                                            let message_id = message.id.clone();
+                                            move |_| delete_for_everyone.call(message_id.clone())
+                                        },
                                         i18n: props.i18n.clone()
                                     }
                                 } else {
                                     MessageReceived {
                                         key: "{message.id}",
                                         message: message.clone(),
+                                        day_separator: day_separator,
+                                        recent_emojis: recent_emojis(),
+                                        on_react: {
+                                            let message_id = message.id.clone();
+                                            move |toggle: ReactionToggle| toggle_reaction.call((message_id.clone(), toggle))
+                                        },
                                         i18n: props.i18n.clone()
                                     }
                                 }
@@ -243,18 +455,82 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                         // Message input
                         footer {
                             class: "mm-input-container",
+                            if !typing().is_empty() {
+                                p {
+                                    class: "mm-typing-indicator",
+                                    "{typing_text}"
+                                }
+                            }
+                            if let Some(error) = attachment_error() {
+                                p { class: "mm-attachment-error", "{error}" }
+                            }
+                            if emoji_picker_open() {
+                                div {
+                                    class: "mm-emoji-picker",
+                                    EmojiPicker {
+                                        recent: recent_emojis(),
+                                        i18n: props.i18n.clone(),
+                                        on_select: move |emoji: String| {
+                                            message_input.write().push_str(&emoji);
+                                            emoji_picker_open.set(false);
+                                            spawn(async move {
+                                                if let Err(e) = add_recent_emoji(emoji).await {
+                                                    eprintln!("Failed to save recent emoji: {e}");
+                                                }
+                                                recent_emojis.set(get_recent_emojis().await.unwrap_or_default());
+                                            });
+                                        },
+                                        on_close: move |_| emoji_picker_open.set(false),
+                                    }
+                                }
+                            }
                             div {
                                 class: "mm-input-wrapper",
-                                button {
-                                    class: "mm-attach-btn",
-                                    onclick: move |_| {
-                                        println!("Attach file");
-                                    },
+                                label {
+                                    class: if uploading() { "mm-attach-btn mm-attach-uploading" } else { "mm-attach-btn" },
                                     Icon {
                                         name: IconName::Paperclip,
                                         i18n: props.i18n.clone(),
                                         class: "mm-attach-icon".to_string()
                                     }
+                                    input {
+                                        r#type: "file",
+                                        class: "mm-attach-input",
+                                        disabled: uploading(),
+                                        onchange: move |evt| {
+                                            let upload_failed = upload_failed.clone();
+                                            let room_id = upload_room_id.clone();
+                                            spawn(async move {
+                                                uploading.set(true);
+                                                attachment_error.set(None);
+                                                for file in evt.files() {
+                                                    let mime_type = file
+                                                        .content_type()
+                                                        .unwrap_or_else(|| "application/octet-stream".to_string());
+                                                    let result = match file.read_bytes().await {
+                                                        Ok(bytes) => {
+                                                            send_attachment(&RelayBlobs, file.name(), mime_type, bytes.to_vec())
+                                                                .await
+                                                                .map_err(|e| e.to_string())
+                                                        }
+                                                        Err(e) => Err(e.to_string()),
+                                                    };
+                                                    let result = match result {
+                                                        Ok(json) => send_message(&RelayMessages, room_id.clone(), String::new(), vec![json])
+                                                            .await
+                                                            .map_err(|e| e.to_string()),
+                                                        Err(e) => Err(e),
+                                                    };
+                                                    if let Err(e) = result {
+                                                        eprintln!("Failed to upload attachment: {e}");
+                                                        attachment_error.set(Some(upload_failed.clone()));
+                                                    }
+                                                }
+                                                messages.set(load_messages(room_id).await);
+                                                uploading.set(false);
+                                            });
+                                        }
+                                    }
                                 }
 
                                 input {
@@ -262,33 +538,20 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                                     r#type: "text",
                                     placeholder: "Type your secure message...",
                                     value: "{message_input()}",
-                                    oninput: move |evt| message_input.set(evt.value()),
+                                    oninput: move |evt| {
+                                        message_input.set(evt.value());
+                                        update_typing.call(evt.value());
+                                    },
                                     onkeypress: move |evt| {
                                         if evt.key() == Key::Enter {
-                                            let content = message_input();
-                                            if !content.trim().is_empty() {
-                                                let mut current_messages = messages();
-                                                let new_message = Message {
-                                                    id: (current_messages.len() + 1).to_string(),
-                                                    sender_id: "current_user".to_string(),
-                                                    sender_name: "You".to_string(),
-                                                    content: content.trim().to_string(),
-                                                    timestamp: "now".to_string(),
-                                                    is_sent: true,
-                                                };
-                                                current_messages.push(new_message);
-                                                messages.set(current_messages);
-                                                message_input.set(String::new());
-                                            }
+                                            send_text.call(());
                                         }
                                     }
                                 }
 
                                 button {
                                     class: "mm-emoji-btn",
-                                    onclick: move |_| {
-                                        println!("Open emoji picker");
-                                    },
+                                    onclick: move |_| emoji_picker_open.toggle(),
                                     Icon {
                                         name: IconName::Smiley,
                                         i18n: props.i18n.clone(),
@@ -299,23 +562,7 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                                 button {
                                     class: "mm-send-btn",
                                     disabled: message_input().trim().is_empty(),
-                                    onclick: move |_| {
-                                        let content = message_input();
-                                        if !content.trim().is_empty() {
-                                            let mut current_messages = messages();
-                                            let new_message = Message {
-                                                id: (current_messages.len() + 1).to_string(),
-                                                sender_id: "current_user".to_string(),
-                                                sender_name: "You".to_string(),
-                                                content: content.trim().to_string(),
-                                                timestamp: "now".to_string(),
-                                                is_sent: true,
-                                            };
-                                            current_messages.push(new_message);
-                                            messages.set(current_messages);
-                                            message_input.set(String::new());
-                                        }
-                                    },
+                                    onclick: move |_| send_text.call(()),
                                     "Send"
                                 }
                             }
@@ -328,6 +575,8 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
                             room_name: props.room_name.clone(),
                             members: members(),
                             shared_files: shared_files(),
+                            retention: retention(),
+                            on_retention_change: move |seconds| change_retention.call(seconds),
                             i18n: props.i18n.clone(),
                             on_close: move |_| show_side_panel.set(false),
                         }
@@ -341,32 +590,72 @@ pub fn MobileMessages(props: MobileMessagesProps) -> Element {
 #[derive(Props, Clone, PartialEq)]
 struct MessageReceivedProps {
     message: Message,
+    /// Label of the day this message starts, e.g. "Today"
+    #[props(default)]
+    day_separator: Option<String>,
+    #[props(default)]
+    recent_emojis: Vec<String>,
+    #[props(optional)]
+    on_react: Option<EventHandler<ReactionToggle>>,
     i18n: I18nContext,
 }
 
 #[component]
 fn MessageReceived(props: MessageReceivedProps) -> Element {
+    let time = TimeFormatter::new(&props.i18n).time(props.message.sent_at);
+
     rsx! {
+        if let Some(day) = &props.day_separator {
+            p { class: "mm-day-separator", role: "separator", "{day}" }
+        }
         div {
             class: "mm-message-group",
             div {
                 class: "mm-message-received",
-                div {
-                    class: "mm-sender-avatar",
-                    "{props.message.sender_name}"
+                ContactAvatar {
+                    contact_id: props.message.sender_contact_id.clone(),
+                    name: props.message.sender_name.clone(),
+                    class: "mm-sender-avatar".to_string(),
                 }
                 div {
                     class: "mm-message-content-received",
                     div {
                         class: "mm-message-bubble-received",
-                        p {
-                            class: "mm-message-text",
-                            "{props.message.content}"
+                        if props.message.deleted {
+                            p {
+                                class: "mm-message-text mm-message-tombstone",
+                                "{props.i18n.translate(\"messages.deleted\")}"
+                            }
+                        }
+                        if !props.message.content.is_empty() {
+                            p {
+                                class: "mm-message-text",
+                                dir: "auto",
+                                "{props.message.content}"
+                            }
+                        }
+                        for attachment in props.message.attachments.iter() {
+                            MessageAttachment {
+                                key: "{attachment.digest_hex()}",
+                                attachment: attachment.clone(),
+                                i18n: props.i18n.clone()
+                            }
+                        }
+                    }
+                    if let (Some(on_react), false) = (props.on_react, props.message.deleted) {
+                        MessageReactions {
+                            reactions: props.message.reactions.clone(),
+                            recent: props.recent_emojis.clone(),
+                            i18n: props.i18n.clone(),
+                            on_toggle: on_react
                         }
                     }
                     p {
                         class: "mm-message-time",
-                        "{props.message.timestamp}"
+                        "{time}"
+                        if props.message.edited {
+                            " · {props.i18n.translate(\"messages.edited\")}"
+                        }
                     }
                 }
             }
@@ -377,12 +666,39 @@ fn MessageReceived(props: MessageReceivedProps) -> Element {
 #[derive(Props, Clone, PartialEq)]
 struct MessageSentProps {
     message: Message,
+    /// Label of the day this message starts, e.g. "Today"
+    #[props(default)]
+    day_separator: Option<String>,
+    #[props(default)]
+    recent_emojis: Vec<String>,
+    #[props(optional)]
+    on_react: Option<EventHandler<ReactionToggle>>,
+    #[props(optional)]
+    on_edit: Option<EventHandler<String>>,
+    #[props(optional)]
+    on_delete: Option<EventHandler<()>>,
     i18n: I18nContext,
 }
 
 #[component]
 fn MessageSent(props: MessageSentProps) -> Element {
+    let mut draft = use_signal(|| Option::<String>::None);
+    let original = props.message.content.clone();
+    let delivery = props.message.delivery.map(|state| match state {
+        DeliveryState::Sent => ("mm-message-delivery", "messages.delivery_sent", "✓"),
+        DeliveryState::Delivered => ("mm-message-delivery", "messages.delivery_delivered", "✓✓"),
+        DeliveryState::Read => (
+            "mm-message-delivery mm-message-delivery-read",
+            "messages.delivery_read",
+            "✓✓",
+        ),
+    });
+    let time = TimeFormatter::new(&props.i18n).time(props.message.sent_at);
+
     rsx! {
+        if let Some(day) = &props.day_separator {
+            p { class: "mm-day-separator", role: "separator", "{day}" }
+        }
         div {
             class: "mm-message-group",
             div {
@@ -391,17 +707,177 @@ fn MessageSent(props: MessageSentProps) -> Element {
                     class: "mm-message-content-sent",
                     div {
                         class: "mm-message-bubble-sent",
-                        p {
-                            class: "mm-message-text",
-                            "{props.message.content}"
+                        if props.message.deleted {
+                            p {
+                                class: "mm-message-text mm-message-tombstone",
+                                "{props.i18n.translate(\"messages.deleted\")}"
+                            }
+                        } else if let Some(text) = draft() {
+                            div {
+                                class: "mm-message-edit",
+                                input {
+                                    r#type: "text",
+                                    class: "mm-message-edit-input",
+                                    dir: "auto",
+                                    value: "{text}",
+                                    oninput: move |evt| draft.set(Some(evt.value())),
+                                }
+                                button {
+                                    class: "mm-message-action",
+                                    disabled: text.trim().is_empty() && props.message.attachments.is_empty(),
+                                    onclick: move |_| {
+                                        if let (Some(text), Some(on_edit)) = (draft(), props.on_edit) {
+                                            if text != original {
+                                                on_edit.call(text);
+                                            }
+                                        }
+                                        draft.set(None);
+                                    },
+                                    "{props.i18n.translate(\"messages.save\")}"
+                                }
+                                button {
+                                    class: "mm-message-action",
+                                    onclick: move |_| draft.set(None),
+                                    "{props.i18n.translate(\"messages.cancel\")}"
+                                }
+                            }
+                        } else {
+                            if !props.message.content.is_empty() {
+                                p {
+                                    class: "mm-message-text",
+                                    dir: "auto",
+                                    "{props.message.content}"
+                                }
+                            }
+                            for attachment in props.message.attachments.iter() {
+                                MessageAttachment {
+                                    key: "{attachment.digest_hex()}",
+                                    attachment: attachment.clone(),
+                                    i18n: props.i18n.clone()
+                                }
+                            }
+                        }
+                    }
+                    if let (Some(on_react), false) = (props.on_react, props.message.deleted) {
+                        MessageReactions {
+                            reactions: props.message.reactions.clone(),
+                            recent: props.recent_emojis.clone(),
+                            i18n: props.i18n.clone(),
+                            on_toggle: on_react
                         }
                     }
                     p {
                         class: "mm-message-time mm-message-time-sent",
-                        "{props.message.timestamp}"
+                        "{time}"
+                        if props.message.edited {
+                            " · {props.i18n.translate(\"messages.edited\")}"
+                        }
+                        if let (Some((class, title, marks)), false) = (delivery, props.message.deleted) {
+                            span {
+                                class: class,
+                                "title": "{props.i18n.translate(title)}",
+                                " {marks}"
+                            }
+                        }
+                    }
+                    if !props.message.deleted && draft().is_none() {
+                        div {
+                            class: "mm-message-actions",
+                            if props.on_edit.is_some() {
+                                button {
+                                    class: "mm-message-action",
+                                    onclick: {
+                                        let text = props.message.content.clone();
+                                        move |_| draft.set(Some(text.clone()))
+                                    },
+                                    "{props.i18n.translate(\"messages.edit\")}"
+                                }
+                            }
+                            if let Some(on_delete) = props.on_delete {
+                                button {
+                                    class: "mm-message-action mm-message-action-danger",
+                                    onclick: move |_| on_delete.call(()),
+                                    "{props.i18n.translate(\"messages.delete\")}"
+                                }
+                            }
+                        }
                     }
                 }
             }
         }
     }
 }
+
+#[derive(Props, Clone, PartialEq)]
+struct MessageAttachmentProps {
+    attachment: Attachment,
+    i18n: I18nContext,
+}
+
+/// File card that downloads, decrypts and saves the attachment when opened
+#[component]
+fn MessageAttachment(props: MessageAttachmentProps) -> Element {
+    let mut progress = use_signal(|| Option::<f32>::None);
+    let mut saved_to = use_signal(|| Option::<String>::None);
+    let download_failed = props.i18n.translate("attachments.download_failed");
+
+    rsx! {
+        AttachmentCard {
+            attachment: props.attachment.clone(),
+            progress: progress(),
+            i18n: props.i18n.clone(),
+            on_open: move |attachment: Attachment| {
+                let download_failed = download_failed.clone();
+                spawn(async move {
+                    progress.set(Some(0.0));
+                    let result = match attachment.to_json() {
+                        Ok(json) => fetch_attachment(&RelayBlobs, json)
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    match result {
+                        Ok(data) => {
+                            match save_download(&download_dir("Documents"), &attachment.file_name, &data) {
+                                Ok(path) => saved_to.set(Some(path.display().to_string())),
+                                Err(e) => saved_to.set(Some(format!("{download_failed}: {e}"))),
+                            }
+                        }
+                        Err(e) => saved_to.set(Some(format!("{download_failed}: {e}"))),
+                    }
+                    progress.set(None);
+                });
+            }
+        }
+        if let Some(saved_to) = saved_to() {
+            p { class: "mm-attachment-saved", "{saved_to}" }
+        }
+    }
+}
+
+/// Fetch new messages from the relay, mark them read since the room is open
+/// and load the room's history
+async fn load_messages(room_id: String) -> Vec<Message> {
+    if let Err(e) = receive_room_messages(&RelayMessages, room_id.clone()).await {
+        eprintln!("Failed to receive messages: {e}");
+    }
+    reload_messages(room_id).await
+}
+
+/// Mark the room's messages read since it is open and load its history
+async fn reload_messages(room_id: String) -> Vec<Message> {
+    if let Err(e) = mark_room_read(&RelayMessages, room_id.clone()).await {
+        eprintln!("Failed to mark messages read: {e}");
+    }
+    match get_room_history(room_id).await {
+        Ok(history) => history
+            .iter()
+            .filter_map(|json| MessageView::from_json(json).ok())
+            .map(Message::from)
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to load history: {e}");
+            Vec::new()
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::components::MobileLayout;
 use api::get_server_data;
 use dioxus::prelude::*;
 use shared::local::{create_room, get_all_rooms};
-use ui::{get_language_name, get_text_direction, I18nContext, Icon, IconName, RoomData};
+use ui::{current_i18n, I18nContext, Icon, IconName, ProfileContext, RoomData, UserProfileMini};
 
 const MOBILE_ROOM_DASH_CSS: Asset = asset!("/assets/mobile_room_dash.css");
 
@@ -12,7 +12,7 @@ pub struct MobileRoomDashboardProps {
     username: String,
     #[props(default = "View Profile".to_string())]
     user_subtitle: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     i18n: I18nContext,
 }
 
@@ -23,14 +23,11 @@ pub fn MobileRoomDashboard(props: MobileRoomDashboardProps) -> Element {
     let mut loading_rooms = use_signal(|| true);
     let mut active_tab = use_signal(|| "rooms".to_string());
     let mut server_data = use_signal(|| None::<String>);
-    let locale = props.i18n.get_current_locale();
+    let profile_context = use_context::<ProfileContext>();
 
-    // Keep for debugging until language switcher is implemented
-    println!("Language: {}", get_language_name(locale));
-    println!("Text direction: {}", get_text_direction(locale));
-
-    // Load rooms on component initialization
+    // Load rooms on component initialization and whenever the active profile changes
     use_effect(move || {
+        let _active_profile = (profile_context.active_profile_id)();
         spawn(async move {
             match get_all_rooms().await {
                 Ok(room_jsons) => {
@@ -86,6 +83,14 @@ pub fn MobileRoomDashboard(props: MobileRoomDashboardProps) -> Element {
                 div {
                     class: "mrd-header-content",
 
+                    // Active profile with the profile switcher
+                    UserProfileMini {
+                        username: props.username.clone(),
+                        status: props.user_subtitle.clone(),
+                        avatar_initial: props.username.chars().next().unwrap_or('U').to_string(),
+                        i18n: props.i18n.clone()
+                    }
+
                     div {
                         class: "mrd-greeting-section",
                         h1 {
@@ -0,0 +1,242 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Attachment transfer to and from the relay.
+//! Downloaded chunks are verified and kept in the local database as they
+//! arrive, so an interrupted download resumes from the first missing chunk.
+
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::attachment::{decrypt_attachment, encrypt_attachment, verify_chunk, Attachment};
+use crate::persistence::database::{Database, Entity};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// How many numbered copies `save_download` tries before giving up
+const MAX_DOWNLOAD_COPIES: usize = 1000;
+
+/// Relay blob storage used to transfer encrypted attachment chunks
+pub trait BlobTransport {
+    fn upload_blob(&self, blob_id: String, data: Vec<u8>) -> impl Future<Output = Result<()>>;
+
+    fn download_blob(&self, blob_id: String) -> impl Future<Output = Result<Option<Vec<u8>>>>;
+}
+
+/// Progress of a partially downloaded attachment
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AttachmentDownload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub attachment: Attachment,
+    pub completed: Vec<bool>,
+}
+
+impl AttachmentDownload {
+    pub fn new(attachment: Attachment) -> Self {
+        Self {
+            id: Some(Self::key_for(&attachment)),
+            completed: vec![false; attachment.chunk_count()],
+            attachment,
+        }
+    }
+
+    /// Storage key of the download for an attachment
+    pub fn key_for(attachment: &Attachment) -> String {
+        format!("{}:{}", Self::key_prefix(), attachment.digest_hex())
+    }
+
+    /// Load the saved progress for an attachment, or start a new download
+    pub fn load_or_new(db: &Database, attachment: &Attachment) -> Result<Self> {
+        match db.load_entity::<Self>(&Self::key_for(attachment))? {
+            Some(download) if download.attachment == *attachment => Ok(download),
+            _ => Ok(Self::new(attachment.clone())),
+        }
+    }
+
+    /// Indexes of chunks that still need downloading
+    pub fn missing_chunks(&self) -> Vec<usize> {
+        self.completed
+            .iter()
+            .enumerate()
+            .filter(|(_, done)| !**done)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed.iter().all(|done| *done)
+    }
+
+    /// Fraction of chunks downloaded, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        if self.completed.is_empty() {
+            return 1.0;
+        }
+        let done = self.completed.iter().filter(|done| **done).count();
+        done as f32 / self.completed.len() as f32
+    }
+
+    fn chunk_key(&self, index: usize) -> String {
+        format!("attachment_chunk:{}:{index}", self.attachment.digest_hex())
+    }
+
+    /// Verify a downloaded chunk and keep it until the download completes
+    pub fn store_chunk(&mut self, db: &Database, index: usize, blob: &[u8]) -> Result<()> {
+        verify_chunk(&self.attachment, index, blob)?;
+        db.save_bytes(&self.chunk_key(index), blob)?;
+        self.completed[index] = true;
+        db.update_entity(self)?;
+        Ok(())
+    }
+
+    /// Decrypt and verify the complete file, then drop the stored chunks
+    pub fn finish(&self, db: &Database) -> Result<Vec<u8>> {
+        if !self.is_complete() {
+            return Err("Attachment download is not complete".into());
+        }
+
+        let mut blobs = Vec::with_capacity(self.completed.len());
+        for index in 0..self.completed.len() {
+            let blob = db
+                .load_bytes(&self.chunk_key(index))?
+                .ok_or_else(|| format!("Stored chunk {index} is missing"))?;
+            blobs.push(blob);
+        }
+
+        let data = decrypt_attachment(&self.attachment, &blobs)?;
+        self.discard(db)?;
+        Ok(data)
+    }
+
+    /// Remove the download progress and any stored chunks
+    pub fn discard(&self, db: &Database) -> Result<()> {
+        for index in 0..self.completed.len() {
+            db.delete_bytes(&self.chunk_key(index))?;
+        }
+        if let Some(id) = &self.id {
+            let _ = db.delete::<Self>(id);
+        }
+        Ok(())
+    }
+}
+
+impl Entity for AttachmentDownload {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "attachment_download"
+    }
+}
+
+/// Encrypt a file and upload its chunks, returning the metadata to send in a message
+pub async fn upload_attachment<T: BlobTransport>(
+    transport: &T,
+    file_name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<Attachment> {
+    let (attachment, blobs) = encrypt_attachment(file_name, mime_type, data)?;
+    for blob in blobs {
+        transport.upload_blob(blob.blob_id, blob.data).await?;
+    }
+    Ok(attachment)
+}
+
+/// Download, verify and decrypt an attachment, resuming any earlier progress
+pub async fn download_attachment<T: BlobTransport>(
+    transport: &T,
+    db: &Database,
+    attachment: &Attachment,
+) -> Result<Vec<u8>> {
+    attachment.check_size()?;
+    let mut download = AttachmentDownload::load_or_new(db, attachment)?;
+    db.update_entity(&download)?;
+
+    for index in download.missing_chunks() {
+        let blob_id = attachment.blob_ids[index].clone();
+        let blob = transport
+            .download_blob(blob_id.clone())
+            .await?
+            .ok_or_else(|| format!("Blob not found on relay: {blob_id}"))?;
+        download.store_chunk(db, index, &blob)?;
+    }
+
+    download.finish(db)
+}
+
+/// Make a sender's file name safe to save: only the final path component is
+/// kept, without control characters or leading dots
+pub fn sanitize_file_name(file_name: &str) -> String {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let name = name.trim().trim_start_matches('.');
+
+    if name.is_empty() {
+        "attachment".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Save a downloaded attachment into `dir` without replacing existing files.
+/// A taken name gets a counter, as in `report (1).pdf`.
+pub fn save_download(dir: &Path, file_name: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+    let name = sanitize_file_name(file_name);
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name.as_str(), None),
+    };
+
+    for copy in 0..MAX_DOWNLOAD_COPIES {
+        let candidate = match (copy, extension) {
+            (0, _) => name.clone(),
+            (_, Some(extension)) => format!("{stem} ({copy}).{extension}"),
+            (_, None) => format!("{stem} ({copy})"),
+        };
+        let path = dir.join(candidate);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(data)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("Too many files named {name}"),
+    ))
+}
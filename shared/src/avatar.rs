@@ -0,0 +1,282 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Avatar images.
+//! An imported avatar is encrypted like an attachment and its encrypted
+//! chunks are kept in the profile database, so it is only decrypted when
+//! shown. Sharing it uploads the chunks to the relay and sends the
+//! `Attachment` inside a signed profile update to every room; contacts
+//! download the chunks later and keep them encrypted the same way.
+
+use crate::attachments::BlobTransport;
+use crate::crypto::attachment::{decrypt_attachment, encrypt_attachment, verify_chunk, Attachment};
+use crate::crypto::identity::{ProfileUpdate, RoomKeyBinding, RoomKeyProof, Signed};
+use crate::crypto::message::{Contact, MessagePayload, Room};
+use crate::history::Rejection;
+use crate::messaging::{send_payload, share_identity, MessageTransport};
+use crate::persistence::database::{Database, Entity};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Width and height of an imported avatar, in pixels
+pub const AVATAR_SIZE: u32 = 256;
+
+/// Largest avatar image accepted, from us or from a contact
+pub const MAX_AVATAR_BYTES: usize = 256 * 1024;
+
+/// Image types an avatar may be stored as
+pub const AVATAR_MIME_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
+
+/// Key prefix of stored avatar chunks
+pub const AVATAR_BLOB_PREFIX: &str = "avatar_blob";
+
+/// File name avatars are encrypted under
+const AVATAR_FILE_NAME: &str = "avatar";
+
+/// Check that avatar data is a small image of a supported type
+pub fn validate_avatar(mime_type: &str, data: &[u8]) -> Result<()> {
+    if !AVATAR_MIME_TYPES.contains(&mime_type) {
+        return Err(format!("Unsupported avatar type: {mime_type}").into());
+    }
+    if data.len() > MAX_AVATAR_BYTES {
+        return Err("Avatar image is too large".into());
+    }
+
+    let matches_type = match mime_type {
+        "image/jpeg" => data.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "image/png" => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        _ => data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP",
+    };
+    if !matches_type {
+        return Err(format!("Avatar data is not a valid {mime_type} image").into());
+    }
+    Ok(())
+}
+
+/// Check the metadata of an avatar before downloading or showing it
+fn validate_avatar_metadata(avatar: &Attachment) -> Result<()> {
+    if !AVATAR_MIME_TYPES.contains(&avatar.mime_type.as_str()) {
+        return Err(format!("Unsupported avatar type: {}", avatar.mime_type).into());
+    }
+    if avatar.size > MAX_AVATAR_BYTES as u64 {
+        return Err("Avatar image is too large".into());
+    }
+    Ok(())
+}
+
+fn blob_key(avatar: &Attachment, index: usize) -> String {
+    format!("{AVATAR_BLOB_PREFIX}:{}:{index}", avatar.digest_hex())
+}
+
+/// Keep the encrypted chunks of an avatar, verifying each one
+pub fn store_avatar(db: &Database, avatar: &Attachment, blobs: &[Vec<u8>]) -> Result<()> {
+    if blobs.len() != avatar.chunk_count() {
+        return Err("Avatar is missing chunks".into());
+    }
+    for (index, blob) in blobs.iter().enumerate() {
+        verify_chunk(avatar, index, blob)?;
+    }
+    for (index, blob) in blobs.iter().enumerate() {
+        db.save_bytes(&blob_key(avatar, index), blob)?;
+    }
+    Ok(())
+}
+
+/// The stored encrypted chunks of an avatar, `None` unless all are present
+fn stored_blobs(db: &Database, avatar: &Attachment) -> Result<Option<Vec<Vec<u8>>>> {
+    let mut blobs = Vec::with_capacity(avatar.chunk_count());
+    for index in 0..avatar.chunk_count() {
+        match db.load_bytes(&blob_key(avatar, index))? {
+            Some(blob) => blobs.push(blob),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(blobs))
+}
+
+/// Decrypt a stored avatar, `None` if it has not been stored
+pub fn load_avatar(db: &Database, avatar: &Attachment) -> Result<Option<Vec<u8>>> {
+    match stored_blobs(db, avatar)? {
+        Some(blobs) => Ok(Some(decrypt_attachment(avatar, &blobs)?)),
+        None => Ok(None),
+    }
+}
+
+/// Remove the stored chunks of an avatar
+pub fn remove_avatar(db: &Database, avatar: &Attachment) -> Result<()> {
+    for index in 0..avatar.chunk_count() {
+        db.delete_bytes(&blob_key(avatar, index))?;
+    }
+    Ok(())
+}
+
+/// Encrypt an avatar image with a fresh key and store it
+pub fn import_avatar(db: &Database, mime_type: &str, data: &[u8]) -> Result<Attachment> {
+    validate_avatar(mime_type, data)?;
+    let (avatar, blobs) = encrypt_attachment(AVATAR_FILE_NAME, mime_type, data)?;
+    let blobs: Vec<Vec<u8>> = blobs.into_iter().map(|blob| blob.data).collect();
+    store_avatar(db, &avatar, &blobs)?;
+    Ok(avatar)
+}
+
+/// Upload the stored chunks of our avatar so contacts can download them
+pub async fn upload_avatar<T: BlobTransport>(
+    transport: &T,
+    db: &Database,
+    avatar: &Attachment,
+) -> Result<()> {
+    let blobs = stored_blobs(db, avatar)?.ok_or("Avatar is not stored")?;
+    for (blob_id, blob) in avatar.blob_ids.iter().zip(blobs) {
+        transport.upload_blob(blob_id.clone(), blob).await?;
+    }
+    Ok(())
+}
+
+/// Download and store a contact's avatar unless it is stored already
+pub async fn download_avatar<T: BlobTransport>(
+    transport: &T,
+    db: &Database,
+    avatar: &Attachment,
+) -> Result<()> {
+    validate_avatar_metadata(avatar)?;
+    if stored_blobs(db, avatar)?.is_some() {
+        return Ok(());
+    }
+
+    let mut blobs = Vec::with_capacity(avatar.chunk_count());
+    for blob_id in &avatar.blob_ids {
+        let blob = transport
+            .download_blob(blob_id.clone())
+            .await?
+            .ok_or_else(|| format!("Blob not found on relay: {blob_id}"))?;
+        blobs.push(blob);
+    }
+
+    // Only keep chunks that decrypt to the promised image
+    let data = decrypt_attachment(avatar, &blobs)?;
+    validate_avatar(&avatar.mime_type, &data)?;
+    store_avatar(db, avatar, &blobs)
+}
+
+/// Download the avatars of contacts that are not stored yet.
+/// Returns the number of avatars downloaded.
+pub async fn sync_contact_avatars<T: BlobTransport>(transport: &T, db: &Database) -> Result<usize> {
+    let mut downloaded = 0;
+    let contacts: Vec<Contact> = db.load_all_entities(Contact::key_prefix())?;
+    for contact in contacts {
+        let Some(avatar) = &contact.avatar else {
+            continue;
+        };
+        if stored_blobs(db, avatar)?.is_some() {
+            continue;
+        }
+        // A missing avatar should not keep the others from loading
+        match download_avatar(transport, db, avatar).await {
+            Ok(()) => downloaded += 1,
+            Err(e) => eprintln!("Failed to download avatar of {}: {e}", contact.name),
+        }
+    }
+    Ok(downloaded)
+}
+
+/// Adopt the identity proven for the sender's room key by contacts that do
+/// not know one yet, so their profile updates can be checked.
+/// Returns whether any contact changed.
+pub fn adopt_contact_identity(
+    db: &Database,
+    room_id: &str,
+    sender_public: &[u8; 32],
+    binding: &Signed<RoomKeyBinding>,
+    proof: &RoomKeyProof,
+) -> Result<bool> {
+    let Some(own_room) = db.load_entity::<Room>(room_id)? else {
+        return Ok(false);
+    };
+    let mut changed = false;
+    let contacts: Vec<Contact> = db.load_all_entities(Contact::key_prefix())?;
+    for mut contact in contacts {
+        if contact.public_key != *sender_public || contact.identity_public.is_some() {
+            continue;
+        }
+        contact
+            .adopt_identity(binding, proof, &own_room)
+            .map_err(|_| Rejection::Unverified)?;
+        db.update_entity(&contact)?;
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Apply a profile update to the contacts owning the sender's key, dropping
+/// avatars they replaced. Updates older than a contact's current profile
+/// are ignored. Returns whether any contact changed.
+pub fn apply_profile_update(
+    db: &Database,
+    sender_public: &[u8; 32],
+    update: &Signed<ProfileUpdate>,
+) -> Result<bool> {
+    let mut changed = false;
+    let contacts: Vec<Contact> = db.load_all_entities(Contact::key_prefix())?;
+    for mut contact in contacts {
+        if !contact.owns_key(sender_public) {
+            continue;
+        }
+        let previous = contact.avatar.clone();
+        if !contact
+            .apply_profile_update(update)
+            .map_err(|_| Rejection::Unverified)?
+        {
+            continue;
+        }
+        db.update_entity(&contact)?;
+        if let Some(previous) = previous.filter(|avatar| contact.avatar.as_ref() != Some(avatar)) {
+            remove_avatar(db, &previous)?;
+        }
+        changed = true;
+    }
+    Ok(changed)
+}
+
+/// Share a signed profile update in every room, uploading its avatar first.
+/// Each room first proves its identity binding, so contacts who added us by
+/// key learn the identity that signs the update.
+pub async fn share_profile<B: BlobTransport, M: MessageTransport>(
+    blobs: &B,
+    messages: &M,
+    db: &Database,
+    update: Signed<ProfileUpdate>,
+) -> Result<()> {
+    if let Some(avatar) = &update.payload.avatar {
+        upload_avatar(blobs, db, avatar).await?;
+    }
+
+    let payload = MessagePayload::profile(update);
+    let rooms: Vec<Room> = db.load_all_entities(Room::key_prefix())?;
+    for room in rooms {
+        let Some(room_id) = room.id() else {
+            continue;
+        };
+        if let Err(e) = share_identity(messages, db, room_id).await {
+            eprintln!("Failed to share identity in room {room_id}: {e}");
+        }
+        // One unreachable room should not keep the others from hearing
+        if let Err(e) = send_payload(messages, db, room_id, &payload).await {
+            eprintln!("Failed to share profile in room {room_id}: {e}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,273 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Anonymous message tokens issued with blind signatures.
+//! Privacy Pass style: the client hashes a random token nonce to a
+//! ristretto255 point and blinds it with a random scalar. The relay
+//! multiplies the blinded point with its issuer key and proves that it used
+//! its published key, so it cannot tell clients apart by signing them with
+//! different keys. The client removes the blind and holds a signature the
+//! relay can check at redemption but never saw at issuance, so a redeemed
+//! token cannot be linked to the request that produced it.
+//! Issuer keys are derived from relay keys and rotate with them.
+
+use crypto_box::aead::{rand_core::RngCore, OsRng};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+/// Domain separation of the issuer key derived from a relay key
+const ISSUER_CONTEXT: &[u8] = b"meeseeks-nuntius/token-issuer/v1";
+/// Domain separation of token nonces hashed to points
+const TOKEN_CONTEXT: &[u8] = b"meeseeks-nuntius/token/v1";
+/// Domain separation of the proof that the issuer key was used
+const PROOF_CONTEXT: &[u8] = b"meeseeks-nuntius/token-proof/v1";
+/// Domain separation of the batch a proof of work pays for
+const BATCH_CONTEXT: &[u8] = b"meeseeks-nuntius/token-batch/v1";
+
+/// Why a token could not be issued or redeemed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    /// A point, scalar or nonce has the wrong encoding
+    Malformed,
+    /// The relay did not prove it signed with its published key
+    InvalidProof,
+    /// The token was not signed by the issuer key
+    Invalid,
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Malformed => write!(f, "Malformed token"),
+            TokenError::InvalidProof => write!(f, "Token signed with an unexpected key"),
+            TokenError::Invalid => write!(f, "Invalid token"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// A blinded token signed by the relay, with proof of the key it used
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct BlindSignature {
+    pub evaluated: Vec<u8>,
+    pub challenge: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+/// An unblinded token, ready to be redeemed
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AnonymousToken {
+    /// Relay key the issuer key was derived from
+    pub key_id: i32,
+    pub nonce: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl AnonymousToken {
+    /// Secret the token is known by once redeemed, like a minted token's
+    pub fn secret(&self) -> String {
+        hex::encode(&self.nonce)
+    }
+}
+
+fn random_scalar() -> Scalar {
+    let mut bytes = [0u8; 64];
+    OsRng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+fn decode_point(bytes: &[u8]) -> Result<RistrettoPoint, TokenError> {
+    CompressedRistretto::from_slice(bytes)
+        .ok()
+        .and_then(|point| point.decompress())
+        .ok_or(TokenError::Malformed)
+}
+
+fn decode_scalar(bytes: &[u8]) -> Result<Scalar, TokenError> {
+    let bytes = <[u8; 32]>::try_from(bytes).map_err(|_| TokenError::Malformed)?;
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(TokenError::Malformed)
+}
+
+fn token_point(nonce: &[u8]) -> RistrettoPoint {
+    RistrettoPoint::from_hash(
+        Sha512::new()
+            .chain_update(TOKEN_CONTEXT)
+            .chain_update(nonce),
+    )
+}
+
+/// Challenge of the proof that `evaluated` is `blinded` times the secret
+/// behind `public`
+fn proof_challenge(
+    public: &RistrettoPoint,
+    blinded: &RistrettoPoint,
+    evaluated: &RistrettoPoint,
+    base_commitment: &RistrettoPoint,
+    blinded_commitment: &RistrettoPoint,
+) -> Scalar {
+    let mut hash = Sha512::new().chain_update(PROOF_CONTEXT);
+    for point in [
+        public,
+        blinded,
+        evaluated,
+        base_commitment,
+        blinded_commitment,
+    ] {
+        hash.update(point.compress().as_bytes());
+    }
+    Scalar::from_hash(hash)
+}
+
+/// Key the relay signs tokens with
+pub struct IssuerKey {
+    secret: Scalar,
+}
+
+impl IssuerKey {
+    /// Derive the issuer key of a relay key
+    pub fn from_relay_key(relay_secret: &[u8; 32]) -> Self {
+        Self {
+            secret: Scalar::from_hash(
+                Sha512::new()
+                    .chain_update(ISSUER_CONTEXT)
+                    .chain_update(relay_secret),
+            ),
+        }
+    }
+
+    /// Public key clients check issued tokens against
+    pub fn public_key(&self) -> Vec<u8> {
+        (self.secret * RISTRETTO_BASEPOINT_POINT)
+            .compress()
+            .to_bytes()
+            .to_vec()
+    }
+
+    /// Sign a blinded token without learning the token
+    pub fn sign_blinded(&self, blinded: &[u8]) -> Result<BlindSignature, TokenError> {
+        let blinded = decode_point(blinded)?;
+        let public = self.secret * RISTRETTO_BASEPOINT_POINT;
+        let evaluated = self.secret * blinded;
+
+        // Chaum-Pedersen proof that public and evaluated share the secret
+        let nonce = random_scalar();
+        let challenge = proof_challenge(
+            &public,
+            &blinded,
+            &evaluated,
+            &(nonce * RISTRETTO_BASEPOINT_POINT),
+            &(nonce * blinded),
+        );
+        let response = nonce - challenge * self.secret;
+
+        Ok(BlindSignature {
+            evaluated: evaluated.compress().to_bytes().to_vec(),
+            challenge: challenge.to_bytes().to_vec(),
+            response: response.to_bytes().to_vec(),
+        })
+    }
+
+    /// Check that a token was signed with this key
+    pub fn verify(&self, token: &AnonymousToken) -> Result<(), TokenError> {
+        if token.nonce.len() != 32 {
+            return Err(TokenError::Malformed);
+        }
+        let signature = decode_point(&token.signature)?;
+        if self.secret * token_point(&token.nonce) == signature {
+            Ok(())
+        } else {
+            Err(TokenError::Invalid)
+        }
+    }
+}
+
+/// A token being issued, kept by the client until the relay signed it
+pub struct TokenRequest {
+    key_id: i32,
+    issuer_public: RistrettoPoint,
+    nonce: [u8; 32],
+    blind: Scalar,
+    blinded: RistrettoPoint,
+}
+
+impl TokenRequest {
+    /// Blind a new random token for the issuer key of relay key `key_id`
+    pub fn new(key_id: i32, issuer_public: &[u8]) -> Result<Self, TokenError> {
+        let issuer_public = decode_point(issuer_public)?;
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+        let blind = random_scalar();
+
+        Ok(Self {
+            key_id,
+            issuer_public,
+            nonce,
+            blind,
+            blinded: blind * token_point(&nonce),
+        })
+    }
+
+    /// The blinded token to send to the relay
+    pub fn blinded(&self) -> Vec<u8> {
+        self.blinded.compress().to_bytes().to_vec()
+    }
+
+    /// Check the relay's proof and remove the blind from its signature
+    pub fn finalize(self, signature: &BlindSignature) -> Result<AnonymousToken, TokenError> {
+        let evaluated = decode_point(&signature.evaluated)?;
+        let challenge = decode_scalar(&signature.challenge)?;
+        let response = decode_scalar(&signature.response)?;
+
+        let base_commitment = response * RISTRETTO_BASEPOINT_POINT + challenge * self.issuer_public;
+        let blinded_commitment = response * self.blinded + challenge * evaluated;
+        let expected = proof_challenge(
+            &self.issuer_public,
+            &self.blinded,
+            &evaluated,
+            &base_commitment,
+            &blinded_commitment,
+        );
+        if expected != challenge {
+            return Err(TokenError::InvalidProof);
+        }
+
+        Ok(AnonymousToken {
+            key_id: self.key_id,
+            nonce: self.nonce.to_vec(),
+            signature: (self.blind.invert() * evaluated)
+                .compress()
+                .to_bytes()
+                .to_vec(),
+        })
+    }
+}
+
+/// Payload a proof of work pays for when a batch of blinded tokens is
+/// signed, a hash of the batch so the work does not grow with its size.
+/// Every blinded token is a 32 byte point, so the batch is hashed without
+/// separators.
+pub fn batch_payload(blinded: &[Vec<u8>]) -> Vec<u8> {
+    let mut hash = Sha512::new().chain_update(BATCH_CONTEXT);
+    for blinded in blinded {
+        hash.update(blinded);
+    }
+    hash.finalize()[..32].to_vec()
+}
@@ -0,0 +1,184 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Encrypted file attachments.
+//! A file is encrypted with its own AES-256-GCM key in fixed-size chunks. Each
+//! encrypted chunk is a relay blob addressed by its SHA-256 digest, so every
+//! chunk can be verified on its own while downloading. The `Attachment`
+//! metadata (key, digests and blob IDs) travels inside the encrypted message.
+
+use aes_gcm::{Aes256Gcm, Key};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::crypto::message::aes256_gcm;
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Size of each plaintext chunk
+pub const ATTACHMENT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Length of the AES-256-GCM nonce stored at the start of each blob
+const NONCE_LEN: usize = 12;
+
+/// Largest encrypted blob a chunk can produce (nonce + chunk + auth tag)
+pub const MAX_BLOB_SIZE: usize = NONCE_LEN + ATTACHMENT_CHUNK_SIZE + 16;
+
+/// Relay blob ID for encrypted chunk data
+pub fn blob_id_for(blob: &[u8]) -> String {
+    hex::encode(Sha256::digest(blob))
+}
+
+/// Metadata needed to download, decrypt and verify an attachment
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub key: [u8; 32],
+    /// SHA-256 digest of the complete plaintext file
+    pub digest: [u8; 32],
+    /// Blob IDs of the encrypted chunks, in order
+    pub blob_ids: Vec<String>,
+}
+
+impl Attachment {
+    /// Number of chunks the file was split into
+    pub fn chunk_count(&self) -> usize {
+        self.blob_ids.len()
+    }
+
+    /// Check that the claimed size matches the number of chunks.
+    /// The sender controls both, so nothing may be sized from `size` before this.
+    pub fn check_size(&self) -> Result<()> {
+        let expected_chunks = self.size.div_ceil(ATTACHMENT_CHUNK_SIZE as u64).max(1);
+        if self.chunk_count() as u64 != expected_chunks {
+            return Err("Attachment size does not match its chunks".into());
+        }
+        Ok(())
+    }
+
+    /// Hex digest of the plaintext, stable across re-sends of the same file
+    pub fn digest_hex(&self) -> String {
+        hex::encode(self.digest)
+    }
+
+    fn cipher_key(&self) -> Key<Aes256Gcm> {
+        self.key.into()
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// An encrypted chunk ready to be uploaded to the relay
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncryptedBlob {
+    pub blob_id: String,
+    pub data: Vec<u8>,
+}
+
+/// Encrypt a file with a fresh key, returning its metadata and encrypted chunks
+pub fn encrypt_attachment(
+    file_name: &str,
+    mime_type: &str,
+    data: &[u8],
+) -> Result<(Attachment, Vec<EncryptedBlob>)> {
+    let key = aes256_gcm::generate_key();
+
+    // An empty file still gets one (empty) chunk so it has something to download
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(ATTACHMENT_CHUNK_SIZE).collect()
+    };
+
+    let mut blobs = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let (ciphertext, nonce) = aes256_gcm::encrypt(&key, chunk)?;
+
+        let mut blob = nonce;
+        blob.extend(ciphertext);
+        blobs.push(EncryptedBlob {
+            blob_id: blob_id_for(&blob),
+            data: blob,
+        });
+    }
+
+    let attachment = Attachment {
+        file_name: file_name.to_string(),
+        mime_type: mime_type.to_string(),
+        size: data.len() as u64,
+        key: key.into(),
+        digest: Sha256::digest(data).into(),
+        blob_ids: blobs.iter().map(|blob| blob.blob_id.clone()).collect(),
+    };
+
+    Ok((attachment, blobs))
+}
+
+/// Check that a downloaded blob is the expected chunk of an attachment
+pub fn verify_chunk(attachment: &Attachment, index: usize, blob: &[u8]) -> Result<()> {
+    let expected = attachment
+        .blob_ids
+        .get(index)
+        .ok_or_else(|| format!("Chunk index out of range: {index}"))?;
+
+    if blob.len() > MAX_BLOB_SIZE || blob_id_for(blob) != *expected {
+        return Err(format!("Chunk {index} failed verification").into());
+    }
+    Ok(())
+}
+
+/// Verify and decrypt a single downloaded chunk
+pub fn decrypt_chunk(attachment: &Attachment, index: usize, blob: &[u8]) -> Result<Vec<u8>> {
+    verify_chunk(attachment, index, blob)?;
+    if blob.len() < NONCE_LEN {
+        return Err(format!("Chunk {index} is truncated").into());
+    }
+
+    let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+    aes256_gcm::decrypt(&attachment.cipher_key(), ciphertext, nonce)
+}
+
+/// Decrypt every chunk in order and verify the reassembled file
+pub fn decrypt_attachment(attachment: &Attachment, blobs: &[Vec<u8>]) -> Result<Vec<u8>> {
+    attachment.check_size()?;
+    if blobs.len() != attachment.chunk_count() {
+        return Err("Attachment is missing chunks".into());
+    }
+
+    // Grow with the decrypted chunks rather than trusting the claimed size
+    let mut data = Vec::new();
+    for (index, blob) in blobs.iter().enumerate() {
+        data.extend(decrypt_chunk(attachment, index, blob)?);
+    }
+
+    if data.len() as u64 != attachment.size || Sha256::digest(&data)[..] != attachment.digest {
+        return Err("Attachment digest does not match".into());
+    }
+
+    Ok(data)
+}
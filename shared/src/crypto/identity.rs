@@ -0,0 +1,349 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Long-term Ed25519 identity keys.
+//! A user's identity key signs their room public keys, invites and profile
+//! updates so contacts can tie every room key back to the same person.
+
+use crypto_box::aead::{rand_core::RngCore, OsRng};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::crypto::attachment::Attachment;
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Domain separation prefix for every identity signature
+const SIGNATURE_CONTEXT: &str = "meeseeks-nuntius/identity/v1";
+
+/// Helper function to get current timestamp
+fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Generate a new identity signing key, returning (secret, public) bytes
+pub fn generate_identity_keypair() -> ([u8; 32], [u8; 32]) {
+    let signing_key = SigningKey::generate(&mut OsRng);
+    (
+        signing_key.to_bytes(),
+        signing_key.verifying_key().to_bytes(),
+    )
+}
+
+/// Derive the public identity key from the secret key bytes
+pub fn identity_public_from_secret(secret: &[u8; 32]) -> [u8; 32] {
+    SigningKey::from_bytes(secret).verifying_key().to_bytes()
+}
+
+/// Human comparable fingerprint of an identity key, used for out-of-band verification
+pub fn identity_fingerprint(identity_public: &[u8; 32]) -> String {
+    let digest = Sha256::digest(identity_public);
+    hex::encode_upper(&digest[..16])
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A payload type that can be signed by an identity key.
+/// `KIND` is mixed into the signed bytes so a signature over one kind of
+/// statement can never be replayed as another.
+pub trait IdentityStatement: Serialize + DeserializeOwned {
+    const KIND: &'static str;
+}
+
+/// Binds a room public key to the identity that owns it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomKeyBinding {
+    pub room_public: [u8; 32],
+    pub issued_at: u64,
+}
+
+impl RoomKeyBinding {
+    pub fn new(room_public: [u8; 32]) -> Self {
+        Self {
+            room_public,
+            issued_at: current_timestamp(),
+        }
+    }
+}
+
+impl IdentityStatement for RoomKeyBinding {
+    const KIND: &'static str = "room-key";
+}
+
+/// Domain separation prefix for room key possession proofs
+const ROOM_KEY_PROOF_CONTEXT: &str = "meeseeks-nuntius/room-key-proof/v1";
+
+/// Proof that the holder of a room secret key vouches for an identity binding.
+/// Room keys cannot sign, so the proof is a box from the room secret to the
+/// recipient's room key, sealing the digest of the signed binding.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomKeyProof {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Digest sealed by a `RoomKeyProof`, covering the identity and the binding
+pub(crate) fn room_key_proof_digest(binding: &Signed<RoomKeyBinding>) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(ROOM_KEY_PROOF_CONTEXT.as_bytes());
+    hasher.update(serde_json::to_vec(binding)?);
+    Ok(hasher.finalize().into())
+}
+
+/// Domain separation prefix for room key signatures
+const ROOM_KEY_SIGNATURE_CONTEXT: &str = "meeseeks-nuntius/room-key-signature/v1";
+
+/// Signature of a room key over an identity, for proofs without a known
+/// recipient such as invites. Room keys are X25519 keys, so this is a
+/// Schnorr signature with the same scalar on the equivalent Edwards curve,
+/// as in XEdDSA. `edwards_public` is the room key in Edwards form, which
+/// also carries the sign the X25519 key leaves out.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomKeySignature {
+    pub edwards_public: [u8; 32],
+    pub commitment: [u8; 32],
+    pub response: [u8; 32],
+}
+
+fn room_key_challenge(
+    commitment: &[u8; 32],
+    edwards_public: &[u8; 32],
+    identity_public: &[u8; 32],
+) -> Scalar {
+    let digest = Sha512::new()
+        .chain_update(ROOM_KEY_SIGNATURE_CONTEXT.as_bytes())
+        .chain_update(commitment)
+        .chain_update(edwards_public)
+        .chain_update(identity_public)
+        .finalize();
+    let mut wide = [0u8; 64];
+    wide.copy_from_slice(&digest);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+impl RoomKeySignature {
+    /// Sign `identity_public` with the secret of a room key
+    pub fn sign(room_secret: &[u8; 32], identity_public: &[u8; 32]) -> Self {
+        let scalar = Scalar::from_bytes_mod_order(clamp_integer(*room_secret));
+        let edwards_public = EdwardsPoint::mul_base(&scalar).compress().to_bytes();
+
+        let mut wide = [0u8; 64];
+        OsRng.fill_bytes(&mut wide);
+        let nonce = Scalar::from_bytes_mod_order_wide(&wide);
+        let commitment = EdwardsPoint::mul_base(&nonce).compress().to_bytes();
+
+        let challenge = room_key_challenge(&commitment, &edwards_public, identity_public);
+        Self {
+            edwards_public,
+            commitment,
+            response: (nonce + challenge * scalar).to_bytes(),
+        }
+    }
+
+    /// Check that the holder of `room_public` signed `identity_public`
+    pub fn verify(&self, room_public: &[u8; 32], identity_public: &[u8; 32]) -> Result<()> {
+        let failed = || "Room key signature verification failed";
+
+        let edwards_public = CompressedEdwardsY(self.edwards_public)
+            .decompress()
+            .filter(|point| !point.is_small_order())
+            .ok_or_else(failed)?;
+        if edwards_public.to_montgomery().to_bytes() != *room_public {
+            return Err("Room key signature is for a different room key".into());
+        }
+        let commitment = CompressedEdwardsY(self.commitment)
+            .decompress()
+            .ok_or_else(failed)?;
+        let response: Scalar =
+            Option::from(Scalar::from_canonical_bytes(self.response)).ok_or_else(failed)?;
+
+        let challenge = room_key_challenge(&self.commitment, &self.edwards_public, identity_public);
+        if EdwardsPoint::mul_base(&response) != commitment + challenge * edwards_public {
+            return Err(failed().into());
+        }
+        Ok(())
+    }
+}
+
+/// An invitation to message a room, handed to a contact out of band
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomInvite {
+    pub display_name: String,
+    pub room_name: String,
+    pub room_public: [u8; 32],
+    pub issued_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// URL of the relay the inviter receives messages on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<String>,
+    /// The room key's signature over the inviter's identity. Anyone can sign
+    /// a public room key, so without it an invite proves nothing about who
+    /// holds the room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_signature: Option<RoomKeySignature>,
+}
+
+impl RoomInvite {
+    pub fn new(display_name: &str, room_name: &str, room_public: [u8; 32]) -> Self {
+        Self {
+            display_name: display_name.to_string(),
+            room_name: room_name.to_string(),
+            room_public,
+            issued_at: current_timestamp(),
+            expires_at: None,
+            relay: None,
+            room_signature: None,
+        }
+    }
+
+    /// Name the relay messages for the invited room should be sent to
+    pub fn with_relay(mut self, relay: Option<String>) -> Self {
+        self.relay = relay;
+        self
+    }
+
+    /// Have the room key vouch for the inviter's identity
+    pub fn with_room_signature(
+        mut self,
+        room_secret: &[u8; 32],
+        identity_public: &[u8; 32],
+    ) -> Self {
+        self.room_signature = Some(RoomKeySignature::sign(room_secret, identity_public));
+        self
+    }
+
+    /// Check that the holder of the invited room key vouches for `identity_public`
+    pub fn verify_room_key(&self, identity_public: &[u8; 32]) -> Result<()> {
+        match &self.room_signature {
+            Some(signature) => signature.verify(&self.room_public, identity_public),
+            None => Err("Invite is not signed by its room key".into()),
+        }
+    }
+
+    /// Check if the invite has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| current_timestamp() > expires_at)
+    }
+}
+
+impl IdentityStatement for RoomInvite {
+    const KIND: &'static str = "invite";
+}
+
+/// Profile details a user shares with their contacts
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProfileUpdate {
+    pub display_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    /// Encrypted avatar image on the relay, see `crate::avatar`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Attachment>,
+    pub issued_at: u64,
+}
+
+impl ProfileUpdate {
+    pub fn new(display_name: &str, status_message: Option<String>) -> Self {
+        Self {
+            display_name: display_name.to_string(),
+            status_message,
+            avatar: None,
+            issued_at: current_timestamp(),
+        }
+    }
+
+    /// Share an avatar along with the profile details
+    pub fn with_avatar(mut self, avatar: Option<Attachment>) -> Self {
+        self.avatar = avatar;
+        self
+    }
+}
+
+impl IdentityStatement for ProfileUpdate {
+    const KIND: &'static str = "profile-update";
+}
+
+/// A statement together with the identity key that signed it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Signed<T> {
+    pub identity_public: [u8; 32],
+    pub payload: T,
+    pub signature: Vec<u8>,
+}
+
+/// Bytes covered by an identity signature
+fn signing_bytes<T: IdentityStatement>(payload: &T) -> Result<Vec<u8>> {
+    let mut bytes = format!("{SIGNATURE_CONTEXT}/{}:", T::KIND).into_bytes();
+    bytes.extend(serde_json::to_vec(payload)?);
+    Ok(bytes)
+}
+
+impl<T: IdentityStatement> Signed<T> {
+    /// Sign a statement with an identity secret key
+    pub fn sign(identity_secret: &[u8; 32], payload: T) -> Result<Self> {
+        let signing_key = SigningKey::from_bytes(identity_secret);
+        let signature = signing_key.sign(&signing_bytes(&payload)?);
+
+        Ok(Self {
+            identity_public: signing_key.verifying_key().to_bytes(),
+            payload,
+            signature: signature.to_bytes().to_vec(),
+        })
+    }
+
+    /// Verify the signature against the embedded identity key
+    pub fn verify(&self) -> Result<()> {
+        let verifying_key = VerifyingKey::from_bytes(&self.identity_public)
+            .map_err(|e| format!("Invalid identity key: {e}"))?;
+        let signature = Signature::from_slice(&self.signature)
+            .map_err(|e| format!("Invalid signature: {e}"))?;
+
+        verifying_key
+            .verify_strict(&signing_bytes(&self.payload)?, &signature)
+            .map_err(|e| format!("Signature verification failed: {e}").into())
+    }
+
+    /// Verify the signature and that it was made by the expected identity
+    pub fn verify_from(&self, identity_public: &[u8; 32]) -> Result<()> {
+        if &self.identity_public != identity_public {
+            return Err("Statement was signed by a different identity".into());
+        }
+        self.verify()
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
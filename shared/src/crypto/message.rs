@@ -73,12 +73,16 @@ pub mod aes256_gcm {
 }
 
 use crypto_box::{
-    aead::{Aead, AeadCore, OsRng},
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng},
     ChaChaBox, PublicKey, SecretKey,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+use crate::crypto::attachment::Attachment;
+use crate::crypto::identity::{
+    room_key_proof_digest, ProfileUpdate, RoomInvite, RoomKeyBinding, RoomKeyProof, Signed,
+};
 use crate::persistence::database::Entity;
 
 // Type alias for convenience
@@ -107,6 +111,24 @@ pub struct Contact {
     pub blocked: bool,
     pub created_at: u64,
     pub last_seen: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_public: Option<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub linked_room_keys: HashSet<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    /// Presence the contact last announced, see `last_seen` for when
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence: Option<PresenceState>,
+    /// Avatar from the contact's last profile update, see `crate::avatar`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Attachment>,
+    /// Home relay from the contact's latest invite, `None` for ours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<String>,
+    /// When the last applied profile update was issued, older ones are replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_issued_at: Option<u64>,
 }
 
 impl Default for Contact {
@@ -121,6 +143,13 @@ impl Default for Contact {
             blocked: false,
             created_at: current_timestamp(),
             last_seen: None,
+            identity_public: None,
+            linked_room_keys: HashSet::new(),
+            status_message: None,
+            presence: None,
+            avatar: None,
+            relay: None,
+            profile_issued_at: None,
         }
     }
 }
@@ -144,6 +173,24 @@ impl Contact {
         }
     }
 
+    /// Create a contact from a signed room invite.
+    /// The room key must vouch for the identity that signed the invite.
+    pub fn from_invite(name: &str, invite: &Signed<RoomInvite>) -> Result<Self> {
+        invite.verify()?;
+        invite.payload.verify_room_key(&invite.identity_public)?;
+        if invite.payload.is_expired() {
+            return Err("Invite has expired".into());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            public_key: invite.payload.room_public,
+            identity_public: Some(invite.identity_public),
+            relay: invite.payload.relay.clone(),
+            ..Default::default()
+        })
+    }
+
     /// Get the public key as a PublicKey object
     pub fn public_key(&self) -> PublicKey {
         PublicKey::from(self.public_key)
@@ -184,6 +231,124 @@ impl Contact {
         );
     }
 
+    /// Record a presence announcement made at `announced_at`.
+    /// Announcements older than what we already know are ignored.
+    pub fn apply_presence(&mut self, state: PresenceState, announced_at: u64) -> bool {
+        if self
+            .last_seen
+            .is_some_and(|last_seen| last_seen > announced_at)
+        {
+            return false;
+        }
+        self.presence = Some(state);
+        self.last_seen = Some(announced_at);
+        true
+    }
+
+    /// Check if a room public key belongs to this contact
+    pub fn owns_key(&self, public_key: &[u8; 32]) -> bool {
+        self.public_key == *public_key || self.linked_room_keys.contains(public_key)
+    }
+
+    /// Check if a room public key belongs to this contact and the contact is verified.
+    /// Verifying a contact covers every room key signed by their identity.
+    pub fn is_verified_key(&self, public_key: &[u8; 32]) -> bool {
+        self.verified && self.owns_key(public_key)
+    }
+
+    /// Link another room key to this contact using a binding signed by their identity
+    pub fn link_room_key(&mut self, binding: &Signed<RoomKeyBinding>) -> Result<()> {
+        match self.identity_public {
+            Some(identity) => binding.verify_from(&identity)?,
+            None => return Err("Contact identity is unknown".into()),
+        }
+
+        self.add_linked_room_key(binding.payload.room_public);
+        Ok(())
+    }
+
+    /// Adopt the identity of a contact without a known one, such as a contact
+    /// added by key. The binding must cover the contact's own room key, and
+    /// `proof` must come from that key's secret, addressed to `own_room`:
+    /// anyone can sign a public room key. See `ControlMessage::Identity`.
+    pub fn adopt_identity(
+        &mut self,
+        binding: &Signed<RoomKeyBinding>,
+        proof: &RoomKeyProof,
+        own_room: &Room,
+    ) -> Result<()> {
+        if self.identity_public.is_some() {
+            return Err("Contact identity is already known".into());
+        }
+        if binding.payload.room_public != self.public_key {
+            return Err("Binding is for a different room key".into());
+        }
+        binding.verify()?;
+
+        let nonce: [u8; 24] = proof
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Invalid nonce length")?;
+        let digest = own_room
+            .create_crypto_box(&self.public_key())
+            .decrypt(&nonce.into(), proof.ciphertext.as_slice())
+            .map_err(|_| "Room key proof verification failed")?;
+        if digest != room_key_proof_digest(binding)? {
+            return Err("Room key proof is for a different binding".into());
+        }
+
+        self.identity_public = Some(binding.identity_public);
+        Ok(())
+    }
+
+    /// Link the room key from an invite signed by this contact's identity
+    pub fn link_invite(&mut self, invite: &Signed<RoomInvite>) -> Result<()> {
+        match self.identity_public {
+            Some(identity) => invite.verify_from(&identity)?,
+            None => return Err("Contact identity is unknown".into()),
+        }
+        invite.payload.verify_room_key(&invite.identity_public)?;
+        if invite.payload.is_expired() {
+            return Err("Invite has expired".into());
+        }
+
+        self.add_linked_room_key(invite.payload.room_public);
+        if invite.payload.relay.is_some() {
+            self.relay = invite.payload.relay.clone();
+        }
+        Ok(())
+    }
+
+    fn add_linked_room_key(&mut self, room_public: [u8; 32]) {
+        if room_public != self.public_key {
+            self.linked_room_keys.insert(room_public);
+        }
+    }
+
+    /// Apply a profile update signed by this contact's identity.
+    /// Updates not newer than the last applied one are ignored, so a
+    /// captured update cannot be replayed to roll the profile back.
+    /// Returns whether the update was applied.
+    pub fn apply_profile_update(&mut self, update: &Signed<ProfileUpdate>) -> Result<bool> {
+        match self.identity_public {
+            Some(identity) => update.verify_from(&identity)?,
+            None => return Err("Contact identity is unknown".into()),
+        }
+        if self
+            .profile_issued_at
+            .is_some_and(|issued_at| issued_at >= update.payload.issued_at)
+        {
+            return Ok(false);
+        }
+
+        self.name = update.payload.display_name.clone();
+        self.status_message = update.payload.status_message.clone();
+        self.avatar = update.payload.avatar.clone();
+        self.profile_issued_at = Some(update.payload.issued_at);
+        Ok(true)
+    }
+
     /// Serialize to JSON
     pub fn to_json(&self) -> Result<String> {
         Ok(serde_json::to_string(&self)?)
@@ -194,14 +359,22 @@ impl Contact {
         Ok(serde_json::from_str(json)?)
     }
 
+    /// Find a contact by identity key from a list of contacts
+    pub fn find_by_identity<'a>(
+        contacts: &'a [Contact],
+        identity_public: &[u8; 32],
+    ) -> Option<&'a Contact> {
+        contacts
+            .iter()
+            .find(|contact| contact.identity_public.as_ref() == Some(identity_public))
+    }
+
     /// Find a contact by public key from a list of contacts
     pub fn find_by_public_key<'a>(
         contacts: &'a [Contact],
         public_key: &[u8; 32],
     ) -> Option<&'a Contact> {
-        contacts
-            .iter()
-            .find(|contact| contact.public_key == *public_key)
+        contacts.iter().find(|contact| contact.owns_key(public_key))
     }
 
     /// Find contacts by public keys from a list of contacts  
@@ -260,6 +433,8 @@ pub struct Room {
     pub secret_key: [u8; 32],
     pub public_key: [u8; 32],
     pub known_contacts: HashSet<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_binding: Option<Signed<RoomKeyBinding>>,
 }
 
 impl Default for Room {
@@ -275,6 +450,7 @@ impl Default for Room {
             secret_key: secret_key.to_bytes(),
             public_key: public_key.to_bytes(),
             known_contacts: HashSet::new(),
+            identity_binding: None,
         }
     }
 }
@@ -306,6 +482,7 @@ impl Room {
             secret_key: secret_bytes,
             public_key: public_bytes,
             known_contacts,
+            identity_binding: None,
         }
     }
     /// Serialize to JSON - ID will be included if present
@@ -330,6 +507,39 @@ impl Room {
         room
     }
 
+    /// Sign this room's public key with an identity key so contacts can link
+    /// it to the owner's other rooms
+    pub fn bind_identity(&mut self, identity_secret: &[u8; 32]) -> Result<()> {
+        self.identity_binding = Some(Signed::sign(
+            identity_secret,
+            RoomKeyBinding::new(self.public_key),
+        )?);
+        Ok(())
+    }
+
+    /// Prove to the owner of `recipient_public` that this room's key vouches for
+    /// an identity binding of it, see `Contact::adopt_identity`
+    pub fn prove_binding(
+        &self,
+        binding: &Signed<RoomKeyBinding>,
+        recipient_public: &PublicKey,
+    ) -> Result<RoomKeyProof> {
+        if binding.payload.room_public != self.public_key {
+            return Err("Binding is for a different room key".into());
+        }
+
+        let nonce = ChaChaBox::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .create_crypto_box(recipient_public)
+            .encrypt(&nonce, room_key_proof_digest(binding)?.as_slice())
+            .map_err(|e| format!("Encryption failed: {e}"))?;
+
+        Ok(RoomKeyProof {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
     /// Get the public key
     pub fn public_key(&self) -> PublicKey {
         PublicKey::from_bytes(self.public_key)
@@ -432,6 +642,9 @@ impl Room {
             sender_public: self.public_key,
             ciphertext,
             nonce: nonce.to_vec(),
+            ephemeral: false,
+            expires_at: None,
+            retract_hash: None,
         })
     }
 
@@ -476,6 +689,26 @@ impl Room {
         let plaintext = self.decrypt_from(message)?;
         String::from_utf8(plaintext).map_err(|e| e.into())
     }
+
+    /// Encrypt message content for another contact
+    pub fn encrypt_payload_for(
+        &mut self,
+        recipient_public: &PublicKey,
+        payload: &MessagePayload,
+    ) -> Result<EncryptedMessage> {
+        let mut message = self.encrypt_for(recipient_public, &serde_json::to_vec(payload)?)?;
+        message.ephemeral = payload.is_ephemeral();
+        message.expires_at = payload
+            .expires_in
+            .map(|seconds| current_timestamp() + seconds);
+        Ok(message)
+    }
+
+    /// Decrypt message content from another contact
+    pub fn decrypt_payload_from(&mut self, message: &EncryptedMessage) -> Result<MessagePayload> {
+        let plaintext = self.decrypt_from(message)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
 }
 
 /// Entity implementation for Room, common boilerplate
@@ -501,6 +734,15 @@ pub struct EncryptedMessage {
     pub sender_public: [u8; 32],
     pub ciphertext: Vec<u8>,
     pub nonce: Vec<u8>,
+    /// Receipts and typing indicators that the relay only keeps briefly
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ephemeral: bool,
+    /// When a disappearing message expires, as a Unix timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Hash of the secret the sender retracts the relay copy with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retract_hash: Option<Vec<u8>>,
 }
 
 impl EncryptedMessage {
@@ -511,6 +753,9 @@ impl EncryptedMessage {
             sender_public: sender_public.to_bytes(),
             ciphertext,
             nonce,
+            ephemeral: false,
+            expires_at: None,
+            retract_hash: None,
         }
     }
 
@@ -531,6 +776,12 @@ impl EncryptedMessage {
     pub fn sender_public_bytes(&self) -> [u8; 32] {
         self.sender_public
     }
+
+    /// Seconds left until a disappearing message expires
+    pub fn expires_in(&self) -> Option<u64> {
+        self.expires_at
+            .map(|expires_at| expires_at.saturating_sub(current_timestamp()))
+    }
 }
 
 impl Entity for EncryptedMessage {
@@ -546,3 +797,229 @@ impl Entity for EncryptedMessage {
         "encrypted_message"
     }
 }
+
+/// Generate a random message ID, unique per sender
+pub fn new_message_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Control messages travel encrypted like normal messages but change earlier
+/// messages or room state instead of being shown on their own
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    /// Add or remove an emoji reaction on another message
+    Reaction {
+        target_id: String,
+        emoji: String,
+        #[serde(default)]
+        remove: bool,
+    },
+    /// Replace the text of one of the sender's own messages
+    Edit { target_id: String, text: String },
+    /// Delete one of the sender's own messages for everyone
+    Delete { target_id: String },
+    /// Acknowledge that messages were delivered to or read by the sender
+    Receipt {
+        target_ids: Vec<String>,
+        kind: ReceiptKind,
+    },
+    /// The sender started or stopped typing
+    Typing { active: bool },
+    /// Set the room's disappearing messages timer, `None` turns it off
+    Retention { seconds: Option<u64> },
+    /// The sender's presence changed, or is repeated to show they are around
+    Presence { state: PresenceState },
+    /// The sender changed their name, status message or avatar
+    Profile { update: Box<Signed<ProfileUpdate>> },
+    /// The identity of the sender's room key, with a proof of that key
+    /// addressed to the recipient, for contacts that do not know it yet
+    Identity {
+        binding: Box<Signed<RoomKeyBinding>>,
+        proof: RoomKeyProof,
+    },
+}
+
+/// How far a message got on the recipient's side
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptKind {
+    Delivered,
+    Read,
+}
+
+/// Availability a user shares with their contacts
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceState {
+    Online,
+    Away,
+    DoNotDisturb,
+    Offline,
+}
+
+impl PresenceState {
+    pub const ALL: [PresenceState; 4] = [
+        PresenceState::Online,
+        PresenceState::Away,
+        PresenceState::DoNotDisturb,
+        PresenceState::Offline,
+    ];
+
+    /// Name used in serialized form and translation keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Online => "online",
+            Self::Away => "away",
+            Self::DoNotDisturb => "do_not_disturb",
+            Self::Offline => "offline",
+        }
+    }
+}
+
+impl ControlMessage {
+    /// ID of the message this control message changes, if it targets a single one
+    pub fn target_id(&self) -> Option<&str> {
+        match self {
+            Self::Reaction { target_id, .. }
+            | Self::Edit { target_id, .. }
+            | Self::Delete { target_id } => Some(target_id),
+            Self::Receipt { .. }
+            | Self::Typing { .. }
+            | Self::Retention { .. }
+            | Self::Presence { .. }
+            | Self::Profile { .. }
+            | Self::Identity { .. } => None,
+        }
+    }
+
+    /// Ephemeral control messages only describe transient state, so they are
+    /// not kept in the history and expire quickly on the relay
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(
+            self,
+            Self::Receipt { .. } | Self::Typing { .. } | Self::Presence { .. }
+        )
+    }
+}
+
+/// The plaintext content of a message, encrypted as JSON into an `EncryptedMessage`
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MessagePayload {
+    #[serde(default)]
+    pub message_id: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    pub sent_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control: Option<ControlMessage>,
+    /// Seconds the message is kept before it disappears
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<u64>,
+}
+
+impl MessagePayload {
+    /// Create a text message
+    pub fn new(text: &str) -> Self {
+        Self {
+            message_id: new_message_id(),
+            text: text.to_string(),
+            attachments: Vec::new(),
+            sent_at: current_timestamp(),
+            control: None,
+            expires_in: None,
+        }
+    }
+
+    /// Create a message carrying attachments, with optional caption text
+    pub fn with_attachments(text: &str, attachments: Vec<Attachment>) -> Self {
+        Self {
+            attachments,
+            ..Self::new(text)
+        }
+    }
+
+    /// Create a control message
+    pub fn control(control: ControlMessage) -> Self {
+        Self {
+            control: Some(control),
+            ..Self::new("")
+        }
+    }
+
+    /// Create a reaction to another message
+    pub fn reaction(target_id: &str, emoji: &str, remove: bool) -> Self {
+        Self::control(ControlMessage::Reaction {
+            target_id: target_id.to_string(),
+            emoji: emoji.to_string(),
+            remove,
+        })
+    }
+
+    /// Create an edit of one of the sender's own messages
+    pub fn edit(target_id: &str, text: &str) -> Self {
+        Self::control(ControlMessage::Edit {
+            target_id: target_id.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Create a delete-for-everyone of one of the sender's own messages
+    pub fn delete(target_id: &str) -> Self {
+        Self::control(ControlMessage::Delete {
+            target_id: target_id.to_string(),
+        })
+    }
+
+    /// Create a delivery or read receipt for messages from another member
+    pub fn receipt(target_ids: Vec<String>, kind: ReceiptKind) -> Self {
+        Self::control(ControlMessage::Receipt { target_ids, kind })
+    }
+
+    /// Create a typing indicator
+    pub fn typing(active: bool) -> Self {
+        Self::control(ControlMessage::Typing { active })
+    }
+
+    /// Create a change of the room's disappearing messages timer
+    pub fn retention(seconds: Option<u64>) -> Self {
+        Self::control(ControlMessage::Retention { seconds })
+    }
+
+    /// Create a presence announcement
+    pub fn presence(state: PresenceState) -> Self {
+        Self::control(ControlMessage::Presence { state })
+    }
+
+    /// Create a profile update for the other members
+    pub fn profile(update: Signed<ProfileUpdate>) -> Self {
+        Self::control(ControlMessage::Profile {
+            update: Box::new(update),
+        })
+    }
+
+    /// Create an identity proof of the sender's room key for one recipient
+    pub fn identity(binding: Signed<RoomKeyBinding>, proof: RoomKeyProof) -> Self {
+        Self::control(ControlMessage::Identity {
+            binding: Box::new(binding),
+            proof,
+        })
+    }
+
+    pub fn has_attachments(&self) -> bool {
+        !self.attachments.is_empty()
+    }
+
+    pub fn is_control(&self) -> bool {
+        self.control.is_some()
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.control
+            .as_ref()
+            .is_some_and(ControlMessage::is_ephemeral)
+    }
+}
@@ -15,7 +15,15 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+pub mod attachment;
+pub mod identity;
 pub mod message;
 
 #[cfg(test)]
 mod test_message;
+
+#[cfg(test)]
+mod test_identity;
+
+#[cfg(test)]
+mod test_attachment;
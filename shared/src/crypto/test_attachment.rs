@@ -0,0 +1,251 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::attachments::*;
+    use crate::crypto::attachment::*;
+    use crate::crypto::message::{MessagePayload, Room};
+    use crate::persistence::database::Database;
+    use serial_test::serial;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// In-memory relay that can be told to drop the connection after some downloads
+    #[derive(Default)]
+    struct MemoryRelay {
+        blobs: Mutex<HashMap<String, Vec<u8>>>,
+        downloads_before_failure: Cell<Option<usize>>,
+        downloads: Cell<usize>,
+    }
+
+    impl BlobTransport for MemoryRelay {
+        async fn upload_blob(&self, blob_id: String, data: Vec<u8>) -> Result<()> {
+            self.blobs.lock().unwrap().insert(blob_id, data);
+            Ok(())
+        }
+
+        async fn download_blob(&self, blob_id: String) -> Result<Option<Vec<u8>>> {
+            if let Some(remaining) = self.downloads_before_failure.get() {
+                if remaining == 0 {
+                    return Err("Connection lost".into());
+                }
+                self.downloads_before_failure.set(Some(remaining - 1));
+            }
+            self.downloads.set(self.downloads.get() + 1);
+            Ok(self.blobs.lock().unwrap().get(&blob_id).cloned())
+        }
+    }
+
+    fn sample_file(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let data = sample_file(ATTACHMENT_CHUNK_SIZE * 2 + 100);
+        let (attachment, blobs) =
+            encrypt_attachment("report.pdf", "application/pdf", &data).unwrap();
+
+        assert_eq!(attachment.chunk_count(), 3);
+        assert_eq!(attachment.size, data.len() as u64);
+        assert!(blobs.iter().all(|blob| blob.data.len() <= MAX_BLOB_SIZE));
+
+        let blob_data: Vec<Vec<u8>> = blobs.into_iter().map(|blob| blob.data).collect();
+        assert_eq!(decrypt_attachment(&attachment, &blob_data).unwrap(), data);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let (attachment, blobs) = encrypt_attachment("empty.txt", "text/plain", &[]).unwrap();
+        assert_eq!(attachment.chunk_count(), 1);
+
+        let blob_data: Vec<Vec<u8>> = blobs.into_iter().map(|blob| blob.data).collect();
+        assert!(decrypt_attachment(&attachment, &blob_data)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_blob_ids_are_content_addresses() {
+        let (_, blobs) = encrypt_attachment("a.bin", "application/octet-stream", b"hello").unwrap();
+        assert_eq!(blobs[0].blob_id, blob_id_for(&blobs[0].data));
+    }
+
+    #[test]
+    fn test_tampered_chunk_is_rejected() {
+        let data = sample_file(1000);
+        let (attachment, blobs) =
+            encrypt_attachment("a.bin", "application/octet-stream", &data).unwrap();
+
+        let mut tampered = blobs[0].data.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+        assert!(verify_chunk(&attachment, 0, &tampered).is_err());
+        assert!(decrypt_chunk(&attachment, 0, &tampered).is_err());
+    }
+
+    #[test]
+    fn test_reordered_chunks_are_rejected() {
+        let data = sample_file(ATTACHMENT_CHUNK_SIZE + 10);
+        let (attachment, blobs) =
+            encrypt_attachment("a.bin", "application/octet-stream", &data).unwrap();
+
+        let reordered = vec![blobs[1].data.clone(), blobs[0].data.clone()];
+        assert!(decrypt_attachment(&attachment, &reordered).is_err());
+    }
+
+    #[test]
+    fn test_inflated_size_is_rejected() {
+        let data = sample_file(1000);
+        let (mut attachment, blobs) =
+            encrypt_attachment("a.bin", "application/octet-stream", &data).unwrap();
+        let blob_data: Vec<Vec<u8>> = blobs.into_iter().map(|blob| blob.data).collect();
+
+        attachment.size = u64::MAX;
+        assert!(attachment.check_size().is_err());
+        assert!(decrypt_attachment(&attachment, &blob_data).is_err());
+
+        attachment.size = ATTACHMENT_CHUNK_SIZE as u64 + 1;
+        assert!(decrypt_attachment(&attachment, &blob_data).is_err());
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("report.pdf"), "report.pdf");
+        assert_eq!(sanitize_file_name("../../.bashrc"), "bashrc");
+        assert_eq!(sanitize_file_name("C:\\Windows\\evil.exe"), "evil.exe");
+        assert_eq!(sanitize_file_name("a\nb\u{7}.txt"), "ab.txt");
+        assert_eq!(sanitize_file_name(".."), "attachment");
+        assert_eq!(sanitize_file_name(""), "attachment");
+    }
+
+    #[test]
+    fn test_save_download_keeps_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("report.pdf"), b"mine").unwrap();
+
+        let first = save_download(dir.path(), "report.pdf", b"one").unwrap();
+        let second = save_download(dir.path(), "../report.pdf", b"two").unwrap();
+        let bare = save_download(dir.path(), "README", b"three").unwrap();
+        let bare_copy = save_download(dir.path(), "README", b"four").unwrap();
+
+        assert_eq!(first, dir.path().join("report (1).pdf"));
+        assert_eq!(second, dir.path().join("report (2).pdf"));
+        assert_eq!(bare, dir.path().join("README"));
+        assert_eq!(bare_copy, dir.path().join("README (1)"));
+        assert_eq!(
+            std::fs::read(dir.path().join("report.pdf")).unwrap(),
+            b"mine"
+        );
+        assert_eq!(std::fs::read(&second).unwrap(), b"two");
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let data = sample_file(1000);
+        let (mut attachment, blobs) =
+            encrypt_attachment("a.bin", "application/octet-stream", &data).unwrap();
+        attachment.key = [0u8; 32];
+
+        assert!(decrypt_chunk(&attachment, 0, &blobs[0].data).is_err());
+    }
+
+    #[test]
+    fn test_attachment_travels_in_message() {
+        let mut alice = Room::new("Alice");
+        let mut bob = Room::new("Bob");
+        let (attachment, _) = encrypt_attachment("photo.png", "image/png", b"png").unwrap();
+
+        let payload = MessagePayload::with_attachments("Look", vec![attachment.clone()]);
+        let message = alice
+            .encrypt_payload_for(&bob.public_key(), &payload)
+            .unwrap();
+        let received = bob.decrypt_payload_from(&message).unwrap();
+
+        assert_eq!(received.text, "Look");
+        assert_eq!(received.attachments, vec![attachment]);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_upload_and_download() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let relay = MemoryRelay::default();
+        let data = sample_file(ATTACHMENT_CHUNK_SIZE + 1);
+        let attachment = upload_attachment(&relay, "a.bin", "application/octet-stream", &data)
+            .await
+            .unwrap();
+
+        let downloaded = download_attachment(&relay, &db, &attachment).await.unwrap();
+        assert_eq!(downloaded, data);
+
+        // Finished downloads leave nothing behind
+        let progress = AttachmentDownload::load_or_new(&db, &attachment).unwrap();
+        assert_eq!(progress.missing_chunks().len(), attachment.chunk_count());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_download_resumes_after_interruption() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let relay = MemoryRelay::default();
+        let data = sample_file(ATTACHMENT_CHUNK_SIZE * 3);
+        let attachment = upload_attachment(&relay, "a.bin", "application/octet-stream", &data)
+            .await
+            .unwrap();
+
+        relay.downloads_before_failure.set(Some(2));
+        assert!(download_attachment(&relay, &db, &attachment).await.is_err());
+
+        let progress = AttachmentDownload::load_or_new(&db, &attachment).unwrap();
+        assert_eq!(progress.missing_chunks(), vec![2]);
+
+        relay.downloads_before_failure.set(None);
+        let downloaded = download_attachment(&relay, &db, &attachment).await.unwrap();
+        assert_eq!(downloaded, data);
+        assert_eq!(relay.downloads.get(), 3);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_download_rejects_substituted_blob() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let relay = MemoryRelay::default();
+        let attachment = upload_attachment(&relay, "a.bin", "application/octet-stream", b"data")
+            .await
+            .unwrap();
+        relay
+            .blobs
+            .lock()
+            .unwrap()
+            .insert(attachment.blob_ids[0].clone(), b"forged".to_vec());
+
+        assert!(download_attachment(&relay, &db, &attachment).await.is_err());
+        let progress = AttachmentDownload::load_or_new(&db, &attachment).unwrap();
+        assert!(!progress.is_complete());
+    }
+}
@@ -0,0 +1,398 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::identity::*;
+    use crate::crypto::message::{Contact, Room};
+    use crate::local::*;
+    use crate::persistence::database::Database;
+    use crate::user_data::UserData;
+    use serial_test::serial;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let (secret, public) = generate_identity_keypair();
+        assert_eq!(identity_public_from_secret(&secret), public);
+
+        let signed = Signed::sign(&secret, ProfileUpdate::new("Alice", None)).unwrap();
+        assert_eq!(signed.identity_public, public);
+        assert!(signed.verify().is_ok());
+        assert!(signed.verify_from(&public).is_ok());
+
+        let json = signed.to_json().unwrap();
+        let restored = Signed::<ProfileUpdate>::from_json(&json).unwrap();
+        assert!(restored.verify().is_ok());
+    }
+
+    #[test]
+    fn test_tampered_payload_fails() {
+        let (secret, _) = generate_identity_keypair();
+        let mut signed = Signed::sign(&secret, ProfileUpdate::new("Alice", None)).unwrap();
+        signed.payload.display_name = "Mallory".to_string();
+        assert!(signed.verify().is_err());
+    }
+
+    #[test]
+    fn test_verify_from_wrong_identity_fails() {
+        let (secret, _) = generate_identity_keypair();
+        let (_, other_public) = generate_identity_keypair();
+        let signed = Signed::sign(&secret, RoomKeyBinding::new([1u8; 32])).unwrap();
+        assert!(signed.verify_from(&other_public).is_err());
+    }
+
+    #[test]
+    fn test_signature_is_bound_to_statement_kind() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct OtherStatement {
+            room_public: [u8; 32],
+            issued_at: u64,
+        }
+        impl IdentityStatement for OtherStatement {
+            const KIND: &'static str = "other";
+        }
+
+        let (secret, _) = generate_identity_keypair();
+        let binding = Signed::sign(&secret, RoomKeyBinding::new([1u8; 32])).unwrap();
+        let replayed = Signed {
+            identity_public: binding.identity_public,
+            payload: OtherStatement {
+                room_public: binding.payload.room_public,
+                issued_at: binding.payload.issued_at,
+            },
+            signature: binding.signature.clone(),
+        };
+        assert!(replayed.verify().is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_format() {
+        let fingerprint = identity_fingerprint(&[0u8; 32]);
+        let groups: Vec<&str> = fingerprint.split(' ').collect();
+        assert_eq!(groups.len(), 8);
+        assert!(groups.iter().all(|group| group.len() == 4));
+        assert_eq!(fingerprint, identity_fingerprint(&[0u8; 32]));
+        assert_ne!(fingerprint, identity_fingerprint(&[1u8; 32]));
+    }
+
+    #[test]
+    fn test_contact_links_room_keys_on_first_use() {
+        let alice = UserData::new("alice", "Alice");
+        let first_room = Room::new("First");
+        let second_room = Room::new("Second");
+        let bob_room = Room::new("Bob");
+
+        let mut contact = Contact::with_key("Alice", &first_room.public_key());
+        assert!(contact.identity_public.is_none());
+
+        // A binding for an unrelated key cannot establish the identity
+        let unrelated = alice.sign_room_key(&second_room).unwrap();
+        assert!(contact.link_room_key(&unrelated).is_err());
+        let proof = second_room
+            .prove_binding(&unrelated, &bob_room.public_key())
+            .unwrap();
+        assert!(contact
+            .adopt_identity(&unrelated, &proof, &bob_room)
+            .is_err());
+
+        let own = alice.sign_room_key(&first_room).unwrap();
+        let proof = first_room
+            .prove_binding(&own, &bob_room.public_key())
+            .unwrap();
+        contact.adopt_identity(&own, &proof, &bob_room).unwrap();
+        assert_eq!(contact.identity_public, alice.identity_public_key);
+
+        contact.link_room_key(&unrelated).unwrap();
+        assert!(contact.owns_key(&second_room.public_key_bytes()));
+
+        contact.set_verified(true);
+        assert!(contact.is_verified_key(&second_room.public_key_bytes()));
+    }
+
+    #[test]
+    fn test_identity_needs_proof_from_room_key() {
+        let alice = UserData::new("alice", "Alice");
+        let mallory = UserData::new("mallory", "Mallory");
+        let alice_room = Room::new("Alice");
+        let mallory_room = Room::new("Mallory");
+        let bob_room = Room::new("Bob");
+
+        let mut contact = Contact::with_key("Alice", &alice_room.public_key());
+
+        // Mallory can sign Alice's public room key, but cannot box from its secret
+        let claimed = mallory.sign_room_key(&alice_room).unwrap();
+        assert!(mallory_room
+            .prove_binding(&claimed, &bob_room.public_key())
+            .is_err());
+        let impostor = Room::from_values(
+            None,
+            "Alice",
+            "",
+            0,
+            mallory_room.secret_key_bytes(),
+            alice_room.public_key_bytes(),
+            Default::default(),
+        );
+        let forged = impostor
+            .prove_binding(&claimed, &bob_room.public_key())
+            .unwrap();
+        assert!(contact
+            .adopt_identity(&claimed, &forged, &bob_room)
+            .is_err());
+
+        // A genuine proof only vouches for the binding it was made for
+        let binding = alice.sign_room_key(&alice_room).unwrap();
+        let proof = alice_room
+            .prove_binding(&binding, &bob_room.public_key())
+            .unwrap();
+        assert!(contact.adopt_identity(&claimed, &proof, &bob_room).is_err());
+
+        // Proofs are addressed to one recipient room
+        let other_room = Room::new("Other");
+        assert!(contact
+            .adopt_identity(&binding, &proof, &other_room)
+            .is_err());
+        assert!(contact.identity_public.is_none());
+
+        contact.adopt_identity(&binding, &proof, &bob_room).unwrap();
+        assert_eq!(contact.identity_public, alice.identity_public_key);
+    }
+
+    #[test]
+    fn test_contact_rejects_other_identity() {
+        let alice = UserData::new("alice", "Alice");
+        let mallory = UserData::new("mallory", "Mallory");
+        let room = Room::new("Room");
+        let mallory_room = Room::new("Mallory Room");
+
+        let invite = alice.create_invite(&room, None).unwrap();
+        let mut contact = Contact::from_invite("Alice", &invite).unwrap();
+
+        let forged = mallory.sign_room_key(&mallory_room).unwrap();
+        assert!(contact.link_room_key(&forged).is_err());
+
+        let forged_invite = mallory.create_invite(&mallory_room, None).unwrap();
+        assert!(contact.link_invite(&forged_invite).is_err());
+        assert!(!contact.owns_key(&mallory_room.public_key_bytes()));
+
+        let forged_update = mallory.sign_profile_update().unwrap();
+        assert!(contact.apply_profile_update(&forged_update).is_err());
+        assert_eq!(contact.name, "Alice");
+    }
+
+    #[test]
+    fn test_invite_needs_room_key_signature() {
+        let alice = UserData::new("alice", "Alice");
+        let mallory = UserData::new("mallory", "Mallory");
+        let room = Room::new("Room");
+        let secret = alice.identity_secret_key.unwrap();
+        let mut contact =
+            Contact::from_invite("Alice", &alice.create_invite(&room, None).unwrap()).unwrap();
+
+        // Without the room secret, an invite cannot claim someone's room key
+        let impostor = Room::from_values(
+            None,
+            "Room",
+            "",
+            0,
+            Room::new("Mallory").secret_key_bytes(),
+            room.public_key_bytes(),
+            Default::default(),
+        );
+        let claimed = mallory.create_invite(&impostor, None).unwrap();
+        assert!(Contact::from_invite("Mallory", &claimed).is_err());
+        let unsigned = Signed::sign(
+            &secret,
+            RoomInvite::new("Alice", "Room", Room::new("Other").public_key_bytes()),
+        )
+        .unwrap();
+        assert!(Contact::from_invite("Alice", &unsigned).is_err());
+        assert!(contact.link_invite(&unsigned).is_err());
+
+        // The room signature only vouches for the identity it names
+        let second_room = Room::new("Second Room");
+        let mut moved = RoomInvite::new("Alice", "Room", second_room.public_key_bytes())
+            .with_room_signature(
+                &second_room.secret_key_bytes(),
+                &mallory.identity_public_key.unwrap(),
+            );
+        assert!(contact
+            .link_invite(&Signed::sign(&secret, moved.clone()).unwrap())
+            .is_err());
+        moved = moved.with_room_signature(
+            &second_room.secret_key_bytes(),
+            &alice.identity_public_key.unwrap(),
+        );
+        contact
+            .link_invite(&Signed::sign(&secret, moved).unwrap())
+            .unwrap();
+        assert!(contact.owns_key(&second_room.public_key_bytes()));
+    }
+
+    #[test]
+    fn test_invite_carries_home_relay() {
+        let alice = UserData::new("alice", "Alice");
+        let room = Room::new("Room");
+        let second_room = Room::new("Second Room");
+
+        let invite = alice
+            .create_invite(&room, Some("https://a.example.org".to_string()))
+            .unwrap();
+        let restored = Signed::<RoomInvite>::from_json(&invite.to_json().unwrap()).unwrap();
+        assert!(restored.verify().is_ok());
+        let mut contact = Contact::from_invite("Alice", &restored).unwrap();
+        assert_eq!(contact.relay.as_deref(), Some("https://a.example.org"));
+
+        // The relay is signed, so it cannot be redirected in transit
+        let mut redirected = invite.clone();
+        redirected.payload.relay = Some("https://evil.example.org".to_string());
+        assert!(Contact::from_invite("Alice", &redirected).is_err());
+
+        // A newer invite moves the contact, one without a relay does not
+        let moved = alice
+            .create_invite(&second_room, Some("https://b.example.org".to_string()))
+            .unwrap();
+        contact.link_invite(&moved).unwrap();
+        assert_eq!(contact.relay.as_deref(), Some("https://b.example.org"));
+        contact
+            .link_invite(&alice.create_invite(&room, None).unwrap())
+            .unwrap();
+        assert_eq!(contact.relay.as_deref(), Some("https://b.example.org"));
+    }
+
+    #[test]
+    fn test_expired_invite_is_rejected() {
+        let alice = UserData::new("alice", "Alice");
+        let room = Room::new("Room");
+        let secret = alice.identity_secret_key.unwrap();
+
+        let mut payload = RoomInvite::new("Alice", "Room", room.public_key_bytes())
+            .with_room_signature(
+                &room.secret_key_bytes(),
+                &alice.identity_public_key.unwrap(),
+            );
+        payload.expires_at = Some(1);
+        let invite = Signed::sign(&secret, payload).unwrap();
+
+        assert!(invite.payload.is_expired());
+        assert!(Contact::from_invite("Alice", &invite).is_err());
+    }
+
+    #[test]
+    fn test_apply_profile_update() {
+        let mut alice = UserData::new("alice", "Alice");
+        let room = Room::new("Room");
+        let mut contact =
+            Contact::from_invite("Alice", &alice.create_invite(&room, None).unwrap()).unwrap();
+
+        alice.set_display_name("Alice Cooper".to_string());
+        alice.set_status_message(Some("On tour".to_string()));
+        contact
+            .apply_profile_update(&alice.sign_profile_update().unwrap())
+            .unwrap();
+
+        assert_eq!(contact.name, "Alice Cooper");
+        assert_eq!(contact.status_message.as_deref(), Some("On tour"));
+
+        // An update that is not newer is a replay and changes nothing
+        let secret = alice.identity_secret_key.unwrap();
+        let mut stale = ProfileUpdate::new("Alice", None);
+        stale.issued_at = contact.profile_issued_at.unwrap();
+        assert!(!contact
+            .apply_profile_update(&Signed::sign(&secret, stale.clone()).unwrap())
+            .unwrap());
+        assert_eq!(contact.name, "Alice Cooper");
+        stale.issued_at += 1;
+        assert!(contact
+            .apply_profile_update(&Signed::sign(&secret, stale).unwrap())
+            .unwrap());
+        assert_eq!(contact.name, "Alice");
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_local_invite_flow() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        // Alice creates a room and invites Bob
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+        let first_room = create_room("First".into(), None).await.unwrap();
+        let second_room = create_room("Second".into(), None).await.unwrap();
+
+        let room = Room::from_json(&get_room(first_room.clone()).await.unwrap().unwrap()).unwrap();
+        assert!(room.identity_binding.unwrap().verify().is_ok());
+
+        let first_invite = create_room_invite(first_room).await.unwrap();
+        let second_invite = create_room_invite(second_room).await.unwrap();
+
+        // Bob accepts both invites, which resolve to a single contact
+        select_profile(bob.clone()).await.unwrap();
+        let contact_id = accept_room_invite(first_invite, None).await.unwrap();
+        let linked_id = accept_room_invite(second_invite, None).await.unwrap();
+        assert_eq!(contact_id, linked_id);
+        assert_eq!(get_all_contacts().await.unwrap().len(), 1);
+
+        let contact =
+            Contact::from_json(&get_contact(contact_id.clone()).await.unwrap().unwrap()).unwrap();
+        assert_eq!(contact.name, "Alice");
+        assert_eq!(contact.linked_room_keys.len(), 1);
+
+        // Alice changes her display name and Bob applies the update
+        select_profile(alice.clone()).await.unwrap();
+        let mut alice_data =
+            UserData::from_json(&get_current_user_data().await.unwrap().unwrap()).unwrap();
+        alice_data.set_display_name("Alice Cooper".to_string());
+        update_user_data(alice_data.to_json().unwrap())
+            .await
+            .unwrap();
+        let update = create_profile_update().await.unwrap();
+
+        select_profile(bob.clone()).await.unwrap();
+        assert_eq!(
+            apply_profile_update(update).await.unwrap(),
+            Some(contact_id.clone())
+        );
+        let contact = Contact::from_json(&get_contact(contact_id).await.unwrap().unwrap()).unwrap();
+        assert_eq!(contact.name, "Alice Cooper");
+
+        let _ = Database::drop_profile(&alice);
+        let _ = Database::drop_profile(&bob);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_profile_update_from_stranger_is_ignored() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let update = create_profile_update().await.unwrap();
+
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+        select_profile(bob.clone()).await.unwrap();
+        assert_eq!(apply_profile_update(update).await.unwrap(), None);
+
+        let _ = Database::drop_profile(&alice);
+        let _ = Database::drop_profile(&bob);
+    }
+}
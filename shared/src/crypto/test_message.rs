@@ -845,6 +845,7 @@ mod test_contact {
             blocked,
             created_at,
             last_seen,
+            ..Default::default()
         };
 
         assert_eq!(contact.id(), id.as_deref());
@@ -0,0 +1,127 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Authentication between federated relays.
+//! A relay forwards a message to a recipient's home relay with a proof: a
+//! hash of the request boxed from its relay key to the destination's relay
+//! key. Only the holder of the origin's secret key can produce a box the
+//! destination opens, and the timestamp in the request limits replays.
+//! Relay keys rotate, so the destination tries its current and previous keys.
+
+use crypto_box::{
+    aead::{Aead, AeadCore, OsRng},
+    ChaChaBox, PublicKey, SecretKey,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How far the timestamp of a forwarded request may be off, in seconds
+pub const MAX_CLOCK_SKEW_SECS: u64 = 5 * 60;
+
+/// Proof that a request was made by the holder of a relay key
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RelayProof {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Why a forwarded request was not accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FederationError {
+    /// Keys or nonce have the wrong length
+    Malformed,
+    /// The request is too old or from the future
+    Stale,
+    /// No known key of the origin made the proof for this request
+    Invalid,
+}
+
+impl std::fmt::Display for FederationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FederationError::Malformed => write!(f, "Malformed relay proof"),
+            FederationError::Stale => write!(f, "Relay request is outside the allowed time"),
+            FederationError::Invalid => write!(f, "Invalid relay proof"),
+        }
+    }
+}
+
+impl std::error::Error for FederationError {}
+
+/// Canonical form of a relay URL, so the same relay always compares equal
+pub fn normalize_relay_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Prove that `request` comes from the relay holding `origin_secret`, for
+/// the relay with `destination_public`
+pub fn seal_request(
+    origin_secret: &[u8; 32],
+    destination_public: &[u8; 32],
+    request: &[u8],
+) -> Result<RelayProof, FederationError> {
+    let crypto_box = ChaChaBox::new(
+        &PublicKey::from(*destination_public),
+        &SecretKey::from_bytes(*origin_secret),
+    );
+    let nonce = ChaChaBox::generate_nonce(&mut OsRng);
+    let ciphertext = crypto_box
+        .encrypt(&nonce, Sha256::digest(request).as_slice())
+        .map_err(|_| FederationError::Malformed)?;
+
+    Ok(RelayProof {
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+/// Check the proof of a forwarded request against every key the origin may
+/// have used and every key of ours it may have been made for
+pub fn verify_request(
+    own_secrets: &[[u8; 32]],
+    origin_publics: &[[u8; 32]],
+    request: &[u8],
+    proof: &RelayProof,
+) -> Result<(), FederationError> {
+    let nonce =
+        <[u8; 24]>::try_from(proof.nonce.as_slice()).map_err(|_| FederationError::Malformed)?;
+    let digest = Sha256::digest(request);
+
+    for own_secret in own_secrets {
+        for origin_public in origin_publics {
+            let crypto_box = ChaChaBox::new(
+                &PublicKey::from(*origin_public),
+                &SecretKey::from_bytes(*own_secret),
+            );
+            if let Ok(plaintext) = crypto_box.decrypt(&nonce.into(), proof.ciphertext.as_slice()) {
+                if plaintext == digest.as_slice() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+    Err(FederationError::Invalid)
+}
+
+/// Check that a request sent at `sent_at` is recent, both in Unix seconds
+pub fn check_freshness(sent_at: u64, now: u64) -> Result<(), FederationError> {
+    if sent_at.abs_diff(now) <= MAX_CLOCK_SKEW_SECS {
+        Ok(())
+    } else {
+        Err(FederationError::Stale)
+    }
+}
@@ -0,0 +1,634 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Local history of decrypted messages per room.
+//! Control messages are applied to the messages they reference, so each
+//! `StoredMessage` holds its aggregated state (reactions, edits, deletion,
+//! delivery). Typing indicators are kept apart since they expire on their own.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::avatar::{adopt_contact_identity, apply_profile_update};
+use crate::crypto::attachment::Attachment;
+use crate::crypto::message::{Contact, ControlMessage, MessagePayload, ReceiptKind};
+use crate::messaging::SentMessage;
+use crate::notifications::RoomNotifications;
+use crate::persistence::database::{Database, Entity};
+use crate::presence::apply_presence;
+use crate::retention::{apply_retention, RoomRetention};
+use crate::search::{update_index, SearchDocument, SearchTerm};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Longest emoji sequence accepted in a reaction
+const MAX_REACTION_LEN: usize = 32;
+
+/// Seconds after which a typing indicator without a refresh is dropped
+pub const TYPING_TIMEOUT_SECS: u64 = 10;
+
+/// Helper function to get current timestamp
+fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Why a payload can never apply. Unlike a storage error, applying it again
+/// would fail the same way, so its mailbox copy need not be kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// A message without an ID to store it under
+    MissingId,
+    /// A reaction with an empty or oversized emoji
+    InvalidReaction,
+    /// An edit removing all content of a message
+    EmptyEdit,
+    /// An edit or deletion from someone other than the sender
+    NotSender,
+    /// A profile update or identity proof that does not verify
+    Unverified,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::MissingId => write!(f, "Message has no ID"),
+            Rejection::InvalidReaction => write!(f, "Invalid reaction"),
+            Rejection::EmptyEdit => write!(f, "Edited message is empty"),
+            Rejection::NotSender => write!(f, "Only the sender may change a message"),
+            Rejection::Unverified => write!(f, "Signature or proof does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Aggregated reaction shown under a message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ReactionSummary {
+    pub emoji: String,
+    pub count: usize,
+    pub reacted_by_me: bool,
+}
+
+/// Delivery progress of one of our own messages
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    Sent,
+    Delivered,
+    Read,
+}
+
+/// An earlier version of an edited message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MessageEdit {
+    pub previous_text: String,
+    pub edited_at: u64,
+}
+
+/// A decrypted message kept in the local history of a room
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub room_id: String,
+    pub message_id: String,
+    pub sender_public: [u8; 32],
+    pub is_own: bool,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    pub sent_at: u64,
+    pub received_at: u64,
+    /// Emoji to the hex public keys of everyone who reacted with it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reactions: BTreeMap<String, BTreeSet<String>>,
+    /// Earlier versions of the text, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edits: Vec<MessageEdit>,
+    /// Set once the sender deleted the message, which is then kept as a tombstone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<u64>,
+    /// Hex public keys of members that acknowledged delivery of our message
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub delivered_to: BTreeSet<String>,
+    /// Hex public keys of members that read our message
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub read_by: BTreeSet<String>,
+    /// When we read a message from another member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<u64>,
+    /// When a disappearing message is removed from the history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl StoredMessage {
+    /// Storage key of a message within a room
+    pub fn key_for(room_id: &str, message_id: &str) -> String {
+        format!("{}:{room_id}:{message_id}", Self::key_prefix())
+    }
+
+    /// Key prefix shared by every message of a room
+    pub fn room_prefix(room_id: &str) -> String {
+        format!("{}:{room_id}:", Self::key_prefix())
+    }
+
+    pub fn from_payload(
+        room_id: &str,
+        sender_public: [u8; 32],
+        is_own: bool,
+        payload: &MessagePayload,
+    ) -> Self {
+        // The timer runs from when the message reached us, so clock skew
+        // between members cannot make it expire early
+        let received_at = current_timestamp();
+        Self {
+            id: Some(Self::key_for(room_id, &payload.message_id)),
+            room_id: room_id.to_string(),
+            message_id: payload.message_id.clone(),
+            sender_public,
+            is_own,
+            text: payload.text.clone(),
+            attachments: payload.attachments.clone(),
+            sent_at: payload.sent_at,
+            received_at,
+            reactions: BTreeMap::new(),
+            edits: Vec::new(),
+            deleted_at: None,
+            delivered_to: BTreeSet::new(),
+            read_by: BTreeSet::new(),
+            read_at: None,
+            expires_at: payload.expires_in.map(|seconds| received_at + seconds),
+        }
+    }
+
+    pub fn is_edited(&self) -> bool {
+        !self.edits.is_empty()
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Replace the text, keeping the previous version in the edit record
+    pub fn apply_edit(&mut self, text: &str, edited_at: u64) {
+        let previous_text = std::mem::replace(&mut self.text, text.to_string());
+        self.edits.push(MessageEdit {
+            previous_text,
+            edited_at,
+        });
+    }
+
+    /// Turn the message into a tombstone, dropping its content
+    pub fn apply_delete(&mut self, deleted_at: u64) {
+        self.text.clear();
+        self.attachments.clear();
+        self.reactions.clear();
+        self.edits.clear();
+        self.deleted_at = Some(deleted_at);
+    }
+
+    /// Record a receipt from another member. A read receipt implies delivery.
+    /// Returns whether anything changed.
+    pub fn apply_receipt(&mut self, member_public: &[u8; 32], kind: ReceiptKind) -> bool {
+        let member = hex::encode(member_public);
+        let delivered = self.delivered_to.insert(member.clone());
+        let read = kind == ReceiptKind::Read && self.read_by.insert(member);
+        delivered || read
+    }
+
+    /// Delivery progress of our own message, `None` for messages from others.
+    /// A message counts as read once any member has read it.
+    pub fn delivery_state(&self) -> Option<DeliveryState> {
+        if !self.is_own {
+            return None;
+        }
+        Some(if !self.read_by.is_empty() {
+            DeliveryState::Read
+        } else if !self.delivered_to.is_empty() {
+            DeliveryState::Delivered
+        } else {
+            DeliveryState::Sent
+        })
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Check if we still have to read a message from another member
+    pub fn is_unread(&self) -> bool {
+        !self.is_own && self.read_at.is_none() && !self.is_deleted()
+    }
+
+    /// Add or remove a reaction from one member
+    pub fn apply_reaction(&mut self, reactor_public: &[u8; 32], emoji: &str, remove: bool) {
+        let reactor = hex::encode(reactor_public);
+        if remove {
+            if let Some(reactors) = self.reactions.get_mut(emoji) {
+                reactors.remove(&reactor);
+                if reactors.is_empty() {
+                    self.reactions.remove(emoji);
+                }
+            }
+        } else {
+            self.reactions
+                .entry(emoji.to_string())
+                .or_default()
+                .insert(reactor);
+        }
+    }
+
+    /// Check if a member has reacted with an emoji
+    pub fn has_reaction_from(&self, reactor_public: &[u8; 32], emoji: &str) -> bool {
+        self.reactions
+            .get(emoji)
+            .is_some_and(|reactors| reactors.contains(&hex::encode(reactor_public)))
+    }
+
+    /// Reactions with their counts, most used first
+    pub fn reaction_summary(&self, own_public: &[u8; 32]) -> Vec<ReactionSummary> {
+        let mut summary: Vec<ReactionSummary> = self
+            .reactions
+            .iter()
+            .map(|(emoji, reactors)| ReactionSummary {
+                emoji: emoji.clone(),
+                count: reactors.len(),
+                reacted_by_me: reactors.contains(&hex::encode(own_public)),
+            })
+            .collect();
+        summary.sort_by_key(|s| Reverse(s.count));
+        summary
+    }
+
+    /// Name shown for the sender, falling back to a short key fingerprint
+    pub fn sender_name(&self, contacts: &[Contact]) -> String {
+        match Contact::find_by_public_key(contacts, &self.sender_public) {
+            Some(contact) => contact.display_name(),
+            None => hex::encode(&self.sender_public[..4]),
+        }
+    }
+
+    /// Prepare the message for display
+    pub fn to_view(&self, own_public: &[u8; 32], contacts: &[Contact]) -> MessageView {
+        MessageView {
+            reactions: self.reaction_summary(own_public),
+            sender_name: self.sender_name(contacts),
+            sender_contact_id: Contact::find_by_public_key(contacts, &self.sender_public)
+                .and_then(|contact| contact.id.clone()),
+            delivery: self.delivery_state(),
+            message: self.clone(),
+        }
+    }
+
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// A stored message with everything the conversation view shows for it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MessageView {
+    pub message: StoredMessage,
+    pub sender_name: String,
+    /// Contact who sent the message, for looking up their avatar
+    #[serde(default)]
+    pub sender_contact_id: Option<String>,
+    pub reactions: Vec<ReactionSummary>,
+    #[serde(default)]
+    pub delivery: Option<DeliveryState>,
+}
+
+impl MessageView {
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl Entity for StoredMessage {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "stored_message"
+    }
+}
+
+/// A room member who is currently typing
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TypingIndicator {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub room_id: String,
+    pub sender_public: [u8; 32],
+    pub updated_at: u64,
+}
+
+impl TypingIndicator {
+    /// Storage key of a member's typing indicator within a room
+    pub fn key_for(room_id: &str, sender_public: &[u8; 32]) -> String {
+        format!(
+            "{}:{room_id}:{}",
+            Self::key_prefix(),
+            hex::encode(sender_public)
+        )
+    }
+
+    /// Key prefix shared by every typing indicator of a room
+    pub fn room_prefix(room_id: &str) -> String {
+        format!("{}:{room_id}:", Self::key_prefix())
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.updated_at) > TYPING_TIMEOUT_SECS
+    }
+}
+
+impl Entity for TypingIndicator {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "typing_indicator"
+    }
+}
+
+/// Public keys of the members typing in a room, dropping expired indicators
+pub fn typing_members(db: &Database, room_id: &str, now: u64) -> Result<Vec<[u8; 32]>> {
+    let indicators: Vec<TypingIndicator> =
+        db.load_all_entities(&TypingIndicator::room_prefix(room_id))?;
+
+    let mut members = Vec::new();
+    for indicator in indicators {
+        if indicator.is_expired(now) {
+            if let Some(id) = indicator.id() {
+                db.delete::<TypingIndicator>(id)?;
+            }
+        } else {
+            members.push(indicator.sender_public);
+        }
+    }
+    Ok(members)
+}
+
+fn clear_typing(db: &Database, room_id: &str, sender_public: &[u8; 32]) -> Result<()> {
+    let key = TypingIndicator::key_for(room_id, sender_public);
+    if db.load_entity::<TypingIndicator>(&key)?.is_some() {
+        db.delete::<TypingIndicator>(&key)?;
+    }
+    Ok(())
+}
+
+/// Mark every unread message from other members as read.
+/// Returns the IDs of the messages that were marked.
+pub fn mark_read(db: &Database, room_id: &str) -> Result<Vec<String>> {
+    let now = current_timestamp();
+    let mut marked = Vec::new();
+    for mut message in load_room_history(db, room_id)? {
+        if message.is_unread() {
+            message.read_at = Some(now);
+            db.update_entity(&message)?;
+            marked.push(message.message_id);
+        }
+    }
+    Ok(marked)
+}
+
+/// Load a room's history, oldest first. Expired messages are left out even
+/// before the purge task removes them.
+pub fn load_room_history(db: &Database, room_id: &str) -> Result<Vec<StoredMessage>> {
+    let now = current_timestamp();
+    let mut messages: Vec<StoredMessage> =
+        db.load_all_entities(&StoredMessage::room_prefix(room_id))?;
+    messages.retain(|message| !message.is_expired(now));
+    messages.sort_by(|a, b| {
+        a.sent_at
+            .cmp(&b.sent_at)
+            .then_with(|| a.received_at.cmp(&b.received_at))
+    });
+    Ok(messages)
+}
+
+/// Delete everything stored for a room besides the room itself: history,
+/// search index, sent deposit records, typing indicators and settings.
+/// Returns the number of entries removed.
+pub fn delete_room_data(db: &Database, room_id: &str) -> Result<usize> {
+    let mut removed = 0;
+    for prefix in [
+        StoredMessage::room_prefix(room_id),
+        SearchTerm::room_prefix(room_id),
+        SearchDocument::room_prefix(room_id),
+        TypingIndicator::room_prefix(room_id),
+        format!("{}:{room_id}:", SentMessage::key_prefix()),
+    ] {
+        removed += db.delete_prefix(&prefix)?;
+    }
+
+    let retention_key = RoomRetention::key_for(room_id);
+    if db.load_entity::<RoomRetention>(&retention_key)?.is_some() {
+        db.delete::<RoomRetention>(&retention_key)?;
+        removed += 1;
+    }
+    let notifications_key = RoomNotifications::key_for(room_id);
+    if db
+        .load_entity::<RoomNotifications>(&notifications_key)?
+        .is_some()
+    {
+        db.delete::<RoomNotifications>(&notifications_key)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Apply a decrypted payload to a room's history.
+/// Returns the ID of the message that was added or changed, if any.
+/// Payloads that can never apply fail with a `Rejection`.
+pub fn apply_payload(
+    db: &Database,
+    room_id: &str,
+    sender_public: [u8; 32],
+    own_public: [u8; 32],
+    payload: &MessagePayload,
+) -> Result<Option<String>> {
+    match &payload.control {
+        None => {
+            if payload.message_id.is_empty() {
+                return Err(Rejection::MissingId.into());
+            }
+
+            // The first message stored under an ID wins, so a replay or a
+            // colliding ID from another member cannot replace it
+            let key = StoredMessage::key_for(room_id, &payload.message_id);
+            if db.load_entity::<StoredMessage>(&key)?.is_some() {
+                return Ok(None);
+            }
+
+            let is_own = sender_public == own_public;
+            let mut message = StoredMessage::from_payload(room_id, sender_public, is_own, payload);
+            db.save_entity(&mut message)?;
+            update_index(db, room_id, &message)?;
+
+            // A sent message ends the sender's typing
+            clear_typing(db, room_id, &sender_public)?;
+            Ok(Some(payload.message_id.clone()))
+        }
+        Some(ControlMessage::Receipt { target_ids, kind }) => {
+            // Our own devices do not acknowledge our messages
+            if sender_public == own_public {
+                return Ok(None);
+            }
+
+            let mut changed = None;
+            for target_id in target_ids {
+                let key = StoredMessage::key_for(room_id, target_id);
+                let Some(mut message) = db.load_entity::<StoredMessage>(&key)? else {
+                    continue;
+                };
+                // Only the sender of a message tracks its receipts
+                if !message.is_own || message.is_deleted() {
+                    continue;
+                }
+                if message.apply_receipt(&sender_public, *kind) {
+                    db.update_entity(&message)?;
+                    changed.get_or_insert_with(|| target_id.clone());
+                }
+            }
+            Ok(changed)
+        }
+        Some(ControlMessage::Typing { active }) => {
+            if sender_public == own_public {
+                return Ok(None);
+            }
+
+            if *active {
+                let mut indicator = TypingIndicator {
+                    id: Some(TypingIndicator::key_for(room_id, &sender_public)),
+                    room_id: room_id.to_string(),
+                    sender_public,
+                    updated_at: current_timestamp(),
+                };
+                db.save_entity(&mut indicator)?;
+            } else {
+                clear_typing(db, room_id, &sender_public)?;
+            }
+            Ok(None)
+        }
+        Some(ControlMessage::Retention { seconds }) => {
+            apply_retention(db, room_id, *seconds, payload.sent_at)?;
+            Ok(None)
+        }
+        Some(ControlMessage::Presence { state }) => {
+            if sender_public != own_public {
+                apply_presence(db, &sender_public, *state, payload.sent_at)?;
+            }
+            Ok(None)
+        }
+        Some(ControlMessage::Profile { update }) => {
+            if sender_public != own_public {
+                apply_profile_update(db, &sender_public, update)?;
+            }
+            Ok(None)
+        }
+        Some(ControlMessage::Identity { binding, proof }) => {
+            if sender_public != own_public {
+                adopt_contact_identity(db, room_id, &sender_public, binding, proof)?;
+            }
+            Ok(None)
+        }
+        Some(control) => {
+            let Some(target_id) = control.target_id() else {
+                return Ok(None);
+            };
+            let key = StoredMessage::key_for(room_id, target_id);
+            let Some(mut message) = db.load_entity::<StoredMessage>(&key)? else {
+                return Ok(None);
+            };
+
+            // Tombstones no longer change
+            if message.is_deleted() {
+                return Ok(None);
+            }
+
+            match control {
+                ControlMessage::Reaction { emoji, remove, .. } => {
+                    if emoji.is_empty() || emoji.len() > MAX_REACTION_LEN {
+                        return Err(Rejection::InvalidReaction.into());
+                    }
+                    message.apply_reaction(&sender_public, emoji, *remove);
+                }
+                ControlMessage::Edit { text, .. } => {
+                    if message.sender_public != sender_public {
+                        return Err(Rejection::NotSender.into());
+                    }
+                    if text.trim().is_empty() && message.attachments.is_empty() {
+                        return Err(Rejection::EmptyEdit.into());
+                    }
+                    message.apply_edit(text, payload.sent_at);
+                }
+                ControlMessage::Delete { .. } => {
+                    if message.sender_public != sender_public {
+                        return Err(Rejection::NotSender.into());
+                    }
+                    message.apply_delete(payload.sent_at);
+                }
+                // Handled above since they do not target a single message
+                ControlMessage::Receipt { .. }
+                | ControlMessage::Typing { .. }
+                | ControlMessage::Retention { .. }
+                | ControlMessage::Presence { .. }
+                | ControlMessage::Profile { .. }
+                | ControlMessage::Identity { .. } => {
+                    return Ok(None);
+                }
+            }
+
+            db.update_entity(&message)?;
+            if !matches!(control, ControlMessage::Reaction { .. }) {
+                update_index(db, room_id, &message)?;
+            }
+            Ok(Some(target_id.to_string()))
+        }
+    }
+}
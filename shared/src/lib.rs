@@ -41,3 +41,164 @@ pub mod local;
     any(feature = "desktop", feature = "mobile")
 ))]
 pub mod user_data;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod profile;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod attachments;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod history;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile", feature = "relay")
+))]
+pub mod mailbox;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile", feature = "relay")
+))]
+pub mod federation;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile", feature = "relay")
+))]
+pub mod blind_token;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile", feature = "relay")
+))]
+pub mod pow;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod messaging;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod retention;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod search;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod presence;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod notifications;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod avatar;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub mod tokens;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_profile;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_history;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_search;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_presence;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_notifications;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_avatar;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_mailbox;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_federation;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_blind_token;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_pow;
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_tokens;
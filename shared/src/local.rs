@@ -18,9 +18,36 @@
 //! Local api functions - these run on the same device as the client
 //! These handle sensitive operations like database access and cryptography
 
-use crate::crypto::message::{Contact, Room};
+use crate::attachments::{
+    download_attachment, upload_attachment, AttachmentDownload, BlobTransport,
+};
+use crate::avatar::{
+    import_avatar, load_avatar, remove_avatar, share_profile, sync_contact_avatars,
+};
+use crate::crypto::attachment::Attachment;
+use crate::crypto::identity::{ProfileUpdate, RoomInvite, Signed};
+use crate::crypto::message::{
+    Contact, EncryptedMessage, MessagePayload, PresenceState, ReceiptKind, Room,
+};
+use crate::history::{
+    delete_room_data, load_room_history, mark_read, typing_members, StoredMessage,
+};
+use crate::messaging::{
+    acknowledge_handled, apply_messages, home_relay, receive_messages, recipient_hash, send_delete,
+    send_edit, send_payload, send_receipt, send_retention, MessageSubscription, MessageTransport,
+    ReceivedMessages,
+};
+use crate::notifications::{
+    deliver, notifications_for, room_notification_mode, save_notification_mode, Notification,
+    NotificationMode, NotificationSettings, NotificationSink,
+};
 use crate::persistence::database::{Database, Entity};
+use crate::presence::refresh_presence;
+use crate::profile::{active_database, adopt_unscoped_entities, ProfileSession};
+use crate::retention::room_retention;
+use crate::search::search_rooms;
 use crate::user_data::UserData;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct LocalApiError {
@@ -55,22 +82,57 @@ impl From<hex::FromHexError> for LocalApiError {
     }
 }
 
+/// Open the database scoped to the active profile
+fn profile_database() -> Result<Database, LocalApiError> {
+    active_database().map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// ID of the active profile, if one is selected
+fn active_profile_id() -> Result<Option<String>, LocalApiError> {
+    let db = Database::new();
+    let session = ProfileSession::load(&db).map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(session.active_profile_id)
+}
+
+/// Load the active profile's user data, if a profile is selected
+fn active_user_data() -> Result<Option<UserData>, LocalApiError> {
+    let db = Database::new();
+    match active_profile_id()? {
+        Some(profile_id) => db
+            .load_entity::<UserData>(&profile_id)
+            .map_err(|e| LocalApiError::new(e.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Load the active profile's user data, failing if there is none
+fn require_active_user_data() -> Result<UserData, LocalApiError> {
+    active_user_data()?.ok_or_else(|| LocalApiError::new("No active profile"))
+}
+
 // Room management functions (local database operations)
 pub async fn create_room(
     name: String,
     description: Option<String>,
 ) -> Result<String, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     let mut room = Room::new(&name);
     if let Some(desc) = description {
         room.description = desc;
     }
+
+    // Sign the room key so contacts can link it to our other rooms
+    if let Some(identity_secret) = active_user_data()?.and_then(|user| user.identity_secret_key) {
+        room.bind_identity(&identity_secret)
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+
     db.save_entity(&mut room)
         .map_err(|e| LocalApiError::new(e.to_string()))
 }
 
 pub async fn get_room(id: String) -> Result<Option<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     match db
         .load_entity::<Room>(&id)
         .map_err(|e| LocalApiError::new(e.to_string()))?
@@ -84,7 +146,7 @@ pub async fn get_room(id: String) -> Result<Option<String>, LocalApiError> {
 }
 
 pub async fn update_room(room_json: String) -> Result<(), LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     let room = Room::from_json(&room_json).map_err(|e| LocalApiError::new(e.to_string()))?;
     db.update_entity(&room)
         .map_err(|e| LocalApiError::new(e.to_string()))?;
@@ -92,14 +154,15 @@ pub async fn update_room(room_json: String) -> Result<(), LocalApiError> {
 }
 
 pub async fn delete_room(id: String) -> Result<(), LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
+    delete_room_data(&db, &id).map_err(|e| LocalApiError::new(e.to_string()))?;
     db.delete::<Room>(&id)
         .map_err(|e| LocalApiError::new(e.to_string()))?;
     Ok(())
 }
 
 pub async fn get_all_rooms() -> Result<Vec<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     let rooms = db
         .load_all_entities::<Room>(Room::key_prefix())
         .map_err(|e| LocalApiError::new(e.to_string()))?;
@@ -114,7 +177,7 @@ pub async fn get_all_rooms() -> Result<Vec<String>, LocalApiError> {
 }
 
 pub async fn find_room_by_name(name: String) -> Result<Option<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     match db
         .find_entity::<Room, _>(Room::key_prefix(), |room| room.name == name)
         .map_err(|e| LocalApiError::new(e.to_string()))?
@@ -129,7 +192,7 @@ pub async fn find_room_by_name(name: String) -> Result<Option<String>, LocalApiE
 
 // Contact management functions (local database + crypto operations)
 pub async fn create_contact(name: String, public_key: String) -> Result<String, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     use crypto_box::PublicKey;
 
     let public_key_bytes: [u8; 32] = hex::decode(&public_key)
@@ -144,7 +207,7 @@ pub async fn create_contact(name: String, public_key: String) -> Result<String,
 }
 
 pub async fn get_contact(id: String) -> Result<Option<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     match db
         .load_entity::<Contact>(&id)
         .map_err(|e| LocalApiError::new(e.to_string()))?
@@ -159,7 +222,7 @@ pub async fn get_contact(id: String) -> Result<Option<String>, LocalApiError> {
 }
 
 pub async fn update_contact(contact_json: String) -> Result<(), LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     let contact =
         Contact::from_json(&contact_json).map_err(|e| LocalApiError::new(e.to_string()))?;
     db.update_entity(&contact)
@@ -168,14 +231,14 @@ pub async fn update_contact(contact_json: String) -> Result<(), LocalApiError> {
 }
 
 pub async fn delete_contact(id: String) -> Result<(), LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     db.delete::<Contact>(&id)
         .map_err(|e| LocalApiError::new(e.to_string()))?;
     Ok(())
 }
 
 pub async fn get_all_contacts() -> Result<Vec<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     let contacts = db
         .load_all_entities::<Contact>(Contact::key_prefix())
         .map_err(|e| LocalApiError::new(e.to_string()))?;
@@ -191,7 +254,7 @@ pub async fn get_all_contacts() -> Result<Vec<String>, LocalApiError> {
 }
 
 pub async fn find_contact_by_name(name: String) -> Result<Option<String>, LocalApiError> {
-    let db = Database::new();
+    let db = profile_database()?;
     match db
         .find_entity::<Contact, _>(Contact::key_prefix(), |contact| contact.name == name)
         .map_err(|e| LocalApiError::new(e.to_string()))?
@@ -205,6 +268,668 @@ pub async fn find_contact_by_name(name: String) -> Result<Option<String>, LocalA
     }
 }
 
+// Identity functions (signed invites and profile updates)
+pub async fn create_room_invite(room_id: String) -> Result<String, LocalApiError> {
+    let db = profile_database()?;
+    let room = db
+        .load_entity::<Room>(&room_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .ok_or_else(|| LocalApiError::new(format!("Room not found: {room_id}")))?;
+
+    let invite = require_active_user_data()?
+        .create_invite(&room, home_relay())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    invite
+        .to_json()
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Accept a signed invite, creating a contact or linking the room key to the
+/// existing contact with the same identity. Returns the contact ID.
+pub async fn accept_room_invite(
+    invite_json: String,
+    name: Option<String>,
+) -> Result<String, LocalApiError> {
+    let db = profile_database()?;
+    let invite = Signed::<RoomInvite>::from_json(&invite_json)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    if let Some(existing) = Contact::find_by_identity(&contacts, &invite.identity_public) {
+        let mut contact = existing.clone();
+        contact
+            .link_invite(&invite)
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+        db.update_entity(&contact)
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+        return contact
+            .id
+            .ok_or_else(|| LocalApiError::new("Contact has no ID"));
+    }
+
+    let name = name.unwrap_or_else(|| invite.payload.display_name.clone());
+    let mut contact =
+        Contact::from_invite(&name, &invite).map_err(|e| LocalApiError::new(e.to_string()))?;
+    db.save_entity(&mut contact)
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+pub async fn create_profile_update() -> Result<String, LocalApiError> {
+    let update = require_active_user_data()?
+        .sign_profile_update()
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    update
+        .to_json()
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Apply a signed profile update to the contact with the signing identity.
+/// Returns the updated contact ID, or None if the identity is not a contact
+/// or the update is older than the contact's profile.
+pub async fn apply_profile_update(update_json: String) -> Result<Option<String>, LocalApiError> {
+    let db = profile_database()?;
+    let update = Signed::<ProfileUpdate>::from_json(&update_json)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let Some(existing) = Contact::find_by_identity(&contacts, &update.identity_public) else {
+        return Ok(None);
+    };
+
+    let mut contact = existing.clone();
+    if !contact
+        .apply_profile_update(&update)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+    {
+        return Ok(None);
+    }
+    db.update_entity(&contact)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    if let Some(replaced) = existing
+        .avatar
+        .as_ref()
+        .filter(|avatar| contact.avatar.as_ref() != Some(*avatar))
+    {
+        remove_avatar(&db, replaced).map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+    Ok(contact.id)
+}
+
+// Attachment functions (encrypted chunks are transferred through the relay)
+pub async fn send_attachment<T: BlobTransport>(
+    transport: &T,
+    file_name: String,
+    mime_type: String,
+    data: Vec<u8>,
+) -> Result<String, LocalApiError> {
+    let attachment = upload_attachment(transport, &file_name, &mime_type, &data)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    attachment
+        .to_json()
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+pub async fn fetch_attachment<T: BlobTransport>(
+    transport: &T,
+    attachment_json: String,
+) -> Result<Vec<u8>, LocalApiError> {
+    let db = profile_database()?;
+    let attachment =
+        Attachment::from_json(&attachment_json).map_err(|e| LocalApiError::new(e.to_string()))?;
+    download_attachment(transport, &db, &attachment)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Fraction of an attachment already downloaded, from 0.0 to 1.0
+pub async fn get_attachment_progress(attachment_json: String) -> Result<f32, LocalApiError> {
+    let db = profile_database()?;
+    let attachment =
+        Attachment::from_json(&attachment_json).map_err(|e| LocalApiError::new(e.to_string()))?;
+    match db
+        .load_entity::<AttachmentDownload>(&AttachmentDownload::key_for(&attachment))
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+    {
+        Some(download) => Ok(download.progress()),
+        None => Ok(0.0),
+    }
+}
+
+// Avatar functions (images are stored and shared encrypted, see `crate::avatar`)
+
+/// Encrypt and store an avatar image for the active profile.
+/// Returns the attachment JSON to set as the profile's `UserData::avatar`.
+pub async fn store_avatar_image(mime_type: String, data: Vec<u8>) -> Result<String, LocalApiError> {
+    let db = profile_database()?;
+    let avatar =
+        import_avatar(&db, &mime_type, &data).map_err(|e| LocalApiError::new(e.to_string()))?;
+    avatar
+        .to_json()
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Decrypt a stored avatar image, `None` if it is not stored on this device
+pub async fn get_avatar_image(avatar_json: String) -> Result<Option<Vec<u8>>, LocalApiError> {
+    let db = profile_database()?;
+    let avatar =
+        Attachment::from_json(&avatar_json).map_err(|e| LocalApiError::new(e.to_string()))?;
+    load_avatar(&db, &avatar).map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// A contact's avatar as its image type and data, once downloaded
+pub async fn get_contact_avatar(
+    contact_id: String,
+) -> Result<Option<(String, Vec<u8>)>, LocalApiError> {
+    let db = profile_database()?;
+    let Some(avatar) = db
+        .load_entity::<Contact>(&contact_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .and_then(|contact| contact.avatar)
+    else {
+        return Ok(None);
+    };
+    let data = load_avatar(&db, &avatar).map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(data.map(|data| (avatar.mime_type, data)))
+}
+
+/// Send our name, status message and avatar to every room
+pub async fn share_profile_update<B: BlobTransport, M: MessageTransport>(
+    blobs: &B,
+    messages: &M,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    let update = require_active_user_data()?
+        .sign_profile_update()
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    share_profile(blobs, messages, &db, update)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Download contact avatars that are not stored yet, returning how many were
+pub async fn download_contact_avatars<T: BlobTransport>(
+    transport: &T,
+) -> Result<usize, LocalApiError> {
+    let db = profile_database()?;
+    sync_contact_avatars(transport, &db)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+// Messaging functions (payloads are encrypted per contact and exchanged through the relay)
+pub async fn send_message<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    text: String,
+    attachments_json: Vec<String>,
+) -> Result<String, LocalApiError> {
+    let db = profile_database()?;
+    let attachments = attachments_json
+        .iter()
+        .map(|json| Attachment::from_json(json))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let payload = MessagePayload::with_attachments(&text, attachments);
+    send_payload(transport, &db, &room_id, &payload)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(payload.message_id)
+}
+
+/// Add or remove a reaction on a message and remember the emoji as recently used
+pub async fn send_reaction<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    target_id: String,
+    emoji: String,
+    remove: bool,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    let payload = MessagePayload::reaction(&target_id, &emoji, remove);
+    send_payload(transport, &db, &room_id, &payload)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    if !remove {
+        add_recent_emoji(emoji).await?;
+    }
+    Ok(())
+}
+
+/// Replace the text of one of our messages for everyone in the room
+pub async fn edit_message<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    message_id: String,
+    text: String,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    send_edit(transport, &db, &room_id, &message_id, &text)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Delete one of our messages for everyone in the room
+pub async fn delete_message<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    message_id: String,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    send_delete(transport, &db, &room_id, &message_id)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Fetch and apply new messages for a room, acknowledging delivery unless
+/// the user opted out
+async fn receive_and_acknowledge<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    user_data: Option<&UserData>,
+) -> Result<ReceivedMessages, LocalApiError> {
+    let received = receive_messages(transport, db, room_id)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    acknowledge_delivery(transport, db, room_id, &received, user_data).await?;
+    Ok(received)
+}
+
+/// Send delivery receipts for newly received messages, unless the user
+/// opted out
+async fn acknowledge_delivery<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    received: &ReceivedMessages,
+    user_data: Option<&UserData>,
+) -> Result<(), LocalApiError> {
+    if user_data.is_none_or(|data| data.send_delivery_receipts) {
+        send_receipt(
+            transport,
+            db,
+            room_id,
+            received.new_message_ids.clone(),
+            ReceiptKind::Delivered,
+        )
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Fetch and apply new messages for a room, acknowledging delivery unless the
+/// user opted out. Returns the number applied.
+pub async fn receive_room_messages<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+) -> Result<usize, LocalApiError> {
+    let db = profile_database()?;
+    let user_data = active_user_data()?;
+    let received = receive_and_acknowledge(transport, &db, &room_id, user_data.as_ref()).await?;
+    Ok(received.applied)
+}
+
+/// Fetch new messages for every room and raise notifications for them
+/// through `sink`, following the user's settings and each room's rule.
+/// Returns the number of notifications raised.
+pub async fn receive_all_messages<T: MessageTransport, S: NotificationSink>(
+    transport: &T,
+    sink: &S,
+) -> Result<usize, LocalApiError> {
+    let db = profile_database()?;
+    let user_data = active_user_data()?;
+    let settings = NotificationSettings::from_user_data(&user_data.clone().unwrap_or_default());
+    let rooms = db
+        .load_all_entities::<Room>(Room::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let mut notifications = Vec::new();
+    for room in rooms {
+        let Some(room_id) = room.id.clone() else {
+            continue;
+        };
+        let received =
+            match receive_and_acknowledge(transport, &db, &room_id, user_data.as_ref()).await {
+                Ok(received) => received,
+                Err(e) => {
+                    eprintln!("Failed to receive messages for room {room_id}: {e}");
+                    continue;
+                }
+            };
+
+        notifications.extend(room_notifications(
+            &db, &room, &received, &contacts, &settings,
+        )?);
+    }
+
+    deliver(sink, &notifications, &settings).map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(notifications.len())
+}
+
+/// Notifications for the messages newly received in a room
+fn room_notifications(
+    db: &Database,
+    room: &Room,
+    received: &ReceivedMessages,
+    contacts: &[Contact],
+    settings: &NotificationSettings,
+) -> Result<Vec<Notification>, LocalApiError> {
+    let room_id = room.id().unwrap_or_default();
+    let mut messages = Vec::new();
+    for message_id in &received.new_message_ids {
+        if let Some(message) = db
+            .load_entity::<StoredMessage>(&StoredMessage::key_for(room_id, message_id))
+            .map_err(|e| LocalApiError::new(e.to_string()))?
+        {
+            messages.push(message);
+        }
+    }
+    notifications_for(db, room, &messages, contacts, settings)
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Mailboxes subscribed on a live delivery connection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LiveSubscriptions {
+    /// Profile the subscribed rooms belong to
+    pub profile_id: Option<String>,
+    /// Subscribed mailboxes and their room IDs
+    pub rooms: HashMap<String, String>,
+}
+
+/// Whether a live delivery connection still matches the active profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionState {
+    /// Every room is subscribed, with the number added now
+    Current(usize),
+    /// The profile changed or a subscribed room is gone. Mailboxes cannot be
+    /// unsubscribed, so the connection has to be replaced.
+    Stale,
+}
+
+/// Subscribe the rooms of the active profile to live delivery.
+/// Only rooms missing from `subscribed` are added; a connection whose
+/// subscriptions belong to another profile or a deleted room is `Stale`.
+pub async fn subscribe_rooms<S: MessageSubscription>(
+    subscription: &mut S,
+    subscribed: &mut LiveSubscriptions,
+) -> Result<SubscriptionState, LocalApiError> {
+    let profile_id = active_profile_id()?;
+    if !subscribed.rooms.is_empty() && subscribed.profile_id != profile_id {
+        return Ok(SubscriptionState::Stale);
+    }
+    subscribed.profile_id = profile_id;
+
+    let db = profile_database()?;
+    let rooms: HashMap<String, Room> = db
+        .load_all_entities::<Room>(Room::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .into_iter()
+        .map(|room| (recipient_hash(&room.public_key_bytes()), room))
+        .collect();
+    if subscribed.rooms.iter().any(|(mailbox, room_id)| {
+        rooms.get(mailbox).and_then(|room| room.id.as_ref()) != Some(room_id)
+    }) {
+        return Ok(SubscriptionState::Stale);
+    }
+
+    let mut added = 0;
+    for (mailbox, room) in rooms {
+        let Some(room_id) = room.id.clone() else {
+            continue;
+        };
+        if subscribed.rooms.contains_key(&mailbox) {
+            continue;
+        }
+        subscription
+            .subscribe(mailbox.clone(), &room.secret_key)
+            .await
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+        subscribed.rooms.insert(mailbox, room_id);
+        added += 1;
+    }
+    Ok(SubscriptionState::Current(added))
+}
+
+/// Apply messages pushed to a room's mailbox, removing the handled ones from
+/// the mailbox, acknowledging delivery unless the user opted out and raising
+/// notifications through `sink`. Returns the number applied.
+pub async fn receive_pushed_messages<T: MessageTransport, S: NotificationSink>(
+    transport: &T,
+    sink: &S,
+    room_id: String,
+    messages: Vec<EncryptedMessage>,
+) -> Result<usize, LocalApiError> {
+    let db = profile_database()?;
+    let user_data = active_user_data()?;
+    let settings = NotificationSettings::from_user_data(&user_data.clone().unwrap_or_default());
+
+    let received =
+        apply_messages(&db, &room_id, messages).map_err(|e| LocalApiError::new(e.to_string()))?;
+    acknowledge_handled(transport, &db, &room_id, &received).await;
+    acknowledge_delivery(transport, &db, &room_id, &received, user_data.as_ref()).await?;
+
+    let room = db
+        .load_entity::<Room>(&room_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .ok_or_else(|| LocalApiError::new(format!("Room not found: {room_id}")))?;
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    let notifications = room_notifications(&db, &room, &received, &contacts, &settings)?;
+    deliver(sink, &notifications, &settings).map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(received.applied)
+}
+
+/// Which messages of a room raise notifications
+pub async fn get_room_notification_mode(
+    room_id: String,
+) -> Result<NotificationMode, LocalApiError> {
+    let db = profile_database()?;
+    room_notification_mode(&db, &room_id).map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Change which messages of a room raise notifications
+pub async fn set_room_notification_mode(
+    room_id: String,
+    mode: NotificationMode,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    save_notification_mode(&db, &room_id, mode).map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Mark a room's messages as read, sending read receipts unless the user
+/// opted out. Returns the number of messages marked.
+pub async fn mark_room_read<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+) -> Result<usize, LocalApiError> {
+    let db = profile_database()?;
+    let marked = mark_read(&db, &room_id).map_err(|e| LocalApiError::new(e.to_string()))?;
+    let count = marked.len();
+
+    let send_receipts = active_user_data()?.is_none_or(|data| data.send_read_receipts);
+    if send_receipts {
+        send_receipt(transport, &db, &room_id, marked, ReceiptKind::Read)
+            .await
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+    Ok(count)
+}
+
+/// Tell the room we started or stopped typing, unless the user opted out
+pub async fn send_typing<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    active: bool,
+) -> Result<(), LocalApiError> {
+    if active_user_data()?.is_some_and(|data| !data.send_typing_indicators) {
+        return Ok(());
+    }
+    let db = profile_database()?;
+    send_payload(transport, &db, &room_id, &MessagePayload::typing(active))
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Our current presence. It is announced to every room when it changed or
+/// is due to be repeated, so call this periodically while the app runs.
+pub async fn update_presence<T: MessageTransport>(
+    transport: &T,
+) -> Result<PresenceState, LocalApiError> {
+    let (chosen, auto_away_minutes) = match active_user_data()? {
+        Some(user_data) => (user_data.chosen_presence, user_data.auto_away_minutes),
+        None => (None, UserData::default().auto_away_minutes),
+    };
+    let db = profile_database()?;
+    refresh_presence(transport, &db, chosen, auto_away_minutes)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Presence picked by the user, `None` when it follows the idle timer
+pub async fn get_chosen_presence() -> Result<Option<PresenceState>, LocalApiError> {
+    Ok(active_user_data()?.and_then(|user_data| user_data.chosen_presence))
+}
+
+/// Pick a presence, or follow the idle timer again with `None`
+pub async fn choose_presence<T: MessageTransport>(
+    transport: &T,
+    presence: Option<PresenceState>,
+) -> Result<PresenceState, LocalApiError> {
+    let db = Database::new();
+    let mut user_data = require_active_user_data()?;
+    user_data.set_chosen_presence(presence);
+    db.update_entity(&user_data)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    update_presence(transport).await
+}
+
+/// Display names of the members currently typing in a room
+pub async fn get_typing_members(room_id: String) -> Result<Vec<String>, LocalApiError> {
+    let db = profile_database()?;
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .as_secs();
+
+    let members =
+        typing_members(&db, &room_id, now).map_err(|e| LocalApiError::new(e.to_string()))?;
+    Ok(members
+        .iter()
+        .map(
+            |public| match Contact::find_by_public_key(&contacts, public) {
+                Some(contact) => contact.display_name(),
+                None => hex::encode(&public[..4]),
+            },
+        )
+        .collect())
+}
+
+/// Seconds messages of a room are kept, `None` when they do not disappear
+pub async fn get_room_retention(room_id: String) -> Result<Option<u64>, LocalApiError> {
+    let db = profile_database()?;
+    room_retention(&db, &room_id).map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// Set the disappearing messages timer of a room for every member
+pub async fn set_room_retention<T: MessageTransport>(
+    transport: &T,
+    room_id: String,
+    seconds: Option<u64>,
+) -> Result<(), LocalApiError> {
+    let db = profile_database()?;
+    send_retention(transport, &db, &room_id, seconds)
+        .await
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
+/// A room's history as `MessageView` JSON, oldest first
+pub async fn get_room_history(room_id: String) -> Result<Vec<String>, LocalApiError> {
+    let db = profile_database()?;
+    let room = db
+        .load_entity::<Room>(&room_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .ok_or_else(|| LocalApiError::new(format!("Room not found: {room_id}")))?;
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let history =
+        load_room_history(&db, &room_id).map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let own_public = room.public_key_bytes();
+    let mut result = Vec::new();
+    for message in history {
+        result.push(
+            message
+                .to_view(&own_public, &contacts)
+                .to_json()
+                .map_err(|e| LocalApiError::new(e.to_string()))?,
+        );
+    }
+    Ok(result)
+}
+
+/// Search decrypted history of one room, or of every room when no room is
+/// given. Returns `SearchHit` JSON, newest first.
+pub async fn search_messages(
+    room_id: Option<String>,
+    query: String,
+) -> Result<Vec<String>, LocalApiError> {
+    let db = profile_database()?;
+    let rooms = match room_id {
+        Some(room_id) => vec![db
+            .load_entity::<Room>(&room_id)
+            .map_err(|e| LocalApiError::new(e.to_string()))?
+            .ok_or_else(|| LocalApiError::new(format!("Room not found: {room_id}")))?],
+        None => db
+            .load_all_entities::<Room>(Room::key_prefix())
+            .map_err(|e| LocalApiError::new(e.to_string()))?,
+    };
+    let contacts = db
+        .load_all_entities::<Contact>(Contact::key_prefix())
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let hits = search_rooms(&db, &rooms, &contacts, &query)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    hits.iter()
+        .map(|hit| hit.to_json().map_err(|e| LocalApiError::new(e.to_string())))
+        .collect()
+}
+
+// Emoji picker functions (recent emojis are kept in the active profile)
+pub async fn get_recent_emojis() -> Result<Vec<String>, LocalApiError> {
+    match active_user_data()? {
+        Some(user_data) => Ok(user_data.recent_emojis.into_iter().collect()),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub async fn add_recent_emoji(emoji: String) -> Result<(), LocalApiError> {
+    let db = Database::new();
+    let mut user_data = require_active_user_data()?;
+    user_data.add_recent_emoji(emoji);
+    db.update_entity(&user_data)
+        .map_err(|e| LocalApiError::new(e.to_string()))
+}
+
 // User data management functions (local database operations)
 pub async fn create_user_data(
     username: String,
@@ -235,8 +960,26 @@ pub async fn update_user_data(user_data_json: String) -> Result<(), LocalApiErro
     let db = Database::new();
     let user_data =
         UserData::from_json(&user_data_json).map_err(|e| LocalApiError::new(e.to_string()))?;
+    let previous = match &user_data.id {
+        Some(id) => db
+            .load_entity::<UserData>(id)
+            .map_err(|e| LocalApiError::new(e.to_string()))?,
+        None => None,
+    };
     db.update_entity(&user_data)
         .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    // Drop the stored image of a replaced avatar
+    if let (Some(profile_id), Some(replaced)) = (
+        &user_data.id,
+        previous
+            .and_then(|previous| previous.avatar)
+            .filter(|avatar| user_data.avatar.as_ref() != Some(avatar)),
+    ) {
+        let profile_db =
+            Database::for_profile(profile_id).map_err(|e| LocalApiError::new(e.to_string()))?;
+        remove_avatar(&profile_db, &replaced).map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
     Ok(())
 }
 
@@ -281,14 +1024,36 @@ pub async fn find_user_data_by_username(username: String) -> Result<Option<Strin
 }
 
 pub async fn get_current_user_data() -> Result<Option<String>, LocalApiError> {
-    // For now, we'll just get the first user data entry
-    // Eventually I would allow multiple profiles and track the current user session
     let db = Database::new();
+    let session = ProfileSession::load(&db).map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    if let Some(profile_id) = session.active_profile_id {
+        if let Some(mut user_data) = db
+            .load_entity::<UserData>(&profile_id)
+            .map_err(|e| LocalApiError::new(e.to_string()))?
+        {
+            // Profiles created before identity keys existed get one now
+            if user_data.ensure_identity_key() {
+                db.update_entity(&user_data)
+                    .map_err(|e| LocalApiError::new(e.to_string()))?;
+            }
+            return Ok(Some(
+                user_data
+                    .to_json()
+                    .map_err(|e| LocalApiError::new(e.to_string()))?,
+            ));
+        }
+    }
+
+    // No usable session, fall back to the first profile and remember it
     let user_data_list = db
         .load_all_entities::<UserData>(UserData::key_prefix())
         .map_err(|e| LocalApiError::new(e.to_string()))?;
 
     if let Some(user_data) = user_data_list.into_iter().next() {
+        if let Some(profile_id) = user_data.id.clone() {
+            select_profile(profile_id).await?;
+        }
         Ok(Some(
             user_data
                 .to_json()
@@ -298,3 +1063,76 @@ pub async fn get_current_user_data() -> Result<Option<String>, LocalApiError> {
         Ok(None)
     }
 }
+
+// Profile management functions (local database operations)
+pub async fn create_profile(
+    username: String,
+    display_name: String,
+) -> Result<String, LocalApiError> {
+    let db = Database::new();
+    let mut user_data = UserData::new(&username, &display_name);
+    let profile_id = db
+        .save_entity(&mut user_data)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    // The first profile becomes active automatically
+    if get_active_profile_id().await?.is_none() {
+        select_profile(profile_id.clone()).await?;
+    }
+
+    Ok(profile_id)
+}
+
+pub async fn get_active_profile_id() -> Result<Option<String>, LocalApiError> {
+    active_profile_id()
+}
+
+pub async fn select_profile(profile_id: String) -> Result<(), LocalApiError> {
+    let db = Database::new();
+    if db
+        .load_entity::<UserData>(&profile_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?
+        .is_none()
+    {
+        return Err(LocalApiError::new(format!(
+            "Profile not found: {profile_id}"
+        )));
+    }
+
+    let mut session = ProfileSession::load(&db).map_err(|e| LocalApiError::new(e.to_string()))?;
+    let first_selection = session.active_profile_id.is_none();
+    session.set_active_profile(Some(profile_id.clone()));
+    session
+        .save(&db)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    // Data created before any profile existed belongs to the first profile selected
+    if first_selection {
+        adopt_unscoped_entities(&profile_id).map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_profile(profile_id: String) -> Result<(), LocalApiError> {
+    let db = Database::new();
+    db.delete::<UserData>(&profile_id)
+        .map_err(|e| LocalApiError::new(e.to_string()))?;
+    Database::drop_profile(&profile_id).map_err(|e| LocalApiError::new(e.to_string()))?;
+
+    let mut session = ProfileSession::load(&db).map_err(|e| LocalApiError::new(e.to_string()))?;
+    if session.active_profile_id.as_deref() == Some(profile_id.as_str()) {
+        // Switch to any remaining profile
+        let next_profile = db
+            .load_all_entities::<UserData>(UserData::key_prefix())
+            .map_err(|e| LocalApiError::new(e.to_string()))?
+            .into_iter()
+            .find_map(|user_data| user_data.id);
+        session.set_active_profile(next_profile);
+        session
+            .save(&db)
+            .map_err(|e| LocalApiError::new(e.to_string()))?;
+    }
+
+    Ok(())
+}
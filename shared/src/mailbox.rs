@@ -0,0 +1,239 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Proof of mailbox ownership for the relay.
+//! A mailbox is addressed by the hash of a room public key, so knowing the
+//! address is not enough to read it. Before a fetch the relay issues a
+//! single-use challenge together with a fresh X25519 key, and the client
+//! answers by boxing the challenge from the room's secret key to that key.
+//! Only the holder of the room key can produce a box the relay opens.
+//! A sender retracts a waiting message with a secret whose hash was
+//! deposited along with it.
+
+use crypto_box::{
+    aead::{rand_core::RngCore, Aead, AeadCore, OsRng},
+    ChaChaBox, PublicKey, SecretKey,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+
+/// Size of the random challenge in bytes
+pub const CHALLENGE_LEN: usize = 32;
+
+/// Binds a proof to this protocol, so no other box can stand in for it
+const PROOF_CONTEXT: &[u8] = b"meeseeks-nuntius mailbox proof v1";
+
+/// Binds a retraction hash to this protocol
+const RETRACT_CONTEXT: &[u8] = b"meeseeks-nuntius retract v1";
+
+/// Size of the secret that retracts a deposited message in bytes
+pub const RETRACT_SECRET_LEN: usize = 32;
+
+/// Mailbox address of a room public key on the relay
+pub fn recipient_hash(public_key: &[u8; 32]) -> String {
+    hex::encode(Sha256::digest(public_key))
+}
+
+/// A challenge the relay issues before a mailbox may be fetched
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MailboxChallenge {
+    pub id: String,
+    pub recipient_hash: String,
+    /// Public half of the key pair the relay made for this challenge
+    pub server_public_key: Vec<u8>,
+    pub challenge: Vec<u8>,
+}
+
+/// A client's answer to a `MailboxChallenge`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MailboxProof {
+    pub challenge_id: String,
+    /// Room public key the mailbox address is derived from
+    pub public_key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Why a mailbox proof was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofError {
+    /// Keys, nonce or challenge have the wrong length
+    Malformed,
+    /// The key does not hash to the challenged mailbox
+    WrongMailbox,
+    /// The proof answers another challenge or was not made with the room key
+    Invalid,
+    /// The challenge is unknown, expired or was answered before
+    UnknownChallenge,
+}
+
+impl std::fmt::Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::Malformed => write!(f, "Malformed mailbox proof"),
+            ProofError::WrongMailbox => write!(f, "Proof key does not own the mailbox"),
+            ProofError::Invalid => write!(f, "Invalid mailbox proof"),
+            ProofError::UnknownChallenge => write!(f, "Unknown or spent mailbox challenge"),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+/// Create a challenge for a mailbox.
+/// Returns the secret key the relay must keep to verify the answer.
+pub fn issue_challenge(id: String, recipient_hash: String) -> (MailboxChallenge, [u8; 32]) {
+    let server_secret = SecretKey::generate(&mut OsRng);
+    let mut challenge = vec![0u8; CHALLENGE_LEN];
+    OsRng.fill_bytes(&mut challenge);
+
+    let issued = MailboxChallenge {
+        id,
+        recipient_hash,
+        server_public_key: server_secret.public_key().as_bytes().to_vec(),
+        challenge,
+    };
+    (issued, server_secret.to_bytes())
+}
+
+/// A fresh secret for retracting one deposited message.
+/// The relay keeps only its `retract_hash`, so nobody else can retract it.
+pub fn generate_retract_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; RETRACT_SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// What the relay keeps of a retraction secret
+pub fn retract_hash(secret: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(RETRACT_CONTEXT);
+    hasher.update(secret);
+    hasher.finalize().to_vec()
+}
+
+/// What the client boxes: the challenge with its ID and mailbox
+fn proof_plaintext(challenge: &MailboxChallenge) -> Vec<u8> {
+    let mut plaintext = PROOF_CONTEXT.to_vec();
+    plaintext.extend_from_slice(challenge.id.as_bytes());
+    plaintext.push(0);
+    plaintext.extend_from_slice(challenge.recipient_hash.as_bytes());
+    plaintext.extend_from_slice(&challenge.challenge);
+    plaintext
+}
+
+/// Answer a challenge with the secret key of the room owning the mailbox
+pub fn prove_ownership(
+    secret_key: &[u8; 32],
+    challenge: &MailboxChallenge,
+) -> Result<MailboxProof, ProofError> {
+    let secret_key = SecretKey::from_bytes(*secret_key);
+    let public_key = secret_key.public_key();
+    if recipient_hash(public_key.as_bytes()) != challenge.recipient_hash {
+        return Err(ProofError::WrongMailbox);
+    }
+    let server_public = <[u8; 32]>::try_from(challenge.server_public_key.as_slice())
+        .map_err(|_| ProofError::Malformed)?;
+
+    let crypto_box = ChaChaBox::new(&PublicKey::from(server_public), &secret_key);
+    let nonce = ChaChaBox::generate_nonce(&mut OsRng);
+    let ciphertext = crypto_box
+        .encrypt(&nonce, proof_plaintext(challenge).as_slice())
+        .map_err(|_| ProofError::Malformed)?;
+
+    Ok(MailboxProof {
+        challenge_id: challenge.id.clone(),
+        public_key: public_key.as_bytes().to_vec(),
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+/// Check a proof against the challenge it answers and the relay's secret
+/// key for it. The relay must discard the challenge whatever the outcome,
+/// so a proof can never be replayed.
+pub fn verify_ownership(
+    challenge: &MailboxChallenge,
+    server_secret: &[u8; 32],
+    proof: &MailboxProof,
+) -> Result<(), ProofError> {
+    if proof.challenge_id != challenge.id {
+        return Err(ProofError::Invalid);
+    }
+    let public_key =
+        <[u8; 32]>::try_from(proof.public_key.as_slice()).map_err(|_| ProofError::Malformed)?;
+    let nonce = <[u8; 24]>::try_from(proof.nonce.as_slice()).map_err(|_| ProofError::Malformed)?;
+    if challenge.challenge.len() != CHALLENGE_LEN {
+        return Err(ProofError::Malformed);
+    }
+    if recipient_hash(&public_key) != challenge.recipient_hash {
+        return Err(ProofError::WrongMailbox);
+    }
+
+    let crypto_box = ChaChaBox::new(
+        &PublicKey::from(public_key),
+        &SecretKey::from_bytes(*server_secret),
+    );
+    let plaintext = crypto_box
+        .decrypt(&nonce.into(), proof.ciphertext.as_slice())
+        .map_err(|_| ProofError::Invalid)?;
+
+    if plaintext == proof_plaintext(challenge) {
+        Ok(())
+    } else {
+        Err(ProofError::Invalid)
+    }
+}
+
+/// Challenges the relay issued and has not seen answered yet
+pub trait ChallengeStore {
+    type Error;
+
+    /// Remove the unexpired challenge `id` issued for `recipient_hash` and
+    /// return it with the relay's secret key for it
+    fn take(
+        &self,
+        id: &str,
+        recipient_hash: &str,
+    ) -> impl Future<Output = Result<Option<(MailboxChallenge, [u8; 32])>, Self::Error>>;
+}
+
+/// Why a proof could not be redeemed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedeemError<E> {
+    /// The challenge could not be looked up
+    Store(E),
+    /// The proof does not answer a live challenge
+    Rejected(ProofError),
+}
+
+/// Check a proof for the mailbox `recipient_hash`, taking the challenge it
+/// answers out of `store` first. Any attempt spends the challenge, so a
+/// proof is accepted at most once.
+pub async fn redeem_proof<S: ChallengeStore>(
+    store: &S,
+    recipient_hash: &str,
+    proof: &MailboxProof,
+) -> Result<(), RedeemError<S::Error>> {
+    let (challenge, server_secret) = store
+        .take(&proof.challenge_id, recipient_hash)
+        .await
+        .map_err(RedeemError::Store)?
+        .ok_or(RedeemError::Rejected(ProofError::UnknownChallenge))?;
+    verify_ownership(&challenge, &server_secret, proof).map_err(RedeemError::Rejected)
+}
@@ -0,0 +1,435 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sending and receiving room messages through the relay.
+//! Each message is encrypted separately for every known contact of a room
+//! and deposited in the mailbox addressed by the contact's room key hash.
+//! The sender remembers where each copy went, so a deleted message can be
+//! retracted from mailboxes that have not been fetched yet.
+//! Receipts, typing indicators and presence are sent flagged as ephemeral,
+//! so the relay drops them after a short time instead of keeping them like
+//! messages.
+//! Messages sent to a room with a retention timer carry it, and the relay
+//! expires their mailbox copies to match.
+//! Contacts whose home relay is another server get their copies deposited
+//! with that relay named, and our relay forwards them there.
+
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::message::{Contact, EncryptedMessage, MessagePayload, ReceiptKind, Room};
+use crate::federation::normalize_relay_url;
+use crate::history::{apply_payload, Rejection, StoredMessage};
+pub use crate::mailbox::recipient_hash;
+use crate::mailbox::{generate_retract_secret, retract_hash};
+use crate::persistence::database::{Database, Entity};
+use crate::retention::{room_retention, validate_retention};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Relay mailbox storage used to exchange encrypted messages
+pub trait MessageTransport {
+    /// Deposit a message, for the recipient's home `relay` if it is not ours
+    fn deposit(
+        &self,
+        recipient_hash: String,
+        message: EncryptedMessage,
+        relay: Option<String>,
+    ) -> impl Future<Output = Result<()>>;
+
+    /// Fetch a mailbox, proving ownership with the room's secret key.
+    /// Messages carry their mailbox entry ID in `id` and stay in the mailbox
+    /// until acknowledged.
+    fn fetch(
+        &self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+    ) -> impl Future<Output = Result<Vec<EncryptedMessage>>>;
+
+    /// Remove handled messages from a mailbox by their mailbox entry IDs
+    fn acknowledge(
+        &self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+        ids: Vec<String>,
+    ) -> impl Future<Output = Result<()>>;
+
+    /// Remove a deposited message that has not been fetched yet, proving it
+    /// is ours with the secret whose hash was deposited with it
+    fn retract(
+        &self,
+        recipient_hash: String,
+        nonce: Vec<u8>,
+        retract_secret: Vec<u8>,
+    ) -> impl Future<Output = Result<()>>;
+}
+
+/// Live delivery from the relay, pushing messages as they are deposited
+pub trait MessageSubscription {
+    /// Have a mailbox pushed, proving ownership with the room's secret key
+    fn subscribe(
+        &mut self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+    ) -> impl Future<Output = Result<()>>;
+
+    /// Wait for messages pushed to a subscribed mailbox.
+    /// Fails once the connection is lost.
+    fn next(&mut self) -> impl Future<Output = Result<(String, Vec<EncryptedMessage>)>>;
+}
+
+/// Relay mailbox copy of a sent message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Deposit {
+    pub recipient_hash: String,
+    pub nonce: Vec<u8>,
+    /// Secret the relay asks for to retract the copy
+    #[serde(default)]
+    pub retract_secret: Vec<u8>,
+}
+
+/// Where the copies of one of our messages were deposited
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SentMessage {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub deposits: Vec<Deposit>,
+}
+
+impl SentMessage {
+    /// Storage key of the deposits of a message within a room
+    pub fn key_for(room_id: &str, message_id: &str) -> String {
+        format!("{}:{room_id}:{message_id}", Self::key_prefix())
+    }
+}
+
+impl Entity for SentMessage {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "sent_message"
+    }
+}
+
+/// URL of the relay this client talks to
+pub fn home_relay() -> Option<String> {
+    std::env::var("DIOXUS_SERVER_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+}
+
+/// The home relay of the contact owning `room_public`, if it is not ours
+fn contact_relay(contacts: &[Contact], room_public: &[u8; 32]) -> Option<String> {
+    let relay = Contact::find_by_public_key(contacts, room_public)?
+        .relay
+        .clone()?;
+    let is_home =
+        home_relay().is_some_and(|home| normalize_relay_url(&home) == normalize_relay_url(&relay));
+    (!is_home).then_some(relay)
+}
+
+fn load_room(db: &Database, room_id: &str) -> Result<Room> {
+    db.load_entity::<Room>(room_id)?
+        .ok_or_else(|| format!("Room not found: {room_id}").into())
+}
+
+/// Encrypt a payload for every known contact of a room, deposit it on the
+/// relay and apply it to the local history.
+/// Messages pick up the room's retention timer, if one is set.
+pub async fn send_payload<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    payload: &MessagePayload,
+) -> Result<()> {
+    let mut room = load_room(db, room_id)?;
+    let own_public = room.public_key_bytes();
+
+    let mut payload = payload.clone();
+    if !payload.is_control() {
+        payload.expires_in = room_retention(db, room_id)?;
+    }
+    let payload = &payload;
+
+    let contacts = db.load_all_entities::<Contact>(Contact::key_prefix())?;
+    let mut deposits = Vec::new();
+    for contact_public in room.known_contacts() {
+        let mut message = room.encrypt_payload_for(&contact_public, payload)?;
+        let deposit = Deposit {
+            recipient_hash: recipient_hash(&contact_public.to_bytes()),
+            nonce: message.nonce.clone(),
+            retract_secret: generate_retract_secret(),
+        };
+        if !payload.is_control() {
+            message.retract_hash = Some(retract_hash(&deposit.retract_secret));
+        }
+        let relay = contact_relay(&contacts, &contact_public.to_bytes());
+        transport
+            .deposit(deposit.recipient_hash.clone(), message, relay)
+            .await?;
+        deposits.push(deposit);
+    }
+
+    // Control messages are never retracted, so only content is tracked
+    if !payload.is_control() {
+        let mut sent = SentMessage {
+            id: Some(SentMessage::key_for(room_id, &payload.message_id)),
+            deposits,
+        };
+        db.save_entity(&mut sent)?;
+    }
+
+    // Ephemeral state is about us, so there is nothing to apply locally
+    if !payload.is_ephemeral() {
+        apply_payload(db, room_id, own_public, own_public, payload)?;
+    }
+    Ok(())
+}
+
+/// Prove the identity bound to a room's key to each of its known contacts.
+/// The proof is addressed to one contact's key, so each gets its own payload.
+pub async fn share_identity<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+) -> Result<()> {
+    let mut room = load_room(db, room_id)?;
+    let Some(binding) = room.identity_binding.clone() else {
+        return Ok(());
+    };
+
+    let contacts = db.load_all_entities::<Contact>(Contact::key_prefix())?;
+    for contact_public in room.known_contacts() {
+        let proof = room.prove_binding(&binding, &contact_public)?;
+        let payload = MessagePayload::identity(binding.clone(), proof);
+        let message = room.encrypt_payload_for(&contact_public, &payload)?;
+        let relay = contact_relay(&contacts, &contact_public.to_bytes());
+        transport
+            .deposit(recipient_hash(&contact_public.to_bytes()), message, relay)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Check that a message exists in the room and was sent by us
+fn require_own_message(db: &Database, room_id: &str, message_id: &str) -> Result<StoredMessage> {
+    let message = db
+        .load_entity::<StoredMessage>(&StoredMessage::key_for(room_id, message_id))?
+        .ok_or_else(|| format!("Message not found: {message_id}"))?;
+    if !message.is_own {
+        return Err("Only the sender may change a message".into());
+    }
+    if message.is_deleted() {
+        return Err("Message was deleted".into());
+    }
+    Ok(message)
+}
+
+/// Replace the text of one of our messages for everyone
+pub async fn send_edit<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    message_id: &str,
+    text: &str,
+) -> Result<()> {
+    require_own_message(db, room_id, message_id)?;
+    send_payload(
+        transport,
+        db,
+        room_id,
+        &MessagePayload::edit(message_id, text),
+    )
+    .await
+}
+
+/// Delete one of our messages for everyone and retract any copies still
+/// waiting in relay mailboxes. Retraction is best effort: copies that were
+/// already fetched are removed by the recipients applying the delete.
+pub async fn send_delete<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    message_id: &str,
+) -> Result<()> {
+    require_own_message(db, room_id, message_id)?;
+    send_payload(transport, db, room_id, &MessagePayload::delete(message_id)).await?;
+
+    let key = SentMessage::key_for(room_id, message_id);
+    if let Some(sent) = db.load_entity::<SentMessage>(&key)? {
+        for deposit in sent.deposits {
+            if let Err(e) = transport
+                .retract(
+                    deposit.recipient_hash,
+                    deposit.nonce,
+                    deposit.retract_secret,
+                )
+                .await
+            {
+                eprintln!("Failed to retract message copy: {e}");
+            }
+        }
+        db.delete::<SentMessage>(&key)?;
+    }
+    Ok(())
+}
+
+/// Outcome of fetching a room's mailbox
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReceivedMessages {
+    /// Number of messages that were added or changed
+    pub applied: usize,
+    /// IDs of new messages from other members, to acknowledge delivery of
+    pub new_message_ids: Vec<String>,
+    /// Mailbox entry IDs of the messages applied or unreadable, which can
+    /// be removed from the relay
+    pub handled_ids: Vec<String>,
+}
+
+/// Fetch a room's mailbox, apply every message that decrypts and remove the
+/// handled ones from the mailbox
+pub async fn receive_messages<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+) -> Result<ReceivedMessages> {
+    let room = load_room(db, room_id)?;
+    let messages = transport
+        .fetch(recipient_hash(&room.public_key_bytes()), &room.secret_key)
+        .await?;
+    let received = apply_messages(db, room_id, messages)?;
+    acknowledge_handled(transport, db, room_id, &received).await;
+    Ok(received)
+}
+
+/// Remove the messages handled in `received` from the room's mailbox.
+/// Messages left behind are fetched again and ignored as already applied,
+/// so a failure is only logged.
+pub async fn acknowledge_handled<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    received: &ReceivedMessages,
+) {
+    if received.handled_ids.is_empty() {
+        return;
+    }
+    let result = match load_room(db, room_id) {
+        Ok(room) => {
+            transport
+                .acknowledge(
+                    recipient_hash(&room.public_key_bytes()),
+                    &room.secret_key,
+                    received.handled_ids.clone(),
+                )
+                .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to acknowledge messages of room {room_id}: {e}");
+    }
+}
+
+/// Apply every message of a room's mailbox that decrypts.
+/// Messages that fail to apply are left out of `handled_ids`, so they stay
+/// on the relay and are tried again, unless they were rejected for good.
+pub fn apply_messages(
+    db: &Database,
+    room_id: &str,
+    messages: Vec<EncryptedMessage>,
+) -> Result<ReceivedMessages> {
+    let mut room = load_room(db, room_id)?;
+    let own_public = room.public_key_bytes();
+
+    let mut received = ReceivedMessages::default();
+    for message in messages {
+        let payload = match room.decrypt_payload_from(&message) {
+            Ok(payload) => payload,
+            Err(e) => {
+                eprintln!("Dropping undecryptable message: {e}");
+                received.handled_ids.extend(message.id);
+                continue;
+            }
+        };
+
+        match apply_payload(
+            db,
+            room_id,
+            message.sender_public_bytes(),
+            own_public,
+            &payload,
+        ) {
+            Ok(Some(message_id)) => {
+                received.applied += 1;
+                if !payload.is_control() {
+                    received.new_message_ids.push(message_id);
+                }
+                received.handled_ids.extend(message.id);
+            }
+            Ok(None) => received.handled_ids.extend(message.id),
+            Err(e) if e.is::<Rejection>() => {
+                eprintln!("Dropping rejected message: {e}");
+                received.handled_ids.extend(message.id);
+            }
+            Err(e) => eprintln!("Skipping message that failed to apply: {e}"),
+        }
+    }
+
+    // Decrypting registers new senders as known contacts
+    db.update_entity(&room)?;
+    Ok(received)
+}
+
+/// Set a room's disappearing messages timer for every member
+pub async fn send_retention<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    seconds: Option<u64>,
+) -> Result<()> {
+    validate_retention(seconds)?;
+    send_payload(transport, db, room_id, &MessagePayload::retention(seconds)).await
+}
+
+/// Tell the other members of a room that we received or read their messages
+pub async fn send_receipt<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    room_id: &str,
+    message_ids: Vec<String>,
+    kind: ReceiptKind,
+) -> Result<()> {
+    if message_ids.is_empty() {
+        return Ok(());
+    }
+    send_payload(
+        transport,
+        db,
+        room_id,
+        &MessagePayload::receipt(message_ids, kind),
+    )
+    .await
+}
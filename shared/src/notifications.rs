@@ -0,0 +1,271 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Notifications for new messages.
+//! Which messages raise a notification depends on the user's settings and
+//! each room's rule: every message, only messages mentioning the user, or
+//! none. Notifications are handed to a `NotificationSink`, so the platform
+//! decides how to show them and tests can record them instead.
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::message::{Contact, PresenceState, Room};
+use crate::history::StoredMessage;
+use crate::persistence::database::{Database, Entity};
+use crate::user_data::UserData;
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Longest message preview shown in a notification, in characters
+const MAX_PREVIEW_CHARS: usize = 120;
+
+/// Which messages of a room raise notifications
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationMode {
+    #[default]
+    All,
+    MentionsOnly,
+    Muted,
+}
+
+impl NotificationMode {
+    pub const ALL: [NotificationMode; 3] = [
+        NotificationMode::All,
+        NotificationMode::MentionsOnly,
+        NotificationMode::Muted,
+    ];
+
+    /// Name used in serialized form and translation keys
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::MentionsOnly => "mentions_only",
+            Self::Muted => "muted",
+        }
+    }
+
+    /// Parse the name returned by `as_str`
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.as_str() == value)
+    }
+}
+
+/// Notification rule of one room. Kept on this device only.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomNotifications {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub room_id: String,
+    pub mode: NotificationMode,
+}
+
+impl RoomNotifications {
+    /// Storage key of a room's notification rule
+    pub fn key_for(room_id: &str) -> String {
+        format!("{}:{room_id}", Self::key_prefix())
+    }
+}
+
+impl Entity for RoomNotifications {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "room_notifications"
+    }
+}
+
+/// Notification rule of a room
+pub fn room_notification_mode(db: &Database, room_id: &str) -> Result<NotificationMode> {
+    Ok(db
+        .load_entity::<RoomNotifications>(&RoomNotifications::key_for(room_id))?
+        .map(|rule| rule.mode)
+        .unwrap_or_default())
+}
+
+/// Change the notification rule of a room
+pub fn save_notification_mode(db: &Database, room_id: &str, mode: NotificationMode) -> Result<()> {
+    let mut rule = RoomNotifications {
+        id: Some(RoomNotifications::key_for(room_id)),
+        room_id: room_id.to_string(),
+        mode,
+    };
+    db.save_entity(&mut rule)?;
+    Ok(())
+}
+
+/// A new message to tell the user about
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub room_id: String,
+    pub room_name: String,
+    pub message_id: String,
+    pub sender_name: String,
+    /// Start of the message, `None` when previews are hidden
+    pub preview: Option<String>,
+    pub mentions_user: bool,
+}
+
+/// Shows notifications on a platform
+pub trait NotificationSink {
+    fn show(&self, notification: &Notification) -> Result<()>;
+
+    fn play_sound(&self) -> Result<()>;
+}
+
+/// Sink for platforms without notifications, everything is dropped
+pub struct SilentNotifications;
+
+impl NotificationSink for SilentNotifications {
+    fn show(&self, _notification: &Notification) -> Result<()> {
+        Ok(())
+    }
+
+    fn play_sound(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The user's notification preferences
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub sound: bool,
+    pub previews: bool,
+    /// Names that mention the user when written as `@name`
+    pub mention_names: Vec<String>,
+}
+
+impl NotificationSettings {
+    /// Preferences of a profile. Choosing do not disturb silences everything.
+    pub fn from_user_data(user_data: &UserData) -> Self {
+        let do_not_disturb = user_data.chosen_presence == Some(PresenceState::DoNotDisturb);
+        let mut mention_names = vec![user_data.username.clone()];
+        if !user_data.display_name.is_empty() && user_data.display_name != user_data.username {
+            mention_names.push(user_data.display_name.clone());
+        }
+        Self {
+            enabled: user_data.notifications_enabled && !do_not_disturb,
+            sound: user_data.sound_enabled,
+            previews: user_data.show_message_previews,
+            mention_names,
+        }
+    }
+}
+
+/// Check whether a text mentions one of the names as `@name`
+pub fn mentions(text: &str, names: &[String]) -> bool {
+    let text = text.to_lowercase();
+    names
+        .iter()
+        .map(|name| format!("@{}", name.to_lowercase()))
+        .filter(|mention| mention.len() > 1)
+        .any(|mention| {
+            text.match_indices(&mention).any(|(index, _)| {
+                let starts_word = text[..index]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c| !c.is_alphanumeric());
+                let ends_word = text[index + mention.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|c| !c.is_alphanumeric());
+                starts_word && ends_word
+            })
+        })
+}
+
+/// Text shown for a message in a notification
+fn preview(message: &StoredMessage) -> String {
+    let text = if message.text.trim().is_empty() {
+        message
+            .attachments
+            .iter()
+            .map(|attachment| attachment.file_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        message.text.trim().to_string()
+    };
+
+    match text.char_indices().nth(MAX_PREVIEW_CHARS) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text,
+    }
+}
+
+/// Notifications due for new messages of a room
+pub fn notifications_for(
+    db: &Database,
+    room: &Room,
+    messages: &[StoredMessage],
+    contacts: &[Contact],
+    settings: &NotificationSettings,
+) -> Result<Vec<Notification>> {
+    let Some(room_id) = room.id() else {
+        return Ok(Vec::new());
+    };
+    if !settings.enabled {
+        return Ok(Vec::new());
+    }
+    let mode = room_notification_mode(db, room_id)?;
+    if mode == NotificationMode::Muted {
+        return Ok(Vec::new());
+    }
+
+    let mut notifications = Vec::new();
+    for message in messages {
+        if message.is_own || message.is_deleted() {
+            continue;
+        }
+        let mentions_user = mentions(&message.text, &settings.mention_names);
+        if mode == NotificationMode::MentionsOnly && !mentions_user {
+            continue;
+        }
+        notifications.push(Notification {
+            room_id: room_id.to_string(),
+            room_name: room.name.clone(),
+            message_id: message.message_id.clone(),
+            sender_name: message.sender_name(contacts),
+            preview: settings.previews.then(|| preview(message)),
+            mentions_user,
+        });
+    }
+    Ok(notifications)
+}
+
+/// Show notifications, with a single sound for the batch when enabled
+pub fn deliver<S: NotificationSink>(
+    sink: &S,
+    notifications: &[Notification],
+    settings: &NotificationSettings,
+) -> Result<()> {
+    for notification in notifications {
+        sink.show(notification)?;
+    }
+    if settings.sound && !notifications.is_empty() {
+        sink.play_sound()?;
+    }
+    Ok(())
+}
@@ -16,7 +16,7 @@
  */
 
 use serde::{Deserialize, Serialize};
-use sled::Db;
+use sled::{Db, Tree};
 use std::path::PathBuf;
 use std::sync::LazyLock;
 
@@ -30,6 +30,7 @@ pub trait Entity: Serialize + for<'de> Deserialize<'de> {
 
 pub struct Database {
     db: &'static Db,
+    tree: Tree,
 }
 
 fn get_database_path() -> PathBuf {
@@ -70,6 +71,10 @@ static DATABASE: LazyLock<Db> = LazyLock::new(|| {
     sled::open(path).expect("Failed to open database")
 });
 
+fn profile_tree_name(profile_id: &str) -> String {
+    format!("profile:{profile_id}")
+}
+
 impl Default for Database {
     fn default() -> Self {
         Self::new()
@@ -78,11 +83,30 @@ impl Default for Database {
 
 impl Database {
     pub fn new() -> Self {
-        Database { db: &DATABASE }
+        Database {
+            db: &DATABASE,
+            tree: (**DATABASE).clone(),
+        }
+    }
+
+    /// Open a database scoped to a single profile.
+    /// Each profile is stored in its own sled tree so rooms, contacts and
+    /// messages are isolated between profiles.
+    pub fn for_profile(profile_id: &str) -> Result<Self, sled::Error> {
+        let tree = DATABASE.open_tree(profile_tree_name(profile_id))?;
+        Ok(Database {
+            db: &DATABASE,
+            tree,
+        })
+    }
+
+    /// Remove all data stored for a profile
+    pub fn drop_profile(profile_id: &str) -> Result<bool, sled::Error> {
+        DATABASE.drop_tree(profile_tree_name(profile_id))
     }
 
     pub fn clear(&self) -> std::result::Result<(), sled::Error> {
-        self.db.clear()
+        self.tree.clear()
     }
 
     fn generate_unique_key(&self, prefix: &str) -> Result<String, Box<dyn std::error::Error>> {
@@ -98,7 +122,7 @@ impl Database {
     }
 
     pub fn flush(&self) -> Result<usize, sled::Error> {
-        self.db.flush()
+        self.tree.flush()
     }
 
     // Save entity - ensures ID consistency
@@ -115,10 +139,10 @@ impl Database {
         };
 
         let json = serde_json::to_vec(&entity)?;
-        self.db.insert(&key, json)?;
+        self.tree.insert(&key, json)?;
 
         // Force flush to disk for mobile persistence
-        self.db.flush()?;
+        self.tree.flush()?;
 
         Ok(key)
     }
@@ -128,7 +152,7 @@ impl Database {
         &self,
         key: &str,
     ) -> Result<Option<T>, Box<dyn std::error::Error>> {
-        if let Some(bytes) = self.db.get(key)? {
+        if let Some(bytes) = self.tree.get(key)? {
             let entity: T = serde_json::from_slice(&bytes)?;
 
             // Optional: Validate that stored ID matches the key
@@ -148,10 +172,10 @@ impl Database {
     pub fn update_entity<T: Entity>(&self, entity: &T) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(id) = entity.id() {
             let json = serde_json::to_vec(entity)?;
-            self.db.insert(id, json)?;
+            self.tree.insert(id, json)?;
 
             // Force flush to disk for mobile persistence
-            self.db.flush()?;
+            self.tree.flush()?;
 
             Ok(())
         } else {
@@ -166,7 +190,7 @@ impl Database {
     ) -> Result<Vec<T>, Box<dyn std::error::Error>> {
         let mut results = Vec::new();
         let prefix_bytes: &[u8] = prefix.as_bytes();
-        self.db.scan_prefix(prefix_bytes).for_each(|row| {
+        self.tree.scan_prefix(prefix_bytes).for_each(|row| {
             let (key, value) = row.unwrap();
             let key_str = String::from_utf8(key.to_vec()).unwrap();
             let entity: T = serde_json::from_slice(&value).unwrap();
@@ -183,12 +207,11 @@ impl Database {
     }
 
     pub fn delete<T: Entity>(&self, key: &str) -> Result<T, Box<dyn std::error::Error>> {
-        let raw = self.db.remove(key)?;
-        if raw.is_some() {
-            let result: T = serde_json::from_slice(&raw.unwrap())?;
+        if let Some(raw) = self.tree.remove(key)? {
+            let result: T = serde_json::from_slice(&raw)?;
 
             // Force flush to disk for mobile persistence
-            self.db.flush()?;
+            self.tree.flush()?;
 
             Ok(result)
         } else {
@@ -196,6 +219,26 @@ impl Database {
         }
     }
 
+    /// Store raw bytes under a key, for binary data too large to keep as JSON
+    pub fn save_bytes(&self, key: &str, bytes: &[u8]) -> Result<(), sled::Error> {
+        self.tree.insert(key, bytes)?;
+
+        // Force flush to disk for mobile persistence
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    /// Load raw bytes stored with `save_bytes`
+    pub fn load_bytes(&self, key: &str) -> Result<Option<Vec<u8>>, sled::Error> {
+        Ok(self.tree.get(key)?.map(|bytes| bytes.to_vec()))
+    }
+
+    /// Remove raw bytes stored with `save_bytes`
+    pub fn delete_bytes(&self, key: &str) -> Result<(), sled::Error> {
+        self.tree.remove(key)?;
+        Ok(())
+    }
+
     /// Find entity by a field value using a predicate function
     pub fn find_entity<T: Entity, F>(
         &self,
@@ -208,7 +251,7 @@ impl Database {
         let prefix_bytes: &[u8] = prefix.as_bytes();
         let mut result = None;
 
-        self.db
+        self.tree
             .scan_prefix(prefix_bytes)
             .try_for_each(|row| -> Result<(), Box<dyn std::error::Error>> {
                 let (_, value) = row?;
@@ -237,7 +280,7 @@ impl Database {
         let mut results = Vec::new();
         let prefix_bytes: &[u8] = prefix.as_bytes();
 
-        self.db.scan_prefix(prefix_bytes).try_for_each(
+        self.tree.scan_prefix(prefix_bytes).try_for_each(
             |row| -> Result<(), Box<dyn std::error::Error>> {
                 let (_, value) = row?;
                 let entity: T = serde_json::from_slice(&value)?;
@@ -251,4 +294,46 @@ impl Database {
 
         Ok(results)
     }
+
+    /// Remove every entry stored under a key prefix.
+    /// Returns the number of entries removed.
+    pub fn delete_prefix(&self, prefix: &str) -> Result<usize, sled::Error> {
+        let mut removed = 0;
+        for row in self.tree.scan_prefix(prefix.as_bytes()) {
+            let (key, _) = row?;
+            self.tree.remove(&key)?;
+            removed += 1;
+        }
+
+        if removed > 0 {
+            // Force flush to disk for mobile persistence
+            self.tree.flush()?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Move every entry stored under a key prefix into another database scope.
+    /// Returns the number of entries moved.
+    pub fn move_prefix_to(
+        &self,
+        prefix: &str,
+        target: &Database,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut moved = 0;
+        for row in self.tree.scan_prefix(prefix.as_bytes()) {
+            let (key, value) = row?;
+            target.tree.insert(&key, value)?;
+            self.tree.remove(&key)?;
+            moved += 1;
+        }
+
+        if moved > 0 {
+            // Force flush to disk for mobile persistence
+            target.tree.flush()?;
+            self.tree.flush()?;
+        }
+
+        Ok(moved)
+    }
 }
@@ -0,0 +1,118 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Proof-of-work challenges that gate anonymous token issuance.
+//! The relay hands out a random nonce with a difficulty and an expiry. The
+//! client searches for a solution whose SHA-256 hash over the nonce, the
+//! request it wants granted and the solution starts with `difficulty` zero
+//! bits. Checking takes one hash, finding takes about `2^difficulty`.
+//! The relay raises the difficulty when tokens are issued faster than it
+//! wants, one bit (twice the work) per doubling of the rate.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Domain separation of proof-of-work hashes
+const POW_CONTEXT: &[u8] = b"meeseeks-nuntius/pow/v1";
+
+/// Most zero bits a challenge can ask for
+pub const MAX_DIFFICULTY: u32 = 64;
+
+/// Attempts between two looks at the clock while solving
+const CLOCK_INTERVAL: u64 = 1 << 16;
+
+/// A proof-of-work challenge issued by the relay
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PowChallenge {
+    pub id: String,
+    /// Random server nonce the work is bound to
+    pub nonce: Vec<u8>,
+    /// Leading zero bits the solution's hash needs
+    pub difficulty: u32,
+    /// Unix time after which the relay no longer accepts a solution
+    pub expires_at: u64,
+}
+
+impl PowChallenge {
+    /// Check if the challenge can no longer be answered at Unix time `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            return bits + byte.leading_zeros();
+        }
+    }
+    bits
+}
+
+fn work_hash(challenge: &PowChallenge, payload: &[u8], solution: u64) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(POW_CONTEXT)
+        .chain_update(challenge.id.as_bytes())
+        .chain_update([0])
+        .chain_update(&challenge.nonce)
+        .chain_update(payload)
+        .chain_update(solution.to_le_bytes())
+        .finalize()
+        .into()
+}
+
+/// Check that `solution` answers `challenge` for `payload`
+pub fn verify_solution(challenge: &PowChallenge, payload: &[u8], solution: u64) -> bool {
+    challenge.difficulty <= MAX_DIFFICULTY
+        && leading_zero_bits(&work_hash(challenge, payload, solution)) >= challenge.difficulty
+}
+
+/// Find a solution to `challenge` for `payload`, the request the work pays
+/// for. Runs for about `2^difficulty` hashes, so call it off the UI thread.
+/// Gives up with `None` once `now` (Unix time) reaches the challenge's
+/// expiry, as the relay would refuse the solution.
+pub fn solve(challenge: &PowChallenge, payload: &[u8], now: impl Fn() -> u64) -> Option<u64> {
+    if challenge.difficulty > MAX_DIFFICULTY {
+        return None;
+    }
+    for solution in 0..=u64::MAX {
+        if solution.is_multiple_of(CLOCK_INTERVAL) && challenge.is_expired(now()) {
+            return None;
+        }
+        if verify_solution(challenge, payload, solution) {
+            return Some(solution);
+        }
+    }
+    None
+}
+
+/// Difficulty for the next challenge when `issued` tokens were issued in
+/// the last window and `target` are wanted per window: `base` up to the
+/// target, one more bit per doubling above it, never more than `max`
+pub fn adaptive_difficulty(base: u32, max: u32, target: u64, issued: u64) -> u32 {
+    let target = target.max(1);
+    let mut difficulty = base;
+    let mut allowed = target;
+    while issued > allowed && difficulty < max {
+        difficulty += 1;
+        allowed = allowed.saturating_mul(2);
+    }
+    difficulty.min(max)
+}
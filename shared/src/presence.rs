@@ -0,0 +1,198 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! User presence.
+//! Our own state is derived from how long the user has been idle, unless
+//! they picked one themselves. It is announced to every room as an
+//! ephemeral control message whenever it changes, and repeated now and then
+//! so contacts can tell that we are still around. A contact whose last
+//! announcement is too old counts as offline.
+
+use std::sync::Mutex;
+
+use crate::crypto::message::{Contact, MessagePayload, PresenceState, Room};
+use crate::messaging::{send_payload, MessageTransport};
+use crate::persistence::database::{Database, Entity};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// How often an unchanged presence is announced again
+pub const PRESENCE_HEARTBEAT_SECS: u64 = 2 * 60;
+
+/// How long a contact's announcement is trusted without a newer one
+pub const PRESENCE_TIMEOUT_SECS: u64 = 3 * PRESENCE_HEARTBEAT_SECS;
+
+static TRACKER: Mutex<PresenceTracker> = Mutex::new(PresenceTracker::new());
+
+/// Helper function to get current timestamp
+fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Idle time and announcement bookkeeping for our own presence
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceTracker {
+    /// Last user input, `None` until the first input since start
+    last_activity: Option<u64>,
+    /// What was announced last and when
+    announced: Option<(PresenceState, u64)>,
+}
+
+impl Default for PresenceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresenceTracker {
+    pub const fn new() -> Self {
+        Self {
+            last_activity: None,
+            announced: None,
+        }
+    }
+
+    /// Note user input
+    pub fn record_activity(&mut self, now: u64) {
+        self.last_activity = Some(now);
+    }
+
+    /// Current presence. A state chosen by the user wins, otherwise the user
+    /// is away after `auto_away_minutes` without input (0 disables it).
+    pub fn resolve(
+        &mut self,
+        chosen: Option<PresenceState>,
+        auto_away_minutes: u32,
+        now: u64,
+    ) -> PresenceState {
+        if let Some(state) = chosen {
+            return state;
+        }
+
+        // Starting the app counts as activity
+        let last_activity = *self.last_activity.get_or_insert(now);
+        let idle = now.saturating_sub(last_activity);
+        if auto_away_minutes > 0 && idle >= u64::from(auto_away_minutes) * 60 {
+            PresenceState::Away
+        } else {
+            PresenceState::Online
+        }
+    }
+
+    /// Whether a state needs announcing: it changed, or the heartbeat is due.
+    /// Being offline is announced once and never repeated.
+    pub fn needs_announcement(&self, state: PresenceState, now: u64) -> bool {
+        match self.announced {
+            Some((announced, _)) if announced != state => true,
+            Some((PresenceState::Offline, _)) => false,
+            Some((_, announced_at)) => now.saturating_sub(announced_at) >= PRESENCE_HEARTBEAT_SECS,
+            None => true,
+        }
+    }
+
+    /// Remember that a state was announced
+    pub fn mark_announced(&mut self, state: PresenceState, now: u64) {
+        self.announced = Some((state, now));
+    }
+}
+
+/// Note user input for the idle timer
+pub fn record_activity() {
+    if let Ok(mut tracker) = TRACKER.lock() {
+        tracker.record_activity(current_timestamp());
+    }
+}
+
+/// What a contact's presence is now, given when they last announced it
+pub fn contact_presence(contact: &Contact, now: u64) -> PresenceState {
+    match (contact.presence, contact.last_seen) {
+        (Some(state), Some(last_seen)) if now.saturating_sub(last_seen) < PRESENCE_TIMEOUT_SECS => {
+            state
+        }
+        _ => PresenceState::Offline,
+    }
+}
+
+/// Apply a presence announcement to the contacts owning the sender's key.
+/// Returns whether any contact changed.
+pub fn apply_presence(
+    db: &Database,
+    sender_public: &[u8; 32],
+    state: PresenceState,
+    announced_at: u64,
+) -> Result<bool> {
+    // Clocks differ, so an announcement cannot be from the future
+    let announced_at = announced_at.min(current_timestamp());
+
+    let mut changed = false;
+    let contacts: Vec<Contact> = db.load_all_entities(Contact::key_prefix())?;
+    for mut contact in contacts {
+        if contact.owns_key(sender_public) && contact.apply_presence(state, announced_at) {
+            db.update_entity(&contact)?;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// Announce a presence state in every room
+pub async fn announce_presence<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    state: PresenceState,
+) -> Result<()> {
+    let payload = MessagePayload::presence(state);
+    let rooms: Vec<Room> = db.load_all_entities(Room::key_prefix())?;
+    for room in rooms {
+        let Some(room_id) = room.id() else {
+            continue;
+        };
+        // One unreachable room should not keep the others from hearing
+        if let Err(e) = send_payload(transport, db, room_id, &payload).await {
+            eprintln!("Failed to announce presence in room {room_id}: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Work out our presence and announce it when needed
+pub async fn refresh_presence<T: MessageTransport>(
+    transport: &T,
+    db: &Database,
+    chosen: Option<PresenceState>,
+    auto_away_minutes: u32,
+) -> Result<PresenceState> {
+    let now = current_timestamp();
+    let (state, announce) = {
+        let mut tracker = TRACKER.lock().map_err(|e| e.to_string())?;
+        let state = tracker.resolve(chosen, auto_away_minutes, now);
+        (state, tracker.needs_announcement(state, now))
+    };
+
+    if announce {
+        announce_presence(transport, db, state).await?;
+        TRACKER
+            .lock()
+            .map_err(|e| e.to_string())?
+            .mark_announced(state, now);
+    }
+    Ok(state)
+}
@@ -0,0 +1,126 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Local profile session tracking.
+//! A profile is a `UserData` entry; its rooms, contacts and messages live in a
+//! profile scoped database (see `Database::for_profile`).
+
+use serde::{Deserialize, Serialize};
+
+use crate::avatar::AVATAR_BLOB_PREFIX;
+use crate::crypto::message::{Contact, EncryptedMessage, Room};
+use crate::persistence::database::{Database, Entity};
+use crate::user_data::UserData;
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Storage key of the single persisted profile session
+const SESSION_KEY: &str = "profile_session:active";
+
+/// Persisted record of which profile is currently selected
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProfileSession {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub active_profile_id: Option<String>,
+}
+
+impl Default for ProfileSession {
+    fn default() -> Self {
+        Self {
+            id: Some(SESSION_KEY.to_string()),
+            active_profile_id: None,
+        }
+    }
+}
+
+impl ProfileSession {
+    /// Load the persisted session, or an empty one if none was saved yet
+    pub fn load(db: &Database) -> Result<Self> {
+        Ok(db.load_entity::<Self>(SESSION_KEY)?.unwrap_or_default())
+    }
+
+    /// Persist the session
+    pub fn save(&mut self, db: &Database) -> Result<()> {
+        self.id = Some(SESSION_KEY.to_string());
+        db.save_entity(self)?;
+        Ok(())
+    }
+
+    /// Set the active profile
+    pub fn set_active_profile(&mut self, profile_id: Option<String>) {
+        self.active_profile_id = profile_id;
+    }
+}
+
+impl Entity for ProfileSession {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "profile_session"
+    }
+}
+
+/// Open the database for the active profile.
+/// Falls back to the unscoped database when no profile has been selected yet.
+pub fn active_database() -> Result<Database> {
+    let db = Database::new();
+    match ProfileSession::load(&db)?.active_profile_id {
+        Some(profile_id) => Ok(Database::for_profile(&profile_id)?),
+        None => Ok(db),
+    }
+}
+
+/// Open the unscoped database and the database of every profile, for
+/// housekeeping that must not wait until a profile is selected again
+pub fn all_databases() -> Result<Vec<Database>> {
+    let db = Database::new();
+    let mut databases = Vec::new();
+    for user_data in db.load_all_entities::<UserData>(UserData::key_prefix())? {
+        if let Some(profile_id) = &user_data.id {
+            databases.push(Database::for_profile(profile_id)?);
+        }
+    }
+    databases.push(db);
+    Ok(databases)
+}
+
+/// Move rooms, contacts, messages and avatars created before profiles existed
+/// into a profile's database. Returns the number of entries moved.
+pub fn adopt_unscoped_entities(profile_id: &str) -> Result<usize> {
+    let db = Database::new();
+    let profile_db = Database::for_profile(profile_id)?;
+
+    let mut moved = 0;
+    for prefix in [
+        Room::key_prefix(),
+        Contact::key_prefix(),
+        EncryptedMessage::key_prefix(),
+        AVATAR_BLOB_PREFIX,
+    ] {
+        moved += db.move_prefix_to(&format!("{prefix}:"), &profile_db)?;
+    }
+
+    Ok(moved)
+}
@@ -0,0 +1,193 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Disappearing messages.
+//! A room's retention timer is shared with its members through a control
+//! message. Every message sent while the timer is set carries it, so each
+//! copy (stored history, sent deposit records, relay mailbox entries) knows
+//! when to go away on its own. A background task purges whatever expired.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::crypto::message::EncryptedMessage;
+use crate::history::StoredMessage;
+use crate::messaging::SentMessage;
+use crate::persistence::database::{Database, Entity};
+use crate::profile::all_databases;
+use crate::search::remove_from_index;
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Longest retention timer a room can set
+pub const MAX_RETENTION_SECS: u64 = 4 * 7 * 24 * 60 * 60;
+
+/// How often the background task looks for expired messages
+const PURGE_INTERVAL: Duration = Duration::from_secs(30);
+
+static PURGE_TASK_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Helper function to get current timestamp
+fn current_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// The disappearing messages timer of a room
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct RoomRetention {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub room_id: String,
+    /// Seconds a message is kept, `None` when messages do not disappear
+    pub seconds: Option<u64>,
+    /// When the timer was last changed by any member
+    pub updated_at: u64,
+}
+
+impl RoomRetention {
+    /// Storage key of a room's retention timer
+    pub fn key_for(room_id: &str) -> String {
+        format!("{}:{room_id}", Self::key_prefix())
+    }
+}
+
+impl Entity for RoomRetention {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "room_retention"
+    }
+}
+
+/// Check that a retention timer is within the supported range
+pub fn validate_retention(seconds: Option<u64>) -> Result<()> {
+    match seconds {
+        Some(0) => Err("Retention timer must be at least one second".into()),
+        Some(seconds) if seconds > MAX_RETENTION_SECS => Err("Retention timer is too long".into()),
+        _ => Ok(()),
+    }
+}
+
+/// Seconds messages of a room are kept, if they disappear
+pub fn room_retention(db: &Database, room_id: &str) -> Result<Option<u64>> {
+    Ok(db
+        .load_entity::<RoomRetention>(&RoomRetention::key_for(room_id))?
+        .and_then(|retention| retention.seconds))
+}
+
+/// Change a room's retention timer. Members may change it concurrently, so
+/// the most recent change wins. Returns whether the timer was changed.
+pub fn apply_retention(
+    db: &Database,
+    room_id: &str,
+    seconds: Option<u64>,
+    changed_at: u64,
+) -> Result<bool> {
+    validate_retention(seconds)?;
+    // The sender picks the time, so a change cannot be from the future
+    let changed_at = changed_at.min(current_timestamp());
+
+    let key = RoomRetention::key_for(room_id);
+    if let Some(current) = db.load_entity::<RoomRetention>(&key)? {
+        if current.updated_at > changed_at || current.seconds == seconds {
+            return Ok(false);
+        }
+    }
+
+    let mut retention = RoomRetention {
+        id: Some(key),
+        room_id: room_id.to_string(),
+        seconds,
+        updated_at: changed_at,
+    };
+    db.save_entity(&mut retention)?;
+    Ok(true)
+}
+
+/// Delete every message copy that expired, in all rooms.
+/// Returns the number of entries removed.
+pub fn purge_expired(db: &Database, now: u64) -> Result<usize> {
+    let mut purged = 0;
+
+    let messages: Vec<StoredMessage> = db.load_all_entities(StoredMessage::key_prefix())?;
+    for message in messages {
+        if !message.is_expired(now) {
+            continue;
+        }
+        if let Some(id) = message.id() {
+            db.delete::<StoredMessage>(id)?;
+            purged += 1;
+        }
+        remove_from_index(db, &message.room_id, &message.message_id)?;
+        let sent_key = SentMessage::key_for(&message.room_id, &message.message_id);
+        if db.load_entity::<SentMessage>(&sent_key)?.is_some() {
+            db.delete::<SentMessage>(&sent_key)?;
+        }
+    }
+
+    let encrypted: Vec<EncryptedMessage> = db.load_all_entities(EncryptedMessage::key_prefix())?;
+    for message in encrypted {
+        if message.expires_at.is_none_or(|expires_at| expires_at > now) {
+            continue;
+        }
+        if let Some(id) = message.id() {
+            db.delete::<EncryptedMessage>(id)?;
+            purged += 1;
+        }
+    }
+
+    Ok(purged)
+}
+
+/// Delete expired messages of every profile, not only the active one.
+/// Returns the number of entries removed.
+pub fn purge_all_profiles(now: u64) -> Result<usize> {
+    let mut purged = 0;
+    for db in all_databases()? {
+        purged += purge_expired(&db, now)?;
+    }
+    Ok(purged)
+}
+
+/// Start the background task that purges expired messages of every
+/// profile. Calling it again while the task runs does nothing.
+pub fn start_purge_task() {
+    if PURGE_TASK_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_all_profiles(current_timestamp()) {
+                eprintln!("Failed to purge expired messages: {e}");
+            }
+        }
+    });
+}
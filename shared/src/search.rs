@@ -0,0 +1,512 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Local full-text search over decrypted message history.
+//! Each room has an inverted index whose key is derived from the room's
+//! secret key. Every (term, message) pair is its own posting, stored under
+//! the keyed term hash and sealed with AES-256-GCM, so the index on disk
+//! reveals neither words nor which messages contain them, and indexing a
+//! message costs the same however large the room is. Nothing here is ever
+//! sent to the relay.
+
+use aes_gcm::{Aes256Gcm, Key};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+use crate::crypto::message::{aes256_gcm, Contact, Room};
+use crate::history::StoredMessage;
+use crate::persistence::database::{Database, Entity};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Longest word prefix that is indexed; longer query words are checked
+/// against the message text after the index lookup
+const MAX_PREFIX_CHARS: usize = 16;
+
+/// Shortest word prefix that is indexed, except for words that are shorter
+const MIN_PREFIX_CHARS: usize = 2;
+
+/// Characters of context kept before the first match in a snippet
+const SNIPPET_BEFORE: usize = 30;
+
+/// Characters kept from the first match onwards in a snippet
+const SNIPPET_AFTER: usize = 90;
+
+/// Most results returned by a search
+pub const MAX_SEARCH_RESULTS: usize = 50;
+
+/// Scripts written without spaces are indexed character by character
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF
+    )
+}
+
+/// Words of a text with their byte ranges, lowercased
+fn word_spans(text: &str) -> Vec<(usize, usize, String)> {
+    let mut spans = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, c) in text.char_indices() {
+        if c.is_alphanumeric() && !is_cjk(c) {
+            current
+                .get_or_insert_with(|| (index, String::new()))
+                .1
+                .extend(c.to_lowercase());
+            continue;
+        }
+        if let Some((start, word)) = current.take() {
+            spans.push((start, index, word));
+        }
+        if is_cjk(c) {
+            spans.push((index, index + c.len_utf8(), c.to_string()));
+        }
+    }
+    if let Some((start, word)) = current {
+        spans.push((start, text.len(), word));
+    }
+    spans
+}
+
+/// Lowercased words of a text
+pub fn tokenize(text: &str) -> Vec<String> {
+    word_spans(text)
+        .into_iter()
+        .map(|(_, _, word)| word)
+        .collect()
+}
+
+/// Prefixes of a word that go into the index
+fn index_terms(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let longest = chars.len().min(MAX_PREFIX_CHARS);
+    let shortest = MIN_PREFIX_CHARS.min(longest);
+    (shortest..=longest)
+        .map(|length| chars[..length].iter().collect())
+        .collect()
+}
+
+/// Index term a query word is looked up by
+fn query_term(word: &str) -> String {
+    word.chars().take(MAX_PREFIX_CHARS).collect()
+}
+
+/// Check that every query word starts a word of the text
+fn matches_all(text: &str, query_words: &[String]) -> bool {
+    let words = tokenize(text);
+    query_words
+        .iter()
+        .all(|query| words.iter().any(|word| word.starts_with(query.as_str())))
+}
+
+/// Text of a message that is searchable: its body and attachment file names
+fn searchable_text(message: &StoredMessage) -> String {
+    std::iter::once(message.text.as_str())
+        .chain(message.attachments.iter().map(|a| a.file_name.as_str()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Part of a search result snippet
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+/// Excerpt of a text around the first match, with matching words highlighted
+pub fn highlight_snippet(text: &str, query_words: &[String]) -> Vec<SnippetPart> {
+    let matches: Vec<(usize, usize)> = word_spans(text)
+        .into_iter()
+        .filter(|(_, _, word)| {
+            query_words
+                .iter()
+                .any(|query| word.starts_with(query.as_str()))
+        })
+        .map(|(start, end, _)| (start, end))
+        .collect();
+
+    // Window of the text around the first match, on character boundaries
+    let first = matches.first().map_or(0, |(start, _)| *start);
+    let window_start = text[..first]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_BEFORE.saturating_sub(1))
+        .map_or(0, |(index, _)| index);
+    let window_end = text[first..]
+        .char_indices()
+        .nth(SNIPPET_AFTER)
+        .map_or(text.len(), |(index, _)| first + index);
+
+    let mut parts = Vec::new();
+    let mut push = |text: &str, highlighted: bool| {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: text.to_string(),
+                highlighted,
+            });
+        }
+    };
+
+    if window_start > 0 {
+        push("…", false);
+    }
+    let mut position = window_start;
+    for (start, end) in matches {
+        if start < window_start || end > window_end {
+            continue;
+        }
+        push(&text[position..start], false);
+        push(&text[start..end], true);
+        position = end;
+    }
+    push(&text[position..window_end], false);
+    if window_end < text.len() {
+        push("…", false);
+    }
+    parts
+}
+
+/// One message ID indexed under one term, sealed
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SearchTerm {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl SearchTerm {
+    /// Storage key of a posting under a hashed term within a room's index
+    pub fn key_for(room_id: &str, term_hash: &str, posting_hash: &str) -> String {
+        format!("{}{posting_hash}", Self::term_prefix(room_id, term_hash))
+    }
+
+    /// Key prefix shared by every posting of a hashed term
+    pub fn term_prefix(room_id: &str, term_hash: &str) -> String {
+        format!("{}{term_hash}:", Self::room_prefix(room_id))
+    }
+
+    /// Key prefix shared by every posting of a room's index
+    pub fn room_prefix(room_id: &str) -> String {
+        format!("{}:{room_id}:", Self::key_prefix())
+    }
+}
+
+impl Entity for SearchTerm {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "search_term"
+    }
+}
+
+/// Hashed terms a message is indexed under, sealed, so the message can be
+/// taken out of the index again when it is edited or deleted
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SearchDocument {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl SearchDocument {
+    /// Storage key of a message's index entry within a room
+    pub fn key_for(room_id: &str, message_id: &str) -> String {
+        format!("{}{message_id}", Self::room_prefix(room_id))
+    }
+
+    /// Key prefix shared by every index entry of a room
+    pub fn room_prefix(room_id: &str) -> String {
+        format!("{}:{room_id}:", Self::key_prefix())
+    }
+}
+
+impl Entity for SearchDocument {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "search_document"
+    }
+}
+
+/// The search index of one room
+pub struct SearchIndex {
+    room_id: String,
+    key: [u8; 32],
+}
+
+impl SearchIndex {
+    pub fn new(room_id: &str, room_secret: &[u8; 32]) -> Self {
+        let key = Sha256::new()
+            .chain_update(b"meeseeks-nuntius search index")
+            .chain_update(room_secret)
+            .finalize()
+            .into();
+        Self {
+            room_id: room_id.to_string(),
+            key,
+        }
+    }
+
+    /// Open the index of a stored room
+    pub fn for_room(room: &Room) -> Result<Self> {
+        let room_id = room.id.as_deref().ok_or("Room has no ID")?;
+        Ok(Self::new(room_id, &room.secret_key_bytes()))
+    }
+
+    fn cipher_key(&self) -> Key<Aes256Gcm> {
+        self.key.into()
+    }
+
+    fn term_hash(&self, term: &str) -> String {
+        hex::encode(
+            Sha256::new()
+                .chain_update(self.key)
+                .chain_update(term.as_bytes())
+                .finalize(),
+        )
+    }
+
+    fn seal<T: Serialize>(&self, value: &T) -> Result<(Vec<u8>, Vec<u8>)> {
+        let (ciphertext, nonce) =
+            aes256_gcm::encrypt(&self.cipher_key(), &serde_json::to_vec(value)?)?;
+        Ok((nonce, ciphertext))
+    }
+
+    fn open<T: DeserializeOwned>(&self, nonce: &[u8], ciphertext: &[u8]) -> Result<T> {
+        let plaintext = aes256_gcm::decrypt(&self.cipher_key(), ciphertext, nonce)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Hides which message a posting belongs to
+    fn posting_hash(&self, term_hash: &str, message_id: &str) -> String {
+        hex::encode(
+            Sha256::new()
+                .chain_update(self.key)
+                .chain_update(term_hash.as_bytes())
+                .chain_update(b":")
+                .chain_update(message_id.as_bytes())
+                .finalize(),
+        )
+    }
+
+    fn posting_key(&self, term_hash: &str, message_id: &str) -> String {
+        SearchTerm::key_for(
+            &self.room_id,
+            term_hash,
+            &self.posting_hash(term_hash, message_id),
+        )
+    }
+
+    /// Message IDs listed under a hashed term
+    fn postings(&self, db: &Database, term_hash: &str) -> Result<BTreeSet<String>> {
+        let prefix = SearchTerm::term_prefix(&self.room_id, term_hash);
+        db.load_all_entities::<SearchTerm>(&prefix)?
+            .iter()
+            .map(|posting| self.open(&posting.nonce, &posting.ciphertext))
+            .collect()
+    }
+
+    /// Add a message to the index, replacing what was indexed for it before.
+    /// Deleted messages are only taken out.
+    pub fn index_message(&self, db: &Database, message: &StoredMessage) -> Result<()> {
+        self.remove_message(db, &message.message_id)?;
+        if message.is_deleted() {
+            return Ok(());
+        }
+
+        let mut terms = BTreeSet::new();
+        for word in tokenize(&searchable_text(message)) {
+            terms.extend(index_terms(&word));
+        }
+        if terms.is_empty() {
+            return Ok(());
+        }
+
+        let hashes: Vec<String> = terms.iter().map(|term| self.term_hash(term)).collect();
+        for hash in &hashes {
+            let (nonce, ciphertext) = self.seal(&message.message_id)?;
+            let mut posting = SearchTerm {
+                id: Some(self.posting_key(hash, &message.message_id)),
+                nonce,
+                ciphertext,
+            };
+            db.save_entity(&mut posting)?;
+        }
+
+        let (nonce, ciphertext) = self.seal(&hashes)?;
+        let mut document = SearchDocument {
+            id: Some(SearchDocument::key_for(&self.room_id, &message.message_id)),
+            nonce,
+            ciphertext,
+        };
+        db.save_entity(&mut document)?;
+        Ok(())
+    }
+
+    /// Take a message out of the index
+    pub fn remove_message(&self, db: &Database, message_id: &str) -> Result<()> {
+        let key = SearchDocument::key_for(&self.room_id, message_id);
+        let Some(document) = db.load_entity::<SearchDocument>(&key)? else {
+            return Ok(());
+        };
+
+        let hashes: Vec<String> = self.open(&document.nonce, &document.ciphertext)?;
+        for hash in hashes {
+            let posting_key = self.posting_key(&hash, message_id);
+            if db.load_entity::<SearchTerm>(&posting_key)?.is_some() {
+                db.delete::<SearchTerm>(&posting_key)?;
+            }
+        }
+        db.delete::<SearchDocument>(&key)?;
+        Ok(())
+    }
+
+    /// Messages of the room containing every word of the query, as a prefix
+    pub fn search(&self, db: &Database, query: &str) -> Result<Vec<StoredMessage>> {
+        let query_words = tokenize(query);
+        let Some((first, rest)) = query_words.split_first() else {
+            return Ok(Vec::new());
+        };
+
+        let mut candidates = self.postings(db, &self.term_hash(&query_term(first)))?;
+        for word in rest {
+            if candidates.is_empty() {
+                break;
+            }
+            let postings = self.postings(db, &self.term_hash(&query_term(word)))?;
+            candidates.retain(|message_id| postings.contains(message_id));
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        let mut messages = Vec::new();
+        for message_id in candidates {
+            let key = StoredMessage::key_for(&self.room_id, &message_id);
+            let Some(message) = db.load_entity::<StoredMessage>(&key)? else {
+                continue;
+            };
+            if message.is_deleted() || message.is_expired(now) {
+                continue;
+            }
+            if matches_all(&searchable_text(&message), &query_words) {
+                messages.push(message);
+            }
+        }
+        Ok(messages)
+    }
+}
+
+/// Update the index of a room after one of its messages was stored or changed
+pub fn update_index(db: &Database, room_id: &str, message: &StoredMessage) -> Result<()> {
+    if let Some(room) = db.load_entity::<Room>(room_id)? {
+        SearchIndex::for_room(&room)?.index_message(db, message)?;
+    }
+    Ok(())
+}
+
+/// Take a message out of its room's index
+pub fn remove_from_index(db: &Database, room_id: &str, message_id: &str) -> Result<()> {
+    if let Some(room) = db.load_entity::<Room>(room_id)? {
+        SearchIndex::for_room(&room)?.remove_message(db, message_id)?;
+    }
+    Ok(())
+}
+
+/// A message found by a search
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub room_id: String,
+    pub room_name: String,
+    pub message_id: String,
+    pub sender_name: String,
+    pub is_own: bool,
+    pub sent_at: u64,
+    pub snippet: Vec<SnippetPart>,
+}
+
+impl SearchHit {
+    /// Serialize to JSON
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self)?)
+    }
+
+    /// Deserialize from JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// Search the given rooms, newest matches first
+pub fn search_rooms(
+    db: &Database,
+    rooms: &[Room],
+    contacts: &[Contact],
+    query: &str,
+) -> Result<Vec<SearchHit>> {
+    let query_words = tokenize(query);
+    let mut hits = Vec::new();
+
+    for room in rooms {
+        let index = SearchIndex::for_room(room)?;
+        for message in index.search(db, query)? {
+            let snippet_text = if message.text.is_empty() {
+                message
+                    .attachments
+                    .iter()
+                    .map(|a| a.file_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            } else {
+                message.text.clone()
+            };
+            hits.push(SearchHit {
+                room_id: index.room_id.clone(),
+                room_name: room.name.clone(),
+                sender_name: message.sender_name(contacts),
+                is_own: message.is_own,
+                sent_at: message.sent_at,
+                snippet: highlight_snippet(&snippet_text, &query_words),
+                message_id: message.message_id,
+            });
+        }
+    }
+
+    hits.sort_by_key(|h| Reverse(h.sent_at));
+    hits.truncate(MAX_SEARCH_RESULTS);
+    Ok(hits)
+}
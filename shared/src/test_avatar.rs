@@ -0,0 +1,286 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+#[cfg(test)]
+mod tests {
+    use crate::attachments::BlobTransport;
+    use crate::avatar::*;
+    use crate::crypto::identity::{ProfileUpdate, Signed};
+    use crate::crypto::message::{Contact, EncryptedMessage, MessagePayload, Room};
+    use crate::messaging::{receive_messages, MessageTransport};
+    use crate::persistence::database::Database;
+    use crate::user_data::UserData;
+    use serial_test::serial;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// In-memory relay with blob storage and mailboxes
+    #[derive(Default)]
+    struct MemoryRelay {
+        blobs: Mutex<HashMap<String, Vec<u8>>>,
+        mailboxes: Mutex<HashMap<String, Vec<EncryptedMessage>>>,
+    }
+
+    impl BlobTransport for MemoryRelay {
+        async fn upload_blob(&self, blob_id: String, data: Vec<u8>) -> Result<()> {
+            self.blobs.lock().unwrap().insert(blob_id, data);
+            Ok(())
+        }
+
+        async fn download_blob(&self, blob_id: String) -> Result<Option<Vec<u8>>> {
+            Ok(self.blobs.lock().unwrap().get(&blob_id).cloned())
+        }
+    }
+
+    impl MessageTransport for MemoryRelay {
+        async fn deposit(
+            &self,
+            recipient_hash: String,
+            message: EncryptedMessage,
+            _relay: Option<String>,
+        ) -> Result<()> {
+            self.mailboxes
+                .lock()
+                .unwrap()
+                .entry(recipient_hash)
+                .or_default()
+                .push(message);
+            Ok(())
+        }
+
+        async fn fetch(
+            &self,
+            recipient_hash: String,
+            _secret_key: &[u8; 32],
+        ) -> Result<Vec<EncryptedMessage>> {
+            Ok(self
+                .mailboxes
+                .lock()
+                .unwrap()
+                .remove(&recipient_hash)
+                .unwrap_or_default())
+        }
+
+        async fn acknowledge(
+            &self,
+            _recipient_hash: String,
+            _secret_key: &[u8; 32],
+            _ids: Vec<String>,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn retract(
+            &self,
+            _recipient_hash: String,
+            _nonce: Vec<u8>,
+            _retract_secret: Vec<u8>,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Bytes that pass for a JPEG image
+    fn sample_jpeg(len: usize) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        data.extend((0..len).map(|i| (i % 251) as u8));
+        data
+    }
+
+    /// Sign a user's profile as issued after `previous`, since updates signed
+    /// within the same second would be taken for replays
+    fn newer_update(user: &UserData, previous: &Signed<ProfileUpdate>) -> Signed<ProfileUpdate> {
+        let mut update = user.sign_profile_update().unwrap().payload;
+        update.issued_at = previous.payload.issued_at + 1;
+        Signed::sign(&user.identity_secret_key.unwrap(), update).unwrap()
+    }
+
+    /// Databases of Alice and Bob, each with a room knowing the other's key,
+    /// and Bob's contact for Alice
+    fn alice_and_bob(alice: &UserData) -> (Database, String, Database, String, String) {
+        let alice_db = Database::for_profile("avatar-test-alice").unwrap();
+        let bob_db = Database::for_profile("avatar-test-bob").unwrap();
+        let _ = alice_db.clear();
+        let _ = bob_db.clear();
+
+        let mut alice_room = Room::new("Alice");
+        let mut bob_room = Room::new("Bob");
+        alice_room.add_contact(&bob_room.public_key());
+        bob_room.add_contact(&alice_room.public_key());
+
+        let mut contact = Contact {
+            name: "alice".to_string(),
+            public_key: alice_room.public_key_bytes(),
+            identity_public: alice.identity_public_key,
+            ..Default::default()
+        };
+        let contact_id = bob_db.save_entity(&mut contact).unwrap();
+
+        let alice_room_id = alice_db.save_entity(&mut alice_room).unwrap();
+        let bob_room_id = bob_db.save_entity(&mut bob_room).unwrap();
+        (alice_db, alice_room_id, bob_db, bob_room_id, contact_id)
+    }
+
+    #[test]
+    fn test_validate_avatar() {
+        assert!(validate_avatar("image/jpeg", &sample_jpeg(100)).is_ok());
+        assert!(validate_avatar("image/png", b"\x89PNG\r\n\x1a\n....").is_ok());
+        assert!(validate_avatar("image/webp", b"RIFF\0\0\0\0WEBPVP8 ").is_ok());
+
+        assert!(validate_avatar("image/gif", b"GIF89a").is_err());
+        assert!(validate_avatar("image/png", &sample_jpeg(100)).is_err());
+        assert!(validate_avatar("image/webp", b"RIFF").is_err());
+        assert!(validate_avatar("image/jpeg", &sample_jpeg(MAX_AVATAR_BYTES)).is_err());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_import_stores_encrypted_avatar() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let data = sample_jpeg(1000);
+        let avatar = import_avatar(&db, "image/jpeg", &data).unwrap();
+        assert_eq!(avatar.mime_type, "image/jpeg");
+        assert_eq!(avatar.size, data.len() as u64);
+
+        // Only the encrypted chunk is kept
+        let stored = db
+            .load_bytes(&format!("{AVATAR_BLOB_PREFIX}:{}:0", avatar.digest_hex()))
+            .unwrap()
+            .unwrap();
+        assert!(!stored.windows(data.len()).any(|window| window == data));
+
+        assert_eq!(load_avatar(&db, &avatar).unwrap(), Some(data));
+        remove_avatar(&db, &avatar).unwrap();
+        assert_eq!(load_avatar(&db, &avatar).unwrap(), None);
+
+        assert!(import_avatar(&db, "text/plain", b"hello").is_err());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_avatar_reaches_contacts() {
+        let relay = MemoryRelay::default();
+        let mut alice = UserData::new("alice", "Alice");
+        let (alice_db, _, bob_db, bob_room_id, contact_id) = alice_and_bob(&alice);
+
+        let data = sample_jpeg(2000);
+        alice.set_avatar(Some(import_avatar(&alice_db, "image/jpeg", &data).unwrap()));
+        let first = alice.sign_profile_update().unwrap();
+        share_profile(&relay, &relay, &alice_db, first.clone())
+            .await
+            .unwrap();
+
+        receive_messages(&relay, &bob_db, &bob_room_id)
+            .await
+            .unwrap();
+        let contact = bob_db.load_entity::<Contact>(&contact_id).unwrap().unwrap();
+        assert_eq!(contact.name, "Alice");
+        let avatar = contact.avatar.expect("avatar was shared");
+        assert_eq!(avatar, alice.avatar.clone().unwrap());
+
+        // Downloaded once, then kept
+        assert_eq!(load_avatar(&bob_db, &avatar).unwrap(), None);
+        assert_eq!(sync_contact_avatars(&relay, &bob_db).await.unwrap(), 1);
+        assert_eq!(sync_contact_avatars(&relay, &bob_db).await.unwrap(), 0);
+        assert_eq!(load_avatar(&bob_db, &avatar).unwrap(), Some(data));
+
+        // A new avatar replaces the old one
+        alice.set_avatar(Some(
+            import_avatar(&alice_db, "image/jpeg", &sample_jpeg(10)).unwrap(),
+        ));
+        share_profile(&relay, &relay, &alice_db, newer_update(&alice, &first))
+            .await
+            .unwrap();
+        receive_messages(&relay, &bob_db, &bob_room_id)
+            .await
+            .unwrap();
+        assert_eq!(load_avatar(&bob_db, &avatar).unwrap(), None);
+        assert_eq!(sync_contact_avatars(&relay, &bob_db).await.unwrap(), 1);
+
+        // Replaying the first update cannot bring the old avatar back
+        let alice_key = bob_db
+            .load_entity::<Contact>(&contact_id)
+            .unwrap()
+            .unwrap()
+            .public_key;
+        assert!(!apply_profile_update(&bob_db, &alice_key, &first).unwrap());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_contact_added_by_key_learns_identity() {
+        let relay = MemoryRelay::default();
+        let alice = UserData::new("alice", "Alice");
+        let (alice_db, alice_room_id, bob_db, bob_room_id, contact_id) = alice_and_bob(&alice);
+
+        let mut contact = bob_db.load_entity::<Contact>(&contact_id).unwrap().unwrap();
+        contact.identity_public = None;
+        bob_db.update_entity(&contact).unwrap();
+        let mut alice_room = alice_db
+            .load_entity::<Room>(&alice_room_id)
+            .unwrap()
+            .unwrap();
+        alice_room
+            .bind_identity(&alice.identity_secret_key.unwrap())
+            .unwrap();
+        alice_db.update_entity(&alice_room).unwrap();
+
+        share_profile(
+            &relay,
+            &relay,
+            &alice_db,
+            alice.sign_profile_update().unwrap(),
+        )
+        .await
+        .unwrap();
+        receive_messages(&relay, &bob_db, &bob_room_id)
+            .await
+            .unwrap();
+
+        let contact = bob_db.load_entity::<Contact>(&contact_id).unwrap().unwrap();
+        assert_eq!(contact.identity_public, alice.identity_public_key);
+        assert_eq!(contact.name, "Alice");
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_profile_update_from_other_identity_is_rejected() {
+        let alice = UserData::new("alice", "Alice");
+        let mallory = UserData::new("mallory", "Mallory");
+        let (_, _, bob_db, _, contact_id) = alice_and_bob(&alice);
+        let alice_key = bob_db
+            .load_entity::<Contact>(&contact_id)
+            .unwrap()
+            .unwrap()
+            .public_key;
+
+        let forged = mallory.sign_profile_update().unwrap();
+        assert!(apply_profile_update(&bob_db, &alice_key, &forged).is_err());
+
+        let update = alice.sign_profile_update().unwrap();
+        assert!(!apply_profile_update(&bob_db, &[9u8; 32], &update).unwrap());
+        assert!(apply_profile_update(&bob_db, &alice_key, &update).unwrap());
+
+        // Profile updates are kept until fetched, not dropped like presence
+        let payload = MessagePayload::profile(update);
+        assert!(payload.is_control());
+        assert!(!payload.is_ephemeral());
+    }
+}
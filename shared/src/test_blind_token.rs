@@ -0,0 +1,132 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::blind_token::*;
+
+    fn issue(issuer: &IssuerKey) -> (Vec<u8>, BlindSignature, AnonymousToken) {
+        let request = TokenRequest::new(1, &issuer.public_key()).unwrap();
+        let blinded = request.blinded();
+        let signature = issuer.sign_blinded(&blinded).unwrap();
+        let token = request.finalize(&signature).unwrap();
+        (blinded, signature, token)
+    }
+
+    #[test]
+    fn test_issued_token_verifies() {
+        let issuer = IssuerKey::from_relay_key(&[7; 32]);
+        let (_, _, token) = issue(&issuer);
+
+        assert_eq!(token.key_id, 1);
+        assert_eq!(token.secret().len(), 64);
+        assert_eq!(issuer.verify(&token), Ok(()));
+
+        // The issuer key follows from the relay key alone
+        assert_eq!(IssuerKey::from_relay_key(&[7; 32]).verify(&token), Ok(()));
+    }
+
+    #[test]
+    fn test_redemption_is_unlinkable() {
+        let issuer = IssuerKey::from_relay_key(&[7; 32]);
+        let (first_blinded, first_signature, first) = issue(&issuer);
+        let (second_blinded, _, second) = issue(&issuer);
+
+        // What the relay sees at issuance shares nothing with the token
+        assert_ne!(first_blinded, first.signature);
+        assert_ne!(first_signature.evaluated, first.signature);
+        assert_ne!(first_blinded, second_blinded);
+        assert_ne!(first.nonce, second.nonce);
+    }
+
+    #[test]
+    fn test_forged_tokens_are_rejected() {
+        let issuer = IssuerKey::from_relay_key(&[7; 32]);
+        let (_, _, token) = issue(&issuer);
+
+        let mut other_nonce = token.clone();
+        other_nonce.nonce[0] ^= 1;
+        assert_eq!(issuer.verify(&other_nonce), Err(TokenError::Invalid));
+
+        let (_, _, other) = issue(&issuer);
+        let swapped = AnonymousToken {
+            signature: other.signature,
+            ..token.clone()
+        };
+        assert_eq!(issuer.verify(&swapped), Err(TokenError::Invalid));
+
+        // Tokens of a rotated relay key do not verify under the new one
+        let rotated = IssuerKey::from_relay_key(&[8; 32]);
+        assert_eq!(rotated.verify(&token), Err(TokenError::Invalid));
+
+        let malformed = AnonymousToken {
+            signature: vec![0xff; 32],
+            ..token.clone()
+        };
+        assert_eq!(issuer.verify(&malformed), Err(TokenError::Malformed));
+        let short_nonce = AnonymousToken {
+            nonce: vec![0; 16],
+            ..token
+        };
+        assert_eq!(issuer.verify(&short_nonce), Err(TokenError::Malformed));
+    }
+
+    #[test]
+    fn test_signature_with_unpublished_key_is_rejected() {
+        let published = IssuerKey::from_relay_key(&[7; 32]);
+        let tagging = IssuerKey::from_relay_key(&[9; 32]);
+
+        // A relay signing with a per-client key cannot prove it used the
+        // published one
+        let request = TokenRequest::new(1, &published.public_key()).unwrap();
+        let signature = tagging.sign_blinded(&request.blinded()).unwrap();
+        assert_eq!(
+            request.finalize(&signature).err(),
+            Some(TokenError::InvalidProof)
+        );
+
+        let request = TokenRequest::new(1, &published.public_key()).unwrap();
+        let mut signature = published.sign_blinded(&request.blinded()).unwrap();
+        signature.response[0] ^= 1;
+        assert!(request.finalize(&signature).is_err());
+    }
+
+    #[test]
+    fn test_malformed_inputs_are_rejected() {
+        let issuer = IssuerKey::from_relay_key(&[7; 32]);
+        assert_eq!(
+            issuer.sign_blinded(&[0xff; 32]).err(),
+            Some(TokenError::Malformed)
+        );
+        assert_eq!(
+            issuer.sign_blinded(&[1; 16]).err(),
+            Some(TokenError::Malformed)
+        );
+        assert!(TokenRequest::new(1, &[0xff; 32]).is_err());
+    }
+
+    #[test]
+    fn test_batch_payload_names_the_batch() {
+        let batch = vec![vec![1; 32], vec![2; 32]];
+        let payload = batch_payload(&batch);
+
+        assert_eq!(payload.len(), 32);
+        assert_eq!(payload, batch_payload(&batch));
+        assert_ne!(payload, batch_payload(&[vec![2; 32], vec![1; 32]]));
+        assert_ne!(payload, batch_payload(&batch[..1]));
+    }
+}
@@ -0,0 +1,154 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::federation::*;
+    use crypto_box::{aead::OsRng, SecretKey};
+
+    fn relay_key() -> ([u8; 32], [u8; 32]) {
+        let secret = SecretKey::generate(&mut OsRng);
+        (secret.to_bytes(), secret.public_key().to_bytes())
+    }
+
+    #[test]
+    fn test_sealed_request_is_accepted() {
+        let (origin_secret, origin_public) = relay_key();
+        let (destination_secret, destination_public) = relay_key();
+
+        let request = br#"{"recipient_hash":"ab"}"#;
+        let proof = seal_request(&origin_secret, &destination_public, request).unwrap();
+        assert_eq!(
+            verify_request(&[destination_secret], &[origin_public], request, &proof),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_tampered_request_is_rejected() {
+        let (origin_secret, origin_public) = relay_key();
+        let (destination_secret, destination_public) = relay_key();
+
+        let proof = seal_request(&origin_secret, &destination_public, b"original").unwrap();
+        assert_eq!(
+            verify_request(&[destination_secret], &[origin_public], b"changed", &proof),
+            Err(FederationError::Invalid)
+        );
+
+        let mut tampered = proof.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(
+            verify_request(
+                &[destination_secret],
+                &[origin_public],
+                b"original",
+                &tampered
+            ),
+            Err(FederationError::Invalid)
+        );
+
+        let short_nonce = RelayProof {
+            nonce: vec![0; 12],
+            ..proof
+        };
+        assert_eq!(
+            verify_request(
+                &[destination_secret],
+                &[origin_public],
+                b"original",
+                &short_nonce
+            ),
+            Err(FederationError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_unknown_origin_is_rejected() {
+        let (_, origin_public) = relay_key();
+        let (impostor_secret, _) = relay_key();
+        let (destination_secret, destination_public) = relay_key();
+
+        // Claiming to be the origin does not help without its secret key
+        let proof = seal_request(&impostor_secret, &destination_public, b"request").unwrap();
+        assert_eq!(
+            verify_request(&[destination_secret], &[origin_public], b"request", &proof),
+            Err(FederationError::Invalid)
+        );
+
+        // Nor does a proof made for another relay
+        let (_, other_public) = relay_key();
+        let (origin_secret, origin_public) = relay_key();
+        let proof = seal_request(&origin_secret, &other_public, b"request").unwrap();
+        assert_eq!(
+            verify_request(&[destination_secret], &[origin_public], b"request", &proof),
+            Err(FederationError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_rotated_keys_are_tried() {
+        let (old_origin_secret, old_origin_public) = relay_key();
+        let (new_origin_secret, new_origin_public) = relay_key();
+        let (old_destination_secret, old_destination_public) = relay_key();
+        let (new_destination_secret, new_destination_public) = relay_key();
+        let own = [new_destination_secret, old_destination_secret];
+        let origin = [new_origin_public, old_origin_public];
+
+        // Made with keys either side has rotated away from since
+        let proof = seal_request(&old_origin_secret, &old_destination_public, b"r").unwrap();
+        assert_eq!(verify_request(&own, &origin, b"r", &proof), Ok(()));
+
+        let proof = seal_request(&new_origin_secret, &new_destination_public, b"r").unwrap();
+        assert_eq!(verify_request(&own, &origin, b"r", &proof), Ok(()));
+
+        // A retired key is no longer accepted
+        assert_eq!(
+            verify_request(&own, &origin[..1], b"r", &{
+                seal_request(&old_origin_secret, &new_destination_public, b"r").unwrap()
+            }),
+            Err(FederationError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_stale_requests_are_rejected() {
+        let now = 1_700_000_000;
+        assert_eq!(check_freshness(now, now), Ok(()));
+        assert_eq!(check_freshness(now - MAX_CLOCK_SKEW_SECS, now), Ok(()));
+        assert_eq!(check_freshness(now + 30, now), Ok(()));
+        assert_eq!(
+            check_freshness(now - MAX_CLOCK_SKEW_SECS - 1, now),
+            Err(FederationError::Stale)
+        );
+        assert_eq!(
+            check_freshness(now + MAX_CLOCK_SKEW_SECS + 1, now),
+            Err(FederationError::Stale)
+        );
+    }
+
+    #[test]
+    fn test_relay_urls_are_normalized() {
+        assert_eq!(
+            normalize_relay_url(" https://Relay.Example.org/ "),
+            "https://relay.example.org"
+        );
+        assert_eq!(
+            normalize_relay_url("http://localhost:8080"),
+            normalize_relay_url("http://LOCALHOST:8080/")
+        );
+    }
+}
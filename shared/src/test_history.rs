@@ -0,0 +1,868 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::{
+        Contact, ControlMessage, EncryptedMessage, MessagePayload, ReceiptKind, Room,
+    };
+    use crate::history::*;
+    use crate::mailbox::retract_hash;
+    use crate::messaging::*;
+    use crate::persistence::database::Database;
+    use crate::retention::*;
+    use crate::user_data::UserData;
+    use serial_test::serial;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// In-memory relay mailboxes, keeping messages until acknowledged
+    #[derive(Default)]
+    struct MemoryMailboxes {
+        mailboxes: Mutex<HashMap<String, Vec<EncryptedMessage>>>,
+        /// Relay named by the latest deposit for each mailbox
+        relays: Mutex<HashMap<String, Option<String>>>,
+        /// Whether acknowledgements fail, as when the relay is unreachable
+        failing_acknowledge: AtomicBool,
+    }
+
+    impl MessageTransport for MemoryMailboxes {
+        async fn deposit(
+            &self,
+            recipient_hash: String,
+            message: EncryptedMessage,
+            relay: Option<String>,
+        ) -> Result<()> {
+            let mut message = message;
+            message.id = Some(hex::encode(&message.nonce));
+            self.relays
+                .lock()
+                .unwrap()
+                .insert(recipient_hash.clone(), relay);
+            self.mailboxes
+                .lock()
+                .unwrap()
+                .entry(recipient_hash)
+                .or_default()
+                .push(message);
+            Ok(())
+        }
+
+        async fn fetch(
+            &self,
+            recipient_hash: String,
+            _secret_key: &[u8; 32],
+        ) -> Result<Vec<EncryptedMessage>> {
+            Ok(self
+                .mailboxes
+                .lock()
+                .unwrap()
+                .get(&recipient_hash)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        async fn acknowledge(
+            &self,
+            recipient_hash: String,
+            _secret_key: &[u8; 32],
+            ids: Vec<String>,
+        ) -> Result<()> {
+            if self.failing_acknowledge.load(Ordering::Relaxed) {
+                return Err("Relay unreachable".into());
+            }
+            if let Some(mailbox) = self.mailboxes.lock().unwrap().get_mut(&recipient_hash) {
+                mailbox.retain(|message| !message.id.as_ref().is_some_and(|id| ids.contains(id)));
+            }
+            Ok(())
+        }
+
+        async fn retract(
+            &self,
+            recipient_hash: String,
+            nonce: Vec<u8>,
+            retract_secret: Vec<u8>,
+        ) -> Result<()> {
+            let hash = retract_hash(&retract_secret);
+            if let Some(mailbox) = self.mailboxes.lock().unwrap().get_mut(&recipient_hash) {
+                mailbox.retain(|message| {
+                    message.nonce != nonce || message.retract_hash.as_ref() != Some(&hash)
+                });
+            }
+            Ok(())
+        }
+    }
+
+    /// Two rooms in the same database that know each other's keys
+    fn paired_rooms(db: &Database) -> (String, String) {
+        let mut alice = Room::new("Alice");
+        let mut bob = Room::new("Bob");
+        alice.add_contact(&bob.public_key());
+        bob.add_contact(&alice.public_key());
+        (
+            db.save_entity(&mut alice).unwrap(),
+            db.save_entity(&mut bob).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_payload_without_message_id_still_parses() {
+        let payload: MessagePayload =
+            serde_json::from_str(r#"{"text":"hello","sent_at":1700000000}"#).unwrap();
+        assert_eq!(payload.text, "hello");
+        assert!(payload.message_id.is_empty());
+        assert!(!payload.is_control());
+    }
+
+    #[test]
+    fn test_reaction_payload_roundtrip() {
+        let payload = MessagePayload::reaction("abc", "👍", false);
+        let json = serde_json::to_string(&payload).unwrap();
+        let parsed: MessagePayload = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            parsed.control,
+            Some(ControlMessage::Reaction {
+                target_id: "abc".to_string(),
+                emoji: "👍".to_string(),
+                remove: false,
+            })
+        );
+        assert!(parsed.text.is_empty());
+    }
+
+    #[test]
+    fn test_reaction_summary() {
+        let payload = MessagePayload::new("hi");
+        let mut message = StoredMessage::from_payload("room", [1u8; 32], false, &payload);
+
+        message.apply_reaction(&[1u8; 32], "👍", false);
+        message.apply_reaction(&[2u8; 32], "👍", false);
+        message.apply_reaction(&[2u8; 32], "🎉", false);
+        // Reacting twice with the same emoji counts once
+        message.apply_reaction(&[2u8; 32], "🎉", false);
+
+        let summary = message.reaction_summary(&[2u8; 32]);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].emoji, "👍");
+        assert_eq!(summary[0].count, 2);
+        assert!(summary[0].reacted_by_me);
+        assert_eq!(summary[1].emoji, "🎉");
+        assert_eq!(summary[1].count, 1);
+
+        message.apply_reaction(&[2u8; 32], "🎉", true);
+        assert!(!message.has_reaction_from(&[2u8; 32], "🎉"));
+        assert!(!message.reactions.contains_key("🎉"));
+        assert!(!message.reaction_summary(&[3u8; 32])[0].reacted_by_me);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_first_message_with_an_id_wins() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let original = MessagePayload::new("original");
+        let mut forged = MessagePayload::new("forged");
+        forged.message_id = original.message_id.clone();
+
+        assert_eq!(
+            apply_payload(&db, "room", [1u8; 32], [1u8; 32], &original).unwrap(),
+            Some(original.message_id.clone())
+        );
+        assert_eq!(
+            apply_payload(&db, "room", [2u8; 32], [1u8; 32], &forged).unwrap(),
+            None
+        );
+
+        let history = load_room_history(&db, "room").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].text, "original");
+        assert!(history[0].is_own);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_reactions_apply_to_stored_message() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let message = MessagePayload::new("hello");
+        apply_payload(&db, "room", [1u8; 32], [1u8; 32], &message).unwrap();
+
+        let reaction = MessagePayload::reaction(&message.message_id, "❤️", false);
+        assert_eq!(
+            apply_payload(&db, "room", [2u8; 32], [1u8; 32], &reaction).unwrap(),
+            Some(message.message_id.clone())
+        );
+
+        // Reactions are not shown as messages of their own
+        let history = load_room_history(&db, "room").unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].has_reaction_from(&[2u8; 32], "❤️"));
+
+        let removal = MessagePayload::reaction(&message.message_id, "❤️", true);
+        apply_payload(&db, "room", [2u8; 32], [1u8; 32], &removal).unwrap();
+        let history = load_room_history(&db, "room").unwrap();
+        assert!(history[0].reactions.is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_invalid_reactions_are_ignored() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let unknown = MessagePayload::reaction("missing", "👍", false);
+        assert_eq!(
+            apply_payload(&db, "room", [2u8; 32], [1u8; 32], &unknown).unwrap(),
+            None
+        );
+
+        let message = MessagePayload::new("hello");
+        apply_payload(&db, "room", [1u8; 32], [1u8; 32], &message).unwrap();
+
+        let empty = MessagePayload::reaction(&message.message_id, "", false);
+        assert!(apply_payload(&db, "room", [2u8; 32], [1u8; 32], &empty).is_err());
+
+        let oversized = MessagePayload::reaction(&message.message_id, &"👍".repeat(20), false);
+        assert!(apply_payload(&db, "room", [2u8; 32], [1u8; 32], &oversized).is_err());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_reaction_roundtrip_through_relay() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        let message = MessagePayload::new("Lunch?");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 1);
+
+        let reaction = MessagePayload::reaction(&message.message_id, "🍕", false);
+        send_payload(&relay, &db, &bob_id, &reaction).await.unwrap();
+        let received = receive_messages(&relay, &db, &alice_id).await.unwrap();
+        assert_eq!(received.applied, 1);
+
+        let alice_room = db.load_entity::<Room>(&alice_id).unwrap().unwrap();
+        let bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+
+        for (room_id, room) in [(&alice_id, &alice_room), (&bob_id, &bob_room)] {
+            let history = load_room_history(&db, room_id).unwrap();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].text, "Lunch?");
+
+            let view = history[0].to_view(&room.public_key_bytes(), &[]);
+            assert_eq!(view.reactions.len(), 1);
+            assert_eq!(view.reactions[0].emoji, "🍕");
+            assert_eq!(view.reactions[0].reacted_by_me, room_id == &bob_id);
+        }
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_messages_stay_until_acknowledged() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+        let bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+        let bob_hash = recipient_hash(&bob_room.public_key_bytes());
+        let waiting = || relay.mailboxes.lock().unwrap()[&bob_hash].len();
+
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("Hi"))
+            .await
+            .unwrap();
+
+        // A failed acknowledgement leaves the message on the relay
+        relay.failing_acknowledge.store(true, Ordering::Relaxed);
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 1);
+        assert_eq!(waiting(), 1);
+
+        // Fetched again it is recognised and acknowledged without a second copy
+        relay.failing_acknowledge.store(false, Ordering::Relaxed);
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 0);
+        assert!(received.new_message_ids.is_empty());
+        assert_eq!(received.handled_ids.len(), 1);
+        assert_eq!(waiting(), 0);
+        assert_eq!(load_room_history(&db, &bob_id).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_rejected_messages_are_acknowledged() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+        let mut bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+        let bob_hash = recipient_hash(&bob_room.public_key_bytes());
+        let mut mallory_room = Room::new("Mallory");
+        mallory_room.add_contact(&bob_room.public_key());
+        bob_room.add_contact(&mallory_room.public_key());
+        db.update_entity(&bob_room).unwrap();
+
+        let message = MessagePayload::new("Hi");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+
+        // Mallory cannot edit Alice's message, and retrying would not change that
+        let forged = mallory_room
+            .encrypt_payload_for(
+                &bob_room.public_key(),
+                &MessagePayload::edit(&message.message_id, "forged"),
+            )
+            .unwrap();
+        relay.deposit(bob_hash.clone(), forged, None).await.unwrap();
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 0);
+        assert_eq!(received.handled_ids.len(), 1);
+        assert!(relay.mailboxes.lock().unwrap()[&bob_hash].is_empty());
+        assert_eq!(load_room_history(&db, &bob_id).unwrap()[0].text, "Hi");
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_deposits_name_the_contacts_home_relay() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+        let bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+        let bob_hash = recipient_hash(&bob_room.public_key_bytes());
+        let deposited_relay = || relay.relays.lock().unwrap().get(&bob_hash).cloned();
+
+        // Without a known home relay the message stays on ours
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("Hi"))
+            .await
+            .unwrap();
+        assert_eq!(deposited_relay(), Some(None));
+
+        let mut bob = Contact::with_key("Bob", &bob_room.public_key());
+        bob.relay = Some("https://relay.example.org".to_string());
+        db.save_entity(&mut bob).unwrap();
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("Hi"))
+            .await
+            .unwrap();
+        assert_eq!(
+            deposited_relay(),
+            Some(Some("https://relay.example.org".to_string()))
+        );
+
+        // A contact on our own relay is not forwarded anywhere
+        std::env::set_var("DIOXUS_SERVER_URL", "https://Relay.example.org/");
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("Hi"))
+            .await
+            .unwrap();
+        std::env::remove_var("DIOXUS_SERVER_URL");
+        assert_eq!(deposited_relay(), Some(None));
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_pushed_messages_apply_like_fetched_ones() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        let message = MessagePayload::new("Pushed");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+
+        // Live delivery hands over the mailbox contents instead of a fetch
+        let bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+        let mut pushed = relay
+            .mailboxes
+            .lock()
+            .unwrap()
+            .remove(&recipient_hash(&bob_room.public_key_bytes()))
+            .unwrap();
+        assert_eq!(pushed.len(), 1);
+
+        // Messages that do not decrypt are dropped, the rest still apply
+        let mut garbled = pushed[0].clone();
+        garbled.ciphertext[0] ^= 1;
+        pushed.push(garbled);
+
+        let received = apply_messages(&db, &bob_id, pushed).unwrap();
+        assert_eq!(received.applied, 1);
+        assert_eq!(received.new_message_ids, vec![message.message_id.clone()]);
+        assert_eq!(received.handled_ids.len(), 2);
+
+        let history = load_room_history(&db, &bob_id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].text, "Pushed");
+
+        // Nothing is left for a later fetch
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 0);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_only_sender_can_edit() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let message = MessagePayload::new("helo");
+        apply_payload(&db, "room", [1u8; 32], [2u8; 32], &message).unwrap();
+
+        let forged = MessagePayload::edit(&message.message_id, "forged");
+        assert!(apply_payload(&db, "room", [2u8; 32], [2u8; 32], &forged).is_err());
+
+        let edit = MessagePayload::edit(&message.message_id, "hello");
+        assert_eq!(
+            apply_payload(&db, "room", [1u8; 32], [2u8; 32], &edit).unwrap(),
+            Some(message.message_id.clone())
+        );
+
+        let history = load_room_history(&db, "room").unwrap();
+        assert_eq!(history[0].text, "hello");
+        assert!(history[0].is_edited());
+        assert_eq!(history[0].edits[0].previous_text, "helo");
+        assert_eq!(history[0].edits[0].edited_at, edit.sent_at);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_delete_leaves_tombstone() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let message = MessagePayload::new("oops");
+        apply_payload(&db, "room", [1u8; 32], [2u8; 32], &message).unwrap();
+        let reaction = MessagePayload::reaction(&message.message_id, "👍", false);
+        apply_payload(&db, "room", [2u8; 32], [2u8; 32], &reaction).unwrap();
+
+        let forged = MessagePayload::delete(&message.message_id);
+        assert!(apply_payload(&db, "room", [2u8; 32], [2u8; 32], &forged).is_err());
+
+        let delete = MessagePayload::delete(&message.message_id);
+        apply_payload(&db, "room", [1u8; 32], [2u8; 32], &delete).unwrap();
+
+        let history = load_room_history(&db, "room").unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].is_deleted());
+        assert!(history[0].text.is_empty());
+        assert!(history[0].reactions.is_empty());
+
+        // Tombstones ignore later changes
+        let edit = MessagePayload::edit(&message.message_id, "back");
+        assert_eq!(
+            apply_payload(&db, "room", [1u8; 32], [2u8; 32], &edit).unwrap(),
+            None
+        );
+        assert!(load_room_history(&db, "room").unwrap()[0].text.is_empty());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_delete_retracts_undelivered_copies() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        let message = MessagePayload::new("wrong room");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+
+        // Bob cannot change Alice's message
+        assert!(send_delete(&relay, &db, &bob_id, &message.message_id)
+            .await
+            .is_err());
+
+        // Knowing where a copy went is not enough to retract it
+        let bob_room = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+        let bob_hash = recipient_hash(&bob_room.public_key_bytes());
+        let nonce = relay.mailboxes.lock().unwrap()[&bob_hash][0].nonce.clone();
+        relay
+            .retract(bob_hash.clone(), nonce, vec![0u8; 32])
+            .await
+            .unwrap();
+        assert_eq!(relay.mailboxes.lock().unwrap()[&bob_hash].len(), 1);
+
+        send_delete(&relay, &db, &alice_id, &message.message_id)
+            .await
+            .unwrap();
+
+        // Only the delete reaches Bob, and it refers to nothing he has
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 0);
+        assert!(load_room_history(&db, &bob_id).unwrap().is_empty());
+        assert!(load_room_history(&db, &alice_id).unwrap()[0].is_deleted());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_edit_roundtrip_through_relay() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        let message = MessagePayload::new("See you at 5");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+
+        send_edit(&relay, &db, &alice_id, &message.message_id, "See you at 6")
+            .await
+            .unwrap();
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.applied, 1);
+
+        let history = load_room_history(&db, &bob_id).unwrap();
+        assert_eq!(history[0].text, "See you at 6");
+        assert_eq!(history[0].edits[0].previous_text, "See you at 5");
+    }
+
+    #[test]
+    fn test_receipt_and_typing_are_ephemeral() {
+        assert!(MessagePayload::receipt(vec!["abc".to_string()], ReceiptKind::Read).is_ephemeral());
+        assert!(MessagePayload::typing(true).is_ephemeral());
+        assert!(!MessagePayload::reaction("abc", "👍", false).is_ephemeral());
+        assert!(!MessagePayload::new("hi").is_ephemeral());
+
+        let mut alice = Room::new("Alice");
+        let bob = Room::new("Bob");
+        let message = alice
+            .encrypt_payload_for(&bob.public_key(), &MessagePayload::typing(true))
+            .unwrap();
+        assert!(message.ephemeral);
+    }
+
+    #[test]
+    fn test_delivery_state() {
+        let payload = MessagePayload::new("hi");
+        let mut message = StoredMessage::from_payload("room", [1u8; 32], true, &payload);
+        assert_eq!(message.delivery_state(), Some(DeliveryState::Sent));
+
+        assert!(message.apply_receipt(&[2u8; 32], ReceiptKind::Delivered));
+        assert!(!message.apply_receipt(&[2u8; 32], ReceiptKind::Delivered));
+        assert_eq!(message.delivery_state(), Some(DeliveryState::Delivered));
+
+        assert!(message.apply_receipt(&[2u8; 32], ReceiptKind::Read));
+        assert_eq!(message.delivery_state(), Some(DeliveryState::Read));
+
+        let received = StoredMessage::from_payload("room", [2u8; 32], false, &payload);
+        assert_eq!(received.delivery_state(), None);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_receipts_roundtrip_through_relay() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        let message = MessagePayload::new("Did you get this?");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+
+        let received = receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(received.new_message_ids, vec![message.message_id.clone()]);
+        send_receipt(
+            &relay,
+            &db,
+            &bob_id,
+            received.new_message_ids,
+            ReceiptKind::Delivered,
+        )
+        .await
+        .unwrap();
+
+        // Receipts are not shown as messages on either side
+        assert_eq!(load_room_history(&db, &bob_id).unwrap().len(), 1);
+        let received = receive_messages(&relay, &db, &alice_id).await.unwrap();
+        assert_eq!(received.applied, 1);
+        let history = load_room_history(&db, &alice_id).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].delivery_state(), Some(DeliveryState::Delivered));
+
+        let read = mark_read(&db, &bob_id).unwrap();
+        assert_eq!(read, vec![message.message_id.clone()]);
+        // Already read messages are not marked again
+        assert!(mark_read(&db, &bob_id).unwrap().is_empty());
+
+        send_receipt(&relay, &db, &bob_id, read, ReceiptKind::Read)
+            .await
+            .unwrap();
+        receive_messages(&relay, &db, &alice_id).await.unwrap();
+        let history = load_room_history(&db, &alice_id).unwrap();
+        assert_eq!(history[0].delivery_state(), Some(DeliveryState::Read));
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_receipts_only_change_own_messages() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (alice_id, bob_id) = paired_rooms(&db);
+        let alice = db.load_entity::<Room>(&alice_id).unwrap().unwrap();
+        let bob = db.load_entity::<Room>(&bob_id).unwrap().unwrap();
+
+        let message = MessagePayload::new("hi");
+        apply_payload(
+            &db,
+            &bob_id,
+            alice.public_key_bytes(),
+            bob.public_key_bytes(),
+            &message,
+        )
+        .unwrap();
+
+        // Alice cannot mark her own message as read in Bob's history
+        let receipt = MessagePayload::receipt(vec![message.message_id.clone()], ReceiptKind::Read);
+        let changed = apply_payload(
+            &db,
+            &bob_id,
+            alice.public_key_bytes(),
+            bob.public_key_bytes(),
+            &receipt,
+        )
+        .unwrap();
+        assert_eq!(changed, None);
+        assert!(load_room_history(&db, &bob_id).unwrap()[0]
+            .read_by
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_typing_indicator() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+        let alice_public = db
+            .load_entity::<Room>(&alice_id)
+            .unwrap()
+            .unwrap()
+            .public_key_bytes();
+
+        send_payload(&relay, &db, &alice_id, &MessagePayload::typing(true))
+            .await
+            .unwrap();
+        // Typing is not applied to the sender's own room
+        assert!(typing_members(&db, &alice_id, u64::MAX).unwrap().is_empty());
+
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(
+            typing_members(&db, &bob_id, now).unwrap(),
+            vec![alice_public]
+        );
+
+        // Indicators expire without a refresh
+        assert!(typing_members(&db, &bob_id, now + TYPING_TIMEOUT_SECS + 1)
+            .unwrap()
+            .is_empty());
+
+        // A message ends typing
+        send_payload(&relay, &db, &alice_id, &MessagePayload::typing(true))
+            .await
+            .unwrap();
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("done"))
+            .await
+            .unwrap();
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert!(typing_members(&db, &bob_id, now).unwrap().is_empty());
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn test_validate_retention() {
+        assert!(validate_retention(None).is_ok());
+        assert!(validate_retention(Some(60)).is_ok());
+        assert!(validate_retention(Some(MAX_RETENTION_SECS)).is_ok());
+        assert!(validate_retention(Some(0)).is_err());
+        assert!(validate_retention(Some(MAX_RETENTION_SECS + 1)).is_err());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_latest_retention_change_wins() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        assert!(apply_retention(&db, "room", Some(60), 100).unwrap());
+        // An older change arriving late is ignored
+        assert!(!apply_retention(&db, "room", Some(3600), 50).unwrap());
+        assert_eq!(room_retention(&db, "room").unwrap(), Some(60));
+
+        assert!(apply_retention(&db, "room", None, 200).unwrap());
+        assert_eq!(room_retention(&db, "room").unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_future_retention_change_does_not_pin_timer() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        assert!(apply_retention(&db, "room", Some(60), u64::MAX).unwrap());
+        assert!(apply_retention(&db, "room", Some(3600), now()).unwrap());
+        assert_eq!(room_retention(&db, "room").unwrap(), Some(3600));
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_disappearing_messages_roundtrip() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, bob_id) = paired_rooms(&db);
+
+        send_retention(&relay, &db, &alice_id, Some(60))
+            .await
+            .unwrap();
+        assert_eq!(room_retention(&db, &alice_id).unwrap(), Some(60));
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+        assert_eq!(room_retention(&db, &bob_id).unwrap(), Some(60));
+
+        let message = MessagePayload::new("This will self-destruct");
+        send_payload(&relay, &db, &alice_id, &message)
+            .await
+            .unwrap();
+        // Mailbox copies expire with the message
+        {
+            let mailboxes = relay.mailboxes.lock().unwrap();
+            let deposited = mailboxes.values().flatten().next().unwrap();
+            assert!(deposited.expires_in().is_some_and(|seconds| seconds <= 60));
+        }
+        receive_messages(&relay, &db, &bob_id).await.unwrap();
+
+        for room_id in [&alice_id, &bob_id] {
+            let history = load_room_history(&db, room_id).unwrap();
+            assert_eq!(history.len(), 1);
+            assert!(history[0].expires_at.is_some());
+        }
+
+        // Expired messages are hidden before the purge and removed by it
+        let later = now() + 61;
+        for room_id in [&alice_id, &bob_id] {
+            let history = load_room_history(&db, room_id).unwrap();
+            assert!(history[0].is_expired(later));
+        }
+        assert_eq!(purge_expired(&db, later).unwrap(), 2);
+        assert!(load_room_history(&db, &alice_id).unwrap().is_empty());
+        assert!(load_room_history(&db, &bob_id).unwrap().is_empty());
+        assert!(db
+            .load_entity::<SentMessage>(&SentMessage::key_for(&alice_id, &message.message_id))
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_messages_before_retention_are_kept() {
+        let db = Database::new();
+        let _ = db.clear();
+        let relay = MemoryMailboxes::default();
+        let (alice_id, _bob_id) = paired_rooms(&db);
+
+        send_payload(&relay, &db, &alice_id, &MessagePayload::new("kept"))
+            .await
+            .unwrap();
+        send_retention(&relay, &db, &alice_id, Some(60))
+            .await
+            .unwrap();
+
+        assert_eq!(purge_expired(&db, now() + 3600).unwrap(), 0);
+        assert_eq!(load_room_history(&db, &alice_id).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_purge_expired_encrypted_messages() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let mut expiring = EncryptedMessage {
+            expires_at: Some(100),
+            ..Default::default()
+        };
+        let mut kept = EncryptedMessage::default();
+        let expiring_id = db.save_entity(&mut expiring).unwrap();
+        let kept_id = db.save_entity(&mut kept).unwrap();
+
+        assert_eq!(purge_expired(&db, 99).unwrap(), 0);
+        assert_eq!(purge_expired(&db, 100).unwrap(), 1);
+        assert!(db
+            .load_entity::<EncryptedMessage>(&expiring_id)
+            .unwrap()
+            .is_none());
+        assert!(db
+            .load_entity::<EncryptedMessage>(&kept_id)
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_purge_covers_inactive_profiles() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let mut user = UserData::new("inactive", "Inactive");
+        db.save_entity(&mut user).unwrap();
+        let profile_id = user.id.clone().unwrap();
+        let profile_db = Database::for_profile(&profile_id).unwrap();
+        let _ = profile_db.clear();
+
+        let mut expiring = EncryptedMessage {
+            expires_at: Some(100),
+            ..Default::default()
+        };
+        let expiring_id = profile_db.save_entity(&mut expiring).unwrap();
+
+        assert_eq!(purge_all_profiles(100).unwrap(), 1);
+        assert!(profile_db
+            .load_entity::<EncryptedMessage>(&expiring_id)
+            .unwrap()
+            .is_none());
+        let _ = Database::drop_profile(&profile_id);
+    }
+}
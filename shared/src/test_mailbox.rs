@@ -0,0 +1,284 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::Room;
+    use crate::mailbox::*;
+    use std::collections::HashMap;
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+
+    fn challenge_for(room: &Room, id: &str) -> (MailboxChallenge, [u8; 32]) {
+        issue_challenge(id.to_string(), recipient_hash(&room.public_key_bytes()))
+    }
+
+    /// Issued challenges kept in memory, like the relay's challenge table
+    #[derive(Default)]
+    struct MemoryChallenges(Mutex<HashMap<String, (MailboxChallenge, [u8; 32])>>);
+
+    impl MemoryChallenges {
+        fn issue(&self, room: &Room, id: &str) -> MailboxChallenge {
+            let (challenge, server_secret) = challenge_for(room, id);
+            self.0
+                .lock()
+                .unwrap()
+                .insert(id.to_string(), (challenge.clone(), server_secret));
+            challenge
+        }
+    }
+
+    impl ChallengeStore for MemoryChallenges {
+        type Error = Infallible;
+
+        async fn take(
+            &self,
+            id: &str,
+            recipient_hash: &str,
+        ) -> Result<Option<(MailboxChallenge, [u8; 32])>, Infallible> {
+            let mut challenges = self.0.lock().unwrap();
+            let issued = challenges
+                .get(id)
+                .is_some_and(|(challenge, _)| challenge.recipient_hash == recipient_hash);
+            Ok(if issued { challenges.remove(id) } else { None })
+        }
+    }
+
+    #[test]
+    fn test_owner_proof_is_accepted() {
+        let room = Room::new("Alice");
+        let (challenge, server_secret) = challenge_for(&room, "c1");
+        assert_eq!(challenge.challenge.len(), CHALLENGE_LEN);
+        assert_eq!(challenge.server_public_key.len(), 32);
+
+        let proof = prove_ownership(&room.secret_key, &challenge).unwrap();
+        assert_eq!(proof.challenge_id, "c1");
+        assert_eq!(proof.public_key, room.public_key_bytes().to_vec());
+        assert_eq!(verify_ownership(&challenge, &server_secret, &proof), Ok(()));
+    }
+
+    #[test]
+    fn test_challenges_are_unique() {
+        let room = Room::new("Alice");
+        let (first, first_secret) = challenge_for(&room, "c1");
+        let (second, second_secret) = challenge_for(&room, "c2");
+        assert_ne!(first.challenge, second.challenge);
+        assert_ne!(first.server_public_key, second.server_public_key);
+        assert_ne!(first_secret, second_secret);
+    }
+
+    #[test]
+    fn test_replayed_proof_is_rejected() {
+        let room = Room::new("Alice");
+        let (first, first_secret) = challenge_for(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &first).unwrap();
+        assert_eq!(verify_ownership(&first, &first_secret, &proof), Ok(()));
+
+        // The relay discards a challenge once answered, a captured proof only
+        // ever meets a fresh one
+        let (second, second_secret) = challenge_for(&room, "c2");
+        assert_eq!(
+            verify_ownership(&second, &second_secret, &proof),
+            Err(ProofError::Invalid)
+        );
+
+        // Relabelling the proof does not help, it was boxed for another key
+        let relabelled = MailboxProof {
+            challenge_id: "c2".to_string(),
+            ..proof
+        };
+        assert_eq!(
+            verify_ownership(&second, &second_secret, &relabelled),
+            Err(ProofError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_proof_for_same_id_and_new_challenge_is_rejected() {
+        let room = Room::new("Alice");
+        let (first, _) = challenge_for(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &first).unwrap();
+
+        let (reissued, reissued_secret) = challenge_for(&room, "c1");
+        assert_eq!(
+            verify_ownership(&reissued, &reissued_secret, &proof),
+            Err(ProofError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_forged_proof_with_own_key_is_rejected() {
+        let victim = Room::new("Alice");
+        let attacker = Room::new("Mallory");
+        let (challenge, server_secret) = challenge_for(&victim, "c1");
+
+        // The attacker cannot even produce an answer for someone else's mailbox
+        assert_eq!(
+            prove_ownership(&attacker.secret_key, &challenge),
+            Err(ProofError::WrongMailbox)
+        );
+
+        // A proof for the attacker's own mailbox does not open the victim's
+        let (own_challenge, _) = challenge_for(&attacker, "c1");
+        let own_proof = prove_ownership(&attacker.secret_key, &own_challenge).unwrap();
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &own_proof),
+            Err(ProofError::WrongMailbox)
+        );
+    }
+
+    #[test]
+    fn test_forged_proof_claiming_victim_key_is_rejected() {
+        let victim = Room::new("Alice");
+        let attacker = Room::new("Mallory");
+        let (challenge, server_secret) = challenge_for(&victim, "c1");
+
+        // Boxed with the attacker's secret but claiming the victim's public key
+        let (attacker_challenge, _) = issue_challenge(
+            "c1".to_string(),
+            recipient_hash(&attacker.public_key_bytes()),
+        );
+        let attacker_challenge = MailboxChallenge {
+            server_public_key: challenge.server_public_key.clone(),
+            ..attacker_challenge
+        };
+        let forged = MailboxProof {
+            public_key: victim.public_key_bytes().to_vec(),
+            ..prove_ownership(&attacker.secret_key, &attacker_challenge).unwrap()
+        };
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &forged),
+            Err(ProofError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_tampered_proof_is_rejected() {
+        let room = Room::new("Alice");
+        let (challenge, server_secret) = challenge_for(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &challenge).unwrap();
+
+        let mut tampered = proof.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &tampered),
+            Err(ProofError::Invalid)
+        );
+
+        let mut tampered = proof.clone();
+        tampered.nonce[0] ^= 1;
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &tampered),
+            Err(ProofError::Invalid)
+        );
+
+        // Only the relay's key for this challenge opens the proof
+        let (_, other_secret) = challenge_for(&room, "c1");
+        assert_eq!(
+            verify_ownership(&challenge, &other_secret, &proof),
+            Err(ProofError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_malformed_proof_is_rejected() {
+        let room = Room::new("Alice");
+        let (challenge, server_secret) = challenge_for(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &challenge).unwrap();
+
+        let short_key = MailboxProof {
+            public_key: vec![0; 31],
+            ..proof.clone()
+        };
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &short_key),
+            Err(ProofError::Malformed)
+        );
+
+        let short_nonce = MailboxProof {
+            nonce: vec![0; 12],
+            ..proof.clone()
+        };
+        assert_eq!(
+            verify_ownership(&challenge, &server_secret, &short_nonce),
+            Err(ProofError::Malformed)
+        );
+
+        let bad_server_key = MailboxChallenge {
+            server_public_key: vec![0; 16],
+            ..challenge
+        };
+        assert_eq!(
+            prove_ownership(&room.secret_key, &bad_server_key),
+            Err(ProofError::Malformed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_same_proof_and_challenge_twice_is_rejected() {
+        let store = MemoryChallenges::default();
+        let room = Room::new("Alice");
+        let mailbox = recipient_hash(&room.public_key_bytes());
+        let challenge = store.issue(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &challenge).unwrap();
+
+        assert_eq!(redeem_proof(&store, &mailbox, &proof).await, Ok(()));
+        // The first use spent the challenge, the identical request fails
+        assert_eq!(
+            redeem_proof(&store, &mailbox, &proof).await,
+            Err(RedeemError::Rejected(ProofError::UnknownChallenge))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempt_spends_the_challenge() {
+        let store = MemoryChallenges::default();
+        let room = Room::new("Alice");
+        let mailbox = recipient_hash(&room.public_key_bytes());
+        let challenge = store.issue(&room, "c1");
+        let proof = prove_ownership(&room.secret_key, &challenge).unwrap();
+
+        let mut tampered = proof.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(
+            redeem_proof(&store, &mailbox, &tampered).await,
+            Err(RedeemError::Rejected(ProofError::Invalid))
+        );
+        // A guess cannot be followed by another attempt at the same challenge
+        assert_eq!(
+            redeem_proof(&store, &mailbox, &proof).await,
+            Err(RedeemError::Rejected(ProofError::UnknownChallenge))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_challenge_of_another_mailbox_is_not_spent() {
+        let store = MemoryChallenges::default();
+        let alice = Room::new("Alice");
+        let bob = Room::new("Bob");
+        let challenge = store.issue(&alice, "c1");
+        let proof = prove_ownership(&alice.secret_key, &challenge).unwrap();
+
+        let bob_mailbox = recipient_hash(&bob.public_key_bytes());
+        assert_eq!(
+            redeem_proof(&store, &bob_mailbox, &proof).await,
+            Err(RedeemError::Rejected(ProofError::UnknownChallenge))
+        );
+        let alice_mailbox = recipient_hash(&alice.public_key_bytes());
+        assert_eq!(redeem_proof(&store, &alice_mailbox, &proof).await, Ok(()));
+    }
+}
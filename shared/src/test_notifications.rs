@@ -0,0 +1,190 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::{MessagePayload, PresenceState, Room};
+    use crate::history::*;
+    use crate::notifications::*;
+    use crate::persistence::database::Database;
+    use crate::user_data::UserData;
+    use serial_test::serial;
+    use std::cell::{Cell, RefCell};
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// Sink that records notifications instead of showing them
+    #[derive(Default)]
+    struct RecordingSink {
+        shown: RefCell<Vec<Notification>>,
+        sounds: Cell<usize>,
+    }
+
+    impl NotificationSink for RecordingSink {
+        fn show(&self, notification: &Notification) -> Result<()> {
+            self.shown.borrow_mut().push(notification.clone());
+            Ok(())
+        }
+
+        fn play_sound(&self) -> Result<()> {
+            self.sounds.set(self.sounds.get() + 1);
+            Ok(())
+        }
+    }
+
+    fn settings() -> NotificationSettings {
+        NotificationSettings::from_user_data(&UserData::new("alice", "Alice Smith"))
+    }
+
+    /// A stored room with messages from another member
+    fn room_with_messages(db: &Database, texts: &[&str]) -> (Room, Vec<StoredMessage>) {
+        let mut room = Room::new("Team");
+        db.save_entity(&mut room).unwrap();
+        let room_id = room.id.clone().unwrap();
+        let own_public = room.public_key_bytes();
+
+        for (index, text) in texts.iter().enumerate() {
+            // Distinct send times keep the history in this order
+            let mut payload = MessagePayload::new(text);
+            payload.sent_at += index as u64;
+            apply_payload(db, &room_id, [9u8; 32], own_public, &payload).unwrap();
+        }
+        (room, load_room_history(db, &room_id).unwrap())
+    }
+
+    #[test]
+    fn test_mentions() {
+        let names = vec!["alice".to_string(), "Alice Smith".to_string()];
+        assert!(mentions("hey @alice, look", &names));
+        assert!(mentions("@ALICE", &names));
+        assert!(mentions("thanks @alice smith!", &names));
+        assert!(!mentions("hey alice", &names));
+        assert!(!mentions("hey @alicebob", &names));
+        assert!(!mentions("mail bob@alice.example", &names));
+        assert!(!mentions("@", &[String::new()]));
+    }
+
+    #[test]
+    fn test_notification_mode_names() {
+        for mode in NotificationMode::ALL {
+            assert_eq!(NotificationMode::parse(mode.as_str()), Some(mode));
+        }
+        assert_eq!(NotificationMode::parse("loud"), None);
+        assert_eq!(NotificationMode::default(), NotificationMode::All);
+    }
+
+    #[test]
+    fn test_settings_follow_user_data() {
+        let mut user = UserData::new("alice", "Alice Smith");
+        let settings = NotificationSettings::from_user_data(&user);
+        assert!(settings.enabled && settings.sound && settings.previews);
+        assert_eq!(settings.mention_names, vec!["alice", "Alice Smith"]);
+
+        user.set_sound_enabled(false);
+        user.set_show_message_previews(false);
+        let settings = NotificationSettings::from_user_data(&user);
+        assert!(settings.enabled && !settings.sound && !settings.previews);
+
+        user.set_chosen_presence(Some(PresenceState::DoNotDisturb));
+        assert!(!NotificationSettings::from_user_data(&user).enabled);
+
+        user.set_chosen_presence(None);
+        user.set_notifications_enabled(false);
+        assert!(!NotificationSettings::from_user_data(&user).enabled);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_notifications_follow_room_mode() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, messages) = room_with_messages(&db, &["lunch?", "@alice are you in?"]);
+        let room_id = room.id.clone().unwrap();
+
+        let all = notifications_for(&db, &room, &messages, &[], &settings()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].room_name, "Team");
+        assert_eq!(all[0].preview.as_deref(), Some("lunch?"));
+        assert!(!all[0].mentions_user);
+        assert!(all[1].mentions_user);
+
+        save_notification_mode(&db, &room_id, NotificationMode::MentionsOnly).unwrap();
+        assert_eq!(
+            room_notification_mode(&db, &room_id).unwrap(),
+            NotificationMode::MentionsOnly
+        );
+        let mentions = notifications_for(&db, &room, &messages, &[], &settings()).unwrap();
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].message_id, messages[1].message_id);
+
+        save_notification_mode(&db, &room_id, NotificationMode::Muted).unwrap();
+        assert!(notifications_for(&db, &room, &messages, &[], &settings())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_own_and_deleted_messages_do_not_notify() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, mut messages) = room_with_messages(&db, &["first", "second"]);
+        messages[0].is_own = true;
+        let deleted_at = messages[1].sent_at + 1;
+        messages[1].apply_delete(deleted_at);
+
+        assert!(notifications_for(&db, &room, &messages, &[], &settings())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_hidden_previews_and_sound() {
+        let db = Database::new();
+        let _ = db.clear();
+        let long = "x".repeat(500);
+        let (room, messages) = room_with_messages(&db, &["secret plans", &long]);
+
+        let mut settings = settings();
+        let notifications = notifications_for(&db, &room, &messages, &[], &settings).unwrap();
+        let preview = notifications[1].preview.clone().unwrap();
+        assert!(preview.ends_with('…'));
+        assert!(preview.chars().count() < 200);
+
+        let sink = RecordingSink::default();
+        deliver(&sink, &notifications, &settings).unwrap();
+        assert_eq!(sink.shown.borrow().len(), 2);
+        // One sound for the whole batch
+        assert_eq!(sink.sounds.get(), 1);
+
+        settings.previews = false;
+        settings.sound = false;
+        let notifications = notifications_for(&db, &room, &messages, &[], &settings).unwrap();
+        assert!(notifications.iter().all(|n| n.preview.is_none()));
+
+        let sink = RecordingSink::default();
+        deliver(&sink, &notifications, &settings).unwrap();
+        assert_eq!(sink.shown.borrow().len(), 2);
+        assert_eq!(sink.sounds.get(), 0);
+
+        // Nothing to show, nothing to hear
+        let sink = RecordingSink::default();
+        deliver(&sink, &[], &self::settings()).unwrap();
+        assert_eq!(sink.sounds.get(), 0);
+    }
+}
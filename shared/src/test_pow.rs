@@ -0,0 +1,115 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::pow::*;
+    use std::cell::Cell;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn challenge(difficulty: u32) -> PowChallenge {
+        PowChallenge {
+            id: "challenge".to_string(),
+            nonce: vec![7; 16],
+            difficulty,
+            expires_at: 1_700_000_300,
+        }
+    }
+
+    #[test]
+    fn test_solution_verifies() {
+        let challenge = challenge(10);
+        let solution = solve(&challenge, b"blinded token", || NOW).unwrap();
+        assert!(verify_solution(&challenge, b"blinded token", solution));
+
+        // Without difficulty anything goes
+        assert!(verify_solution(&self::challenge(0), b"anything", 0));
+    }
+
+    #[test]
+    fn test_solution_is_bound_to_challenge_and_payload() {
+        let challenge = challenge(12);
+        let solution = solve(&challenge, b"blinded token", || NOW).unwrap();
+
+        // The work only pays for the request and challenge it was done for
+        let other_nonce = PowChallenge {
+            nonce: vec![8; 16],
+            ..challenge.clone()
+        };
+        let other_id = PowChallenge {
+            id: "other".to_string(),
+            ..challenge.clone()
+        };
+        assert!(!verify_solution(&challenge, b"another token", solution));
+        assert!(!verify_solution(&other_nonce, b"blinded token", solution));
+        assert!(!verify_solution(&other_id, b"blinded token", solution));
+    }
+
+    #[test]
+    fn test_difficulty_is_bounded() {
+        let impossible = challenge(MAX_DIFFICULTY + 1);
+        assert_eq!(solve(&impossible, b"token", || NOW), None);
+        assert!(!verify_solution(&impossible, b"token", 0));
+    }
+
+    #[test]
+    fn test_solving_stops_at_expiry() {
+        // Expired before the search starts
+        let easy = challenge(0);
+        assert_eq!(solve(&easy, b"token", || easy.expires_at), None);
+
+        // Expires while searching, far before 2^40 hashes are done
+        let hard = challenge(40);
+        let looks = Cell::new(0);
+        let clock = || {
+            looks.set(looks.get() + 1);
+            if looks.get() > 2 {
+                hard.expires_at
+            } else {
+                NOW
+            }
+        };
+        assert_eq!(solve(&hard, b"token", clock), None);
+        assert_eq!(looks.get(), 3);
+    }
+
+    #[test]
+    fn test_challenge_expiry() {
+        let challenge = challenge(8);
+        assert!(!challenge.is_expired(challenge.expires_at - 1));
+        assert!(challenge.is_expired(challenge.expires_at));
+    }
+
+    #[test]
+    fn test_difficulty_adapts_to_issuance_rate() {
+        // Up to the target the base difficulty applies
+        assert_eq!(adaptive_difficulty(16, 24, 100, 0), 16);
+        assert_eq!(adaptive_difficulty(16, 24, 100, 100), 16);
+
+        // Every doubling of the rate costs one more bit
+        assert_eq!(adaptive_difficulty(16, 24, 100, 101), 17);
+        assert_eq!(adaptive_difficulty(16, 24, 100, 200), 17);
+        assert_eq!(adaptive_difficulty(16, 24, 100, 201), 18);
+        assert_eq!(adaptive_difficulty(16, 24, 100, 800), 19);
+
+        // Never beyond the maximum
+        assert_eq!(adaptive_difficulty(16, 24, 100, u64::MAX), 24);
+        assert_eq!(adaptive_difficulty(16, 16, 100, 1_000), 16);
+        assert_eq!(adaptive_difficulty(16, 24, 0, 2), 17);
+    }
+}
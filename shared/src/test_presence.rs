@@ -0,0 +1,160 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::{Contact, MessagePayload, PresenceState};
+    use crate::history::*;
+    use crate::persistence::database::Database;
+    use crate::presence::*;
+    use serial_test::serial;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_presence_is_ephemeral() {
+        let payload = MessagePayload::presence(PresenceState::Away);
+        assert!(payload.is_control());
+        assert!(payload.is_ephemeral());
+        assert!(payload.control.as_ref().unwrap().target_id().is_none());
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"state\":\"away\""));
+        assert_eq!(PresenceState::DoNotDisturb.as_str(), "do_not_disturb");
+    }
+
+    #[test]
+    fn test_idle_user_goes_away() {
+        let mut tracker = PresenceTracker::new();
+        assert_eq!(tracker.resolve(None, 15, NOW), PresenceState::Online);
+        assert_eq!(
+            tracker.resolve(None, 15, NOW + 14 * 60),
+            PresenceState::Online
+        );
+        assert_eq!(
+            tracker.resolve(None, 15, NOW + 15 * 60),
+            PresenceState::Away
+        );
+
+        tracker.record_activity(NOW + 20 * 60);
+        assert_eq!(
+            tracker.resolve(None, 15, NOW + 21 * 60),
+            PresenceState::Online
+        );
+
+        // Zero minutes turns auto-away off
+        assert_eq!(
+            tracker.resolve(None, 0, NOW + 24 * 60 * 60),
+            PresenceState::Online
+        );
+    }
+
+    #[test]
+    fn test_chosen_presence_wins() {
+        let mut tracker = PresenceTracker::new();
+        tracker.record_activity(NOW);
+        assert_eq!(
+            tracker.resolve(Some(PresenceState::DoNotDisturb), 15, NOW + 60 * 60),
+            PresenceState::DoNotDisturb
+        );
+        assert_eq!(
+            tracker.resolve(Some(PresenceState::Offline), 15, NOW),
+            PresenceState::Offline
+        );
+    }
+
+    #[test]
+    fn test_announcements_on_change_and_heartbeat() {
+        let mut tracker = PresenceTracker::new();
+        assert!(tracker.needs_announcement(PresenceState::Online, NOW));
+        tracker.mark_announced(PresenceState::Online, NOW);
+
+        assert!(!tracker.needs_announcement(PresenceState::Online, NOW + 1));
+        assert!(tracker.needs_announcement(PresenceState::Away, NOW + 1));
+        assert!(tracker.needs_announcement(PresenceState::Online, NOW + PRESENCE_HEARTBEAT_SECS));
+
+        // Appearing offline is announced once, without a heartbeat
+        tracker.mark_announced(PresenceState::Offline, NOW);
+        assert!(!tracker.needs_announcement(PresenceState::Offline, NOW + 24 * 60 * 60));
+        assert!(tracker.needs_announcement(PresenceState::Online, NOW + 1));
+    }
+
+    #[test]
+    fn test_contact_presence_times_out() {
+        let mut contact = Contact::default();
+        assert_eq!(contact_presence(&contact, NOW), PresenceState::Offline);
+
+        assert!(contact.apply_presence(PresenceState::DoNotDisturb, NOW));
+        assert_eq!(contact.last_seen, Some(NOW));
+        assert_eq!(
+            contact_presence(&contact, NOW + 1),
+            PresenceState::DoNotDisturb
+        );
+        assert_eq!(
+            contact_presence(&contact, NOW + PRESENCE_TIMEOUT_SECS),
+            PresenceState::Offline
+        );
+
+        // Announcements arriving out of order do not roll back
+        assert!(!contact.apply_presence(PresenceState::Online, NOW - 10));
+        assert_eq!(contact.presence, Some(PresenceState::DoNotDisturb));
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_presence_updates_contact_last_seen() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let sender = [7u8; 32];
+        let mut contact = Contact {
+            name: "Alice".to_string(),
+            public_key: sender,
+            ..Default::default()
+        };
+        let contact_id = db.save_entity(&mut contact).unwrap();
+
+        let mut stranger = Contact {
+            name: "Mallory".to_string(),
+            public_key: [8u8; 32],
+            ..Default::default()
+        };
+        let stranger_id = db.save_entity(&mut stranger).unwrap();
+
+        let payload = MessagePayload::presence(PresenceState::Away);
+        assert_eq!(
+            apply_payload(&db, "room", sender, [1u8; 32], &payload).unwrap(),
+            None
+        );
+
+        let contact = db.load_entity::<Contact>(&contact_id).unwrap().unwrap();
+        assert_eq!(contact.presence, Some(PresenceState::Away));
+        assert_eq!(contact.last_seen, Some(payload.sent_at));
+        let stranger = db.load_entity::<Contact>(&stranger_id).unwrap().unwrap();
+        assert_eq!(stranger.presence, None);
+        assert_eq!(stranger.last_seen, None);
+
+        // Presence is not history
+        assert!(load_room_history(&db, "room").unwrap().is_empty());
+
+        // Our own announcements, echoed back, change nothing
+        let own = MessagePayload::presence(PresenceState::Offline);
+        apply_payload(&db, "room", sender, sender, &own).unwrap();
+        let contact = db.load_entity::<Contact>(&contact_id).unwrap().unwrap();
+        assert_eq!(contact.presence, Some(PresenceState::Away));
+    }
+}
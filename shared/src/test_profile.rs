@@ -0,0 +1,254 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::{EncryptedMessage, Room};
+    use crate::local::*;
+    use crate::messaging::MessageSubscription;
+    use crate::persistence::database::{Database, Entity};
+    use crate::profile::ProfileSession;
+    use crate::user_data::UserData;
+    use serial_test::serial;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// Live connection that only records the mailboxes subscribed
+    #[derive(Default)]
+    struct RecordedSubscription {
+        mailboxes: Vec<String>,
+    }
+
+    impl MessageSubscription for RecordedSubscription {
+        async fn subscribe(
+            &mut self,
+            recipient_hash: String,
+            _secret_key: &[u8; 32],
+        ) -> Result<()> {
+            self.mailboxes.push(recipient_hash);
+            Ok(())
+        }
+
+        async fn next(&mut self) -> Result<(String, Vec<EncryptedMessage>)> {
+            Err("Nothing is pushed".into())
+        }
+    }
+
+    fn room_names(rooms: Vec<String>) -> Vec<String> {
+        let mut names: Vec<String> = rooms
+            .iter()
+            .map(|json| Room::from_json(json).unwrap().name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_first_profile_becomes_active() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        assert!(get_active_profile_id().await.unwrap().is_none());
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let _bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+
+        assert_eq!(get_active_profile_id().await.unwrap(), Some(alice.clone()));
+
+        let current = get_current_user_data().await.unwrap().unwrap();
+        assert_eq!(UserData::from_json(&current).unwrap().username, "alice");
+
+        let _ = Database::drop_profile(&alice);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_profiles_are_isolated() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+
+        create_room("Alice Room".into(), None).await.unwrap();
+        create_contact("Carol".into(), hex::encode([7u8; 32]))
+            .await
+            .unwrap();
+
+        select_profile(bob.clone()).await.unwrap();
+        assert!(get_all_rooms().await.unwrap().is_empty());
+        assert!(get_all_contacts().await.unwrap().is_empty());
+
+        create_room("Bob Room".into(), None).await.unwrap();
+        assert_eq!(room_names(get_all_rooms().await.unwrap()), vec!["Bob Room"]);
+
+        select_profile(alice.clone()).await.unwrap();
+        assert_eq!(
+            room_names(get_all_rooms().await.unwrap()),
+            vec!["Alice Room"]
+        );
+        assert_eq!(get_all_contacts().await.unwrap().len(), 1);
+
+        let _ = Database::drop_profile(&alice);
+        let _ = Database::drop_profile(&bob);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_active_profile_is_persisted() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let _alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+        select_profile(bob.clone()).await.unwrap();
+
+        let session = ProfileSession::load(&Database::new()).unwrap();
+        assert_eq!(session.active_profile_id, Some(bob.clone()));
+
+        let current = get_current_user_data().await.unwrap().unwrap();
+        assert_eq!(UserData::from_json(&current).unwrap().username, "bob");
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_select_unknown_profile_fails() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        assert!(select_profile("user_data:missing".into()).await.is_err());
+        assert!(get_active_profile_id().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_delete_active_profile_switches_and_drops_data() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+        create_room("Alice Room".into(), None).await.unwrap();
+
+        delete_profile(alice.clone()).await.unwrap();
+
+        assert_eq!(get_active_profile_id().await.unwrap(), Some(bob.clone()));
+        assert!(get_all_rooms().await.unwrap().is_empty());
+        assert_eq!(get_all_user_data().await.unwrap().len(), 1);
+
+        let alice_db = Database::for_profile(&alice).unwrap();
+        let rooms: Vec<Room> = alice_db.load_all_entities(Room::key_prefix()).unwrap();
+        assert!(rooms.is_empty());
+
+        delete_profile(bob).await.unwrap();
+        assert!(get_active_profile_id().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_unscoped_data_is_adopted_by_first_profile() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        // Rooms created before any profile existed
+        create_room("Legacy Room".into(), None).await.unwrap();
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            room_names(get_all_rooms().await.unwrap()),
+            vec!["Legacy Room"]
+        );
+
+        let unscoped: Vec<Room> = db.load_all_entities(Room::key_prefix()).unwrap();
+        assert!(unscoped.is_empty());
+
+        let _ = Database::drop_profile(&alice);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_live_subscriptions_follow_profile_and_rooms() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        let alice = create_profile("alice".into(), "Alice".into())
+            .await
+            .unwrap();
+        let bob = create_profile("bob".into(), "Bob".into()).await.unwrap();
+        let first_room = create_room("First".into(), None).await.unwrap();
+
+        let mut subscription = RecordedSubscription::default();
+        let mut subscribed = LiveSubscriptions::default();
+        assert_eq!(
+            subscribe_rooms(&mut subscription, &mut subscribed)
+                .await
+                .unwrap(),
+            SubscriptionState::Current(1)
+        );
+        assert_eq!(subscribed.profile_id, Some(alice.clone()));
+
+        // New rooms are added to the same connection
+        let second_room = create_room("Second".into(), None).await.unwrap();
+        assert_eq!(
+            subscribe_rooms(&mut subscription, &mut subscribed)
+                .await
+                .unwrap(),
+            SubscriptionState::Current(1)
+        );
+        assert_eq!(subscription.mailboxes.len(), 2);
+        assert!(subscribed.rooms.values().any(|id| *id == second_room));
+
+        // A deleted room cannot be unsubscribed, the connection is replaced
+        delete_room(first_room).await.unwrap();
+        assert_eq!(
+            subscribe_rooms(&mut subscription, &mut subscribed)
+                .await
+                .unwrap(),
+            SubscriptionState::Stale
+        );
+
+        let mut subscription = RecordedSubscription::default();
+        let mut subscribed = LiveSubscriptions::default();
+        subscribe_rooms(&mut subscription, &mut subscribed)
+            .await
+            .unwrap();
+        assert_eq!(subscription.mailboxes.len(), 1);
+
+        // So is one subscribed for another profile
+        select_profile(bob.clone()).await.unwrap();
+        assert_eq!(
+            subscribe_rooms(&mut subscription, &mut subscribed)
+                .await
+                .unwrap(),
+            SubscriptionState::Stale
+        );
+
+        let _ = Database::drop_profile(&alice);
+        let _ = Database::drop_profile(&bob);
+    }
+}
@@ -0,0 +1,288 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::message::{MessagePayload, Room};
+    use crate::history::*;
+    use crate::persistence::database::{Database, Entity};
+    use crate::retention::*;
+    use crate::search::*;
+    use serial_test::serial;
+
+    /// A stored room and its own public key
+    fn saved_room(db: &Database, name: &str) -> (Room, [u8; 32]) {
+        let mut room = Room::new(name);
+        db.save_entity(&mut room).unwrap();
+        let own_public = room.public_key_bytes();
+        (room, own_public)
+    }
+
+    fn send(db: &Database, room: &Room, own_public: [u8; 32], payload: &MessagePayload) {
+        let room_id = room.id.as_deref().unwrap();
+        apply_payload(db, room_id, own_public, own_public, payload).unwrap();
+    }
+
+    fn found_ids(db: &Database, room: &Room, query: &str) -> Vec<String> {
+        SearchIndex::for_room(room)
+            .unwrap()
+            .search(db, query)
+            .unwrap()
+            .into_iter()
+            .map(|message| message.message_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("Hello, World! It's 2025."),
+            vec!["hello", "world", "it", "s", "2025"]
+        );
+        assert_eq!(tokenize("Über café"), vec!["über", "café"]);
+        assert_eq!(tokenize("会议 at 3"), vec!["会", "议", "at", "3"]);
+        assert!(tokenize("  ... ").is_empty());
+    }
+
+    #[test]
+    fn test_highlight_snippet() {
+        let query = tokenize("lunch");
+        let parts = highlight_snippet("Are we still on for Lunch tomorrow?", &query);
+        let highlighted: Vec<&str> = parts
+            .iter()
+            .filter(|part| part.highlighted)
+            .map(|part| part.text.as_str())
+            .collect();
+        assert_eq!(highlighted, vec!["Lunch"]);
+        let joined: String = parts.iter().map(|part| part.text.as_str()).collect();
+        assert_eq!(joined, "Are we still on for Lunch tomorrow?");
+
+        // Long texts are cut around the first match
+        let long = format!("{} needle {}", "hay ".repeat(50), "hay ".repeat(50));
+        let parts = highlight_snippet(&long, &tokenize("need"));
+        assert_eq!(parts.first().unwrap().text, "…");
+        assert_eq!(parts.last().unwrap().text, "…");
+        assert!(parts
+            .iter()
+            .any(|part| part.highlighted && part.text == "needle"));
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_search_matches_word_prefixes() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+
+        let lunch = MessagePayload::new("Lunch at the Italian place?");
+        let dinner = MessagePayload::new("Dinner at eight");
+        send(&db, &room, own, &lunch);
+        send(&db, &room, own, &dinner);
+
+        assert_eq!(
+            found_ids(&db, &room, "ital"),
+            vec![lunch.message_id.clone()]
+        );
+        assert_eq!(
+            found_ids(&db, &room, "LUNCH italian"),
+            vec![lunch.message_id.clone()]
+        );
+        assert_eq!(found_ids(&db, &room, "at").len(), 2);
+        assert!(found_ids(&db, &room, "lunch eight").is_empty());
+        assert!(found_ids(&db, &room, "talian").is_empty());
+        assert!(found_ids(&db, &room, "").is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_index_does_not_store_plaintext() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+        let message = MessagePayload::new("confidential merger plans");
+        send(&db, &room, own, &message);
+
+        let terms: Vec<SearchTerm> = db.load_all_entities(SearchTerm::key_prefix()).unwrap();
+        let documents: Vec<SearchDocument> =
+            db.load_all_entities(SearchDocument::key_prefix()).unwrap();
+        assert!(!terms.is_empty());
+        assert_eq!(documents.len(), 1);
+        for term in &terms {
+            let key = term.id().unwrap();
+            assert!(!key.contains("merger") && !key.contains("confidential"));
+            let sealed = String::from_utf8_lossy(&term.ciphertext);
+            assert!(!sealed.contains(&message.message_id));
+        }
+
+        // Another room's index uses another key
+        let (other, _) = saved_room(&db, "Other");
+        assert!(found_ids(&db, &other, "merger").is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_edits_and_deletes_update_index() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+
+        let message = MessagePayload::new("meet at the station");
+        send(&db, &room, own, &message);
+        assert_eq!(
+            found_ids(&db, &room, "station"),
+            vec![message.message_id.clone()]
+        );
+
+        send(
+            &db,
+            &room,
+            own,
+            &MessagePayload::edit(&message.message_id, "meet at the airport"),
+        );
+        assert!(found_ids(&db, &room, "station").is_empty());
+        assert_eq!(
+            found_ids(&db, &room, "airport"),
+            vec![message.message_id.clone()]
+        );
+
+        send(
+            &db,
+            &room,
+            own,
+            &MessagePayload::delete(&message.message_id),
+        );
+        assert!(found_ids(&db, &room, "airport").is_empty());
+        assert!(found_ids(&db, &room, "meet").is_empty());
+
+        // Nothing of the message is left in the index
+        let terms: Vec<SearchTerm> = db.load_all_entities(SearchTerm::key_prefix()).unwrap();
+        let documents: Vec<SearchDocument> =
+            db.load_all_entities(SearchDocument::key_prefix()).unwrap();
+        assert!(terms.is_empty());
+        assert!(documents.is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_purged_messages_leave_index() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+
+        let mut message = MessagePayload::new("self destructing note");
+        message.expires_in = Some(60);
+        send(&db, &room, own, &message);
+        assert_eq!(found_ids(&db, &room, "note").len(), 1);
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        purge_expired(&db, now + 61).unwrap();
+        assert!(found_ids(&db, &room, "note").is_empty());
+        let documents: Vec<SearchDocument> =
+            db.load_all_entities(SearchDocument::key_prefix()).unwrap();
+        assert!(documents.is_empty());
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_search_rooms_newest_first() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (first, own_first) = saved_room(&db, "First");
+        let (second, own_second) = saved_room(&db, "Second");
+
+        let mut older = MessagePayload::new("budget draft");
+        older.sent_at -= 100;
+        let newer = MessagePayload::new("final budget");
+        send(&db, &first, own_first, &older);
+        send(&db, &second, own_second, &newer);
+
+        let hits = search_rooms(&db, &[first.clone(), second.clone()], &[], "budget").unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].message_id, newer.message_id);
+        assert_eq!(hits[0].room_name, "Second");
+        assert_eq!(hits[1].room_id, first.id.clone().unwrap());
+        assert!(hits[0]
+            .snippet
+            .iter()
+            .any(|part| part.highlighted && part.text == "budget"));
+
+        let hit = SearchHit::from_json(&hits[1].to_json().unwrap()).unwrap();
+        assert_eq!(hit, hits[1]);
+
+        let hits = search_rooms(&db, &[first], &[], "budget").unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_each_posting_is_stored_apart() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+
+        let alpha = MessagePayload::new("shared alpha");
+        let beta = MessagePayload::new("shared beta");
+        send(&db, &room, own, &alpha);
+        send(&db, &room, own, &beta);
+
+        // One posting per indexed prefix and message: 5 + 4 and 5 + 3
+        let terms: Vec<SearchTerm> = db.load_all_entities(SearchTerm::key_prefix()).unwrap();
+        assert_eq!(terms.len(), 17);
+        assert_eq!(found_ids(&db, &room, "shared").len(), 2);
+
+        send(&db, &room, own, &MessagePayload::delete(&alpha.message_id));
+        assert_eq!(found_ids(&db, &room, "shared"), vec![beta.message_id]);
+        let terms: Vec<SearchTerm> = db.load_all_entities(SearchTerm::key_prefix()).unwrap();
+        assert_eq!(terms.len(), 8);
+    }
+
+    #[test]
+    #[serial(local_db)]
+    fn test_deleted_room_leaves_nothing_behind() {
+        let db = Database::new();
+        let _ = db.clear();
+        let (room, own) = saved_room(&db, "Search");
+        let (other, other_own) = saved_room(&db, "Other");
+        let room_id = room.id.clone().unwrap();
+
+        send(&db, &room, own, &MessagePayload::new("quarterly report"));
+        send(
+            &db,
+            &other,
+            other_own,
+            &MessagePayload::new("quarterly review"),
+        );
+        apply_retention(&db, &room_id, Some(60), 1).unwrap();
+
+        assert!(delete_room_data(&db, &room_id).unwrap() > 0);
+        assert!(load_room_history(&db, &room_id).unwrap().is_empty());
+        assert_eq!(room_retention(&db, &room_id).unwrap(), None);
+        let documents: Vec<SearchDocument> = db
+            .load_all_entities(&SearchDocument::room_prefix(&room_id))
+            .unwrap();
+        let terms: Vec<SearchTerm> = db
+            .load_all_entities(&SearchTerm::room_prefix(&room_id))
+            .unwrap();
+        assert!(documents.is_empty() && terms.is_empty());
+
+        // Other rooms keep their history and index
+        assert_eq!(found_ids(&db, &other, "quarterly").len(), 1);
+    }
+}
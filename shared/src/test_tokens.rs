@@ -0,0 +1,140 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::blind_token::AnonymousToken;
+    use crate::persistence::database::Database;
+    use crate::tokens::*;
+    use serial_test::serial;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+    /// Issues batches of `batch` numbered tokens and remembers the ones
+    /// redeemed
+    struct CountingSource {
+        batch: usize,
+        issued: AtomicUsize,
+        redeemed: Mutex<Vec<String>>,
+        refuse: AtomicBool,
+    }
+
+    impl CountingSource {
+        fn new(batch: usize) -> Self {
+            Self {
+                batch,
+                issued: AtomicUsize::new(0),
+                redeemed: Mutex::new(Vec::new()),
+                refuse: AtomicBool::new(false),
+            }
+        }
+
+        fn issued(&self) -> usize {
+            self.issued.load(Ordering::SeqCst)
+        }
+    }
+
+    impl TokenSource for CountingSource {
+        async fn issue(&self) -> Result<Vec<AnonymousToken>> {
+            let n = self.issued.fetch_add(1, Ordering::SeqCst);
+            Ok((0..self.batch)
+                .map(|i| AnonymousToken {
+                    key_id: 1,
+                    nonce: vec![n as u8, i as u8],
+                    signature: Vec::new(),
+                })
+                .collect())
+        }
+
+        async fn redeem(&self, token: AnonymousToken) -> Result<String> {
+            if self.refuse.load(Ordering::SeqCst) {
+                return Err("relay unreachable".into());
+            }
+            let secret = token.secret();
+            self.redeemed.lock().unwrap().push(secret.clone());
+            Ok(secret)
+        }
+    }
+
+    struct FailingSource;
+
+    impl TokenSource for FailingSource {
+        async fn issue(&self) -> Result<Vec<AnonymousToken>> {
+            Err("issuance disabled".into())
+        }
+
+        async fn redeem(&self, _token: AnonymousToken) -> Result<String> {
+            Err("nothing to redeem".into())
+        }
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_every_deposit_spends_its_own_token() {
+        let db = Database::new();
+        let _ = db.clear();
+        let source = CountingSource::new(2);
+
+        let spent = [
+            take_token(&source, &db).await.unwrap(),
+            take_token(&source, &db).await.unwrap(),
+            take_token(&source, &db).await.unwrap(),
+        ];
+
+        assert_eq!(spent, ["0001", "0000", "0101"]);
+        assert_eq!(*source.redeemed.lock().unwrap(), spent);
+        assert_eq!(source.issued(), 2);
+        assert_eq!(wallet_size(&db).unwrap(), 1);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_failed_redemption_does_not_reuse_the_token() {
+        let db = Database::new();
+        let _ = db.clear();
+        let source = CountingSource::new(3);
+
+        source.refuse.store(true, Ordering::SeqCst);
+        assert!(take_token(&source, &db).await.is_err());
+        assert_eq!(wallet_size(&db).unwrap(), 2);
+
+        source.refuse.store(false, Ordering::SeqCst);
+        assert_eq!(take_token(&source, &db).await.unwrap(), "0001");
+        assert_eq!(source.issued(), 1);
+    }
+
+    #[tokio::test]
+    #[serial(local_db)]
+    async fn test_failed_issuance_keeps_no_token() {
+        let db = Database::new();
+        let _ = db.clear();
+
+        assert!(take_token(&FailingSource, &db).await.is_err());
+        assert_eq!(wallet_size(&db).unwrap(), 0);
+        let source = CountingSource::new(1);
+        assert_eq!(take_token(&source, &db).await.unwrap(), "0000");
+    }
+
+    #[test]
+    fn test_redeem_delay_is_bounded() {
+        for _ in 0..100 {
+            assert!(redeem_delay() <= MAX_REDEEM_DELAY);
+        }
+    }
+}
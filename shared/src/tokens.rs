@@ -0,0 +1,120 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Message tokens held by the client.
+//! Every deposit spends a token of its own, so the relay cannot link two
+//! deposits by the token that paid for them. Tokens are obtained in batches
+//! from a `TokenSource` and kept unredeemed in the wallet. Each is redeemed
+//! only when a deposit needs it, after a random delay and apart from the
+//! request that issued it, so its redemption cannot be linked to the
+//! issuance either. A token leaves the wallet before it is redeemed, so a
+//! failed attempt never presents it twice.
+
+use std::future::Future;
+use std::time::Duration;
+
+use crypto_box::aead::{rand_core::RngCore, OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::blind_token::AnonymousToken;
+use crate::persistence::database::{Database, Entity};
+
+// Type alias for convenience
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Storage key of the wallet
+const WALLET_KEY: &str = "message_token:wallet";
+
+/// Longest a redemption is held back, see `redeem_delay`
+pub const MAX_REDEEM_DELAY: Duration = Duration::from_secs(5);
+
+/// Held while a token is taken, so concurrent deposits neither obtain two
+/// batches nor take the same token twice
+static WALLET: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Unredeemed tokens a profile spends on its deposits
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TokenWallet {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub tokens: Vec<AnonymousToken>,
+}
+
+impl TokenWallet {
+    pub fn new(tokens: Vec<AnonymousToken>) -> Self {
+        Self {
+            id: Some(WALLET_KEY.to_string()),
+            tokens,
+        }
+    }
+}
+
+impl Entity for TokenWallet {
+    fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = Some(id);
+    }
+
+    fn key_prefix() -> &'static str {
+        "message_token"
+    }
+}
+
+/// Obtains and redeems anonymous tokens, for example at the relay
+pub trait TokenSource {
+    /// A batch of unblinded tokens, not redeemed yet
+    fn issue(&self) -> impl Future<Output = Result<Vec<AnonymousToken>>>;
+    /// Redeem `token` apart from the request that issued it, giving the
+    /// secret to deposit with
+    fn redeem(&self, token: AnonymousToken) -> impl Future<Output = Result<String>>;
+}
+
+/// Random time to wait before redeeming a token, up to `MAX_REDEEM_DELAY`,
+/// so a redemption does not follow its issuance at a telling interval
+pub fn redeem_delay() -> Duration {
+    let millis = MAX_REDEEM_DELAY.as_millis() as u64;
+    Duration::from_millis(OsRng.next_u64() % (millis + 1))
+}
+
+/// Unredeemed tokens left in the wallet of `db`
+pub fn wallet_size(db: &Database) -> Result<usize> {
+    Ok(db
+        .load_entity::<TokenWallet>(WALLET_KEY)?
+        .map_or(0, |wallet| wallet.tokens.len()))
+}
+
+/// Secret of a fresh token to spend on the next deposit. A new batch is
+/// obtained when the wallet is empty.
+pub async fn take_token(source: &impl TokenSource, db: &Database) -> Result<String> {
+    let token = {
+        let _wallet = WALLET.lock().await;
+        let mut wallet = match db.load_entity::<TokenWallet>(WALLET_KEY)? {
+            Some(wallet) if !wallet.tokens.is_empty() => wallet,
+            _ => TokenWallet::new(source.issue().await?),
+        };
+        let Some(token) = wallet.tokens.pop() else {
+            return Err("The relay issued no tokens".into());
+        };
+        wallet.id = Some(WALLET_KEY.to_string());
+        db.save_entity(&mut wallet)?;
+        token
+    };
+    source.redeem(token).await
+}
@@ -18,11 +18,29 @@
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
+use crate::crypto::attachment::Attachment;
+use crate::crypto::identity::{
+    generate_identity_keypair, identity_fingerprint, identity_public_from_secret, ProfileUpdate,
+    RoomInvite, RoomKeyBinding, Signed,
+};
+use crate::crypto::message::{PresenceState, Room};
 use crate::persistence::database::Entity;
 
 // Type alias for convenience
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Number of recently used emojis remembered for the emoji picker
+pub const MAX_RECENT_EMOJIS: usize = 24;
+
+/// Serde default for settings that are on unless the user opts out
+fn default_true() -> bool {
+    true
+}
+
+fn default_time_format() -> String {
+    "auto".to_string()
+}
+
 /// Helper function to get current timestamp
 fn current_timestamp() -> u64 {
     std::time::SystemTime::now()
@@ -40,15 +58,39 @@ pub struct UserData {
     pub display_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avatar_url: Option<String>,
+    /// Imported avatar image, stored encrypted in the profile database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Attachment>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status_message: Option<String>,
     pub recent_rooms: VecDeque<String>,
     pub max_recent_rooms: usize,
     pub theme: String,
     pub language: String,
+    /// "12h" or "24h" clock, "auto" uses the language's usual one
+    #[serde(default = "default_time_format")]
+    pub time_format: String,
     pub notifications_enabled: bool,
     pub sound_enabled: bool,
+    /// Show the start of a message in notifications
+    #[serde(default = "default_true")]
+    pub show_message_previews: bool,
     pub auto_away_minutes: u32,
+    /// Presence picked by the user, `None` follows the idle timer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chosen_presence: Option<PresenceState>,
+    #[serde(default = "default_true")]
+    pub send_delivery_receipts: bool,
+    #[serde(default = "default_true")]
+    pub send_read_receipts: bool,
+    #[serde(default = "default_true")]
+    pub send_typing_indicators: bool,
+    #[serde(default)]
+    pub recent_emojis: VecDeque<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_secret_key: Option<[u8; 32]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity_public_key: Option<[u8; 32]>,
     pub created_at: u64,
     pub last_updated: u64,
 }
@@ -56,19 +98,30 @@ pub struct UserData {
 impl Default for UserData {
     fn default() -> Self {
         let now = current_timestamp();
+        let (identity_secret, identity_public) = generate_identity_keypair();
         Self {
             id: None,
             username: String::new(),
             display_name: String::new(),
             avatar_url: None,
+            avatar: None,
             status_message: None,
             recent_rooms: VecDeque::new(),
             max_recent_rooms: 10,
             theme: "dark".to_string(),
             language: "en".to_string(),
+            time_format: default_time_format(),
             notifications_enabled: true,
             sound_enabled: true,
+            show_message_previews: true,
             auto_away_minutes: 15,
+            chosen_presence: None,
+            send_delivery_receipts: true,
+            send_read_receipts: true,
+            send_typing_indicators: true,
+            recent_emojis: VecDeque::new(),
+            identity_secret_key: Some(identity_secret),
+            identity_public_key: Some(identity_public),
             created_at: now,
             last_updated: now,
         }
@@ -102,6 +155,12 @@ impl UserData {
         self.update_timestamp();
     }
 
+    /// Update the imported avatar image
+    pub fn set_avatar(&mut self, avatar: Option<Attachment>) {
+        self.avatar = avatar;
+        self.update_timestamp();
+    }
+
     /// Update the status message
     pub fn set_status_message(&mut self, status_message: Option<String>) {
         self.status_message = status_message;
@@ -145,6 +204,17 @@ impl UserData {
         self.recent_rooms.iter().cloned().collect()
     }
 
+    /// Record an emoji as recently used, most recent first
+    pub fn add_recent_emoji(&mut self, emoji: String) {
+        if let Some(pos) = self.recent_emojis.iter().position(|e| e == &emoji) {
+            self.recent_emojis.remove(pos);
+        }
+
+        self.recent_emojis.push_front(emoji);
+        self.recent_emojis.truncate(MAX_RECENT_EMOJIS);
+        self.update_timestamp();
+    }
+
     /// Set the maximum number of recent rooms to keep
     pub fn set_max_recent_rooms(&mut self, max_recent_rooms: usize) {
         self.max_recent_rooms = max_recent_rooms;
@@ -169,6 +239,12 @@ impl UserData {
         self.update_timestamp();
     }
 
+    /// Update the 12/24 hour clock preference
+    pub fn set_time_format(&mut self, time_format: String) {
+        self.time_format = time_format;
+        self.update_timestamp();
+    }
+
     /// Enable/disable notifications
     pub fn set_notifications_enabled(&mut self, enabled: bool) {
         self.notifications_enabled = enabled;
@@ -181,6 +257,36 @@ impl UserData {
         self.update_timestamp();
     }
 
+    /// Pick a presence, or go back to following the idle timer with `None`
+    pub fn set_chosen_presence(&mut self, presence: Option<PresenceState>) {
+        self.chosen_presence = presence;
+        self.update_timestamp();
+    }
+
+    /// Show/hide message text in notifications
+    pub fn set_show_message_previews(&mut self, enabled: bool) {
+        self.show_message_previews = enabled;
+        self.update_timestamp();
+    }
+
+    /// Enable/disable telling senders their messages arrived
+    pub fn set_send_delivery_receipts(&mut self, enabled: bool) {
+        self.send_delivery_receipts = enabled;
+        self.update_timestamp();
+    }
+
+    /// Enable/disable telling senders their messages were read
+    pub fn set_send_read_receipts(&mut self, enabled: bool) {
+        self.send_read_receipts = enabled;
+        self.update_timestamp();
+    }
+
+    /// Enable/disable showing others when we are typing
+    pub fn set_send_typing_indicators(&mut self, enabled: bool) {
+        self.send_typing_indicators = enabled;
+        self.update_timestamp();
+    }
+
     /// Set auto-away timeout in minutes
     pub fn set_auto_away_minutes(&mut self, minutes: u32) {
         self.auto_away_minutes = minutes;
@@ -211,6 +317,67 @@ impl UserData {
         }
     }
 
+    /// Generate an identity key if this profile predates identity keys.
+    /// Returns true if a new key was generated and the profile needs saving.
+    pub fn ensure_identity_key(&mut self) -> bool {
+        if self.identity_secret_key.is_some() && self.identity_public_key.is_some() {
+            return false;
+        }
+
+        let (identity_secret, identity_public) = generate_identity_keypair();
+        self.identity_secret_key = Some(identity_secret);
+        self.identity_public_key = Some(identity_public);
+        self.update_timestamp();
+        true
+    }
+
+    /// Get the fingerprint of the identity key for out-of-band verification
+    pub fn identity_fingerprint(&self) -> Option<String> {
+        self.identity_public_key.as_ref().map(identity_fingerprint)
+    }
+
+    fn identity_secret(&self) -> Result<&[u8; 32]> {
+        self.identity_secret_key
+            .as_ref()
+            .ok_or_else(|| "Profile has no identity key".into())
+    }
+
+    /// Sign a room's public key with the identity key
+    pub fn sign_room_key(&self, room: &Room) -> Result<Signed<RoomKeyBinding>> {
+        Signed::sign(
+            self.identity_secret()?,
+            RoomKeyBinding::new(room.public_key_bytes()),
+        )
+    }
+
+    /// Create a signed invite for a room, naming the relay we receive on.
+    /// The room key vouches for our identity, see `RoomInvite::room_signature`.
+    pub fn create_invite(&self, room: &Room, relay: Option<String>) -> Result<Signed<RoomInvite>> {
+        let identity_secret = self.identity_secret()?;
+        Signed::sign(
+            identity_secret,
+            RoomInvite::new(
+                self.effective_display_name(),
+                &room.name,
+                room.public_key_bytes(),
+            )
+            .with_relay(relay)
+            .with_room_signature(
+                &room.secret_key_bytes(),
+                &identity_public_from_secret(identity_secret),
+            ),
+        )
+    }
+
+    /// Create a signed profile update for contacts
+    pub fn sign_profile_update(&self) -> Result<Signed<ProfileUpdate>> {
+        Signed::sign(
+            self.identity_secret()?,
+            ProfileUpdate::new(self.effective_display_name(), self.status_message.clone())
+                .with_avatar(self.avatar.clone()),
+        )
+    }
+
     /// Serialize to JSON
     pub fn to_json(&self) -> Result<String> {
         Ok(serde_json::to_string(&self)?)
@@ -276,13 +443,17 @@ mod tests {
         assert_eq!(user.display_name, "Alice Smith");
         assert_eq!(user.theme, "dark");
         assert_eq!(user.language, "en");
+        assert_eq!(user.time_format, "auto");
         assert_eq!(user.max_recent_rooms, 10);
         assert!(user.notifications_enabled);
         assert!(user.sound_enabled);
         assert_eq!(user.auto_away_minutes, 15);
+        assert!(user.chosen_presence.is_none());
         assert!(user.recent_rooms.is_empty());
         assert!(user.avatar_url.is_none());
         assert!(user.status_message.is_none());
+        assert!(user.identity_secret_key.is_some());
+        assert!(user.identity_public_key.is_some());
     }
 
     #[test]
@@ -349,6 +520,24 @@ mod tests {
         assert!(user.get_most_recent_room().is_none());
     }
 
+    #[test]
+    fn test_recent_emojis_management() {
+        let mut user = UserData::new("test", "Test");
+        assert!(user.recent_emojis.is_empty());
+
+        user.add_recent_emoji("👍".to_string());
+        user.add_recent_emoji("🎉".to_string());
+        user.add_recent_emoji("👍".to_string());
+        assert_eq!(user.recent_emojis, vec!["👍", "🎉"]);
+
+        for i in 0..MAX_RECENT_EMOJIS {
+            user.add_recent_emoji(format!("e{i}"));
+        }
+        assert_eq!(user.recent_emojis.len(), MAX_RECENT_EMOJIS);
+        assert_eq!(user.recent_emojis[0], format!("e{}", MAX_RECENT_EMOJIS - 1));
+        assert!(!user.recent_emojis.contains(&"👍".to_string()));
+    }
+
     #[test]
     fn test_setters_update_timestamp() {
         let mut user = UserData::new("test", "Test");
@@ -402,6 +591,9 @@ mod tests {
         user.set_language("fr".to_string());
         assert_eq!(user.language, "fr");
 
+        user.set_time_format("24h".to_string());
+        assert_eq!(user.time_format, "24h");
+
         user.set_notifications_enabled(false);
         assert!(!user.notifications_enabled);
 
@@ -412,6 +604,80 @@ mod tests {
         assert_eq!(user.auto_away_minutes, 60);
     }
 
+    #[test]
+    fn test_receipt_opt_outs() {
+        let mut user = UserData::default();
+        assert!(user.send_delivery_receipts);
+        assert!(user.send_read_receipts);
+        assert!(user.send_typing_indicators);
+
+        user.set_send_read_receipts(false);
+        user.set_send_typing_indicators(false);
+        assert!(user.send_delivery_receipts);
+        assert!(!user.send_read_receipts);
+        assert!(!user.send_typing_indicators);
+
+        // Profiles saved before the opt-outs existed keep everything enabled
+        let mut json: serde_json::Value = serde_json::from_str(&user.to_json().unwrap()).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("send_delivery_receipts");
+        fields.remove("send_read_receipts");
+        fields.remove("send_typing_indicators");
+        let loaded = UserData::from_json(&json.to_string()).unwrap();
+        assert!(loaded.send_delivery_receipts);
+        assert!(loaded.send_read_receipts);
+        assert!(loaded.send_typing_indicators);
+    }
+
+    #[test]
+    fn test_identity_keys_are_unique_and_persisted() {
+        let alice = UserData::new("alice", "Alice");
+        let bob = UserData::new("bob", "Bob");
+        assert_ne!(alice.identity_public_key, bob.identity_public_key);
+
+        let json = alice.to_json().expect("Should serialize to JSON");
+        let loaded = UserData::from_json(&json).expect("Should deserialize from JSON");
+        assert_eq!(loaded.identity_secret_key, alice.identity_secret_key);
+        assert_eq!(loaded.identity_public_key, alice.identity_public_key);
+        assert_eq!(loaded.identity_fingerprint(), alice.identity_fingerprint());
+    }
+
+    #[test]
+    fn test_legacy_user_data_gets_identity_key() {
+        let mut user = UserData::new("legacy", "Legacy");
+        user.identity_secret_key = None;
+        user.identity_public_key = None;
+
+        // Stored profiles from before identity keys have no identity fields
+        let json = user.to_json().expect("Should serialize to JSON");
+        assert!(!json.contains("identity"));
+        let mut loaded = UserData::from_json(&json).expect("Should deserialize from JSON");
+        assert!(loaded.sign_profile_update().is_err());
+
+        assert!(loaded.ensure_identity_key());
+        assert!(!loaded.ensure_identity_key());
+        assert!(loaded.sign_profile_update().is_ok());
+    }
+
+    #[test]
+    fn test_signed_statements_verify_against_identity() {
+        let user = UserData::new("alice", "Alice");
+        let identity = user.identity_public_key.unwrap();
+        let room = Room::new("Alice Room");
+
+        let binding = user.sign_room_key(&room).unwrap();
+        assert_eq!(binding.payload.room_public, room.public_key_bytes());
+        assert!(binding.verify_from(&identity).is_ok());
+
+        let invite = user.create_invite(&room, None).unwrap();
+        assert_eq!(invite.payload.display_name, "Alice");
+        assert_eq!(invite.payload.room_name, "Alice Room");
+        assert!(invite.verify_from(&identity).is_ok());
+
+        let update = user.sign_profile_update().unwrap();
+        assert!(update.verify_from(&identity).is_ok());
+    }
+
     #[test]
     fn test_age_and_recent_modification() {
         let user = UserData::new("test", "Test");
@@ -0,0 +1,135 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{current_i18n, I18nContext, Icon, IconName};
+use dioxus::prelude::*;
+use shared::crypto::attachment::Attachment;
+
+const ATTACHMENT_CARD_CSS: Asset = asset!("/assets/styling/attachment_card.css");
+
+/// Pick the file card icon for an attachment's MIME type
+pub fn attachment_icon(mime_type: &str) -> IconName {
+    let mime_type = mime_type.to_ascii_lowercase();
+    if mime_type.starts_with("image/") {
+        IconName::Image
+    } else if mime_type.contains("spreadsheet")
+        || mime_type.contains("excel")
+        || mime_type == "text/csv"
+    {
+        IconName::FileSpreadsheet
+    } else if mime_type == "application/pdf"
+        || mime_type.starts_with("text/")
+        || mime_type.contains("document")
+        || mime_type.contains("msword")
+    {
+        IconName::FileText
+    } else {
+        IconName::File
+    }
+}
+
+/// Translation key for the kind of file shown on the card
+fn attachment_kind_key(icon: &IconName) -> &'static str {
+    match icon {
+        IconName::Image => "attachments.kind.image",
+        IconName::FileSpreadsheet => "attachments.kind.spreadsheet",
+        IconName::FileText => "attachments.kind.document",
+        _ => "attachments.kind.file",
+    }
+}
+
+/// Human readable file size
+pub fn format_file_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct AttachmentCardProps {
+    pub attachment: Attachment,
+    /// Download progress from 0.0 to 1.0 while a download is running
+    #[props(default = None)]
+    pub progress: Option<f32>,
+    #[props(default = current_i18n())]
+    pub i18n: I18nContext,
+    #[props(optional)]
+    pub on_open: Option<EventHandler<Attachment>>,
+}
+
+#[component]
+pub fn AttachmentCard(props: AttachmentCardProps) -> Element {
+    let icon = attachment_icon(&props.attachment.mime_type);
+    let kind = props.i18n.translate(attachment_kind_key(&icon));
+    let size = format_file_size(props.attachment.size);
+    let downloading = props.progress.is_some_and(|progress| progress < 1.0);
+    let percent = (props.progress.unwrap_or(0.0) * 100.0).round();
+    let attachment = props.attachment.clone();
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: ATTACHMENT_CARD_CSS }
+
+        div {
+            class: "ac-card",
+            Icon {
+                name: icon,
+                i18n: props.i18n.clone(),
+                size: "28".to_string(),
+                class: "ac-icon".to_string()
+            }
+            div {
+                class: "ac-info",
+                p { class: "ac-name", title: "{props.attachment.file_name}", "{props.attachment.file_name}" }
+                p { class: "ac-meta", "{kind} · {size}" }
+                if downloading {
+                    div {
+                        class: "ac-progress",
+                        div {
+                            class: "ac-progress-bar",
+                            style: "width: {percent}%",
+                        }
+                    }
+                }
+            }
+            button {
+                class: "ac-open-btn",
+                disabled: downloading,
+                title: "{props.i18n.translate(\"attachments.open\")}",
+                onclick: move |_| {
+                    if let Some(handler) = &props.on_open {
+                        handler.call(attachment.clone());
+                    }
+                },
+                if downloading {
+                    "{props.i18n.translate(\"attachments.downloading\")}"
+                } else {
+                    "{props.i18n.translate(\"attachments.open\")}"
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dioxus::prelude::*;
+
+/// Square region of the source image that becomes the avatar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AvatarCrop {
+    pub x: u32,
+    pub y: u32,
+    pub size: u32,
+}
+
+/// Centered square crop of a `width` x `height` image.
+/// A `zoom` of 1.0 keeps the whole short side, larger values crop closer.
+pub fn square_crop(width: u32, height: u32, zoom: f64) -> AvatarCrop {
+    let short_side = width.min(height);
+    let zoom = if zoom.is_finite() { zoom.max(1.0) } else { 1.0 };
+    let size = ((short_side as f64 / zoom).round() as u32)
+        .max(1)
+        .min(short_side);
+    AvatarCrop {
+        x: (width - size) / 2,
+        y: (height - size) / 2,
+        size,
+    }
+}
+
+/// Embed image data in a `data:` URL for an `img` source
+pub fn avatar_data_url(mime_type: &str, data: &[u8]) -> String {
+    format!("data:{mime_type};base64,{}", STANDARD.encode(data))
+}
+
+/// Split a base64 `data:` URL into its MIME type and data
+pub fn parse_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    let data = STANDARD.decode(data).ok()?;
+    Some((mime_type.to_string(), data))
+}
+
+/// First letter of a name, shown when there is no avatar image
+pub fn avatar_initial(name: &str) -> String {
+    name.chars()
+        .find(|c| c.is_alphanumeric())
+        .map(|c| c.to_uppercase().to_string())
+        .unwrap_or_else(|| "?".to_string())
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct AvatarProps {
+    /// Image source, usually from `avatar_data_url`
+    #[props(default = None)]
+    pub image: Option<String>,
+    pub name: String,
+    #[props(default = "avatar".to_string())]
+    pub class: String,
+}
+
+/// Round avatar showing an image, or the name's initial without one
+#[component]
+pub fn Avatar(props: AvatarProps) -> Element {
+    rsx! {
+        div {
+            class: "{props.class}",
+            title: "{props.name}",
+            if let Some(image) = props.image.as_ref() {
+                img {
+                    src: "{image}",
+                    alt: "{props.name}",
+                    style: "width: 100%; height: 100%; border-radius: inherit; object-fit: cover;",
+                }
+            } else {
+                "{avatar_initial(&props.name)}"
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{avatar_data_url, Avatar, ProfileContext};
+use dioxus::prelude::*;
+use shared::attachments::BlobTransport;
+use shared::local::{
+    download_contact_avatars, get_avatar_image, get_contact_avatar, share_profile_update,
+};
+use shared::messaging::MessageTransport;
+use shared::user_data::UserData;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How often contact avatars announced in profile updates are downloaded
+const AVATAR_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// App-wide avatar images.
+/// Contact avatars are cached encrypted in the profile database, components
+/// showing them reload when `revision` changes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct AvatarContext {
+    /// The active profile's avatar as an image source
+    pub own: Signal<Option<String>>,
+    /// Bumped whenever contact avatars were downloaded
+    pub revision: Signal<u64>,
+    share: Callback<()>,
+}
+
+impl AvatarContext {
+    /// Send the active profile's name, status and avatar to all contacts
+    pub fn share(&self) {
+        self.share.call(());
+    }
+}
+
+/// Image source for a profile's avatar, imported avatars take precedence
+async fn own_avatar(profile: Option<UserData>) -> Option<String> {
+    let profile = profile?;
+    if let Some(avatar) = profile.avatar {
+        let avatar_json = avatar.to_json().ok()?;
+        match get_avatar_image(avatar_json).await {
+            Ok(Some(data)) => return Some(avatar_data_url(&avatar.mime_type, &data)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to load avatar: {e}"),
+        }
+    }
+    profile.avatar_url
+}
+
+/// Provide the avatar context to the component tree, sharing profile
+/// updates through `messages` and moving avatar images through `blobs`
+pub fn use_avatar_provider<B, M>(profile: ProfileContext, blobs: B, messages: M) -> AvatarContext
+where
+    B: BlobTransport + 'static,
+    M: MessageTransport + 'static,
+{
+    let blobs = use_hook(|| Rc::new(blobs));
+    let messages = use_hook(|| Rc::new(messages));
+    let mut own = use_signal(|| Option::<String>::None);
+    let mut revision = use_signal(|| 0u64);
+
+    // Reload our avatar whenever the active profile or its avatar changes
+    use_effect(move || {
+        let active = profile.active_profile();
+        spawn(async move {
+            own.set(own_avatar(active).await);
+        });
+    });
+
+    let share_blobs = blobs.clone();
+    let share = use_callback(move |_: ()| {
+        let blobs = share_blobs.clone();
+        let messages = messages.clone();
+        spawn(async move {
+            if let Err(e) = share_profile_update(&*blobs, &*messages).await {
+                eprintln!("Failed to share profile: {e}");
+            }
+        });
+    });
+
+    use_future(move || {
+        let blobs = blobs.clone();
+        async move {
+            loop {
+                match download_contact_avatars(&*blobs).await {
+                    Ok(0) => {}
+                    Ok(_) => revision += 1,
+                    Err(e) => eprintln!("Failed to download contact avatars: {e}"),
+                }
+                tokio::time::sleep(AVATAR_SYNC_INTERVAL).await;
+            }
+        }
+    });
+
+    use_context_provider(|| AvatarContext {
+        own,
+        revision,
+        share,
+    })
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct ContactAvatarProps {
+    /// Contact whose avatar is shown, the initial is shown without one
+    #[props(default = None)]
+    pub contact_id: Option<String>,
+    pub name: String,
+    #[props(default = "avatar".to_string())]
+    pub class: String,
+}
+
+/// A contact's avatar, loaded from the profile database
+#[component]
+pub fn ContactAvatar(props: ContactAvatarProps) -> Element {
+    let revision = try_use_context::<AvatarContext>().map(|avatars| avatars.revision);
+    let mut image = use_signal(|| Option::<String>::None);
+
+    use_effect(use_reactive((&props.contact_id,), move |(contact_id,)| {
+        // Subscribe to downloads of new avatars
+        let _ = revision.map(|revision| revision());
+        spawn(async move {
+            let loaded = match contact_id {
+                Some(contact_id) => match get_contact_avatar(contact_id).await {
+                    Ok(avatar) => {
+                        avatar.map(|(mime_type, data)| avatar_data_url(&mime_type, &data))
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to load contact avatar: {e}");
+                        None
+                    }
+                },
+                None => None,
+            };
+            image.set(loaded);
+        });
+    }));
+
+    rsx! {
+        Avatar {
+            image: image(),
+            name: props.name.clone(),
+            class: props.class.clone(),
+        }
+    }
+}
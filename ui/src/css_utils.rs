@@ -16,42 +16,69 @@
  */
 
 /// Utility functions for working with CSS variables and colors
+use crate::theme::theme_variable;
 use std::collections::HashMap;
-use std::sync::OnceLock;
 
-/// Parse CSS variables from variables.css at compile time
-fn parse_css_variables() -> HashMap<String, String> {
-    let css_content = include_str!("../../mobile/assets/variables.css");
-    let mut variables = HashMap::new();
+/// Remove `/* ... */` comments from a stylesheet
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
 
-    for line in css_content.lines() {
-        let line = line.trim();
-        if line.starts_with("--") && line.contains(':') {
-            if let Some(colon_pos) = line.find(':') {
-                let var_name = line[..colon_pos].trim();
-                let var_value = line[colon_pos + 1..].trim().trim_end_matches(';').trim();
-                variables.insert(var_name.to_string(), var_value.to_string());
-            }
-        }
+/// Split a stylesheet into its `(selector, body)` rule blocks
+pub(crate) fn rule_blocks(css: &str) -> Vec<(String, String)> {
+    let css = strip_comments(css);
+    let mut blocks = Vec::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let selector = rest[..open].trim().to_string();
+        let body = rest[open + 1..open + close].to_string();
+        blocks.push((selector, body));
+        rest = &rest[open + close + 1..];
     }
+    blocks
+}
 
-    variables
+/// Parse the `name: value` declarations of a rule block body
+pub(crate) fn declarations(body: &str) -> Vec<(String, String)> {
+    body.split(';')
+        .filter_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            let (name, value) = (name.trim(), value.trim());
+            (!name.is_empty() && !value.is_empty()).then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
 }
 
-/// Get the static map of CSS variables
-fn get_css_variables() -> &'static HashMap<String, String> {
-    static CSS_VARIABLES: OnceLock<HashMap<String, String>> = OnceLock::new();
-    CSS_VARIABLES.get_or_init(parse_css_variables)
+/// Parse the CSS variables declared anywhere in a stylesheet
+pub(crate) fn parse_css_variables(css: &str) -> HashMap<String, String> {
+    rule_blocks(css)
+        .iter()
+        .flat_map(|(_, body)| declarations(body))
+        .filter(|(name, _)| name.starts_with("--"))
+        .collect()
 }
 
 /// Converts a CSS variable name to its actual color value
-/// Returns the actual hex color value for known CSS variables
+/// Returns the value the active theme gives the variable
 pub fn css_var_to_color(var_name: &str) -> Option<String> {
-    get_css_variables().get(var_name).cloned()
+    theme_variable(var_name)
 }
 
-/// Converts a CSS variable reference (like "var(--color-text-primary)") to its actual color value
-/// Also handles direct color values (like "#ffffff" or "red")
+/// Converts a CSS variable reference (like "var(--color-text-primary)") to its value in the
+/// active theme. Also handles direct color values (like "#ffffff" or "red")
 pub fn resolve_color(color_value: &str) -> String {
     if color_value.starts_with("var(") && color_value.ends_with(")") {
         // Extract the variable name from var(--variable-name)
@@ -0,0 +1,201 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{current_i18n, I18nContext};
+use dioxus::prelude::*;
+
+const EMOJI_PICKER_CSS: Asset = asset!("/assets/styling/emoji_picker.css");
+
+/// Emojis offered by the picker, each with the keywords it can be found by
+pub const EMOJI_CATALOG: &[(&str, &str)] = &[
+    ("😀", "grinning smile happy"),
+    ("😂", "joy laugh tears funny"),
+    ("🤣", "rofl rolling laugh"),
+    ("😊", "blush smile happy"),
+    ("😍", "heart eyes love"),
+    ("😘", "kiss love"),
+    ("😉", "wink"),
+    ("😎", "cool sunglasses"),
+    ("🤔", "thinking hmm"),
+    ("🙄", "eye roll"),
+    ("😴", "sleep tired zzz"),
+    ("😅", "sweat smile relief"),
+    ("😢", "cry sad tear"),
+    ("😭", "sob cry sad"),
+    ("😡", "angry mad rage"),
+    ("😱", "scream shock fear"),
+    ("😮", "wow surprised open mouth"),
+    ("🤯", "mind blown exploding"),
+    ("🥳", "party celebrate"),
+    ("🤗", "hug"),
+    ("🙃", "upside down silly"),
+    ("😬", "grimace awkward"),
+    ("🤐", "zipper secret quiet"),
+    ("🤫", "shush secret quiet"),
+    ("👍", "thumbs up yes like ok"),
+    ("👎", "thumbs down no dislike"),
+    ("👏", "clap applause"),
+    ("🙌", "raised hands hooray"),
+    ("🙏", "pray thanks please"),
+    ("👋", "wave hello bye"),
+    ("🤝", "handshake deal"),
+    ("✌️", "victory peace"),
+    ("👌", "ok perfect"),
+    ("💪", "strong muscle flex"),
+    ("👀", "eyes look"),
+    ("🫡", "salute"),
+    ("❤️", "red heart love"),
+    ("💔", "broken heart"),
+    ("💯", "hundred perfect score"),
+    ("🔥", "fire hot lit"),
+    ("✨", "sparkles shiny"),
+    ("⭐", "star"),
+    ("🎉", "tada party celebrate"),
+    ("🎂", "birthday cake"),
+    ("🎁", "gift present"),
+    ("✅", "check done yes"),
+    ("❌", "cross no wrong"),
+    ("❓", "question"),
+    ("❗", "exclamation important"),
+    ("⚠️", "warning caution"),
+    ("💡", "idea light bulb"),
+    ("🔒", "lock secure private"),
+    ("🔑", "key"),
+    ("🛡️", "shield security"),
+    ("🚀", "rocket launch ship"),
+    ("🐛", "bug"),
+    ("💻", "laptop computer"),
+    ("📱", "phone mobile"),
+    ("📎", "paperclip attachment"),
+    ("📅", "calendar date"),
+    ("⏰", "alarm clock time"),
+    ("☕", "coffee"),
+    ("🍕", "pizza food"),
+    ("🍺", "beer drink cheers"),
+    ("🌮", "taco food"),
+    ("🍎", "apple fruit"),
+    ("🐶", "dog puppy"),
+    ("🐱", "cat kitten"),
+    ("🦀", "crab rust"),
+    ("🦇", "bat cave"),
+    ("🌈", "rainbow"),
+    ("☀️", "sun sunny"),
+    ("🌙", "moon night"),
+    ("⚡", "lightning zap fast"),
+    ("🎵", "music note song"),
+    ("🏆", "trophy win"),
+];
+
+/// Emojis whose keywords match the search query, in catalog order
+pub fn search_emojis(query: &str) -> Vec<&'static str> {
+    let query = query.trim().to_lowercase();
+    EMOJI_CATALOG
+        .iter()
+        .filter(|(emoji, keywords)| {
+            query.is_empty()
+                || *emoji == query
+                || keywords
+                    .split_whitespace()
+                    .any(|keyword| keyword.starts_with(&query))
+        })
+        .map(|(emoji, _)| *emoji)
+        .collect()
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct EmojiPickerProps {
+    /// Recently used emojis, most recent first
+    #[props(default)]
+    pub recent: Vec<String>,
+    #[props(default = current_i18n())]
+    pub i18n: I18nContext,
+    pub on_select: EventHandler<String>,
+    #[props(optional)]
+    pub on_close: Option<EventHandler<()>>,
+}
+
+#[component]
+pub fn EmojiPicker(props: EmojiPickerProps) -> Element {
+    #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
+    let mut query = use_signal(|| String::new());
+    let results = search_emojis(&query());
+    let show_recent = query().trim().is_empty() && !props.recent.is_empty();
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: EMOJI_PICKER_CSS }
+
+        div {
+            class: "ep-picker",
+            "role": "dialog",
+            "aria-label": "{props.i18n.translate(\"emoji.title\")}",
+
+            div {
+                class: "ep-header",
+                input {
+                    r#type: "search",
+                    class: "ep-search",
+                    placeholder: "{props.i18n.translate(\"emoji.search_placeholder\")}",
+                    value: "{query}",
+                    oninput: move |evt| query.set(evt.value()),
+                }
+                if let Some(on_close) = props.on_close {
+                    button {
+                        class: "ep-close",
+                        title: "{props.i18n.translate(\"emoji.close\")}",
+                        onclick: move |_| on_close.call(()),
+                        "✕"
+                    }
+                }
+            }
+
+            if show_recent {
+                p { class: "ep-section-title", "{props.i18n.translate(\"emoji.recent\")}" }
+                div {
+                    class: "ep-grid",
+                    for emoji in props.recent.iter() {
+                        button {
+                            key: "recent-{emoji}",
+                            class: "ep-emoji",
+                            onclick: {
+                                let emoji = emoji.clone();
+                                move |_| props.on_select.call(emoji.clone())
+                            },
+                            "{emoji}"
+                        }
+                    }
+                }
+                p { class: "ep-section-title", "{props.i18n.translate(\"emoji.all\")}" }
+            }
+
+            if results.is_empty() {
+                p { class: "ep-empty", "{props.i18n.translate(\"emoji.no_results\")}" }
+            } else {
+                div {
+                    class: "ep-grid",
+                    for emoji in results {
+                        button {
+                            key: "{emoji}",
+                            class: "ep-emoji",
+                            onclick: move |_| props.on_select.call(emoji.to_string()),
+                            "{emoji}"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
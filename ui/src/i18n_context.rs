@@ -15,19 +15,32 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::message_format::{format_message, I18nArg};
+use crate::time_format::HourCycle;
+use dioxus::prelude::*;
 use std::collections::HashMap;
+#[cfg(debug_assertions)]
+use std::collections::HashSet;
+#[cfg(debug_assertions)]
+use std::sync::Mutex;
+use std::sync::{Arc, LazyLock};
+
+type Translations = HashMap<String, HashMap<String, String>>;
 
 #[derive(Clone)]
 pub struct I18nContext {
     pub current_locale: String,
     pub available_locales: Vec<String>,
-    translations: HashMap<String, HashMap<String, String>>,
+    /// The user's 12/24 hour clock preference, `None` follows the locale
+    pub hour_cycle: Option<HourCycle>,
+    translations: Arc<Translations>,
 }
 
 impl PartialEq for I18nContext {
     fn eq(&self, other: &Self) -> bool {
         self.current_locale == other.current_locale
             && self.available_locales == other.available_locales
+            && self.hour_cycle == other.hour_cycle
     }
 }
 
@@ -41,34 +54,39 @@ const ZH_TW_TRANSLATIONS: &str = include_str!("../../locales/zh-TW.yml");
 const AR_TRANSLATIONS: &str = include_str!("../../locales/ar.yml");
 const JA_TRANSLATIONS: &str = include_str!("../../locales/ja.yml");
 
-impl I18nContext {
-    pub fn new(initial_locale: &str) -> Self {
-        let mut translations = HashMap::new();
-
-        // Load all translations from embedded YAML
-        let locales = [
-            ("en", EN_TRANSLATIONS),
-            ("es", ES_TRANSLATIONS),
-            ("fr", FR_TRANSLATIONS),
-            ("de", DE_TRANSLATIONS),
-            ("zh", ZH_TRANSLATIONS),
-            ("zh-TW", ZH_TW_TRANSLATIONS),
-            ("ar", AR_TRANSLATIONS),
-            ("ja", JA_TRANSLATIONS),
-        ];
-
-        for (locale_code, yaml_content) in locales {
-            match load_translations_from_yaml(yaml_content) {
-                Ok(locale_translations) => {
-                    translations.insert(locale_code.to_string(), locale_translations);
-                }
-                Err(e) => {
-                    eprintln!("Failed to load translations for {locale_code}: {e}");
-                    // Continue with other locales
-                }
+/// Translations of every bundled locale, parsed once and shared by all contexts
+static TRANSLATIONS: LazyLock<Arc<Translations>> = LazyLock::new(|| {
+    let mut translations = HashMap::new();
+
+    // Load all translations from embedded YAML
+    let locales = [
+        ("en", EN_TRANSLATIONS),
+        ("es", ES_TRANSLATIONS),
+        ("fr", FR_TRANSLATIONS),
+        ("de", DE_TRANSLATIONS),
+        ("zh", ZH_TRANSLATIONS),
+        ("zh-TW", ZH_TW_TRANSLATIONS),
+        ("ar", AR_TRANSLATIONS),
+        ("ja", JA_TRANSLATIONS),
+    ];
+
+    for (locale_code, yaml_content) in locales {
+        match load_translations_from_yaml(yaml_content) {
+            Ok(locale_translations) => {
+                translations.insert(locale_code.to_string(), locale_translations);
+            }
+            Err(e) => {
+                eprintln!("Failed to load translations for {locale_code}: {e}");
+                // Continue with other locales
             }
         }
+    }
 
+    Arc::new(translations)
+});
+
+impl I18nContext {
+    pub fn new(initial_locale: &str) -> Self {
         Self {
             current_locale: initial_locale.to_string(),
             available_locales: vec![
@@ -81,10 +99,17 @@ impl I18nContext {
                 "ar".to_string(),
                 "ja".to_string(),
             ],
-            translations,
+            hour_cycle: None,
+            translations: TRANSLATIONS.clone(),
         }
     }
 
+    /// Use a 12 or 24 hour clock regardless of the locale's usual one
+    pub fn with_hour_cycle(mut self, hour_cycle: Option<HourCycle>) -> Self {
+        self.hour_cycle = hour_cycle;
+        self
+    }
+
     pub fn get_current_locale(&self) -> &str {
         &self.current_locale
     }
@@ -96,24 +121,96 @@ impl I18nContext {
     }
 
     pub fn translate(&self, key: &str) -> String {
-        let current_locale = &self.current_locale;
+        self.lookup(key)
+            .map(|(_, translation)| translation)
+            // Return the key itself if no translation found
+            .unwrap_or_else(|| key.to_string())
+    }
 
-        // Try current locale first
-        if let Some(locale_map) = self.translations.get(current_locale) {
-            if let Some(translation) = get_nested_value(locale_map, key) {
-                return translation;
-            }
+    /// Translate a message with placeholder and plural arguments, e.g.
+    /// `"{count, plural, one {# member} other {# members}}"`.
+    /// Plurals follow the rules of the locale the message was found in.
+    pub fn translate_with(&self, key: &str, args: &[(&str, I18nArg)]) -> String {
+        match self.lookup(key) {
+            Some((locale, pattern)) => format_message(&pattern, locale, args),
+            None => key.to_string(),
         }
+    }
 
-        // Fallback to English
-        if let Some(en_map) = self.translations.get("en") {
-            if let Some(translation) = get_nested_value(en_map, key) {
-                return translation;
-            }
+    /// Find a translation and the locale it came from
+    fn lookup(&self, key: &str) -> Option<(&str, String)> {
+        // Try current locale first, then fall back to English
+        let found = [self.current_locale.as_str(), "en"]
+            .into_iter()
+            .find_map(|locale| {
+                let translation = get_nested_value(self.translations.get(locale)?, key)?;
+                Some((locale, translation))
+            });
+
+        #[cfg(debug_assertions)]
+        if found
+            .as_ref()
+            .is_none_or(|(locale, _)| *locale != self.current_locale)
+        {
+            report_missing_translation(&self.current_locale, key);
+        }
+
+        found
+    }
+}
+
+/// App-wide locale, following the active profile's language.
+/// Views get it through `current_i18n`, which also makes them re-render
+/// when the language changes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LocaleContext {
+    pub locale: Signal<String>,
+    pub hour_cycle: Signal<Option<HourCycle>>,
+    change: Callback<String>,
+}
+
+impl LocaleContext {
+    pub(crate) fn new(
+        locale: Signal<String>,
+        hour_cycle: Signal<Option<HourCycle>>,
+        change: Callback<String>,
+    ) -> Self {
+        Self {
+            locale,
+            hour_cycle,
+            change,
         }
+    }
+
+    /// Translations for the current locale and clock preference
+    pub fn i18n(&self) -> I18nContext {
+        I18nContext::new(&self.locale.read()).with_hour_cycle(*self.hour_cycle.read())
+    }
+
+    /// Switch the app to another locale and remember it in the active profile
+    pub fn change(&self, locale: String) {
+        self.change.call(locale);
+    }
+}
+
+/// Translations for the app's current locale, English outside the app.
+/// Meant for `#[props(default = current_i18n())]`.
+pub fn current_i18n() -> I18nContext {
+    try_consume_context::<LocaleContext>()
+        .map(|context| context.i18n())
+        .unwrap_or_else(|| I18nContext::new("en"))
+}
+
+/// Log a key the locale has no translation for, once per locale and key
+#[cfg(debug_assertions)]
+fn report_missing_translation(locale: &str, key: &str) {
+    static REPORTED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
 
-        // Return the key itself if no translation found
-        key.to_string()
+    let Ok(mut reported) = REPORTED.lock() else {
+        return;
+    };
+    if reported.insert(format!("{locale}:{key}")) {
+        eprintln!("Missing translation for \"{key}\" in {locale}");
     }
 }
 
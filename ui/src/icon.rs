@@ -15,7 +15,7 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::{resolve_color, I18nContext};
+use crate::{current_i18n, get_text_direction, resolve_color, I18nContext, ThemeContext};
 use dioxus::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -112,6 +112,15 @@ impl IconName {
         }
     }
 
+    /// Whether the icon points along the reading direction and is mirrored
+    /// in right-to-left locales
+    pub fn is_directional(&self) -> bool {
+        matches!(
+            self,
+            IconName::ArrowLeft | IconName::ArrowRight | IconName::LogOut
+        )
+    }
+
     pub fn default_translation_key(&self) -> &'static str {
         match self {
             IconName::Dashboard => "icons.dashboard",
@@ -147,7 +156,7 @@ impl IconName {
 #[derive(Props, Clone, PartialEq)]
 pub struct IconProps {
     pub name: IconName,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(default = "16".to_string())]
     pub size: String,
@@ -176,6 +185,20 @@ pub fn Icon(props: IconProps) -> Element {
         props.i18n.translate(props.name.default_translation_key())
     };
 
+    // Re-render with the new colors when the theme changes
+    if let Some(theme) = try_use_context::<ThemeContext>() {
+        let _ = theme.active.read();
+    }
+
+    // Arrows point the other way when text runs right to left
+    let mirrored =
+        props.name.is_directional() && get_text_direction(props.i18n.get_current_locale()) == "rtl";
+    let style = if mirrored {
+        "transform: scaleX(-1);"
+    } else {
+        ""
+    };
+
     // Check if the SVG contains tokens that need replacement
     let svg_content = props.name.svg_content();
     let needs_token_replacement =
@@ -204,6 +227,7 @@ pub fn Icon(props: IconProps) -> Element {
         rsx! {
             div {
                 class: "{props.class}",
+                style: "{style}",
                 dangerous_inner_html: "{processed_svg}",
                 "aria-label": "{alt_text}",
             }
@@ -216,6 +240,7 @@ pub fn Icon(props: IconProps) -> Element {
                 height: "{props.size}",
                 alt: "{alt_text}",
                 class: "{props.class}",
+                style: "{style}",
             }
         }
     }
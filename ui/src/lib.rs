@@ -21,6 +21,12 @@ mod i18n_context;
 pub use i18n_context::*;
 mod test_i18n_context;
 
+mod message_format;
+pub use message_format::{format_message, plural_category, I18nArg, PluralCategory};
+
+mod time_format;
+pub use time_format::{unix_now, HourCycle, TimeFormatter};
+
 pub mod types;
 pub use types::*;
 
@@ -30,7 +36,71 @@ pub use icon::{Icon, IconName};
 mod css_utils;
 pub use css_utils::{css_var_to_color, resolve_color};
 
+mod theme;
+pub use theme::{
+    active_theme, load_theme_file, parse_themes, register_theme, resolve_theme, themes,
+    ColorScheme, Theme, CUSTOM_THEMES_FILE, THEME_AUTO,
+};
+
+mod theme_context;
+pub use theme_context::{use_theme_provider, ThemeContext, ThemeStyle};
+
+mod emoji_picker;
+pub use emoji_picker::{search_emojis, EmojiPicker, EMOJI_CATALOG};
+
+mod retention_select;
+pub use retention_select::{parse_retention, RetentionSelect, RETENTION_CHOICES};
+
+mod avatar;
+pub use avatar::{
+    avatar_data_url, avatar_initial, parse_data_url, square_crop, Avatar, AvatarCrop,
+};
+
 // non-web modules
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod profile_context;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use profile_context::{use_locale_provider, use_profile_provider, ProfileContext};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod presence_context;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use presence_context::{use_presence_provider, PresenceContext};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod avatar_context;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use avatar_context::{use_avatar_provider, AvatarContext, ContactAvatar};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod live_context;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use live_context::{use_live_provider, LiveContext};
+
 #[cfg(all(
     not(target_arch = "wasm32"),
     any(feature = "desktop", feature = "mobile")
@@ -53,5 +123,79 @@ mod user_profile_edit;
 ))]
 pub use user_profile_edit::*;
 
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod attachment_card;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use attachment_card::{attachment_icon, format_file_size, AttachmentCard};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod message_reactions;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use message_reactions::{MessageReactions, ReactionToggle};
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod message_search;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use message_search::MessageSearch;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod notification_mode_select;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use notification_mode_select::NotificationModeSelect;
+
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod relay;
+#[cfg(all(
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+pub use relay::{download_dir, RelayBlobs, RelayMessages, RelaySubscription, RelayTokens};
+
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_attachment_card;
+mod test_avatar;
+mod test_emoji_picker;
 mod test_icon;
+mod test_message_format;
+#[cfg(all(
+    test,
+    not(target_arch = "wasm32"),
+    any(feature = "desktop", feature = "mobile")
+))]
+mod test_notification_mode_select;
+mod test_retention_select;
+mod test_theme;
+mod test_time_format;
+mod test_translation_coverage;
 mod test_utils;
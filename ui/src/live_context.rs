@@ -0,0 +1,177 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use dioxus::prelude::*;
+use shared::local::{
+    receive_all_messages, receive_pushed_messages, subscribe_rooms, LiveSubscriptions,
+    SubscriptionState,
+};
+use shared::messaging::{MessageSubscription, MessageTransport};
+use shared::notifications::NotificationSink;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How long to poll before trying to open a live connection again
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the subscriptions are checked against the active profile's rooms
+const ROOM_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// App-wide live delivery of relay messages.
+/// While `connected`, messages of every room are pushed and applied as they
+/// arrive, so room views only reload their history when `revision` changes.
+/// Otherwise they poll the relay themselves.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LiveContext {
+    pub connected: Signal<bool>,
+    /// Bumped whenever pushed messages were applied
+    pub revision: Signal<u64>,
+}
+
+impl LiveContext {
+    /// Whether views have to poll the relay for new messages
+    pub fn polling(&self) -> bool {
+        !*self.connected.peek()
+    }
+}
+
+/// Apply pushed messages until the live connection is lost or has to be
+/// replaced, keeping the subscriptions in line with the active profile's
+/// rooms. Messages that are not applied stay on the relay, a replacement
+/// connection pushes them again. Returns whether to reconnect right away.
+async fn deliver_pushed<S, T, N>(
+    subscription: &mut S,
+    subscribed: &mut LiveSubscriptions,
+    transport: &T,
+    sink: &N,
+    mut revision: Signal<u64>,
+) -> bool
+where
+    S: MessageSubscription,
+    T: MessageTransport,
+    N: NotificationSink,
+{
+    let mut room_check = tokio::time::interval(ROOM_CHECK_INTERVAL);
+    // Rooms were just subscribed, the first check is due after an interval
+    room_check.reset();
+    loop {
+        tokio::select! {
+            pushed = subscription.next() => {
+                let (mailbox, messages) = match pushed {
+                    Ok(pushed) => pushed,
+                    Err(e) => {
+                        eprintln!("Live delivery disconnected: {e}");
+                        return false;
+                    }
+                };
+                // The profile or its rooms may have changed since the last check
+                match subscribe_rooms(subscription, subscribed).await {
+                    Ok(SubscriptionState::Current(_)) => {}
+                    Ok(SubscriptionState::Stale) => return true,
+                    Err(e) => {
+                        eprintln!("Failed to update live subscriptions: {e}");
+                        return false;
+                    }
+                }
+                // Mailboxes of other profiles are left for when they are active
+                let Some(room_id) = subscribed.rooms.get(&mailbox).cloned() else {
+                    continue;
+                };
+                match receive_pushed_messages(transport, sink, room_id, messages).await {
+                    Ok(_) => revision += 1,
+                    Err(e) => {
+                        eprintln!("Failed to apply pushed messages: {e}");
+                        return false;
+                    }
+                }
+            }
+            _ = room_check.tick() => match subscribe_rooms(subscription, subscribed).await {
+                Ok(SubscriptionState::Current(_)) => {}
+                Ok(SubscriptionState::Stale) => return true,
+                Err(e) => {
+                    eprintln!("Failed to update live subscriptions: {e}");
+                    return false;
+                }
+            },
+        }
+    }
+}
+
+/// Provide live delivery to the component tree.
+/// `connect` opens a live connection to the relay, `transport` is polled
+/// while there is none and `sink` raises notifications for new messages.
+pub fn use_live_provider<C, F, S, T, N, M>(connect: C, transport: T, sink: N) -> LiveContext
+where
+    C: Fn() -> F + 'static,
+    F: Future<Output = Result<S, Box<dyn std::error::Error>>>,
+    S: MessageSubscription,
+    T: MessageTransport + 'static,
+    N: Fn() -> M + 'static,
+    M: NotificationSink,
+{
+    let connect = use_hook(|| Rc::new(connect));
+    let transport = use_hook(|| Rc::new(transport));
+    let sink = use_hook(|| Rc::new(sink));
+    let mut connected = use_signal(|| false);
+    let revision = use_signal(|| 0u64);
+
+    use_future(move || {
+        let connect = connect.clone();
+        let transport = transport.clone();
+        let sink = sink.clone();
+        async move {
+            loop {
+                match connect().await {
+                    Ok(mut subscription) => {
+                        let mut subscribed = LiveSubscriptions::default();
+                        match subscribe_rooms(&mut subscription, &mut subscribed).await {
+                            Ok(_) => {
+                                connected.set(true);
+                                let resubscribe = deliver_pushed(
+                                    &mut subscription,
+                                    &mut subscribed,
+                                    &*transport,
+                                    &sink(),
+                                    revision,
+                                )
+                                .await;
+                                connected.set(false);
+                                if resubscribe {
+                                    continue;
+                                }
+                            }
+                            Err(e) => eprintln!("Failed to subscribe to live delivery: {e}"),
+                        }
+                    }
+                    Err(e) => eprintln!("Live delivery unavailable: {e}"),
+                }
+
+                // Poll until the next attempt to reconnect
+                if let Err(e) = receive_all_messages(&*transport, &sink()).await {
+                    eprintln!("Failed to check for new messages: {e}");
+                }
+                tokio::time::sleep(RECONNECT_INTERVAL).await;
+            }
+        }
+    });
+
+    use_context_provider(|| LiveContext {
+        connected,
+        revision,
+    })
+}
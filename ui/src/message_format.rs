@@ -0,0 +1,263 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! A subset of ICU MessageFormat for translations.
+//! Supports `{name}` placeholders, `{count, plural, ...}` with CLDR plural
+//! categories and `=N` exact matches, `#` for the count inside a plural
+//! branch, and `{value, select, ...}`.
+//! Text arguments are wrapped in Unicode isolates, so a name written in
+//! another script does not reorder the sentence around it.
+
+use std::fmt;
+
+/// First strong isolate, opens text with its own detected direction
+const FIRST_STRONG_ISOLATE: char = '\u{2068}';
+/// Pop directional isolate, closes `FIRST_STRONG_ISOLATE`
+const POP_DIRECTIONAL_ISOLATE: char = '\u{2069}';
+
+/// An argument substituted into a translation
+#[derive(Debug, Clone, PartialEq)]
+pub enum I18nArg {
+    Number(i64),
+    Text(String),
+}
+
+impl fmt::Display for I18nArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            I18nArg::Number(n) => write!(f, "{n}"),
+            I18nArg::Text(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+macro_rules! number_arg {
+    ($($t:ty),*) => {
+        $(impl From<$t> for I18nArg {
+            fn from(n: $t) -> Self {
+                I18nArg::Number(n.into())
+            }
+        })*
+    };
+}
+
+number_arg!(i32, u32);
+
+impl From<i64> for I18nArg {
+    fn from(n: i64) -> Self {
+        I18nArg::Number(n)
+    }
+}
+
+impl From<usize> for I18nArg {
+    fn from(n: usize) -> Self {
+        I18nArg::Number(i64::try_from(n).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<u64> for I18nArg {
+    fn from(n: u64) -> Self {
+        I18nArg::Number(i64::try_from(n).unwrap_or(i64::MAX))
+    }
+}
+
+impl From<&str> for I18nArg {
+    fn from(text: &str) -> Self {
+        I18nArg::Text(text.to_string())
+    }
+}
+
+impl From<String> for I18nArg {
+    fn from(text: String) -> Self {
+        I18nArg::Text(text)
+    }
+}
+
+/// CLDR plural categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        }
+    }
+}
+
+/// Plural category of a whole number in a locale, following the CLDR rules
+/// for the bundled languages. Unknown languages use the English rule.
+pub fn plural_category(locale: &str, n: i64) -> PluralCategory {
+    let language = locale.split(['-', '_']).next().unwrap_or(locale);
+    let n = n.unsigned_abs();
+    let millions = n != 0 && n.is_multiple_of(1_000_000);
+    match language {
+        "ar" => match (n, n % 100) {
+            (0, _) => PluralCategory::Zero,
+            (1, _) => PluralCategory::One,
+            (2, _) => PluralCategory::Two,
+            (_, 3..=10) => PluralCategory::Few,
+            (_, 11..=99) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "fr" => match n {
+            0 | 1 => PluralCategory::One,
+            _ if millions => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "es" => match n {
+            1 => PluralCategory::One,
+            _ if millions => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "ja" | "zh" => PluralCategory::Other,
+        _ => match n {
+            1 => PluralCategory::One,
+            _ => PluralCategory::Other,
+        },
+    }
+}
+
+/// Format a translation pattern with its arguments.
+/// Placeholders without a matching argument are left in place.
+pub fn format_message(pattern: &str, locale: &str, args: &[(&str, I18nArg)]) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    format_into(&mut out, pattern, locale, args, None);
+    out
+}
+
+fn format_into(
+    out: &mut String,
+    pattern: &str,
+    locale: &str,
+    args: &[(&str, I18nArg)],
+    count: Option<i64>,
+) {
+    let mut rest = pattern;
+    while let Some(offset) = rest.find(|c: char| c == '{' || (c == '#' && count.is_some())) {
+        out.push_str(&rest[..offset]);
+        rest = &rest[offset..];
+        if let (Some(stripped), Some(count)) = (rest.strip_prefix('#'), count) {
+            out.push_str(&count.to_string());
+            rest = stripped;
+            continue;
+        }
+        let Some(end) = matching_brace(rest) else {
+            break;
+        };
+        format_argument(out, &rest[1..end], locale, args, count);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+}
+
+/// Byte offset of the `}` closing the `{` that `text` starts with
+fn matching_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (offset, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `one {...} other {...}` into its `(selector, message)` branches
+fn branches(options: &str) -> Vec<(&str, &str)> {
+    let mut branches = Vec::new();
+    let mut rest = options;
+    while let Some(open) = rest.find('{') {
+        let selector = rest[..open].trim();
+        let Some(end) = matching_brace(&rest[open..]) else {
+            break;
+        };
+        branches.push((selector, &rest[open + 1..open + end]));
+        rest = &rest[open + end + 1..];
+    }
+    branches
+}
+
+fn format_argument(
+    out: &mut String,
+    argument: &str,
+    locale: &str,
+    args: &[(&str, I18nArg)],
+    count: Option<i64>,
+) {
+    let mut parts = argument.splitn(3, ',');
+    let name = parts.next().unwrap_or_default().trim();
+    let kind = parts.next().map(str::trim);
+    let options = branches(parts.next().unwrap_or_default());
+    let value = args
+        .iter()
+        .find(|(arg_name, _)| *arg_name == name)
+        .map(|(_, value)| value);
+    let branch = |selectors: &[&str]| {
+        selectors.iter().find_map(|selector| {
+            options
+                .iter()
+                .find(|(option, _)| option == selector)
+                .map(|(_, message)| *message)
+        })
+    };
+
+    match (kind, value) {
+        (None, Some(I18nArg::Number(n))) => out.push_str(&n.to_string()),
+        (None, Some(I18nArg::Text(text))) => {
+            out.push(FIRST_STRONG_ISOLATE);
+            out.push_str(text);
+            out.push(POP_DIRECTIONAL_ISOLATE);
+        }
+        (Some("plural"), Some(I18nArg::Number(n))) => {
+            let exact = format!("={n}");
+            let category = plural_category(locale, *n);
+            if let Some(message) = branch(&[exact.as_str(), category.as_str(), "other"]) {
+                format_into(out, message, locale, args, Some(*n));
+            }
+        }
+        (Some("select"), Some(value)) => {
+            let value = value.to_string();
+            if let Some(message) = branch(&[value.as_str(), "other"]) {
+                format_into(out, message, locale, args, count);
+            }
+        }
+        _ => {
+            out.push('{');
+            out.push_str(argument);
+            out.push('}');
+        }
+    }
+}
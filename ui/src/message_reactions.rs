@@ -0,0 +1,92 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{current_i18n, EmojiPicker, I18nContext};
+use dioxus::prelude::*;
+use shared::history::ReactionSummary;
+
+const MESSAGE_REACTIONS_CSS: Asset = asset!("/assets/styling/message_reactions.css");
+
+/// A reaction toggled by the user: the emoji and whether to remove it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReactionToggle {
+    pub emoji: String,
+    pub remove: bool,
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct MessageReactionsProps {
+    #[props(default)]
+    pub reactions: Vec<ReactionSummary>,
+    /// Recently used emojis offered first in the picker
+    #[props(default)]
+    pub recent: Vec<String>,
+    #[props(default = current_i18n())]
+    pub i18n: I18nContext,
+    pub on_toggle: EventHandler<ReactionToggle>,
+}
+
+/// Reaction chips shown under a message, with a button to add a new one
+#[component]
+pub fn MessageReactions(props: MessageReactionsProps) -> Element {
+    let mut picker_open = use_signal(|| false);
+    let on_toggle = props.on_toggle;
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: MESSAGE_REACTIONS_CSS }
+
+        div {
+            class: "mr-reactions",
+            for reaction in props.reactions.iter() {
+                button {
+                    key: "{reaction.emoji}",
+                    class: if reaction.reacted_by_me { "mr-chip mr-chip-mine" } else { "mr-chip" },
+                    onclick: {
+                        let toggle = ReactionToggle {
+                            emoji: reaction.emoji.clone(),
+                            remove: reaction.reacted_by_me,
+                        };
+                        move |_| on_toggle.call(toggle.clone())
+                    },
+                    span { class: "mr-emoji", "{reaction.emoji}" }
+                    span { class: "mr-count", "{reaction.count}" }
+                }
+            }
+            button {
+                class: "mr-add",
+                title: "{props.i18n.translate(\"emoji.add_reaction\")}",
+                onclick: move |_| picker_open.toggle(),
+                "☺+"
+            }
+        }
+
+        if picker_open() {
+            div {
+                class: "mr-picker",
+                EmojiPicker {
+                    recent: props.recent.clone(),
+                    i18n: props.i18n.clone(),
+                    on_select: move |emoji: String| {
+                        picker_open.set(false);
+                        on_toggle.call(ReactionToggle { emoji, remove: false });
+                    },
+                    on_close: move |_| picker_open.set(false),
+                }
+            }
+        }
+    }
+}
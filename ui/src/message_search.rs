@@ -0,0 +1,130 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{current_i18n, I18nContext};
+use dioxus::prelude::*;
+use shared::search::SearchHit;
+
+const MESSAGE_SEARCH_CSS: Asset = asset!("/assets/styling/message_search.css");
+
+#[derive(Props, Clone, PartialEq)]
+pub struct MessageSearchProps {
+    #[props(default)]
+    pub query: String,
+    /// Whether every room is searched instead of the open one
+    #[props(default)]
+    pub all_rooms: bool,
+    #[props(default)]
+    pub hits: Vec<SearchHit>,
+    #[props(default = current_i18n())]
+    pub i18n: I18nContext,
+    pub on_query: EventHandler<String>,
+    pub on_scope: EventHandler<bool>,
+    pub on_select: EventHandler<SearchHit>,
+    pub on_close: EventHandler<()>,
+}
+
+/// Search box over the local message index with highlighted results
+#[component]
+pub fn MessageSearch(props: MessageSearchProps) -> Element {
+    let on_query = props.on_query;
+    let on_scope = props.on_scope;
+    let on_select = props.on_select;
+    let on_close = props.on_close;
+    let searched = !props.query.trim().is_empty();
+
+    rsx! {
+        document::Link { rel: "stylesheet", href: MESSAGE_SEARCH_CSS }
+
+        section {
+            class: "ms-panel",
+            "aria-label": "{props.i18n.translate(\"search.title\")}",
+
+            div {
+                class: "ms-bar",
+                input {
+                    r#type: "search",
+                    class: "ms-input",
+                    autofocus: true,
+                    placeholder: "{props.i18n.translate(\"search.placeholder\")}",
+                    value: "{props.query}",
+                    oninput: move |evt| on_query.call(evt.value()),
+                    onkeydown: move |evt| {
+                        if evt.key() == Key::Escape {
+                            on_close.call(());
+                        }
+                    },
+                }
+                div {
+                    class: "ms-scope",
+                    button {
+                        class: if props.all_rooms { "ms-scope-option" } else { "ms-scope-option active" },
+                        onclick: move |_| on_scope.call(false),
+                        "{props.i18n.translate(\"search.this_room\")}"
+                    }
+                    button {
+                        class: if props.all_rooms { "ms-scope-option active" } else { "ms-scope-option" },
+                        onclick: move |_| on_scope.call(true),
+                        "{props.i18n.translate(\"search.all_rooms\")}"
+                    }
+                }
+                button {
+                    class: "ms-close",
+                    title: "{props.i18n.translate(\"search.close\")}",
+                    onclick: move |_| on_close.call(()),
+                    "✕"
+                }
+            }
+
+            if searched && props.hits.is_empty() {
+                p { class: "ms-empty", "{props.i18n.translate(\"search.no_results\")}" }
+            }
+
+            ul {
+                class: "ms-results",
+                for hit in props.hits.iter() {
+                    li {
+                        key: "{hit.room_id}-{hit.message_id}",
+                        class: "ms-result",
+                        onclick: {
+                            let hit = hit.clone();
+                            move |_| on_select.call(hit.clone())
+                        },
+                        div {
+                            class: "ms-result-meta",
+                            span { class: "ms-result-sender", dir: "auto", "{hit.sender_name}" }
+                            if props.all_rooms {
+                                span { class: "ms-result-room", dir: "auto", "{hit.room_name}" }
+                            }
+                        }
+                        p {
+                            class: "ms-result-snippet",
+                            dir: "auto",
+                            for (index, part) in hit.snippet.iter().enumerate() {
+                                if part.highlighted {
+                                    mark { key: "{index}", "{part.text}" }
+                                } else {
+                                    span { key: "{index}", "{part.text}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
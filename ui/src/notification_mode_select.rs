@@ -0,0 +1,60 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::I18nContext;
+use dioxus::prelude::*;
+use shared::notifications::NotificationMode;
+
+#[derive(Props, Clone, PartialEq)]
+pub struct NotificationModeSelectProps {
+    pub mode: NotificationMode,
+    pub i18n: I18nContext,
+    pub on_change: EventHandler<NotificationMode>,
+    #[props(default = "notification-mode-select".to_string())]
+    pub class: String,
+}
+
+/// Picker for which messages of a room raise notifications
+#[component]
+pub fn NotificationModeSelect(props: NotificationModeSelectProps) -> Element {
+    rsx! {
+        label {
+            class: "{props.class}",
+            span {
+                class: "{props.class}-label",
+                "{props.i18n.translate(\"notifications.title\")}"
+            }
+            select {
+                class: "{props.class}-input",
+                value: "{props.mode.as_str()}",
+                onchange: move |evt| {
+                    if let Some(mode) = NotificationMode::parse(&evt.value()) {
+                        props.on_change.call(mode);
+                    }
+                },
+
+                for mode in NotificationMode::ALL {
+                    option {
+                        value: "{mode.as_str()}",
+                        selected: props.mode == mode,
+                        {props.i18n.translate(&format!("notifications.{}", mode.as_str()))}
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use dioxus::prelude::*;
+use shared::crypto::message::PresenceState;
+use shared::local::{choose_presence, get_chosen_presence, update_presence};
+use shared::messaging::MessageTransport;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How often the idle timer is checked, announcing presence when due
+const PRESENCE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// App-wide presence of the user.
+/// The app feeds user input into `record_activity` so the idle timer knows
+/// when the user is away.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PresenceContext {
+    pub state: Signal<PresenceState>,
+    /// Presence picked by the user, `None` when it follows the idle timer
+    pub chosen: Signal<Option<PresenceState>>,
+    refresh: Callback<()>,
+    choose: Callback<Option<PresenceState>>,
+}
+
+impl PresenceContext {
+    /// Note user input. Coming back after being away is announced right away.
+    pub fn record_activity(&self) {
+        shared::presence::record_activity();
+        if (self.state)() == PresenceState::Away && (self.chosen)().is_none() {
+            let mut state = self.state;
+            state.set(PresenceState::Online);
+            self.refresh.call(());
+        }
+    }
+
+    /// Pick a presence, or follow the idle timer again with `None`
+    pub fn choose(&self, presence: Option<PresenceState>) {
+        self.choose.call(presence);
+    }
+}
+
+/// Reload the chosen presence and announce the current one if due
+async fn refresh<T: MessageTransport>(
+    transport: &T,
+    mut state: Signal<PresenceState>,
+    mut chosen: Signal<Option<PresenceState>>,
+) {
+    if let Ok(presence) = get_chosen_presence().await {
+        chosen.set(presence);
+    }
+    match update_presence(transport).await {
+        Ok(presence) => state.set(presence),
+        Err(e) => eprintln!("Failed to update presence: {e}"),
+    }
+}
+
+/// Provide the presence context to the component tree and keep announcing
+/// presence through `transport` while the app runs
+pub fn use_presence_provider<T: MessageTransport + 'static>(transport: T) -> PresenceContext {
+    let transport = use_hook(|| Rc::new(transport));
+    let state = use_signal(|| PresenceState::Online);
+    let mut chosen = use_signal(|| Option::<PresenceState>::None);
+
+    let refresh_transport = transport.clone();
+    let refresh_now = use_callback(move |_: ()| {
+        let transport = refresh_transport.clone();
+        spawn(async move {
+            refresh(&*transport, state, chosen).await;
+        });
+    });
+
+    let choose_transport = transport.clone();
+    let choose = use_callback(move |presence: Option<PresenceState>| {
+        let transport = choose_transport.clone();
+        chosen.set(presence);
+        spawn(async move {
+            if let Err(e) = choose_presence(&*transport, presence).await {
+                eprintln!("Failed to set presence: {e}");
+            }
+            refresh(&*transport, state, chosen).await;
+        });
+    });
+
+    use_future(move || {
+        let transport = transport.clone();
+        async move {
+            loop {
+                refresh(&*transport, state, chosen).await;
+                tokio::time::sleep(PRESENCE_CHECK_INTERVAL).await;
+            }
+        }
+    });
+
+    use_context_provider(|| PresenceContext {
+        state,
+        chosen,
+        refresh: refresh_now,
+        choose,
+    })
+}
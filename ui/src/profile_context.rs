@@ -0,0 +1,175 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::{HourCycle, LocaleContext};
+use dioxus::prelude::*;
+use shared::local::{
+    create_profile, delete_profile, get_active_profile_id, get_all_user_data,
+    get_current_user_data, select_profile, update_user_data,
+};
+use shared::user_data::UserData;
+
+/// App-wide state for the local profiles and which one is active.
+/// Views that show profile scoped data should read `active_profile_id`
+/// so they reload when the user switches profiles.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ProfileContext {
+    pub profiles: Signal<Vec<UserData>>,
+    pub active_profile_id: Signal<Option<String>>,
+}
+
+impl ProfileContext {
+    /// Get the active profile's data, if one is selected
+    pub fn active_profile(&self) -> Option<UserData> {
+        let active_id = (self.active_profile_id)()?;
+        self.profiles
+            .read()
+            .iter()
+            .find(|profile| profile.id.as_deref() == Some(active_id.as_str()))
+            .cloned()
+    }
+
+    /// Reload profiles and the active selection from the local database
+    pub fn refresh(&self) {
+        let context = *self;
+        spawn(async move {
+            context.reload().await;
+        });
+    }
+
+    /// Make another profile active
+    pub fn switch_to(&self, profile_id: String) {
+        let context = *self;
+        spawn(async move {
+            if let Err(e) = select_profile(profile_id).await {
+                eprintln!("Failed to switch profile: {e}");
+            }
+            context.reload().await;
+        });
+    }
+
+    /// Create a new profile and switch to it
+    pub fn create(&self, username: String, display_name: String) {
+        let context = *self;
+        spawn(async move {
+            match create_profile(username, display_name).await {
+                Ok(profile_id) => {
+                    if let Err(e) = select_profile(profile_id).await {
+                        eprintln!("Failed to switch profile: {e}");
+                    }
+                }
+                Err(e) => eprintln!("Failed to create profile: {e}"),
+            }
+            context.reload().await;
+        });
+    }
+
+    /// Delete a profile along with its rooms, contacts and messages
+    pub fn delete(&self, profile_id: String) {
+        let context = *self;
+        spawn(async move {
+            if let Err(e) = delete_profile(profile_id).await {
+                eprintln!("Failed to delete profile: {e}");
+            }
+            context.reload().await;
+        });
+    }
+
+    /// Persist changes to a profile, creating it first if it has no ID yet
+    pub async fn save(&self, mut user_data: UserData) -> Result<(), String> {
+        if user_data.id.is_none() {
+            let profile_id =
+                create_profile(user_data.username.clone(), user_data.display_name.clone())
+                    .await
+                    .map_err(|e| e.to_string())?;
+            user_data.id = Some(profile_id);
+        }
+
+        let user_data_json = user_data.to_json().map_err(|e| e.to_string())?;
+        update_user_data(user_data_json)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.reload().await;
+        Ok(())
+    }
+
+    async fn reload(&self) {
+        let mut profiles = self.profiles;
+        let mut active_profile_id = self.active_profile_id;
+
+        // Resolves and persists a fallback profile when none is selected
+        let _ = get_current_user_data().await;
+
+        if let Ok(profile_jsons) = get_all_user_data().await {
+            profiles.set(
+                profile_jsons
+                    .iter()
+                    .filter_map(|json| UserData::from_json(json).ok())
+                    .collect(),
+            );
+        }
+
+        if let Ok(active_id) = get_active_profile_id().await {
+            active_profile_id.set(active_id);
+        }
+    }
+}
+
+/// Provide the profile context to the component tree and load it
+pub fn use_profile_provider() -> ProfileContext {
+    let context = use_context_provider(|| ProfileContext {
+        profiles: Signal::new(Vec::new()),
+        active_profile_id: Signal::new(None),
+    });
+    use_hook(move || context.refresh());
+    context
+}
+
+/// Provide the app locale, following the active profile's language and
+/// clock preference
+pub fn use_locale_provider(profile: ProfileContext) -> LocaleContext {
+    let mut locale = use_signal(|| "en".to_string());
+    let mut hour_cycle = use_signal(|| None);
+
+    use_effect(move || {
+        if let Some(profile) = profile.active_profile() {
+            let preferred_cycle = HourCycle::parse(&profile.time_format);
+            if *hour_cycle.peek() != preferred_cycle {
+                hour_cycle.set(preferred_cycle);
+            }
+            if *locale.peek() != profile.language {
+                locale.set(profile.language);
+            }
+        }
+    });
+
+    let change = use_callback(move |language: String| {
+        locale.set(language.clone());
+        spawn(async move {
+            let Some(mut user_data) = profile.active_profile() else {
+                return;
+            };
+            user_data.set_language(language);
+            if let Err(e) = profile.save(user_data).await {
+                eprintln!("Failed to save language: {e}");
+            }
+        });
+    });
+
+    use_context_provider(|| LocaleContext::new(locale, hour_cycle, change))
+}
@@ -0,0 +1,263 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Relay server transports shared by the desktop and mobile apps.
+
+use crate::unix_now;
+use api::{LiveEvent, LiveRequest, RelayEnvelope};
+use dioxus::fullstack::{WebSocketOptions, Websocket};
+use shared::attachments::BlobTransport;
+use shared::blind_token::{batch_payload, AnonymousToken, TokenRequest};
+use shared::crypto::message::EncryptedMessage;
+use shared::mailbox::prove_ownership;
+use shared::messaging::{MessageSubscription, MessageTransport};
+use shared::pow::{solve, PowChallenge};
+use shared::profile::active_database;
+use shared::tokens::{redeem_delay, take_token, TokenSource};
+use std::path::PathBuf;
+
+/// Attachment blob transfer through the relay server functions
+pub struct RelayBlobs;
+
+impl BlobTransport for RelayBlobs {
+    async fn upload_blob(
+        &self,
+        blob_id: String,
+        data: Vec<u8>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        api::upload_blob(blob_id, data)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+
+    async fn download_blob(
+        &self,
+        blob_id: String,
+    ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        api::download_blob(blob_id)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+}
+
+/// A message fetched from a relay mailbox, `None` if the envelope is malformed
+fn envelope_message(envelope: RelayEnvelope) -> Option<EncryptedMessage> {
+    let sender_public = <[u8; 32]>::try_from(envelope.sender_public_key).ok()?;
+    Some(EncryptedMessage {
+        id: envelope.id,
+        sender_public,
+        ciphertext: envelope.ciphertext,
+        nonce: envelope.nonce,
+        ephemeral: envelope.ephemeral,
+        expires_at: None,
+        retract_hash: None,
+    })
+}
+
+/// Anonymous message tokens issued by the relay server
+pub struct RelayTokens;
+
+impl TokenSource for RelayTokens {
+    async fn issue(&self) -> Result<Vec<AnonymousToken>, Box<dyn std::error::Error>> {
+        let issuer = api::token_issuer_key().await.map_err(|e| e.to_string())?;
+        let requests = (0..issuer.batch_size.max(1))
+            .map(|_| TokenRequest::new(issuer.key_id, &issuer.public_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let blinded: Vec<Vec<u8>> = requests.iter().map(TokenRequest::blinded).collect();
+
+        let challenge: PowChallenge = api::request_token_challenge()
+            .await
+            .map_err(|e| e.to_string())?
+            .into();
+        // The work takes a while, so it runs off the async runtime
+        let solution = {
+            let (challenge, payload) = (challenge.clone(), batch_payload(&blinded));
+            tokio::task::spawn_blocking(move || solve(&challenge, &payload, unix_now)).await?
+        }
+        .ok_or("The token challenge expired before it was solved")?;
+
+        let signatures = api::issue_tokens(issuer.key_id, blinded, challenge.id, solution)
+            .await
+            .map_err(|e| e.to_string())?;
+        if signatures.len() != requests.len() {
+            return Err("The relay signed a different number of tokens".into());
+        }
+        let tokens = requests
+            .into_iter()
+            .zip(signatures)
+            .map(|(request, signature)| request.finalize(&signature.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(tokens)
+    }
+
+    async fn redeem(&self, token: AnonymousToken) -> Result<String, Box<dyn std::error::Error>> {
+        tokio::time::sleep(redeem_delay()).await;
+        let secret = token.secret();
+        api::redeem_token_apart(token.into()).await?;
+        Ok(secret)
+    }
+}
+
+/// Message mailboxes on the relay server
+pub struct RelayMessages;
+
+impl MessageTransport for RelayMessages {
+    async fn deposit(
+        &self,
+        recipient_hash: String,
+        message: EncryptedMessage,
+        relay: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let envelope = RelayEnvelope {
+            id: None,
+            sender_public_key: message.sender_public.to_vec(),
+            expires_in: message.expires_in(),
+            ciphertext: message.ciphertext,
+            nonce: message.nonce,
+            ephemeral: message.ephemeral,
+            retract_hash: message.retract_hash,
+        };
+        let db = active_database()?;
+        // A refused token was revoked or spent elsewhere, a fresh one is
+        // tried once before giving up
+        let mut attempts = 2;
+        loop {
+            attempts -= 1;
+            let token = take_token(&RelayTokens, &db).await?;
+            let result = api::deposit_message(
+                recipient_hash.clone(),
+                envelope.clone(),
+                relay.clone(),
+                token.clone(),
+            )
+            .await;
+            match result {
+                Err(e) if e.to_string().contains(api::TOKEN_REFUSED) => {
+                    if attempts == 0 {
+                        return Err(e.to_string().into());
+                    }
+                }
+                result => return result.map_err(|e| e.to_string().into()),
+            }
+        }
+    }
+
+    async fn fetch(
+        &self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+    ) -> Result<Vec<EncryptedMessage>, Box<dyn std::error::Error>> {
+        let challenge = api::request_mailbox_challenge(recipient_hash.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let proof = prove_ownership(secret_key, &challenge.into())?;
+        let envelopes = api::fetch_messages(recipient_hash, proof.into())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(envelopes.into_iter().filter_map(envelope_message).collect())
+    }
+
+    async fn acknowledge(
+        &self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+        ids: Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let challenge = api::request_mailbox_challenge(recipient_hash.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let proof = prove_ownership(secret_key, &challenge.into())?;
+        api::acknowledge_messages(recipient_hash, proof.into(), ids)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+
+    async fn retract(
+        &self,
+        recipient_hash: String,
+        nonce: Vec<u8>,
+        retract_secret: Vec<u8>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        api::retract_message(recipient_hash, nonce, retract_secret)
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+}
+
+/// Live delivery connection to the relay server
+pub struct RelaySubscription {
+    socket: Websocket<LiveRequest, LiveEvent>,
+}
+
+impl RelaySubscription {
+    pub async fn connect() -> Result<Self, Box<dyn std::error::Error>> {
+        let socket = api::live_messages(WebSocketOptions::new())
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Self { socket })
+    }
+}
+
+impl MessageSubscription for RelaySubscription {
+    async fn subscribe(
+        &mut self,
+        recipient_hash: String,
+        secret_key: &[u8; 32],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let challenge = api::request_mailbox_challenge(recipient_hash.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        let proof = prove_ownership(secret_key, &challenge.into())?;
+        self.socket
+            .send(LiveRequest::Subscribe {
+                recipient_hash,
+                proof: proof.into(),
+            })
+            .await
+            .map_err(|e| e.to_string().into())
+    }
+
+    async fn next(
+        &mut self,
+    ) -> Result<(String, Vec<EncryptedMessage>), Box<dyn std::error::Error>> {
+        loop {
+            match self.socket.recv().await.map_err(|e| e.to_string())? {
+                LiveEvent::Messages {
+                    recipient_hash,
+                    envelopes,
+                } => {
+                    let messages = envelopes.into_iter().filter_map(envelope_message).collect();
+                    return Ok((recipient_hash, messages));
+                }
+                LiveEvent::Subscribed { .. } => {}
+                LiveEvent::Rejected {
+                    recipient_hash,
+                    reason,
+                } => eprintln!("Live delivery refused for mailbox {recipient_hash}: {reason}"),
+            }
+        }
+    }
+}
+
+/// Directory downloaded attachments are saved in, see `save_download`.
+/// `folder` is the platform's usual download folder in the home directory.
+pub fn download_dir(folder: &str) -> PathBuf {
+    std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(folder))
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(std::env::temp_dir)
+}
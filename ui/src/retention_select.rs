@@ -0,0 +1,96 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::I18nContext;
+use dioxus::prelude::*;
+
+/// Disappearing message timers offered for a room, in seconds
+pub const RETENTION_CHOICES: &[(Option<u64>, &str)] = &[
+    (None, "retention.off"),
+    (Some(5 * 60), "retention.five_minutes"),
+    (Some(60 * 60), "retention.one_hour"),
+    (Some(24 * 60 * 60), "retention.one_day"),
+    (Some(7 * 24 * 60 * 60), "retention.one_week"),
+];
+
+/// Option value of a retention timer
+fn retention_value(seconds: Option<u64>) -> String {
+    match seconds {
+        Some(seconds) => seconds.to_string(),
+        None => "off".to_string(),
+    }
+}
+
+/// Parse the option value of a retention timer
+pub fn parse_retention(value: &str) -> Option<u64> {
+    value.parse().ok().filter(|seconds| *seconds > 0)
+}
+
+#[derive(Props, Clone, PartialEq)]
+pub struct RetentionSelectProps {
+    /// Current timer, `None` when messages do not disappear
+    pub seconds: Option<u64>,
+    pub i18n: I18nContext,
+    pub on_change: EventHandler<Option<u64>>,
+    #[props(default = "retention-select".to_string())]
+    pub class: String,
+}
+
+/// Picker for a room's disappearing messages timer
+#[component]
+pub fn RetentionSelect(props: RetentionSelectProps) -> Element {
+    // Another member may have picked a timer this client does not offer
+    let custom = props.seconds.filter(|seconds| {
+        !RETENTION_CHOICES
+            .iter()
+            .any(|(choice, _)| *choice == Some(*seconds))
+    });
+
+    rsx! {
+        label {
+            class: "{props.class}",
+            span {
+                class: "{props.class}-label",
+                "{props.i18n.translate(\"retention.title\")}"
+            }
+            select {
+                class: "{props.class}-input",
+                value: "{retention_value(props.seconds)}",
+                onchange: move |evt| props.on_change.call(parse_retention(&evt.value())),
+
+                for (seconds, label) in RETENTION_CHOICES.iter() {
+                    option {
+                        value: "{retention_value(*seconds)}",
+                        selected: props.seconds == *seconds,
+                        "{props.i18n.translate(label)}"
+                    }
+                }
+                if let Some(seconds) = custom {
+                    option {
+                        value: "{seconds}",
+                        selected: true,
+                        "{seconds}s"
+                    }
+                }
+            }
+            small {
+                class: "{props.class}-hint",
+                "{props.i18n.translate(\"retention.description\")}"
+            }
+        }
+    }
+}
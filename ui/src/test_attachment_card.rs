@@ -0,0 +1,52 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::attachment_card::*;
+    use crate::IconName;
+
+    #[test]
+    fn test_attachment_icon_by_mime_type() {
+        assert_eq!(attachment_icon("image/png"), IconName::Image);
+        assert_eq!(attachment_icon("IMAGE/JPEG"), IconName::Image);
+        assert_eq!(attachment_icon("application/pdf"), IconName::FileText);
+        assert_eq!(attachment_icon("text/plain"), IconName::FileText);
+        assert_eq!(
+            attachment_icon(
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            ),
+            IconName::FileText
+        );
+        assert_eq!(attachment_icon("text/csv"), IconName::FileSpreadsheet);
+        assert_eq!(
+            attachment_icon("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+            IconName::FileSpreadsheet
+        );
+        assert_eq!(attachment_icon("application/zip"), IconName::File);
+    }
+
+    #[test]
+    fn test_format_file_size() {
+        assert_eq!(format_file_size(0), "0 B");
+        assert_eq!(format_file_size(1023), "1023 B");
+        assert_eq!(format_file_size(1024), "1.0 KB");
+        assert_eq!(format_file_size(1536), "1.5 KB");
+        assert_eq!(format_file_size(5 * 1024 * 1024), "5.0 MB");
+        assert_eq!(format_file_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
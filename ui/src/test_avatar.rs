@@ -0,0 +1,79 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::avatar::*;
+    use crate::test_utils::test_helpers::*;
+    use dioxus::prelude::*;
+
+    #[test]
+    fn test_square_crop() {
+        assert_eq!(
+            square_crop(400, 300, 1.0),
+            AvatarCrop {
+                x: 50,
+                y: 0,
+                size: 300
+            }
+        );
+        assert_eq!(
+            square_crop(300, 400, 2.0),
+            AvatarCrop {
+                x: 75,
+                y: 125,
+                size: 150
+            }
+        );
+        // Zooming out past the image is not possible
+        assert_eq!(square_crop(200, 200, 0.5).size, 200);
+        assert_eq!(square_crop(200, 200, f64::NAN).size, 200);
+    }
+
+    #[test]
+    fn test_data_url_round_trip() {
+        let data = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10];
+        let url = avatar_data_url("image/jpeg", &data);
+        assert_eq!(url, "data:image/jpeg;base64,/9j/4AAQ");
+        assert_eq!(parse_data_url(&url), Some(("image/jpeg".to_string(), data)));
+
+        assert_eq!(parse_data_url("https://example.com/a.png"), None);
+        assert_eq!(parse_data_url("data:text/plain,hello"), None);
+    }
+
+    #[test]
+    fn test_avatar_falls_back_to_initial() {
+        assert_eq!(avatar_initial("alice"), "A");
+        assert_eq!(avatar_initial("  émile"), "É");
+        assert_eq!(avatar_initial(""), "?");
+
+        let html = render_to_string(rsx! {
+            Avatar { name: "bob".to_string() }
+        });
+        assert!(html.contains(">B</div>"));
+
+        let html = render_to_string(rsx! {
+            Avatar {
+                name: "bob".to_string(),
+                image: Some("data:image/png;base64,AA==".to_string()),
+                class: "contact-avatar".to_string(),
+            }
+        });
+        assert!(html.contains("class=\"contact-avatar\""));
+        assert!(html.contains("src=\"data:image/png;base64,AA==\""));
+    }
+}
@@ -0,0 +1,79 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::emoji_picker::*;
+    use crate::test_utils::test_helpers::*;
+    use crate::I18nContext;
+    use dioxus::prelude::*;
+
+    #[test]
+    fn test_empty_query_returns_whole_catalog() {
+        assert_eq!(search_emojis("").len(), EMOJI_CATALOG.len());
+        assert_eq!(search_emojis("   ").len(), EMOJI_CATALOG.len());
+    }
+
+    #[test]
+    fn test_search_matches_keyword_prefixes() {
+        let results = search_emojis("thumb");
+        assert_eq!(results, vec!["👍", "👎"]);
+
+        let results = search_emojis("PARTY");
+        assert!(results.contains(&"🥳"));
+        assert!(results.contains(&"🎉"));
+
+        // Matches start at keyword boundaries only
+        assert!(!search_emojis("umbs").contains(&"👍"));
+        assert!(search_emojis("zzzz-nothing").is_empty());
+    }
+
+    #[test]
+    fn test_search_by_emoji_itself() {
+        assert_eq!(search_emojis("🦀"), vec!["🦀"]);
+    }
+
+    #[test]
+    fn test_catalog_has_no_duplicates() {
+        let mut emojis: Vec<&str> = EMOJI_CATALOG.iter().map(|(emoji, _)| *emoji).collect();
+        emojis.sort();
+        emojis.dedup();
+        assert_eq!(emojis.len(), EMOJI_CATALOG.len());
+    }
+
+    #[test]
+    fn test_picker_shows_recent_emojis_first() {
+        // Event handlers need a runtime, so the picker renders inside an app
+        #[component]
+        fn App() -> Element {
+            rsx! {
+                EmojiPicker {
+                    recent: vec!["🦇".to_string()],
+                    i18n: I18nContext::new("en"),
+                    on_select: |_| {},
+                }
+            }
+        }
+
+        let rendered = render_to_string(rsx! { App {} });
+
+        let recent_title = rendered.find("Recently used").unwrap();
+        let all_title = rendered.find("All emoji").unwrap();
+        assert!(recent_title < all_title);
+        assert!(rendered.find("🦇").unwrap() < all_title);
+    }
+}
@@ -18,6 +18,8 @@
 #[cfg(test)]
 mod tests {
     use crate::i18n_context::*;
+    use crate::test_utils::test_helpers::*;
+    use dioxus::prelude::*;
 
     #[test]
     fn test_load_translations_from_yaml() {
@@ -175,4 +177,126 @@ booleans:
         assert_eq!(get_text_direction("en"), "ltr");
         assert_eq!(get_text_direction("es"), "ltr");
     }
+
+    #[test]
+    fn test_translate_with_plurals() {
+        let en = I18nContext::new("en");
+        let count = |n: usize| [("count", n.into())];
+        assert_eq!(
+            en.translate_with("messages.member_count", &count(0)),
+            "No members"
+        );
+        assert_eq!(
+            en.translate_with("messages.member_count", &count(1)),
+            "1 member"
+        );
+        assert_eq!(
+            en.translate_with("messages.member_count", &count(5)),
+            "5 members"
+        );
+
+        let ar = I18nContext::new("ar");
+        assert_eq!(
+            ar.translate_with("messages.member_count", &count(2)),
+            "عضوان"
+        );
+        assert_eq!(
+            ar.translate_with("messages.member_count", &count(3)),
+            "3 أعضاء"
+        );
+        assert_eq!(
+            ar.translate_with("messages.member_count", &count(11)),
+            "11 عضوًا"
+        );
+        assert_eq!(
+            ar.translate_with("messages.member_count", &count(100)),
+            "100 عضو"
+        );
+
+        let ja = I18nContext::new("ja");
+        assert_eq!(
+            ja.translate_with("messages.member_count", &count(1)),
+            "1人のメンバー"
+        );
+    }
+
+    #[test]
+    fn test_translate_with_placeholders() {
+        let en = I18nContext::new("en");
+        let typing = |names: &[&str]| {
+            en.translate_with(
+                "messages.typing",
+                &[
+                    ("count", names.len().into()),
+                    ("names", names.join(", ").into()),
+                ],
+            )
+        };
+        assert_eq!(typing(&["Ana"]), "\u{2068}Ana\u{2069} is typing…");
+        assert_eq!(
+            typing(&["Ana", "Bo"]),
+            "\u{2068}Ana, Bo\u{2069} are typing…"
+        );
+
+        assert_eq!(
+            en.translate_with("missing.key", &[("count", 1.into())]),
+            "missing.key"
+        );
+    }
+
+    #[test]
+    fn test_every_locale_formats_plural_messages() {
+        let en = I18nContext::new("en");
+        for locale in &en.available_locales {
+            let context = I18nContext::new(locale);
+            for key in [
+                "messages.typing",
+                "messages.member_count",
+                "messages.room_status",
+            ] {
+                for n in [0, 1, 2, 3, 11, 100, 1_000_000] {
+                    let text = context
+                        .translate_with(key, &[("count", n.into()), ("names", "Ana".into())]);
+                    assert!(
+                        !text.is_empty() && !text.contains('{') && !text.contains('#'),
+                        "{locale} {key} {n}: {text}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[component]
+    fn Settings(#[props(default = current_i18n())] i18n: I18nContext) -> Element {
+        rsx! { "{i18n.translate(\"nav.settings\")}" }
+    }
+
+    #[test]
+    fn test_current_i18n_follows_locale_context() {
+        #[component]
+        fn App() -> Element {
+            let locale = use_signal(|| "de".to_string());
+            let hour_cycle = use_signal(|| None);
+            let change = use_callback(|_: String| {});
+            use_context_provider(|| LocaleContext::new(locale, hour_cycle, change));
+            rsx! {
+                Settings {}
+            }
+        }
+
+        assert_eq!(render_to_string(rsx! { App {} }), "Einstellungen");
+    }
+
+    #[test]
+    fn test_current_i18n_defaults_to_english() {
+        // The default prop reads the context, so it needs a runtime too
+        #[component]
+        fn App() -> Element {
+            rsx! {
+                Settings {}
+            }
+        }
+
+        assert_eq!(render_to_string(rsx! { App {} }), "Settings");
+    }
 }
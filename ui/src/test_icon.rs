@@ -186,4 +186,28 @@ mod tests {
         assert_eq!(props3.class, "custom-icon");
         assert_eq!(props3.alt_text, Some("Custom Alt Text".to_string()));
     }
+
+    #[test]
+    fn test_directional_icons_mirror_in_rtl() {
+        use crate::test_utils::test_helpers::*;
+        use dioxus::prelude::*;
+
+        assert!(IconName::ArrowLeft.is_directional());
+        assert!(IconName::ArrowRight.is_directional());
+        assert!(!IconName::Users.is_directional());
+
+        let render = |name: IconName, locale: &str| {
+            render_to_string(rsx! {
+                Icon {
+                    name: name,
+                    i18n: I18nContext::new(locale),
+                }
+            })
+        };
+
+        assert!(render(IconName::ArrowRight, "ar").contains("scaleX(-1)"));
+        assert!(render(IconName::ArrowLeft, "ar").contains("scaleX(-1)"));
+        assert!(!render(IconName::ArrowRight, "en").contains("scaleX(-1)"));
+        assert!(!render(IconName::Users, "ar").contains("scaleX(-1)"));
+    }
 }
@@ -0,0 +1,113 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::message_format::*;
+
+    const FILES: &str = "{count, plural, =0 {No files} one {# file} other {# files}}";
+
+    #[test]
+    fn test_plural_categories() {
+        assert_eq!(plural_category("en", 1), PluralCategory::One);
+        assert_eq!(plural_category("en", 0), PluralCategory::Other);
+        assert_eq!(plural_category("de", 2), PluralCategory::Other);
+
+        assert_eq!(plural_category("fr", 0), PluralCategory::One);
+        assert_eq!(plural_category("fr", 1), PluralCategory::One);
+        assert_eq!(plural_category("fr", 2_000_000), PluralCategory::Many);
+        assert_eq!(plural_category("es", 0), PluralCategory::Other);
+        assert_eq!(plural_category("es", 1_000_000), PluralCategory::Many);
+
+        assert_eq!(plural_category("ar", 0), PluralCategory::Zero);
+        assert_eq!(plural_category("ar", 2), PluralCategory::Two);
+        assert_eq!(plural_category("ar", 103), PluralCategory::Few);
+        assert_eq!(plural_category("ar", 111), PluralCategory::Many);
+        assert_eq!(plural_category("ar", 102), PluralCategory::Other);
+
+        assert_eq!(plural_category("ja", 1), PluralCategory::Other);
+        assert_eq!(plural_category("zh-TW", 1), PluralCategory::Other);
+        assert_eq!(plural_category("pt_BR", 1), PluralCategory::One);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            format_message("Hello {name}!", "en", &[("name", "Ana".into())]),
+            "Hello \u{2068}Ana\u{2069}!"
+        );
+        assert_eq!(
+            format_message("{ a }+{b}", "en", &[("a", 1.into()), ("b", 2.into())]),
+            "1+2"
+        );
+        // Missing arguments stay visible instead of vanishing
+        assert_eq!(format_message("Hi {name}", "en", &[]), "Hi {name}");
+        assert_eq!(format_message("Hi {name", "en", &[]), "Hi {name");
+        // `#` only means the count inside a plural branch
+        assert_eq!(format_message("Room #1", "en", &[]), "Room #1");
+    }
+
+    #[test]
+    fn test_plural() {
+        let files = |n: i64, locale| format_message(FILES, locale, &[("count", n.into())]);
+        assert_eq!(files(0, "en"), "No files");
+        assert_eq!(files(1, "en"), "1 file");
+        assert_eq!(files(7, "en"), "7 files");
+        // Categories the message lacks fall back to `other`
+        assert_eq!(files(2, "ar"), "2 files");
+        // A text argument cannot select a plural branch
+        assert_eq!(
+            format_message("{count, plural, other {#}}", "en", &[("count", "x".into())]),
+            "{count, plural, other {#}}"
+        );
+    }
+
+    #[test]
+    fn test_nested_arguments() {
+        let message = "{count, plural, one {{name} sent # photo} other {{name} sent # photos}}";
+        assert_eq!(
+            format_message(message, "en", &[("count", 3.into()), ("name", "Bo".into())]),
+            "\u{2068}Bo\u{2069} sent 3 photos"
+        );
+    }
+
+    #[test]
+    fn test_select() {
+        let message = "{kind, select, image {Photo} video {Video} other {File}}";
+        let kind = |kind: &str| format_message(message, "en", &[("kind", kind.into())]);
+        assert_eq!(kind("image"), "Photo");
+        assert_eq!(kind("video"), "Video");
+        assert_eq!(kind("pdf"), "File");
+    }
+
+    #[test]
+    fn test_arg_conversions() {
+        assert_eq!(I18nArg::from(3usize), I18nArg::Number(3));
+        assert_eq!(I18nArg::from(-2), I18nArg::Number(-2));
+        assert_eq!(I18nArg::from("a"), I18nArg::Text("a".to_string()));
+        assert_eq!(I18nArg::from(42u32).to_string(), "42");
+    }
+
+    #[test]
+    fn test_text_arguments_are_isolated() {
+        // An Arabic name keeps an English sentence in order, and vice versa
+        let message = format_message("{name} is typing", "en", &[("name", "سارة".into())]);
+        assert_eq!(message, "\u{2068}سارة\u{2069} is typing");
+        let message = format_message("{name} يكتب", "ar", &[("name", "Sara".into())]);
+        assert_eq!(message, "\u{2068}Sara\u{2069} يكتب");
+    }
+}
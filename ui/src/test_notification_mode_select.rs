@@ -0,0 +1,54 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::notification_mode_select::*;
+    use crate::test_utils::test_helpers::*;
+    use crate::I18nContext;
+    use dioxus::prelude::*;
+    use shared::notifications::NotificationMode;
+
+    #[test]
+    fn test_modes_are_translated() {
+        let i18n = I18nContext::new("en");
+        for mode in NotificationMode::ALL {
+            let key = format!("notifications.{}", mode.as_str());
+            assert_ne!(i18n.translate(&key), key);
+        }
+    }
+
+    #[test]
+    fn test_current_mode_is_selected() {
+        // Event handlers need a runtime, so the select renders inside an app
+        #[component]
+        fn App() -> Element {
+            rsx! {
+                NotificationModeSelect {
+                    mode: NotificationMode::MentionsOnly,
+                    i18n: I18nContext::new("en"),
+                    on_change: |_| {},
+                }
+            }
+        }
+
+        let rendered = render_to_string(rsx! { App {} });
+
+        assert!(rendered.contains("Mentions only"));
+        assert!(rendered.contains("value=\"mentions_only\""));
+    }
+}
@@ -0,0 +1,177 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_helpers::*;
+    use crate::theme::*;
+    use crate::{resolve_color, ThemeContext, ThemeStyle};
+    use dioxus::prelude::*;
+
+    const CUSTOM_THEMES: &str = r#"
+        /* A dark theme with a warmer accent */
+        [data-theme="ember"] {
+            --theme-name: "Ember";
+            --color-accent-primary: #ff7043;
+        }
+
+        :root[data-theme='paper'] {
+            color-scheme: light;
+            --color-bg-primary: #fdf6e3;
+        }
+
+        .not-a-theme { --color-bg-primary: #000000; }
+    "#;
+
+    #[test]
+    fn test_builtin_themes() {
+        let dark = Theme::dark();
+        let light = Theme::light();
+
+        assert_eq!(dark.color_scheme, ColorScheme::Dark);
+        assert_eq!(light.color_scheme, ColorScheme::Light);
+        assert!(dark.is_builtin() && light.is_builtin());
+        assert_ne!(
+            dark.variables["--color-bg-primary"],
+            light.variables["--color-bg-primary"]
+        );
+        // Variables the light palette does not override come from dark
+        assert_eq!(
+            dark.variables["--spacing-md"],
+            light.variables["--spacing-md"]
+        );
+    }
+
+    #[test]
+    fn test_parse_custom_themes() {
+        let themes = parse_themes(CUSTOM_THEMES);
+        assert_eq!(themes.len(), 2);
+
+        let ember = &themes[0];
+        assert_eq!(ember.id, "ember");
+        assert_eq!(ember.name, "Ember");
+        assert_eq!(ember.color_scheme, ColorScheme::Dark);
+        assert_eq!(ember.variables["--color-accent-primary"], "#ff7043");
+        assert_eq!(
+            ember.variables["--color-bg-primary"],
+            Theme::dark().variables["--color-bg-primary"]
+        );
+        assert!(!ember.variables.contains_key("--theme-name"));
+
+        let paper = &themes[1];
+        assert_eq!(paper.id, "paper");
+        assert_eq!(paper.name, "paper");
+        assert_eq!(paper.color_scheme, ColorScheme::Light);
+        assert_eq!(paper.variables["--color-bg-primary"], "#fdf6e3");
+        assert_eq!(
+            paper.variables["--color-text-primary"],
+            Theme::light().variables["--color-text-primary"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_theme() {
+        assert_eq!(resolve_theme("light", true).id, "light");
+        assert_eq!(resolve_theme(THEME_AUTO, true).id, "dark");
+        assert_eq!(resolve_theme(THEME_AUTO, false).id, "light");
+        assert_eq!(resolve_theme("missing", false).id, DEFAULT_THEME);
+        assert_eq!(resolve_theme("", false).id, DEFAULT_THEME);
+    }
+
+    #[test]
+    fn test_register_theme() {
+        let mut theme = parse_themes(CUSTOM_THEMES).remove(0);
+        theme.id = "test-register".to_string();
+        assert!(register_theme(theme.clone()));
+        assert_eq!(resolve_theme("test-register", true), theme);
+
+        // Registering again replaces the theme
+        theme.name = "Renamed".to_string();
+        assert!(register_theme(theme));
+        let registered: Vec<_> = themes()
+            .into_iter()
+            .filter(|theme| theme.id == "test-register")
+            .collect();
+        assert_eq!(registered.len(), 1);
+        assert_eq!(registered[0].name, "Renamed");
+
+        let mut builtin = Theme::light();
+        builtin.id = THEME_AUTO.to_string();
+        assert!(!register_theme(builtin));
+        assert!(!register_theme(Theme::dark()));
+    }
+
+    #[test]
+    fn test_load_theme_file() {
+        let path = std::env::temp_dir().join(format!("themes-{}.css", std::process::id()));
+        std::fs::write(&path, CUSTOM_THEMES).unwrap();
+        assert_eq!(load_theme_file(&path).unwrap(), 2);
+        assert_eq!(resolve_theme("paper", true).id, "paper");
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(load_theme_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_resolve_color_follows_active_theme() {
+        set_active_theme(Theme::light());
+        assert_eq!(
+            resolve_color("var(--color-bg-primary)"),
+            Theme::light().variables["--color-bg-primary"]
+        );
+        assert_eq!(active_theme().id, "light");
+
+        set_active_theme(Theme::dark());
+        assert_eq!(
+            resolve_color("var(--color-bg-primary)"),
+            Theme::dark().variables["--color-bg-primary"]
+        );
+    }
+
+    #[test]
+    fn test_theme_css() {
+        let css = Theme::light().to_css();
+        assert!(css.starts_with("html:root {"));
+        assert!(css.contains("color-scheme: light;"));
+        assert!(css.contains(&format!(
+            "--color-bg-primary: {};",
+            Theme::light().variables["--color-bg-primary"]
+        )));
+    }
+
+    #[test]
+    fn test_theme_style_renders_active_theme() {
+        #[component]
+        fn Themed() -> Element {
+            use_context_provider(|| ThemeContext {
+                preference: Memo::new(|| "light".to_string()),
+                prefers_dark: Signal::new(true),
+                active: Memo::new(|| resolve_theme("light", true)),
+            });
+            rsx! {
+                ThemeStyle {}
+            }
+        }
+
+        let rendered = render_to_string(rsx! {
+            Themed {}
+        });
+
+        assert!(rendered.contains("<style>"));
+        assert!(rendered.contains("color-scheme: light;"));
+    }
+}
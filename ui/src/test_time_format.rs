@@ -0,0 +1,126 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::time_format::*;
+    use crate::I18nContext;
+    use chrono::FixedOffset;
+
+    /// Friday 14 March 2025, 21:05 UTC
+    const NOW: u64 = 1_741_986_300;
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+
+    fn formatter(locale: &str) -> TimeFormatter {
+        TimeFormatter::new(&I18nContext::new(locale)).with_offset(FixedOffset::east_opt(0).unwrap())
+    }
+
+    #[test]
+    fn test_hour_cycle_preference() {
+        assert_eq!(HourCycle::parse("12h"), Some(HourCycle::H12));
+        assert_eq!(HourCycle::parse("24h"), Some(HourCycle::H24));
+        assert_eq!(HourCycle::parse("auto"), None);
+
+        assert_eq!(formatter("en").hour_cycle(), HourCycle::H12);
+        assert_eq!(formatter("de").hour_cycle(), HourCycle::H24);
+
+        let i18n = I18nContext::new("en").with_hour_cycle(Some(HourCycle::H24));
+        assert_eq!(TimeFormatter::new(&i18n).hour_cycle(), HourCycle::H24);
+        assert!(i18n != I18nContext::new("en"));
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        assert_eq!(formatter("en").time(NOW), "9:05 PM");
+        assert_eq!(formatter("en").time(NOW - 21 * HOUR), "12:05 AM");
+        assert_eq!(formatter("de").time(NOW), "21:05");
+        assert_eq!(formatter("de").time(NOW - 21 * HOUR), "00:05");
+
+        let ja = I18nContext::new("ja").with_hour_cycle(Some(HourCycle::H12));
+        let ja = TimeFormatter::new(&ja).with_offset(FixedOffset::east_opt(0).unwrap());
+        assert_eq!(ja.time(NOW), "午後9:05");
+
+        let en = I18nContext::new("en").with_hour_cycle(Some(HourCycle::H24));
+        let en = TimeFormatter::new(&en).with_offset(FixedOffset::east_opt(0).unwrap());
+        assert_eq!(en.time(NOW), "21:05");
+    }
+
+    #[test]
+    fn test_time_zone() {
+        let tokyo = formatter("de").with_offset(FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(tokyo.time(NOW), "06:05");
+        assert_eq!(tokyo.date(NOW), "15.3.2025");
+
+        // Same day in UTC, the day before in Tokyo
+        assert_eq!(formatter("en").day_label(NOW - 8 * HOUR, NOW), "Today");
+        let tokyo = formatter("en").with_offset(FixedOffset::east_opt(9 * 3600).unwrap());
+        assert_eq!(tokyo.day_label(NOW - 8 * HOUR, NOW), "Yesterday");
+    }
+
+    #[test]
+    fn test_dates_and_day_labels() {
+        assert_eq!(formatter("en").date(NOW), "3/14/2025");
+        assert_eq!(formatter("de").date(NOW), "14.3.2025");
+        assert_eq!(formatter("ja").date(NOW), "2025/3/14");
+
+        let en = formatter("en");
+        assert_eq!(en.day_label(NOW - HOUR, NOW), "Today");
+        assert_eq!(en.day_label(NOW - DAY, NOW), "Yesterday");
+        assert_eq!(en.day_label(NOW - 3 * DAY, NOW), "Tuesday");
+        assert_eq!(en.day_label(NOW - 6 * DAY, NOW), "Saturday");
+        assert_eq!(en.day_label(NOW - 10 * DAY, NOW), "3/4/2025");
+
+        let fr = formatter("fr");
+        assert_eq!(fr.day_label(NOW, NOW), "Aujourd'hui");
+        assert_eq!(fr.day_label(NOW - 3 * DAY, NOW), "Mardi");
+    }
+
+    #[test]
+    fn test_day_separators() {
+        let timestamps = [NOW - 26 * HOUR, NOW - 25 * HOUR, NOW - 60, NOW];
+        assert_eq!(
+            formatter("en").day_separators(&timestamps, NOW),
+            vec![
+                Some("Yesterday".to_string()),
+                None,
+                Some("Today".to_string()),
+                None
+            ]
+        );
+        assert!(formatter("en").day_separators(&[], NOW).is_empty());
+    }
+
+    #[test]
+    fn test_last_seen() {
+        let en = formatter("en");
+        assert_eq!(en.last_seen(NOW - 30, NOW), "Just now");
+        assert_eq!(en.last_seen(NOW - 60, NOW), "1 minute ago");
+        assert_eq!(en.last_seen(NOW - 300, NOW), "5 minutes ago");
+        assert_eq!(en.last_seen(NOW - 2 * HOUR, NOW), "2 hours ago");
+        assert_eq!(en.last_seen(NOW - 26 * HOUR, NOW), "Yesterday at 7:05 PM");
+        // Clock skew between devices
+        assert_eq!(en.last_seen(NOW + 30, NOW), "Just now");
+
+        let de = formatter("de");
+        assert_eq!(de.last_seen(NOW - 2 * HOUR, NOW), "vor 2 Stunden");
+        assert_eq!(de.last_seen(NOW - 26 * HOUR, NOW), "Gestern um 19:05");
+
+        let ar = formatter("ar");
+        assert_eq!(ar.last_seen(NOW - 120, NOW), "منذ دقيقتين");
+    }
+}
@@ -0,0 +1,196 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+#[cfg(test)]
+mod tests {
+    use crate::i18n_context::*;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use std::path::{Path, PathBuf};
+
+    /// Keys each locale is known to be missing, see the file's header
+    const MISSING_TRANSLATIONS: &str = include_str!("missing_translations.yml");
+
+    /// Workspace crates whose sources are scanned for translation keys
+    const SOURCE_CRATES: [&str; 4] = ["ui", "desktop", "mobile", "web"];
+
+    fn workspace_root() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .expect("ui crate is inside the workspace")
+            .to_path_buf()
+    }
+
+    /// Every `locales/*.yml` file, keyed by locale
+    fn load_locale_files() -> BTreeMap<String, HashMap<String, String>> {
+        let dir = workspace_root().join("locales");
+        std::fs::read_dir(&dir)
+            .expect("locales directory is readable")
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "yml" {
+                    return None;
+                }
+                let locale = path.file_stem()?.to_str()?.to_string();
+                let content = std::fs::read_to_string(&path).ok()?;
+                let translations = load_translations_from_yaml(&content)
+                    .unwrap_or_else(|e| panic!("{} is not valid YAML: {e}", path.display()));
+                Some((locale, translations))
+            })
+            .collect()
+    }
+
+    /// Keys of `reference` that `translations` lacks, and keys it has extra
+    fn compare_keys(
+        reference: &HashMap<String, String>,
+        translations: &HashMap<String, String>,
+    ) -> (BTreeSet<String>, BTreeSet<String>) {
+        let missing = reference
+            .keys()
+            .filter(|key| !translations.contains_key(*key))
+            .cloned()
+            .collect();
+        let extra = translations
+            .keys()
+            .filter(|key| !reference.contains_key(*key))
+            .cloned()
+            .collect();
+        (missing, extra)
+    }
+
+    fn rust_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.is_dir() {
+                rust_sources(&path, sources);
+            } else if path.extension().is_some_and(|ext| ext == "rs") {
+                sources.push(path);
+            }
+        }
+    }
+
+    /// Literal keys passed to `translate` and `translate_with`, including the
+    /// escaped form used inside rsx strings
+    fn translation_keys(source: &str) -> Vec<String> {
+        let mut keys = Vec::new();
+        for call in ["translate(", "translate_with("] {
+            let mut rest = source;
+            while let Some(start) = rest.find(call) {
+                rest = &rest[start + call.len()..];
+                let argument = rest.trim_start();
+                let argument = argument.strip_prefix('\\').unwrap_or(argument);
+                let Some(argument) = argument.strip_prefix('"') else {
+                    continue;
+                };
+                if let Some(end) = argument.find(['"', '\\']) {
+                    keys.push(argument[..end].to_string());
+                }
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn test_locale_files_match_available_locales() {
+        let files: BTreeSet<String> = load_locale_files().into_keys().collect();
+        let available: BTreeSet<String> = I18nContext::new("en")
+            .available_locales
+            .into_iter()
+            .collect();
+        assert_eq!(files, available);
+    }
+
+    #[test]
+    fn test_translation_coverage() {
+        let locales = load_locale_files();
+        let reference = &locales["en"];
+        let known_missing: BTreeMap<String, BTreeSet<String>> =
+            serde_yml::from_str(MISSING_TRANSLATIONS).expect("baseline is valid YAML");
+
+        let mut problems = Vec::new();
+        for (locale, translations) in &locales {
+            let (missing, extra) = compare_keys(reference, translations);
+            let allowed = known_missing.get(locale).cloned().unwrap_or_default();
+            for key in missing.difference(&allowed) {
+                problems.push(format!("{locale}: missing {key}"));
+            }
+            for key in allowed.difference(&missing) {
+                problems.push(format!(
+                    "{locale}: {key} is translated now, remove it from missing_translations.yml"
+                ));
+            }
+            for key in extra {
+                problems.push(format!("{locale}: {key} does not exist in en.yml"));
+            }
+        }
+
+        assert!(
+            problems.is_empty(),
+            "Translation coverage changed:\n{}",
+            problems.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_source_keys_exist() {
+        let locales = load_locale_files();
+        let mut sources = Vec::new();
+        for krate in SOURCE_CRATES {
+            rust_sources(&workspace_root().join(krate).join("src"), &mut sources);
+        }
+        assert!(!sources.is_empty());
+
+        let mut problems = Vec::new();
+        for path in sources {
+            // Tests translate unknown keys on purpose
+            let is_test = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("test_"));
+            if is_test {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).expect("source is readable");
+            for key in translation_keys(&source) {
+                if !locales.values().any(|locale| locale.contains_key(&key)) {
+                    problems.push(format!("{}: {key}", path.display()));
+                }
+            }
+        }
+
+        assert!(
+            problems.is_empty(),
+            "Keys without a translation in any locale:\n{}",
+            problems.join("\n")
+        );
+    }
+
+    #[test]
+    fn test_translation_keys_scanner() {
+        let source = r#"
+            i18n.translate("nav.messages");
+            rsx! { "{i18n.translate(\"nav.settings\")}" }
+            i18n.translate_with("messages.typing", &args);
+            i18n.translate(&format!("user_profile.themes.{id}"));
+        "#;
+        assert_eq!(
+            translation_keys(source),
+            vec!["nav.messages", "nav.settings", "messages.typing"]
+        );
+    }
+}
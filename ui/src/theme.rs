@@ -0,0 +1,243 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Theme registry for the design system variables.
+//! The built-in dark and light themes are compiled in, custom themes can be
+//! loaded from a stylesheet at startup. The active theme is what
+//! `resolve_color` resolves against.
+
+use crate::css_utils::{declarations, parse_css_variables, rule_blocks};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{LazyLock, RwLock};
+
+/// Theme preference that follows the system's light or dark setting
+pub const THEME_AUTO: &str = "auto";
+/// Theme used when the preference names no known theme
+pub const DEFAULT_THEME: &str = "dark";
+/// Stylesheet custom themes are loaded from, next to the app database
+pub const CUSTOM_THEMES_FILE: &str = "themes.css";
+
+const DARK_VARIABLES: &str = include_str!("../../mobile/assets/variables.css");
+const LIGHT_VARIABLES: &str = include_str!("../assets/themes/light.css");
+
+/// Custom property naming a custom theme in the theme file
+const THEME_NAME_PROPERTY: &str = "--theme-name";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    Dark,
+    Light,
+}
+
+impl ColorScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorScheme::Dark => "dark",
+            ColorScheme::Light => "light",
+        }
+    }
+}
+
+/// A named set of values for the design system's CSS variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub id: String,
+    pub name: String,
+    pub color_scheme: ColorScheme,
+    pub variables: BTreeMap<String, String>,
+}
+
+impl Theme {
+    /// The default theme, parsed from the shared variables stylesheet
+    pub fn dark() -> Self {
+        Self {
+            id: "dark".to_string(),
+            name: "Dark".to_string(),
+            color_scheme: ColorScheme::Dark,
+            variables: parse_css_variables(DARK_VARIABLES).into_iter().collect(),
+        }
+    }
+
+    /// The dark theme with the light palette laid over it
+    pub fn light() -> Self {
+        let mut variables = Self::dark().variables;
+        variables.extend(parse_css_variables(LIGHT_VARIABLES));
+        Self {
+            id: "light".to_string(),
+            name: "Light".to_string(),
+            color_scheme: ColorScheme::Light,
+            variables,
+        }
+    }
+
+    /// Whether this is one of the themes compiled into the app
+    pub fn is_builtin(&self) -> bool {
+        is_builtin_id(&self.id)
+    }
+
+    /// Render the theme as a rule overriding the stylesheet's `:root` variables
+    pub fn to_css(&self) -> String {
+        // `html:root` outranks the plain `:root` of variables.css no matter
+        // which of the two the document loads last
+        let mut css = format!(
+            "html:root {{\n  color-scheme: {};\n",
+            self.color_scheme.as_str()
+        );
+        for (name, value) in &self.variables {
+            css.push_str(&format!("  {name}: {value};\n"));
+        }
+        css.push('}');
+        css
+    }
+}
+
+fn is_builtin_id(id: &str) -> bool {
+    id == "dark" || id == "light" || id == THEME_AUTO
+}
+
+/// Read the theme ID from a `[data-theme="id"]` selector
+fn selector_theme_id(selector: &str) -> Option<String> {
+    let start = selector.find("data-theme")?;
+    let value = selector[start + "data-theme".len()..]
+        .trim_start()
+        .strip_prefix('=')?
+        .trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    let id = &value[..value.find(quote)?];
+    (!id.trim().is_empty()).then(|| id.trim().to_string())
+}
+
+/// Parse custom themes from a stylesheet.
+/// Each `[data-theme="id"]` rule is one theme. It starts from the built-in
+/// theme matching its `color-scheme` (dark unless it says `light`) and
+/// overrides the variables it declares. `--theme-name` sets its display name.
+pub fn parse_themes(css: &str) -> Vec<Theme> {
+    rule_blocks(css)
+        .into_iter()
+        .filter_map(|(selector, body)| {
+            let id = selector_theme_id(&selector)?;
+            let declarations = declarations(&body);
+            let color_scheme = match declarations
+                .iter()
+                .find(|(name, _)| name == "color-scheme")
+                .map(|(_, value)| value.as_str())
+            {
+                Some("light") => ColorScheme::Light,
+                _ => ColorScheme::Dark,
+            };
+            let mut theme = match color_scheme {
+                ColorScheme::Dark => Theme::dark(),
+                ColorScheme::Light => Theme::light(),
+            };
+            theme.name = id.clone();
+            theme.id = id;
+            for (name, value) in declarations {
+                if name == THEME_NAME_PROPERTY {
+                    theme.name = value.trim_matches(['"', '\'']).to_string();
+                } else if name.starts_with("--") {
+                    theme.variables.insert(name, value);
+                }
+            }
+            Some(theme)
+        })
+        .collect()
+}
+
+struct ThemeRegistry {
+    themes: Vec<Theme>,
+    active: Theme,
+}
+
+static REGISTRY: LazyLock<RwLock<ThemeRegistry>> = LazyLock::new(|| {
+    RwLock::new(ThemeRegistry {
+        themes: vec![Theme::dark(), Theme::light()],
+        active: Theme::dark(),
+    })
+});
+
+/// All registered themes, built-in ones first
+pub fn themes() -> Vec<Theme> {
+    REGISTRY
+        .read()
+        .map(|registry| registry.themes.clone())
+        .unwrap_or_default()
+}
+
+/// Add a custom theme, replacing an earlier one with the same ID.
+/// Returns false for IDs the built-in themes use.
+pub fn register_theme(theme: Theme) -> bool {
+    if theme.is_builtin() {
+        return false;
+    }
+    let Ok(mut registry) = REGISTRY.write() else {
+        return false;
+    };
+    registry.themes.retain(|existing| existing.id != theme.id);
+    registry.themes.push(theme);
+    true
+}
+
+/// Register the custom themes of a theme file, returning how many it had
+pub fn load_theme_file(path: impl AsRef<Path>) -> std::io::Result<usize> {
+    let css = std::fs::read_to_string(path)?;
+    Ok(parse_themes(&css)
+        .into_iter()
+        .filter(|theme| register_theme(theme.clone()))
+        .count())
+}
+
+/// Pick the theme for a preference.
+/// "auto" follows the system, unknown IDs fall back to the default theme.
+pub fn resolve_theme(preference: &str, prefers_dark: bool) -> Theme {
+    let theme_id = match preference {
+        THEME_AUTO if prefers_dark => "dark",
+        THEME_AUTO => "light",
+        theme_id => theme_id,
+    };
+    let themes = themes();
+    themes
+        .iter()
+        .find(|theme| theme.id == theme_id)
+        .or_else(|| themes.iter().find(|theme| theme.id == DEFAULT_THEME))
+        .cloned()
+        .unwrap_or_else(Theme::dark)
+}
+
+/// The theme colors currently resolve against
+pub fn active_theme() -> Theme {
+    REGISTRY
+        .read()
+        .map(|registry| registry.active.clone())
+        .unwrap_or_else(|_| Theme::dark())
+}
+
+/// Make a theme the one colors resolve against
+pub fn set_active_theme(theme: Theme) {
+    if let Ok(mut registry) = REGISTRY.write() {
+        registry.active = theme;
+    }
+}
+
+/// Look up a variable in the active theme
+pub(crate) fn theme_variable(name: &str) -> Option<String> {
+    REGISTRY
+        .read()
+        .ok()
+        .and_then(|registry| registry.active.variables.get(name).cloned())
+}
@@ -0,0 +1,77 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::theme::{resolve_theme, set_active_theme, Theme};
+use dioxus::prelude::*;
+
+/// Reports whether the system prefers dark colors, and again on every change
+const PREFERS_DARK_SCRIPT: &str = r#"
+    const query = window.matchMedia("(prefers-color-scheme: dark)");
+    dioxus.send(query.matches);
+    query.addEventListener("change", (event) => dioxus.send(event.matches));
+    await new Promise(() => {});
+"#;
+
+/// App-wide theme state.
+/// `ThemeStyle` applies `active` to the document, components that resolve
+/// colors in Rust read it to re-render when the theme changes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ThemeContext {
+    /// Theme ID picked by the user, or "auto"
+    pub preference: Memo<String>,
+    pub prefers_dark: Signal<bool>,
+    pub active: Memo<Theme>,
+}
+
+/// Provide the theme context to the component tree.
+/// `preference` is re-run whenever the signals it reads change, so it can
+/// follow the active profile's theme setting.
+pub fn use_theme_provider(preference: impl FnMut() -> String + 'static) -> ThemeContext {
+    let preference = use_memo(preference);
+    let mut prefers_dark = use_signal(|| true);
+
+    use_future(move || async move {
+        let mut query = document::eval(PREFERS_DARK_SCRIPT);
+        while let Ok(dark) = query.recv::<bool>().await {
+            prefers_dark.set(dark);
+        }
+    });
+
+    let active = use_memo(move || {
+        let theme = resolve_theme(&preference(), prefers_dark());
+        // Update the registry before anything subscribed to the memo renders
+        set_active_theme(theme.clone());
+        theme
+    });
+
+    use_context_provider(|| ThemeContext {
+        preference,
+        prefers_dark,
+        active,
+    })
+}
+
+/// Apply the active theme's variables to the document
+#[component]
+pub fn ThemeStyle() -> Element {
+    let theme = use_context::<ThemeContext>();
+    let css = theme.active.read().to_css();
+
+    rsx! {
+        style { "{css}" }
+    }
+}
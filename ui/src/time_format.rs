@@ -0,0 +1,222 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Locale-aware message times, day separators and last-seen text.
+//! Patterns and names come from the `datetime` section of the locale files,
+//! times are shown in the device's time zone.
+
+use crate::{I18nArg, I18nContext};
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveDate, TimeZone, Timelike, Utc};
+
+/// Weekday keys in `datetime.weekdays`, Monday first like chrono
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// How many days back a day separator names the weekday instead of the date
+const WEEKDAY_LABEL_DAYS: i64 = 6;
+
+/// Whether times use a 12 hour clock with AM/PM or a 24 hour clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HourCycle {
+    H12,
+    H24,
+}
+
+impl HourCycle {
+    /// Read a `UserData::time_format` preference, `None` for "auto"
+    pub fn parse(time_format: &str) -> Option<Self> {
+        match time_format {
+            "12h" => Some(HourCycle::H12),
+            "24h" => Some(HourCycle::H24),
+            _ => None,
+        }
+    }
+
+    /// The clock a locale usually uses
+    pub fn for_locale(i18n: &I18nContext) -> Self {
+        match i18n.translate("datetime.hour_cycle").as_str() {
+            "12" => HourCycle::H12,
+            _ => HourCycle::H24,
+        }
+    }
+}
+
+/// Current time as Unix seconds
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats Unix timestamps for the locale and clock of an `I18nContext`
+#[derive(Clone, PartialEq)]
+pub struct TimeFormatter {
+    i18n: I18nContext,
+    hour_cycle: HourCycle,
+    /// Fixed time zone, `None` uses the device's
+    offset: Option<FixedOffset>,
+}
+
+impl TimeFormatter {
+    pub fn new(i18n: &I18nContext) -> Self {
+        Self {
+            hour_cycle: i18n
+                .hour_cycle
+                .unwrap_or_else(|| HourCycle::for_locale(i18n)),
+            i18n: i18n.clone(),
+            offset: None,
+        }
+    }
+
+    /// Format in a fixed time zone instead of the device's
+    pub fn with_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn hour_cycle(&self) -> HourCycle {
+        self.hour_cycle
+    }
+
+    fn local(&self, timestamp: u64) -> DateTime<FixedOffset> {
+        let utc = Utc
+            .timestamp_opt(i64::try_from(timestamp).unwrap_or(i64::MAX), 0)
+            .single()
+            .unwrap_or_default();
+        match self.offset {
+            Some(offset) => utc.with_timezone(&offset),
+            None => utc.with_timezone(&Local).fixed_offset(),
+        }
+    }
+
+    fn day(&self, timestamp: u64) -> NaiveDate {
+        self.local(timestamp).date_naive()
+    }
+
+    /// Time of day, e.g. "9:05 PM" or "21:05"
+    pub fn time(&self, timestamp: u64) -> String {
+        let local = self.local(timestamp);
+        let minute = format!("{:02}", local.minute());
+        match self.hour_cycle {
+            HourCycle::H12 => {
+                let (is_pm, hour) = local.hour12();
+                let period = self
+                    .i18n
+                    .translate(if is_pm { "datetime.pm" } else { "datetime.am" });
+                fill(
+                    &self.i18n.translate("datetime.time_12h"),
+                    &[
+                        ("hour", hour.to_string()),
+                        ("minute", minute),
+                        ("period", period),
+                    ],
+                )
+            }
+            HourCycle::H24 => fill(
+                &self.i18n.translate("datetime.time_24h"),
+                &[("hour", format!("{:02}", local.hour())), ("minute", minute)],
+            ),
+        }
+    }
+
+    /// Calendar date in the locale's numeric order
+    pub fn date(&self, timestamp: u64) -> String {
+        let day = self.day(timestamp);
+        fill(
+            &self.i18n.translate("datetime.date"),
+            &[
+                ("day", day.day().to_string()),
+                ("month", day.month().to_string()),
+                ("year", day.year().to_string()),
+            ],
+        )
+    }
+
+    /// Name of a day relative to `now`: today, yesterday, a weekday within
+    /// the last week, otherwise the date
+    pub fn day_label(&self, timestamp: u64, now: u64) -> String {
+        let day = self.day(timestamp);
+        match (self.day(now) - day).num_days() {
+            0 => self.i18n.translate("datetime.today"),
+            1 => self.i18n.translate("datetime.yesterday"),
+            2..=WEEKDAY_LABEL_DAYS => {
+                let weekday = WEEKDAYS[day.weekday().num_days_from_monday() as usize];
+                self.i18n.translate(&format!("datetime.weekdays.{weekday}"))
+            }
+            _ => self.date(timestamp),
+        }
+    }
+
+    /// Day separators for messages sent at `timestamps`, in order.
+    /// The first message of each day gets the day's label.
+    pub fn day_separators(&self, timestamps: &[u64], now: u64) -> Vec<Option<String>> {
+        let mut previous = None;
+        timestamps
+            .iter()
+            .map(|&timestamp| {
+                let day = self.day(timestamp);
+                let separator = (previous != Some(day)).then(|| self.day_label(timestamp, now));
+                previous = Some(day);
+                separator
+            })
+            .collect()
+    }
+
+    /// When a contact was last seen: moments ago, minutes or hours ago
+    /// today, otherwise the day and time
+    pub fn last_seen(&self, timestamp: u64, now: u64) -> String {
+        let elapsed = now.saturating_sub(timestamp);
+        if elapsed < 60 {
+            self.i18n.translate("datetime.just_now")
+        } else if elapsed < 3600 {
+            self.i18n.translate_with(
+                "datetime.minutes_ago",
+                &[("count", I18nArg::from(elapsed / 60))],
+            )
+        } else if self.day(timestamp) == self.day(now) {
+            self.i18n.translate_with(
+                "datetime.hours_ago",
+                &[("count", I18nArg::from(elapsed / 3600))],
+            )
+        } else {
+            fill(
+                &self.i18n.translate("datetime.day_at_time"),
+                &[
+                    ("day", self.day_label(timestamp, now)),
+                    ("time", self.time(timestamp)),
+                ],
+            )
+        }
+    }
+}
+
+/// Substitute `{name}` placeholders of a date or time pattern
+fn fill(pattern: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(pattern.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
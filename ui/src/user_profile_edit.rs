@@ -15,12 +15,54 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::I18nContext;
+use crate::{
+    avatar_data_url, get_language_name, parse_data_url, square_crop, themes, AvatarContext,
+    I18nContext, LocaleContext, THEME_AUTO,
+};
 use dioxus::prelude::*;
+use shared::avatar::{AVATAR_MIME_TYPES, AVATAR_SIZE};
+use shared::crypto::attachment::Attachment;
+use shared::local::store_avatar_image;
 use shared::user_data::UserData;
 
 const USER_PROFILE_EDIT_CSS: Asset = asset!("/assets/styling/user_profile_edit.css");
 
+/// Loads an image from a `data:` URL, reports its size, then draws the
+/// requested square crop resized to the avatar size and returns it as JPEG
+const AVATAR_CROP_SCRIPT: &str = r#"
+    const image = new Image();
+    image.src = await dioxus.recv();
+    await image.decode();
+    dioxus.send([image.naturalWidth, image.naturalHeight]);
+    const [x, y, size, output] = await dioxus.recv();
+    const canvas = document.createElement("canvas");
+    canvas.width = output;
+    canvas.height = output;
+    canvas.getContext("2d").drawImage(image, x, y, size, size, 0, 0, output, output);
+    dioxus.send(canvas.toDataURL("image/jpeg", 0.85));
+"#;
+
+/// Crop an imported image to a square avatar at `zoom` in the webview
+async fn crop_avatar(source: String, zoom: f64) -> Result<String, String> {
+    let mut script = document::eval(AVATAR_CROP_SCRIPT);
+    script.send(source).map_err(|e| e.to_string())?;
+    let [width, height] = script.recv::<[u32; 2]>().await.map_err(|e| e.to_string())?;
+    let crop = square_crop(width, height, zoom);
+    script
+        .send([crop.x, crop.y, crop.size, AVATAR_SIZE])
+        .map_err(|e| e.to_string())?;
+    script.recv::<String>().await.map_err(|e| e.to_string())
+}
+
+/// Store a cropped avatar encrypted in the profile database
+async fn store_cropped_avatar(cropped: &str) -> Result<Attachment, String> {
+    let (mime_type, data) = parse_data_url(cropped).ok_or("invalid avatar image")?;
+    let avatar_json = store_avatar_image(mime_type, data)
+        .await
+        .map_err(|e| e.to_string())?;
+    Attachment::from_json(&avatar_json).map_err(|e| e.to_string())
+}
+
 #[derive(Props, Clone, PartialEq)]
 pub struct UserProfileEditProps {
     pub initial_data: UserData,
@@ -35,25 +77,52 @@ pub struct UserProfileEditProps {
 pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
     let mut profile_data = use_signal(|| props.initial_data.clone());
     let mut show_avatar_upload = use_signal(|| false);
+    // Shown avatar, starts with the saved one and follows imports
+    let saved_avatar = try_use_context::<AvatarContext>().and_then(|avatars| (avatars.own)());
+    let mut avatar_preview = use_signal(|| saved_avatar);
+    // Imported image and the crop applied to it, stored when saving
+    let mut avatar_source = use_signal(|| Option::<String>::None);
+    let mut avatar_zoom = use_signal(|| 1.0f64);
+    let mut cropped_avatar = use_signal(|| Option::<String>::None);
+    let mut avatar_error = use_signal(|| Option::<String>::None);
     #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
     let mut validation_errors = use_signal(|| Vec::<String>::new());
 
-    // Available theme options
-    let theme_options = [
-        ("dark", "user_profile.themes.dark"),
-        ("light", "user_profile.themes.light"),
-        ("auto", "user_profile.themes.auto"),
-    ];
+    // Available theme options, built-in ones are translated
+    let mut theme_options: Vec<(String, String)> = ["dark", "light", THEME_AUTO]
+        .iter()
+        .map(|id| {
+            let label = props.i18n.translate(&format!("user_profile.themes.{id}"));
+            (id.to_string(), label)
+        })
+        .collect();
+    theme_options.extend(
+        themes()
+            .into_iter()
+            .filter(|theme| !theme.is_builtin())
+            .map(|theme| (theme.id, theme.name)),
+    );
 
     // Available language options - always display in native text
-    let language_options = [
-        ("en", "English"),
-        ("es", "Español"),
-        ("fr", "Français"),
-        ("de", "Deutsch"),
-        ("ja", "日本語"),
-        ("zh", "中文"),
-    ];
+    let language_options: Vec<(String, &str)> = props
+        .i18n
+        .available_locales
+        .iter()
+        .map(|locale| (locale.clone(), get_language_name(locale)))
+        .collect();
+    // Language changes apply to the whole app right away
+    let locale_context = try_use_context::<LocaleContext>();
+
+    // Clock options, "auto" follows the language
+    let time_format_options: Vec<(&str, String)> = ["auto", "12h", "24h"]
+        .into_iter()
+        .map(|id| {
+            let label = props
+                .i18n
+                .translate(&format!("user_profile.time_formats.{id}"));
+            (id, label)
+        })
+        .collect();
 
     // Auto-away minute options
     let auto_away_options = [
@@ -69,6 +138,30 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
     // Clone i18n for use in closures
     let i18n = props.i18n.clone();
     let i18n_validate = i18n.clone();
+    let invalid_avatar = i18n.translate("user_profile.avatar.invalid");
+    let avatar_save_failed = i18n.translate("user_profile.avatar.save_failed");
+
+    // Re-crop the imported image whenever it or the zoom changes
+    let crop_failed = invalid_avatar.clone();
+    use_effect(move || {
+        let (Some(source), zoom) = (avatar_source(), avatar_zoom()) else {
+            return;
+        };
+        let crop_failed = crop_failed.clone();
+        spawn(async move {
+            match crop_avatar(source, zoom).await {
+                Ok(cropped) => {
+                    avatar_preview.set(Some(cropped.clone()));
+                    cropped_avatar.set(Some(cropped));
+                }
+                Err(e) => {
+                    eprintln!("Failed to crop avatar: {e}");
+                    avatar_source.set(None);
+                    avatar_error.set(Some(crop_failed));
+                }
+            }
+        });
+    });
 
     // Validation function
     let mut validate_form = move || {
@@ -87,11 +180,31 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
         errors.is_empty()
     };
 
-    // Handle save
+    // Handle save, storing a newly imported avatar first
     let mut handle_save = move |_| {
-        if validate_form() {
-            props.on_save.call(profile_data());
+        if !validate_form() {
+            return;
         }
+        let avatar_save_failed = avatar_save_failed.clone();
+        spawn(async move {
+            let mut data = profile_data();
+            if let Some(cropped) = cropped_avatar() {
+                match store_cropped_avatar(&cropped).await {
+                    Ok(avatar) => {
+                        data.set_avatar(Some(avatar));
+                        data.set_avatar_url(None);
+                        cropped_avatar.set(None);
+                        profile_data.set(data.clone());
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to store avatar: {e}");
+                        validation_errors.set(vec![avatar_save_failed]);
+                        return;
+                    }
+                }
+            }
+            props.on_save.call(data);
+        });
     };
 
     rsx! {
@@ -152,9 +265,9 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                             class: "current-avatar",
                             onclick: move |_| show_avatar_upload.set(!show_avatar_upload()),
 
-                            if let Some(ref avatar_url) = profile_data().avatar_url {
+                            if let Some(avatar) = avatar_preview() {
                                 img {
-                                    src: "{avatar_url}",
+                                    src: "{avatar}",
                                     alt: "User Avatar",
                                     class: "avatar-image"
                                 }
@@ -175,29 +288,72 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                             div {
                                 class: "avatar-upload",
 
-                                input {
-                                    r#type: "url",
-                                    placeholder: i18n.translate("user_profile.avatar.url_placeholder"),
-                                    class: "avatar-input",
-                                    value: profile_data().avatar_url.as_deref().unwrap_or(""),
-                                    oninput: move |evt| {
-                                        let mut data = profile_data();
-                                        data.set_avatar_url(if evt.value().is_empty() {
-                                            None
-                                        } else {
-                                            Some(evt.value())
-                                        });
-                                        profile_data.set(data);
+                                label {
+                                    class: "avatar-choose-btn",
+                                    "{i18n.translate(\"user_profile.avatar.choose\")}"
+                                    input {
+                                        r#type: "file",
+                                        class: "avatar-file-input",
+                                        accept: AVATAR_MIME_TYPES.join(","),
+                                        onchange: move |evt| {
+                                            let invalid_avatar = invalid_avatar.clone();
+                                            spawn(async move {
+                                                avatar_error.set(None);
+                                                let Some(file) = evt.files().into_iter().next() else {
+                                                    return;
+                                                };
+                                                let mime_type = file.content_type().unwrap_or_default();
+                                                if !AVATAR_MIME_TYPES.contains(&mime_type.as_str()) {
+                                                    avatar_error.set(Some(invalid_avatar));
+                                                    return;
+                                                }
+                                                match file.read_bytes().await {
+                                                    Ok(bytes) => {
+                                                        avatar_zoom.set(1.0);
+                                                        avatar_source.set(Some(avatar_data_url(&mime_type, &bytes)));
+                                                    }
+                                                    Err(e) => {
+                                                        eprintln!("Failed to read avatar: {e}");
+                                                        avatar_error.set(Some(invalid_avatar));
+                                                    }
+                                                }
+                                            });
+                                        }
                                     }
                                 }
 
+                                if avatar_source().is_some() {
+                                    label {
+                                        class: "avatar-zoom",
+                                        span { "{i18n.translate(\"user_profile.avatar.zoom\")}" }
+                                        input {
+                                            r#type: "range",
+                                            min: "1",
+                                            max: "4",
+                                            step: "0.1",
+                                            value: "{avatar_zoom()}",
+                                            oninput: move |evt| {
+                                                avatar_zoom.set(evt.value().parse().unwrap_or(1.0));
+                                            }
+                                        }
+                                    }
+                                }
+
+                                if let Some(error) = avatar_error() {
+                                    p { class: "avatar-error", "{error}" }
+                                }
+
                                 button {
                                     r#type: "button",
                                     class: "remove-avatar-btn",
                                     onclick: move |_| {
                                         let mut data = profile_data();
+                                        data.set_avatar(None);
                                         data.set_avatar_url(None);
                                         profile_data.set(data);
+                                        avatar_source.set(None);
+                                        cropped_avatar.set(None);
+                                        avatar_preview.set(None);
                                         show_avatar_upload.set(false);
                                     },
                                     "{i18n.translate(\"user_profile.avatar.remove\")}"
@@ -317,11 +473,11 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                                     profile_data.set(data);
                                 },
 
-                                for (value, text_key) in theme_options.iter() {
+                                for (value, label) in theme_options.iter() {
                                     option {
                                         value: "{value}",
                                         selected: profile_data().theme == *value,
-                                        "{props.i18n.translate(text_key)}"
+                                        "{label}"
                                     }
                                 }
                             }
@@ -342,6 +498,9 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                                     let mut data = profile_data();
                                     data.set_language(evt.value());
                                     profile_data.set(data);
+                                    if let Some(locale_context) = locale_context {
+                                        locale_context.change(evt.value());
+                                    }
                                 },
 
                                 for (value, native_name) in language_options.iter() {
@@ -355,6 +514,33 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                         }
                     }
 
+                    div {
+                        class: "form-group",
+                        label {
+                            class: "form-label",
+                            r#for: "time_format",
+                            "{props.i18n.translate(\"user_profile.time_format\")}"
+                        }
+                        select {
+                            id: "time_format",
+                            class: "form-select",
+                            value: "{profile_data().time_format}",
+                            onchange: move |evt| {
+                                let mut data = profile_data();
+                                data.set_time_format(evt.value());
+                                profile_data.set(data);
+                            },
+
+                            for (value, label) in time_format_options.iter() {
+                                option {
+                                    value: "{value}",
+                                    selected: profile_data().time_format == *value,
+                                    "{label}"
+                                }
+                            }
+                        }
+                    }
+
                     div {
                         class: "form-group",
                         label {
@@ -437,6 +623,100 @@ pub fn UserProfileEdit(props: UserProfileEditProps) -> Element {
                             }
                         }
                     }
+
+                    div {
+                        class: "form-group checkbox-group",
+                        label {
+                            class: "checkbox-label",
+                            input {
+                                r#type: "checkbox",
+                                class: "form-checkbox",
+                                checked: profile_data().show_message_previews,
+                                onchange: move |evt| {
+                                    let mut data = profile_data();
+                                    data.set_show_message_previews(evt.checked());
+                                    profile_data.set(data);
+                                }
+                            }
+                            span {
+                                class: "checkbox-text",
+                                "{props.i18n.translate(\"user_profile.show_message_previews\")}"
+                            }
+                        }
+                    }
+                }
+
+                // Privacy settings section
+                section {
+                    class: "form-section",
+
+                    h2 {
+                        class: "section-title",
+                        "{props.i18n.translate(\"user_profile.privacy.title\")}"
+                    }
+
+                    div {
+                        class: "form-group checkbox-group",
+                        label {
+                            class: "checkbox-label",
+                            input {
+                                r#type: "checkbox",
+                                class: "form-checkbox",
+                                checked: profile_data().send_delivery_receipts,
+                                onchange: move |evt| {
+                                    let mut data = profile_data();
+                                    data.set_send_delivery_receipts(evt.checked());
+                                    profile_data.set(data);
+                                }
+                            }
+                            span {
+                                class: "checkbox-text",
+                                "{props.i18n.translate(\"user_profile.send_delivery_receipts\")}"
+                            }
+                        }
+                    }
+
+                    div {
+                        class: "form-group checkbox-group",
+                        label {
+                            class: "checkbox-label",
+                            input {
+                                r#type: "checkbox",
+                                class: "form-checkbox",
+                                checked: profile_data().send_read_receipts,
+                                onchange: move |evt| {
+                                    let mut data = profile_data();
+                                    data.set_send_read_receipts(evt.checked());
+                                    profile_data.set(data);
+                                }
+                            }
+                            span {
+                                class: "checkbox-text",
+                                "{props.i18n.translate(\"user_profile.send_read_receipts\")}"
+                            }
+                        }
+                    }
+
+                    div {
+                        class: "form-group checkbox-group",
+                        label {
+                            class: "checkbox-label",
+                            input {
+                                r#type: "checkbox",
+                                class: "form-checkbox",
+                                checked: profile_data().send_typing_indicators,
+                                onchange: move |evt| {
+                                    let mut data = profile_data();
+                                    data.set_send_typing_indicators(evt.checked());
+                                    profile_data.set(data);
+                                }
+                            }
+                            span {
+                                class: "checkbox-text",
+                                "{props.i18n.translate(\"user_profile.send_typing_indicators\")}"
+                            }
+                        }
+                    }
                 }
 
                 // Form actions
@@ -15,11 +15,21 @@
  *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::I18nContext;
+use crate::{current_i18n, I18nContext, PresenceContext, ProfileContext};
 use dioxus::prelude::*;
+use shared::crypto::message::PresenceState;
+use shared::user_data::UserData;
 
 const USER_PROFILE_MINI_CSS: Asset = asset!("/assets/styling/user_profile_mini.css");
 
+/// Presence choices offered to the user, `None` follows the idle timer
+const PRESENCE_CHOICES: [(Option<PresenceState>, &str); 4] = [
+    (None, "presence.automatic"),
+    (Some(PresenceState::Away), "presence.away"),
+    (Some(PresenceState::DoNotDisturb), "presence.do_not_disturb"),
+    (Some(PresenceState::Offline), "presence.appear_offline"),
+];
+
 #[derive(Props, Clone, PartialEq)]
 pub struct UserProfileMiniProps {
     #[props(default = "User".to_string())]
@@ -28,7 +38,7 @@ pub struct UserProfileMiniProps {
     pub status: String,
     #[props(default = "U".to_string())]
     pub avatar_initial: String,
-    #[props(default = I18nContext::new("en"))]
+    #[props(default = current_i18n())]
     pub i18n: I18nContext,
     #[props(optional)]
     pub onclick: Option<EventHandler<MouseEvent>>,
@@ -36,6 +46,38 @@ pub struct UserProfileMiniProps {
 
 #[component]
 pub fn UserProfileMini(props: UserProfileMiniProps) -> Element {
+    // The profile switcher is only available when the app provides profiles
+    let profile_context = try_use_context::<ProfileContext>();
+    // Likewise the presence menu needs the app to track presence
+    let presence_context = try_use_context::<PresenceContext>();
+    let mut show_switcher = use_signal(|| false);
+    let mut show_presence_menu = use_signal(|| false);
+    #[allow(clippy::redundant_closure)] // use_signal requires closures, not function pointers
+    let mut new_profile_name = use_signal(|| String::new());
+
+    // Prefer the active profile over the values passed in by the view
+    let active_profile = profile_context.and_then(|context| context.active_profile());
+    let (username, avatar_initial) = match &active_profile {
+        Some(profile) => {
+            let name = profile.effective_display_name().to_string();
+            let initial = name
+                .chars()
+                .next()
+                .unwrap_or('U')
+                .to_uppercase()
+                .to_string();
+            (name, initial)
+        }
+        None => (props.username.clone(), props.avatar_initial.clone()),
+    };
+    let presence = presence_context.map(|context| (context.state)());
+    let status = match presence {
+        Some(state) => props
+            .i18n
+            .translate(&format!("presence.{}", state.as_str())),
+        None => props.status.clone(),
+    };
+
     rsx! {
         document::Link { rel: "stylesheet", href: USER_PROFILE_MINI_CSS }
 
@@ -48,12 +90,175 @@ pub fn UserProfileMini(props: UserProfileMiniProps) -> Element {
             },
             div {
                 class: "upm-avatar",
-                "{props.avatar_initial}"
+                "{avatar_initial}"
+                if let Some(state) = presence {
+                    span { class: "upm-presence-dot upm-presence-{state.as_str()}" }
+                }
             }
             div {
                 class: "upm-info",
-                div { class: "upm-name", "{props.username}" }
-                div { class: "upm-status", "{props.status}" }
+                div { class: "upm-name", "{username}" }
+                if presence_context.is_some() {
+                    button {
+                        r#type: "button",
+                        class: "upm-status upm-status-btn",
+                        "title": "{props.i18n.translate(\"presence.title\")}",
+                        onclick: move |evt| {
+                            evt.stop_propagation();
+                            show_switcher.set(false);
+                            show_presence_menu.set(!show_presence_menu());
+                        },
+                        "{status}"
+                    }
+                } else {
+                    div { class: "upm-status", "{status}" }
+                }
+            }
+
+            if profile_context.is_some() {
+                button {
+                    class: "upm-switch-btn",
+                    r#type: "button",
+                    "title": "{props.i18n.translate(\"profiles.switch\")}",
+                    onclick: move |evt| {
+                        evt.stop_propagation();
+                        show_presence_menu.set(false);
+                        show_switcher.set(!show_switcher());
+                    },
+                    "⇅"
+                }
+            }
+
+            if let Some(context) = presence_context.filter(|_| show_presence_menu()) {
+                div {
+                    class: "upm-switcher",
+                    onclick: move |evt| evt.stop_propagation(),
+
+                    div {
+                        class: "upm-switcher-title",
+                        "{props.i18n.translate(\"presence.title\")}"
+                    }
+
+                    for (choice, label) in PRESENCE_CHOICES {
+                        button {
+                            key: "{label}",
+                            r#type: "button",
+                            class: if (context.chosen)() == choice { "upm-presence-option upm-presence-option-active" } else { "upm-presence-option" },
+                            onclick: move |_| {
+                                context.choose(choice);
+                                show_presence_menu.set(false);
+                            },
+                            span { class: "upm-presence-dot upm-presence-{choice.unwrap_or(PresenceState::Online).as_str()}" }
+                            "{props.i18n.translate(label)}"
+                        }
+                    }
+                }
+            }
+
+            if let Some(context) = profile_context.filter(|_| show_switcher()) {
+                div {
+                    class: "upm-switcher",
+                    onclick: move |evt| evt.stop_propagation(),
+
+                    div {
+                        class: "upm-switcher-title",
+                        "{props.i18n.translate(\"profiles.title\")}"
+                    }
+
+                    for profile in (context.profiles)() {
+                        ProfileSwitcherItem {
+                            key: "{profile.id.as_deref().unwrap_or(&profile.username)}",
+                            is_active: profile.id.is_some() && profile.id == (context.active_profile_id)(),
+                            profile: profile.clone(),
+                            i18n: props.i18n.clone(),
+                            on_select: move |profile_id: String| {
+                                context.switch_to(profile_id);
+                                show_switcher.set(false);
+                            },
+                            on_delete: move |profile_id: String| {
+                                context.delete(profile_id);
+                            }
+                        }
+                    }
+
+                    div {
+                        class: "upm-new-profile",
+                        input {
+                            r#type: "text",
+                            class: "upm-new-profile-input",
+                            placeholder: "{props.i18n.translate(\"profiles.new_placeholder\")}",
+                            value: "{new_profile_name()}",
+                            oninput: move |evt| new_profile_name.set(evt.value())
+                        }
+                        button {
+                            r#type: "button",
+                            class: "upm-new-profile-btn",
+                            disabled: new_profile_name().trim().is_empty(),
+                            onclick: move |_| {
+                                let name = new_profile_name().trim().to_string();
+                                if !name.is_empty() {
+                                    context.create(name.clone(), name);
+                                    new_profile_name.set(String::new());
+                                    show_switcher.set(false);
+                                }
+                            },
+                            "{props.i18n.translate(\"profiles.create\")}"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Props, Clone, PartialEq)]
+struct ProfileSwitcherItemProps {
+    profile: UserData,
+    is_active: bool,
+    i18n: I18nContext,
+    on_select: EventHandler<String>,
+    on_delete: EventHandler<String>,
+}
+
+#[component]
+fn ProfileSwitcherItem(props: ProfileSwitcherItemProps) -> Element {
+    let profile_id = props.profile.id.clone().unwrap_or_default();
+    let delete_id = profile_id.clone();
+    let name = props.profile.effective_display_name().to_string();
+    let initial = name
+        .chars()
+        .next()
+        .unwrap_or('U')
+        .to_uppercase()
+        .to_string();
+
+    rsx! {
+        div {
+            class: if props.is_active { "upm-profile-item upm-profile-active" } else { "upm-profile-item" },
+            onclick: move |_| {
+                if !props.is_active {
+                    props.on_select.call(profile_id.clone());
+                }
+            },
+            span { class: "upm-profile-initial", "{initial}" }
+            span { class: "upm-profile-name", "{name}" }
+
+            if props.is_active {
+                span {
+                    class: "upm-profile-badge",
+                    "{props.i18n.translate(\"profiles.active\")}"
+                }
+            } else {
+                button {
+                    r#type: "button",
+                    class: "upm-profile-delete",
+                    "title": "{props.i18n.translate(\"profiles.delete\")}",
+                    onclick: move |evt| {
+                        evt.stop_propagation();
+                        props.on_delete.call(delete_id.clone());
+                    },
+                    "✕"
+                }
             }
         }
     }
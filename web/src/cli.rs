@@ -0,0 +1,430 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Admin commands of the server binary.
+//! Every command prints a human readable summary, or a single JSON object
+//! with `--json`, and reports its outcome through the exit code.
+
+use api::admin::{
+    ensure_relay_key, mint_token, parse_token_id, queue_stats, revoke_token, rotate_relay_key,
+    sweep_expired,
+};
+use api::config::{config, init_config, ServerConfig};
+use api::persistence::postgres::{
+    check_migration_status, establish_connection, ping_database, rollback_migrations,
+    run_migrations,
+};
+use clap::{Parser, Subcommand};
+use sea_orm::DatabaseConnection;
+use sea_orm_migration::MigrationStatus;
+use serde_json::{json, Value};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+/// The command finished successfully
+pub const EXIT_OK: u8 = 0;
+/// The command failed while running
+pub const EXIT_FAILURE: u8 = 1;
+/// The arguments were invalid, also used by clap for parse errors
+pub const EXIT_USAGE: u8 = 2;
+/// The database is not configured or cannot be reached
+pub const EXIT_UNAVAILABLE: u8 = 3;
+/// The token to revoke does not exist or is no longer active
+pub const EXIT_NOT_FOUND: u8 = 4;
+/// `migrate status` found migrations that are not applied yet
+pub const EXIT_PENDING: u8 = 5;
+/// The server configuration is missing settings or invalid
+pub const EXIT_CONFIG: u8 = 6;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  the command failed
+  2  invalid arguments
+  3  database not configured or unreachable
+  4  token not found or not active
+  5  migrations are pending (migrate status)
+  6  invalid configuration";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "web",
+    about = "Meeseeks Nuntius relay server",
+    after_help = EXIT_CODES_HELP
+)]
+pub struct Cli {
+    /// Server configuration file [default: $MEESEEKS_CONFIG or ./meeseeks.toml]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Print the result as a JSON object
+    #[arg(long, global = true)]
+    pub json: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the web server, the default without a command
+    Serve,
+    /// Apply, roll back or list database migrations
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Check the database
+    #[command(subcommand)]
+    Db(DbCommand),
+    /// Delete expired messages and blobs, and expire stale tokens
+    Sweep,
+    /// Mint or revoke message tokens
+    #[command(subcommand)]
+    Token(TokenCommand),
+    /// Manage the relay key
+    #[command(subcommand)]
+    Keys(KeysCommand),
+    /// Print mailbox and blob queue statistics
+    Stats,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+    /// Apply all pending migrations
+    Up,
+    /// Roll back applied migrations, dropping the data they created
+    Down {
+        /// Number of migrations to roll back
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        steps: u32,
+        /// Roll back without asking, required when not run from a terminal
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List migrations and whether they are applied
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// Connect to the database and measure a round trip
+    Ping,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TokenCommand {
+    /// Create a token, printing its secret once
+    Mint {
+        /// Messages the token allows [default: tokens.default_max_messages]
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        max_messages: Option<i32>,
+        /// Days until the token expires [default: tokens.default_valid_days]
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=3650))]
+        valid_days: Option<u32>,
+    },
+    /// Revoke an active token by its ID
+    Revoke {
+        /// Token ID as printed when it was minted
+        id: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Generate a new relay key, keeping only the previous one
+    Rotate,
+}
+
+/// Why a command did not succeed
+#[derive(Debug)]
+struct CommandError {
+    code: u8,
+    message: String,
+    /// Result details to print along with the error, e.g. pending migrations
+    details: Option<(Value, String)>,
+}
+
+impl CommandError {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    fn failed(error: impl std::fmt::Display) -> Self {
+        Self::new(EXIT_FAILURE, error.to_string())
+    }
+}
+
+/// A command's result as JSON and as text
+type CommandOutput = (Value, String);
+
+fn migration_status_label(status: MigrationStatus) -> &'static str {
+    match status {
+        MigrationStatus::Applied => "applied",
+        MigrationStatus::Pending => "pending",
+    }
+}
+
+/// Run an admin command, printing its result
+pub fn run(command: Command, json_output: bool) -> ExitCode {
+    let result = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(execute(command)),
+        Err(e) => Err(CommandError::failed(e)),
+    };
+
+    match result {
+        Ok((value, text)) => {
+            if json_output {
+                println!("{value}");
+            } else {
+                println!("{text}");
+            }
+            ExitCode::from(EXIT_OK)
+        }
+        Err(error) => report_error(&error, json_output),
+    }
+}
+
+/// Load, validate and install the server configuration.
+/// Problems are reported like command errors, with the exit code to use.
+pub fn load_config(path: Option<&Path>, json_output: bool) -> Result<(), ExitCode> {
+    match ServerConfig::load(path) {
+        Ok(config) => {
+            init_config(config);
+            Ok(())
+        }
+        Err(e) => Err(report_error(
+            &CommandError::new(EXIT_CONFIG, e.to_string()),
+            json_output,
+        )),
+    }
+}
+
+fn report_error(error: &CommandError, json_output: bool) -> ExitCode {
+    if json_output {
+        let mut value = json!({ "error": error.message, "exit_code": error.code });
+        if let Some((details, _)) = &error.details {
+            value["details"] = details.clone();
+        }
+        println!("{value}");
+    } else {
+        if let Some((_, text)) = &error.details {
+            println!("{text}");
+        }
+        eprintln!("Error: {}", error.message);
+    }
+    ExitCode::from(error.code)
+}
+
+/// Ask before a destructive command. Without a terminal to ask on, only
+/// `--yes` confirms it.
+fn confirm(action: &str, yes: bool) -> Result<(), CommandError> {
+    if yes {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(CommandError::new(
+            EXIT_USAGE,
+            format!("{action}, pass --yes to confirm"),
+        ));
+    }
+
+    eprint!("{action}. Continue? [y/N] ");
+    std::io::stderr().flush().map_err(CommandError::failed)?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(CommandError::failed)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(CommandError::new(EXIT_FAILURE, "Aborted")),
+    }
+}
+
+async fn connect() -> Result<DatabaseConnection, CommandError> {
+    establish_connection().await.map_err(|e| {
+        CommandError::new(EXIT_UNAVAILABLE, format!("Database connection failed: {e}"))
+    })
+}
+
+async fn execute(command: Command) -> Result<CommandOutput, CommandError> {
+    match command {
+        // The server is launched by main
+        Command::Serve => Err(CommandError::new(
+            EXIT_USAGE,
+            "serve is not an admin command",
+        )),
+        Command::Migrate(command) => migrate(command).await,
+        Command::Db(DbCommand::Ping) => {
+            let started = Instant::now();
+            let db = connect().await?;
+            ping_database(&db)
+                .await
+                .map_err(|e| CommandError::new(EXIT_UNAVAILABLE, e.to_string()))?;
+            let millis = started.elapsed().as_millis();
+            Ok((
+                json!({ "ok": true, "latency_ms": millis }),
+                format!("Database reachable ({millis} ms)"),
+            ))
+        }
+        Command::Sweep => {
+            let db = connect().await?;
+            let report = sweep_expired(&db).await.map_err(CommandError::failed)?;
+            Ok((
+                json!(report),
+                format!(
+                    "Removed {} expired messages and {} expired blobs, expired {} tokens",
+                    report.messages, report.blobs, report.tokens
+                ),
+            ))
+        }
+        Command::Token(TokenCommand::Mint {
+            max_messages,
+            valid_days,
+        }) => {
+            let quotas = &config().tokens;
+            let max_messages = max_messages.unwrap_or(quotas.default_max_messages);
+            let valid_days = valid_days.unwrap_or(quotas.default_valid_days);
+            let db = connect().await?;
+            let minted = mint_token(&db, max_messages, valid_days)
+                .await
+                .map_err(CommandError::failed)?;
+            Ok((
+                json!(minted),
+                format!(
+                    "Token ID: {}\nToken:    {}\nAllows {} messages until {}\nThe token is not shown again.",
+                    minted.id, minted.token, minted.max_messages, minted.expires_at
+                ),
+            ))
+        }
+        Command::Token(TokenCommand::Revoke { id }) => {
+            let id =
+                parse_token_id(&id).map_err(|e| CommandError::new(EXIT_USAGE, e.to_string()))?;
+            let db = connect().await?;
+            if !revoke_token(&db, id).await.map_err(CommandError::failed)? {
+                return Err(CommandError::new(
+                    EXIT_NOT_FOUND,
+                    format!("No active token with ID {id}"),
+                ));
+            }
+            Ok((json!({ "revoked": id }), format!("Revoked token {id}")))
+        }
+        Command::Keys(KeysCommand::Rotate) => {
+            let db = connect().await?;
+            let key = rotate_relay_key(&db).await.map_err(CommandError::failed)?;
+            Ok((
+                json!(key),
+                format!(
+                    "Relay key {} is now active: {}\nRetired {} old keys",
+                    key.id, key.public_key, key.retired
+                ),
+            ))
+        }
+        Command::Stats => {
+            let db = connect().await?;
+            let stats = queue_stats(&db).await.map_err(CommandError::failed)?;
+            let oldest = stats
+                .oldest_message
+                .map_or_else(|| "-".to_string(), |oldest| oldest.to_string());
+            Ok((
+                json!(stats),
+                format!(
+                    "Messages:       {} ({} expired)\nMailboxes:      {}\nOldest message: {}\nBlobs:          {} ({} bytes)\nActive tokens:  {} ({} of {} messages used)\nRelay keys:     {}\nTo forward:     {}\nToken batches:  {} in the last hour ({} bits of work)",
+                    stats.messages,
+                    stats.expired_messages,
+                    stats.mailboxes,
+                    oldest,
+                    stats.blobs,
+                    stats.blob_bytes,
+                    stats.active_tokens,
+                    stats.token_messages_used,
+                    stats.token_messages_allowed,
+                    stats.relay_keys,
+                    stats.federation_queue,
+                    stats.recent_token_issuances,
+                    stats.token_pow_difficulty
+                ),
+            ))
+        }
+    }
+}
+
+async fn migrate(command: MigrateCommand) -> Result<CommandOutput, CommandError> {
+    if let MigrateCommand::Down { steps, yes } = &command {
+        confirm(
+            &format!("Rolling back {steps} migrations drops the tables and data they created"),
+            *yes,
+        )?;
+    }
+
+    let db = connect().await?;
+    match command {
+        MigrateCommand::Up => {
+            run_migrations(&db).await.map_err(CommandError::failed)?;
+            // A new relay gets its key before any replica serves
+            let key_created = ensure_relay_key(&db).await.map_err(CommandError::failed)?;
+            let mut text = "Database migrations completed successfully".to_string();
+            if key_created {
+                text.push_str("\nCreated the first relay key");
+            }
+            Ok((
+                json!({ "migrated": true, "relay_key_created": key_created }),
+                text,
+            ))
+        }
+        MigrateCommand::Down { steps, .. } => {
+            rollback_migrations(&db, steps)
+                .await
+                .map_err(CommandError::failed)?;
+            Ok((
+                json!({ "rolled_back": steps }),
+                format!("Rolled back {steps} migrations"),
+            ))
+        }
+        MigrateCommand::Status => {
+            let migrations = check_migration_status(&db)
+                .await
+                .map_err(CommandError::failed)?;
+            let pending = migrations
+                .iter()
+                .filter(|(_, status)| *status == MigrationStatus::Pending)
+                .count();
+            let value = json!({
+                "pending": pending,
+                "migrations": migrations
+                    .iter()
+                    .map(|(name, status)| json!({ "name": name, "status": migration_status_label(*status) }))
+                    .collect::<Vec<_>>(),
+            });
+            let text = migrations
+                .iter()
+                .map(|(name, status)| format!("{:<8} {name}", migration_status_label(*status)))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if pending > 0 {
+                return Err(CommandError {
+                    code: EXIT_PENDING,
+                    message: format!("{pending} migrations are pending"),
+                    details: Some((value, text)),
+                });
+            }
+            Ok((value, text))
+        }
+    }
+}
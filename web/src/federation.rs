@@ -0,0 +1,109 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Endpoints other relays use to federate with this one, and the background
+//! task forwarding queued messages to them. Both are only active with
+//! `federation.enabled` set.
+
+use api::config::config;
+use api::federation::{
+    forward_due, receive_deposit, relay_keys, DepositError, SignedDeposit, DEPOSIT_PATH, KEYS_PATH,
+};
+use api::persistence::postgres::establish_connection;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use dioxus::logger::tracing::{info, warn};
+use serde_json::json;
+use std::sync::Once;
+
+/// Routes other relays call
+pub fn router() -> Router {
+    Router::new()
+        .route(KEYS_PATH, get(keys))
+        .route(DEPOSIT_PATH, post(deposit))
+}
+
+/// Start forwarding queued messages in the background, once per process.
+/// The interval is `federation.forward_interval_secs` of the server
+/// configuration.
+pub fn start_forwarder() {
+    static STARTED: Once = Once::new();
+    if !config().federation.enabled {
+        return;
+    }
+    STARTED.call_once(|| {
+        tokio::spawn(async {
+            loop {
+                tokio::time::sleep(config().federation.forward_interval()).await;
+                let result = match establish_connection().await {
+                    Ok(db) => forward_due(&db).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(report) if report.forwarded + report.failed + report.dropped > 0 => {
+                        info!(
+                            "Forwarded {} messages, {} failed, {} dropped",
+                            report.forwarded, report.failed, report.dropped
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Forwarding queued messages failed: {e}"),
+                }
+            }
+        });
+    });
+}
+
+async fn keys() -> Response {
+    if !config().federation.enabled {
+        return error(StatusCode::NOT_FOUND, DepositError::Disabled.to_string());
+    }
+    let keys = match establish_connection().await {
+        Ok(db) => relay_keys(&db).await,
+        Err(e) => Err(e),
+    };
+    match keys {
+        Ok(keys) => Json(keys).into_response(),
+        Err(e) => error(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+    }
+}
+
+async fn deposit(Json(signed): Json<SignedDeposit>) -> Response {
+    let db = match establish_connection().await {
+        Ok(db) => db,
+        Err(e) => return error(StatusCode::SERVICE_UNAVAILABLE, e.to_string()),
+    };
+    match receive_deposit(&db, signed).await {
+        Ok(()) => Json(json!({ "status": "accepted" })).into_response(),
+        Err(e) => {
+            let status = match e {
+                DepositError::Disabled => StatusCode::NOT_FOUND,
+                DepositError::Malformed(_) => StatusCode::BAD_REQUEST,
+                DepositError::UnknownPeer(_) | DepositError::Rejected(_) => StatusCode::FORBIDDEN,
+                DepositError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+                DepositError::Throttled(_) => StatusCode::TOO_MANY_REQUESTS,
+            };
+            error(status, e.to_string())
+        }
+    }
+}
+
+fn error(status: StatusCode, reason: String) -> Response {
+    (status, Json(json!({ "status": "error", "reason": reason }))).into_response()
+}
@@ -0,0 +1,132 @@
+/*  This file is part of a secure messaging project codename meeseeks-nuntius
+ *  Copyright (C) 2025  Grant DeFayette
+ *
+ *  meeseeks-nuntius is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU General Public License as published by
+ *  the Free Software Foundation, either version 3 of the License, or
+ *  (at your option) any later version.
+ *
+ *  meeseeks-nuntius is distributed in the hope that it will be useful,
+ *  but WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ *  GNU General Public License for more details.
+ *
+ *  You should have received a copy of the GNU General Public License
+ *  along with meeseeks-nuntius.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Health checks and metrics served next to the app.
+//! `/healthz` only tells that the process is up, `/readyz` also requires a
+//! reachable database without pending migrations, and `/metrics` exposes
+//! the relay counters for Prometheus to holders of `server.metrics_token`.
+
+use api::admin::{queue_stats, sweep_expired};
+use api::config::config;
+use api::metrics::{is_authorized, METRICS};
+use api::persistence::postgres::{check_migration_status, establish_connection, ping_database};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use dioxus::logger::tracing::{info, warn};
+use sea_orm_migration::MigrationStatus;
+use serde_json::json;
+use std::sync::Once;
+
+/// Content type of the Prometheus text format
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Routes for health checks and metrics
+pub fn router() -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+}
+
+/// Start sweeping expired relay data in the background, once per process,
+/// reading the queue gauges for `/metrics` after every sweep.
+/// The interval is `relay.sweep_interval_secs` of the server configuration.
+pub fn start_sweeper() {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        tokio::spawn(async {
+            let mut interval = tokio::time::interval(config().relay.sweep_interval());
+            loop {
+                interval.tick().await;
+                let db = match establish_connection().await {
+                    Ok(db) => db,
+                    Err(e) => {
+                        METRICS.record_sweep(None);
+                        METRICS.record_stats(None);
+                        warn!("Sweeping expired relay data failed: {e}");
+                        continue;
+                    }
+                };
+                match sweep_expired(&db).await {
+                    Ok(report) => {
+                        METRICS.record_sweep(Some(&report));
+                        info!(
+                            "Swept {} messages, {} blobs and {} tokens",
+                            report.messages, report.blobs, report.tokens
+                        );
+                    }
+                    Err(e) => {
+                        METRICS.record_sweep(None);
+                        warn!("Sweeping expired relay data failed: {e}");
+                    }
+                }
+                METRICS.record_stats(queue_stats(&db).await.ok());
+            }
+        });
+    });
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz() -> Response {
+    let db = match establish_connection().await {
+        Ok(db) => db,
+        Err(e) => return not_ready(format!("Database connection failed: {e}")),
+    };
+    if let Err(e) = ping_database(&db).await {
+        return not_ready(format!("Database ping failed: {e}"));
+    }
+    let pending = match check_migration_status(&db).await {
+        Ok(migrations) => migrations
+            .iter()
+            .filter(|(_, status)| *status == MigrationStatus::Pending)
+            .count(),
+        Err(e) => return not_ready(format!("Migration status unavailable: {e}")),
+    };
+    if pending > 0 {
+        return not_ready(format!("{pending} migrations are pending"));
+    }
+
+    Json(json!({ "status": "ready", "database": "ok", "pending_migrations": 0 })).into_response()
+}
+
+fn not_ready(reason: String) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({ "status": "not_ready", "reason": reason })),
+    )
+        .into_response()
+}
+
+async fn metrics(headers: HeaderMap) -> Response {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !is_authorized(token) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        METRICS.render(),
+    )
+        .into_response()
+}
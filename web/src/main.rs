@@ -16,63 +16,79 @@
  */
 
 #[cfg(not(target_arch = "wasm32"))]
-use api::persistence::postgres::{establish_connection, run_migrations};
+use clap::Parser;
 use dioxus::logger::tracing::{info, Level};
 use dioxus::prelude::*;
 use dioxus_logger::init;
-use std::env;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logging
-    init(Level::INFO).expect("failed to init logger");
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+#[cfg(feature = "server")]
+mod federation;
+#[cfg(feature = "server")]
+mod health;
 
-    // Load environment variables and check for migration argument
+fn main() -> ExitCode {
+    // Load environment variables and configuration, then run admin commands
+    // without the server
     #[cfg(not(target_arch = "wasm32"))]
     {
         dotenvy::dotenv().ok();
 
-        let args: Vec<String> = env::args().collect();
-        if args.len() > 1 && args[1] == "migrate" {
-            match run_migrations_sync() {
-                Ok(_) => return Ok(()),
-                Err(e) => {
-                    return Err(Box::new(std::io::Error::other(format!(
-                        "Migration failed: {}",
-                        e
-                    ))))
-                }
-            }
+        let args = cli::Cli::parse();
+        if let Err(code) = cli::load_config(args.config.as_deref(), args.json) {
+            return code;
+        }
+        match args.command {
+            None | Some(cli::Command::Serve) => {}
+            Some(command) => return cli::run(command, args.json),
+        }
+
+        // dioxus reads the listen address from IP and PORT
+        if let Some(address) = api::config::config().bind_address() {
+            std::env::set_var("IP", address.ip().to_string());
+            std::env::set_var("PORT", address.port().to_string());
         }
     }
 
-    info!("Starting Meeseeks Nuntius server...");
+    // Initialize logging
+    init(Level::INFO).expect("failed to init logger");
 
-    // Launch the server
-    launch(app);
-    Ok(())
-}
+    info!("Starting Meeseeks Nuntius server...");
 
-fn run_migrations_sync() -> Result<(), Box<dyn std::error::Error>> {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        use tokio::runtime::Runtime;
-        let rt = Runtime::new()?;
-        rt.block_on(async {
-            info!("Running database migrations...");
+    // The server adds health checks, metrics and federation next to the
+    // app's routes
+    #[cfg(feature = "server")]
+    dioxus::serve(|| async move {
+        ensure_relay_key().await;
+        health::start_sweeper();
+        federation::start_forwarder();
+        Ok(dioxus::server::router(app)
+            .merge(health::router())
+            .merge(federation::router()))
+    });
 
-            let db = establish_connection().await?;
-            info!("Database connection established");
+    #[cfg(not(feature = "server"))]
+    launch(app);
 
-            run_migrations(&db).await?;
-            info!("Database migrations completed successfully");
+    ExitCode::SUCCESS
+}
 
-            Ok(())
-        })
-    }
+/// Create the first relay key before serving, so replicas never race to
+/// create it on demand
+#[cfg(feature = "server")]
+async fn ensure_relay_key() {
+    use dioxus::logger::tracing::warn;
 
-    #[cfg(target_arch = "wasm32")]
-    {
-        Ok(())
+    let result = match api::persistence::postgres::establish_connection().await {
+        Ok(db) => api::admin::ensure_relay_key(&db).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(true) => info!("Created the first relay key"),
+        Ok(false) => {}
+        Err(e) => warn!("Could not check the relay key, run `web migrate up`: {e}"),
     }
 }
 